@@ -7,32 +7,66 @@ use clap::{App, ArgMatches};
 use log::{debug, info};
 
 type Keepalive = u64;
+type Mboxes<'a> = Vec<&'a str>;
+type Accounts<'a> = Vec<&'a str>;
+type Dir<'a> = &'a str;
 
 /// IMAP commands.
-pub enum Command {
-    /// Start the IMAP notify mode with the give keepalive duration.
-    Notify(Keepalive),
+pub enum Command<'a> {
+    /// Start the IMAP notify mode with the give keepalive duration,
+    /// on the given mailboxes of the given accounts.
+    Notify(Keepalive, Mboxes<'a>, Accounts<'a>),
 
-    /// Start the IMAP watch mode with the give keepalive duration.
-    Watch(Keepalive),
+    /// Start the IMAP watch mode with the give keepalive duration, on
+    /// the given mailboxes of the given accounts.
+    Watch(Keepalive, Mboxes<'a>, Accounts<'a>),
+
+    /// Synchronize the IMAP account with the given Maildir directory.
+    Sync(Dir<'a>),
+
+    /// Show the capabilities, the identity and the namespaces of the
+    /// IMAP server, and the quotas of the given mailboxes.
+    Info(Mboxes<'a>),
 }
 
 /// IMAP command matcher.
-pub fn matches(m: &ArgMatches) -> Result<Option<Command>> {
+pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
     info!("entering imap command matcher");
 
     if let Some(m) = m.subcommand_matches("notify") {
         info!("notify command matched");
         let keepalive = clap::value_t_or_exit!(m.value_of("keepalive"), u64);
         debug!("keepalive: {}", keepalive);
-        return Ok(Some(Command::Notify(keepalive)));
+        let mboxes: Vec<&str> = m.values_of("mboxes").unwrap_or_default().collect();
+        debug!("mailboxes: {:?}", mboxes);
+        let accounts: Vec<&str> = m.values_of("accounts").unwrap_or_default().collect();
+        debug!("accounts: {:?}", accounts);
+        return Ok(Some(Command::Notify(keepalive, mboxes, accounts)));
     }
 
     if let Some(m) = m.subcommand_matches("watch") {
         info!("watch command matched");
         let keepalive = clap::value_t_or_exit!(m.value_of("keepalive"), u64);
         debug!("keepalive: {}", keepalive);
-        return Ok(Some(Command::Watch(keepalive)));
+        let mboxes: Vec<&str> = m.values_of("mboxes").unwrap_or_default().collect();
+        debug!("mailboxes: {:?}", mboxes);
+        let accounts: Vec<&str> = m.values_of("accounts").unwrap_or_default().collect();
+        debug!("accounts: {:?}", accounts);
+        return Ok(Some(Command::Watch(keepalive, mboxes, accounts)));
+    }
+
+    if let Some(m) = m.subcommand_matches("info") {
+        info!("info command matched");
+        let mboxes: Vec<&str> = m.values_of("mboxes").unwrap_or_default().collect();
+        debug!("mailboxes: {:?}", mboxes);
+        return Ok(Some(Command::Info(mboxes)));
+    }
+
+    if let Some(m) = m.subcommand_matches("sync") {
+        info!("sync command matched");
+        let dir = m.value_of("dir").unwrap_or_default();
+        debug!("dir: {}", dir);
+        return Ok(Some(Command::Sync(dir)));
     }
 
     Ok(None)
@@ -40,27 +74,67 @@ pub fn matches(m: &ArgMatches) -> Result<Option<Command>> {
 
 /// IMAP subcommands.
 pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
-    vec![
+    #[allow(unused_mut)]
+    let mut subcmds = vec![
         clap::SubCommand::with_name("notify")
-            .about("Notifies when new messages arrive in the given mailbox")
+            .about("Notifies when new messages arrive in the given mailboxes")
             .aliases(&["idle"])
+            .args(&watch_args()),
+        clap::SubCommand::with_name("watch")
+            .about("Watches IMAP server changes in the given mailboxes")
+            .args(&watch_args()),
+        clap::SubCommand::with_name("info")
+            .about("Shows the capabilities, identity, namespaces and quotas of the IMAP server")
             .arg(
-                clap::Arg::with_name("keepalive")
-                    .help("Specifies the keepalive duration")
-                    .short("k")
-                    .long("keepalive")
-                    .value_name("SECS")
-                    .default_value("500"),
+                clap::Arg::with_name("mboxes")
+                    .help("Specifies the mailboxes to show the quota of")
+                    .long_help(
+                        "Specifies the mailboxes to show the quota of. Defaults to the \
+                         mailbox selected with --mailbox, or to the inbox.",
+                    )
+                    .value_name("MBOX")
+                    .multiple(true),
             ),
-        clap::SubCommand::with_name("watch")
-            .about("Watches IMAP server changes")
+    ];
+
+    #[cfg(feature = "maildir-backend")]
+    subcmds.push(
+        clap::SubCommand::with_name("sync")
+            .about("Synchronizes IMAP mailboxes with a local Maildir tree, in both directions")
             .arg(
-                clap::Arg::with_name("keepalive")
-                    .help("Specifies the keepalive duration")
-                    .short("k")
-                    .long("keepalive")
-                    .value_name("SECS")
-                    .default_value("500"),
+                clap::Arg::with_name("dir")
+                    .help("Specifies the Maildir directory")
+                    .value_name("DIR")
+                    .required(true),
             ),
+    );
+
+    subcmds
+}
+
+/// Represents the arguments shared by the notify and watch
+/// subcommands.
+fn watch_args<'a>() -> Vec<clap::Arg<'a, 'a>> {
+    vec![
+        clap::Arg::with_name("keepalive")
+            .help("Specifies the keepalive duration")
+            .short("k")
+            .long("keepalive")
+            .value_name("SECS")
+            .default_value("500"),
+        clap::Arg::with_name("mboxes")
+            .help("Specifies the mailboxes to watch, one connection is opened per mailbox")
+            .long_help(
+                "Specifies the mailboxes to watch, one connection is opened per mailbox. \
+                 Defaults to the mailbox selected with --mailbox, or to the inbox.",
+            )
+            .value_name("MBOX")
+            .multiple(true),
+        clap::Arg::with_name("accounts")
+            .help("Watches the mailboxes of the given IMAP accounts instead of the selected one")
+            .long("accounts")
+            .value_name("NAME")
+            .multiple(true)
+            .number_of_values(1),
     ]
 }
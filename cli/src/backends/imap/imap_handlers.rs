@@ -2,14 +2,157 @@
 //!
 //! This module gathers all IMAP handlers triggered by the CLI.
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+#[cfg(feature = "maildir-backend")]
+use std::path::Path;
 
-use crate::backends::ImapBackend;
+use himalaya_lib::{
+    backends::{Backend, ImapBackend, ImapWatcher},
+    config::{AccountConfig, BackendConfig, DeserializedConfig, ImapBackendConfig},
+};
 
-pub fn notify(keepalive: u64, mbox: &str, imap: &mut ImapBackend) -> Result<()> {
-    imap.notify(keepalive, mbox)
+use crate::output::PrinterService;
+
+pub fn notify(
+    keepalive: u64,
+    mboxes: &[&str],
+    accounts: &[&str],
+    config: &DeserializedConfig,
+    account_config: &AccountConfig,
+    imap_config: &ImapBackendConfig,
+) -> Result<()> {
+    watcher(
+        keepalive,
+        mboxes,
+        accounts,
+        config,
+        account_config,
+        imap_config,
+    )?
+    .notify()
+}
+
+pub fn watch(
+    keepalive: u64,
+    mboxes: &[&str],
+    accounts: &[&str],
+    config: &DeserializedConfig,
+    account_config: &AccountConfig,
+    imap_config: &ImapBackendConfig,
+) -> Result<()> {
+    watcher(
+        keepalive,
+        mboxes,
+        accounts,
+        config,
+        account_config,
+        imap_config,
+    )?
+    .watch()
+}
+
+/// Builds a watcher of the given mailboxes, for each of the given
+/// accounts or for the current account if none is given. Mailbox
+/// aliases are resolved per account.
+fn watcher(
+    keepalive: u64,
+    mboxes: &[&str],
+    accounts: &[&str],
+    config: &DeserializedConfig,
+    account_config: &AccountConfig,
+    imap_config: &ImapBackendConfig,
+) -> Result<ImapWatcher> {
+    let mut watcher = ImapWatcher::new(keepalive);
+
+    if accounts.is_empty() {
+        for mbox in mboxes {
+            watcher.add(
+                account_config,
+                imap_config,
+                &account_config.get_mbox_alias(mbox)?,
+            );
+        }
+        return Ok(watcher);
+    }
+
+    for name in accounts {
+        let (account_config, backend_config) =
+            AccountConfig::from_config_and_opt_account_name(config, Some(name))?;
+        let imap_config = match backend_config {
+            BackendConfig::Imap(imap_config) => imap_config,
+            #[allow(unreachable_patterns)]
+            _ => {
+                return Err(anyhow!(
+                    "cannot watch account {:?}: not an IMAP account",
+                    name
+                ))
+            }
+        };
+        for mbox in mboxes {
+            watcher.add(
+                &account_config,
+                &imap_config,
+                &account_config.get_mbox_alias(mbox)?,
+            );
+        }
+    }
+    Ok(watcher)
+}
+
+#[cfg(feature = "maildir-backend")]
+pub fn sync<P: PrinterService>(
+    dir: &str,
+    dry_run: bool,
+    printer: &mut P,
+    imap: &mut ImapBackend,
+) -> Result<()> {
+    let reports = imap.sync(Path::new(dir), dry_run)?;
+    for report in reports {
+        let report = if dry_run {
+            format!(
+                "{}: {} to pull, {} to push, {} to delete locally, {} to delete remotely, {} to flag locally, {} to flag remotely",
+                report.mbox,
+                report.pulled,
+                report.pushed,
+                report.deleted_local,
+                report.deleted_remote,
+                report.flagged_local,
+                report.flagged_remote
+            )
+        } else {
+            format!(
+                "{}: {} pulled, {} pushed, {} deleted locally, {} deleted remotely, {} flagged locally, {} flagged remotely",
+                report.mbox,
+                report.pulled,
+                report.pushed,
+                report.deleted_local,
+                report.deleted_remote,
+                report.flagged_local,
+                report.flagged_remote
+            )
+        };
+        printer.print_str(report)?;
+    }
+    imap.disconnect()?;
+    if dry_run {
+        printer.print_struct(format!("Dry run, account not synchronized with {:?}", dir))
+    } else {
+        printer.print_struct(format!("Account successfully synchronized with {:?}", dir))
+    }
 }
 
-pub fn watch(keepalive: u64, mbox: &str, imap: &mut ImapBackend) -> Result<()> {
-    imap.watch(keepalive, mbox)
+pub fn info<P: PrinterService>(
+    mboxes: &[&str],
+    account_config: &AccountConfig,
+    printer: &mut P,
+    imap: &mut ImapBackend,
+) -> Result<()> {
+    let mboxes = mboxes
+        .iter()
+        .map(|mbox| account_config.get_mbox_alias(mbox))
+        .collect::<Result<Vec<_>>>()?;
+    let mboxes: Vec<&str> = mboxes.iter().map(String::as_str).collect();
+    let info = imap.server_info(&mboxes)?;
+    imap.disconnect()?;
+    printer.print_struct(info)
 }
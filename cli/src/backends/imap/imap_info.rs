@@ -0,0 +1,205 @@
+//! IMAP server info module.
+//!
+//! This module provides the plain text representation of the IMAP
+//! server info.
+
+use anyhow::{Context, Result};
+use himalaya_lib::{
+    backends::{ImapNamespace, ImapQuotaResource, ImapServerInfo},
+    mbox::size_to_string,
+};
+
+use crate::output::{Print, WriteColor};
+
+impl Print for ImapServerInfo {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        writeln!(writer, "Capabilities: {}", self.capabilities.join(" "))
+            .context("cannot print server capabilities")?;
+
+        if self.id.is_empty() {
+            writeln!(writer, "Identity: unknown")?;
+        } else {
+            writeln!(writer, "Identity:")?;
+            for (key, value) in &self.id {
+                writeln!(writer, "  {}: {}", key, value)?;
+            }
+        }
+
+        match &self.namespaces {
+            None => writeln!(writer, "Namespaces: not supported")?,
+            Some(namespaces) => {
+                writeln!(writer, "Namespaces:")?;
+                writeln!(
+                    writer,
+                    "  personal: {}",
+                    namespaces_to_string(&namespaces.personal)
+                )?;
+                writeln!(
+                    writer,
+                    "  other: {}",
+                    namespaces_to_string(&namespaces.other)
+                )?;
+                writeln!(
+                    writer,
+                    "  shared: {}",
+                    namespaces_to_string(&namespaces.shared)
+                )?;
+            }
+        }
+
+        if self.quotas.is_empty() {
+            writeln!(writer, "Quotas: not supported")?;
+        }
+        for quota in &self.quotas {
+            if quota.roots.is_empty() {
+                writeln!(writer, "Quota of {}: none", quota.mbox)?;
+                continue;
+            }
+            writeln!(writer, "Quota of {}:", quota.mbox)?;
+            for root in &quota.roots {
+                let resources = root
+                    .resources
+                    .iter()
+                    .map(resource_to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(writer, "  {:?}: {}", root.name, resources)?;
+            }
+        }
+
+        Ok(writer.reset()?)
+    }
+}
+
+/// Formats the given namespaces as their quoted prefix followed by
+/// their delimiter.
+fn namespaces_to_string(namespaces: &[ImapNamespace]) -> String {
+    if namespaces.is_empty() {
+        return String::from("none");
+    }
+    namespaces
+        .iter()
+        .map(|namespace| match &namespace.delim {
+            Some(delim) => format!("{:?} ({})", namespace.prefix, delim),
+            None => format!("{:?}", namespace.prefix),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats the usage and the limit of the given quota resource. The
+/// storage is counted in units of 1024 octets.
+fn resource_to_string(resource: &ImapQuotaResource) -> String {
+    let (usage, limit) = if resource.name.eq_ignore_ascii_case("STORAGE") {
+        (
+            size_to_string(resource.usage * 1024),
+            size_to_string(resource.limit * 1024),
+        )
+    } else {
+        (resource.usage.to_string(), resource.limit.to_string())
+    };
+    let percent = match resource.limit {
+        0 => String::new(),
+        limit => format!(" ({}%)", resource.usage * 100 / limit),
+    };
+    format!("{} {}/{}{}", resource.name, usage, limit, percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use himalaya_lib::backends::{ImapMboxQuota, ImapNamespaces, ImapQuotaRoot};
+    use std::io;
+    use termcolor::ColorSpec;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct StringWriter {
+        content: String,
+    }
+
+    impl io::Write for StringWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.content.push_str(&String::from_utf8_lossy(buf));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl termcolor::WriteColor for StringWriter {
+        fn supports_color(&self) -> bool {
+            false
+        }
+
+        fn set_color(&mut self, _spec: &ColorSpec) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn reset(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl WriteColor for StringWriter {}
+
+    #[test]
+    fn it_should_print_server_info() {
+        let info = ImapServerInfo {
+            capabilities: vec!["IDLE".into(), "IMAP4rev1".into(), "QUOTA".into()],
+            id: vec![("name".into(), "Dovecot".into())]
+                .into_iter()
+                .collect(),
+            namespaces: Some(ImapNamespaces {
+                personal: vec![ImapNamespace {
+                    prefix: "".into(),
+                    delim: Some("/".into()),
+                }],
+                ..ImapNamespaces::default()
+            }),
+            quotas: vec![
+                ImapMboxQuota {
+                    mbox: "INBOX".into(),
+                    roots: vec![ImapQuotaRoot {
+                        name: "".into(),
+                        resources: vec![
+                            ImapQuotaResource {
+                                name: "STORAGE".into(),
+                                usage: 256,
+                                limit: 1024,
+                            },
+                            ImapQuotaResource {
+                                name: "MESSAGE".into(),
+                                usage: 3,
+                                limit: 0,
+                            },
+                        ],
+                    }],
+                },
+                ImapMboxQuota {
+                    mbox: "Archives".into(),
+                    roots: vec![],
+                },
+            ],
+        };
+
+        let mut writer = StringWriter::default();
+        info.print(&mut writer).unwrap();
+
+        let expected = concat!(
+            "Capabilities: IDLE IMAP4rev1 QUOTA\n",
+            "Identity:\n",
+            "  name: Dovecot\n",
+            "Namespaces:\n",
+            "  personal: \"\" (/)\n",
+            "  other: none\n",
+            "  shared: none\n",
+            "Quota of INBOX:\n",
+            "  \"\": STORAGE 256.0KiB/1.0MiB (25%), MESSAGE 3/0\n",
+            "Quota of Archives: none\n",
+        );
+        assert_eq!(expected, writer.content);
+    }
+}
@@ -1,36 +1,15 @@
 //! IMAP mailbox module.
 //!
-//! This module provides IMAP types and conversion utilities related
-//! to the mailbox.
+//! This module provides the table representation of IMAP mailboxes.
 
 use anyhow::Result;
-use serde::Serialize;
-use std::fmt::{self, Display};
-use std::ops::Deref;
+use himalaya_lib::backends::{ImapMbox, ImapMboxes};
 
-use crate::mbox::Mboxes;
 use crate::{
     output::{PrintTable, PrintTableOpts, WriteColor},
     ui::{Cell, Row, Table},
 };
 
-use super::ImapMboxAttrs;
-
-/// Represents a list of IMAP mailboxes.
-#[derive(Debug, Default, Serialize)]
-pub struct ImapMboxes {
-    #[serde(rename = "response")]
-    pub mboxes: Vec<ImapMbox>,
-}
-
-impl Deref for ImapMboxes {
-    type Target = Vec<ImapMbox>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.mboxes
-    }
-}
-
 impl PrintTable for ImapMboxes {
     fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
         writeln!(writer)?;
@@ -40,43 +19,14 @@ impl PrintTable for ImapMboxes {
     }
 }
 
-impl Mboxes for ImapMboxes {
-    //
-}
-
-/// Represents the IMAP mailbox.
-#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
-pub struct ImapMbox {
-    /// Represents the mailbox hierarchie delimiter.
-    pub delim: String,
-
-    /// Represents the mailbox name.
-    pub name: String,
-
-    /// Represents the mailbox attributes.
-    pub attrs: ImapMboxAttrs,
-}
-
-impl ImapMbox {
-    pub fn new(name: &str) -> Self {
-        Self {
-            name: name.into(),
-            ..Self::default()
-        }
-    }
-}
-
-impl Display for ImapMbox {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.name)
-    }
-}
-
 impl Table for ImapMbox {
     fn head() -> Row {
         Row::new()
             .cell(Cell::new("DELIM").bold().underline().white())
             .cell(Cell::new("NAME").bold().underline().white())
+            .cell(Cell::new("UNSEEN").bold().underline().white())
+            .cell(Cell::new("TOTAL").bold().underline().white())
+            .cell(Cell::new("RECENT").bold().underline().white())
             .cell(
                 Cell::new("ATTRIBUTES")
                     .shrinkable()
@@ -87,68 +37,13 @@ impl Table for ImapMbox {
     }
 
     fn row(&self) -> Row {
+        let count = |count: Option<usize>| count.map(|n| n.to_string()).unwrap_or_default();
         Row::new()
             .cell(Cell::new(&self.delim).white())
             .cell(Cell::new(&self.name).green())
+            .cell(Cell::new(count(self.counts.map(|c| c.unseen))).bold())
+            .cell(Cell::new(count(self.counts.map(|c| c.messages))).white())
+            .cell(Cell::new(count(self.counts.and_then(|c| c.recent))).white())
             .cell(Cell::new(&self.attrs.to_string()).shrinkable().blue())
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use crate::backends::ImapMboxAttr;
-
-    use super::*;
-
-    #[test]
-    fn it_should_create_new_mbox() {
-        assert_eq!(ImapMbox::default(), ImapMbox::new(""));
-        assert_eq!(
-            ImapMbox {
-                name: "INBOX".into(),
-                ..ImapMbox::default()
-            },
-            ImapMbox::new("INBOX")
-        );
-    }
-
-    #[test]
-    fn it_should_display_mbox() {
-        let default_mbox = ImapMbox::default();
-        assert_eq!("", default_mbox.to_string());
-
-        let new_mbox = ImapMbox::new("INBOX");
-        assert_eq!("INBOX", new_mbox.to_string());
-
-        let full_mbox = ImapMbox {
-            delim: ".".into(),
-            name: "Sent".into(),
-            attrs: ImapMboxAttrs(vec![ImapMboxAttr::NoSelect]),
-        };
-        assert_eq!("Sent", full_mbox.to_string());
-    }
-}
-
-/// Represents a list of raw mailboxes returned by the `imap` crate.
-pub type RawImapMboxes = imap::types::ZeroCopy<Vec<RawImapMbox>>;
-
-impl<'a> From<RawImapMboxes> for ImapMboxes {
-    fn from(raw_mboxes: RawImapMboxes) -> Self {
-        Self {
-            mboxes: raw_mboxes.iter().map(ImapMbox::from).collect(),
-        }
-    }
-}
-
-/// Represents the raw mailbox returned by the `imap` crate.
-pub type RawImapMbox = imap::types::Name;
-
-impl<'a> From<&'a RawImapMbox> for ImapMbox {
-    fn from(raw_mbox: &'a RawImapMbox) -> Self {
-        Self {
-            delim: raw_mbox.delimiter().unwrap_or_default().into(),
-            name: raw_mbox.name().into(),
-            attrs: raw_mbox.attributes().into(),
-        }
-    }
-}
@@ -0,0 +1,145 @@
+//! IMAP thread module.
+//!
+//! This module provides the table representation of IMAP threads.
+
+use anyhow::Result;
+use himalaya_lib::backends::{ImapEnvelope, ImapFlag, ImapThreads};
+
+use crate::{
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Represents a row of the threads table: an envelope along with its
+/// depth in its thread.
+struct ImapThreadRow<'a> {
+    depth: usize,
+    envelope: &'a ImapEnvelope,
+}
+
+impl PrintTable for ImapThreads {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        let rows: Vec<_> = self
+            .iter()
+            .flat_map(|thread| thread.flatten())
+            .map(|(depth, envelope)| ImapThreadRow { depth, envelope })
+            .collect();
+        writeln!(writer)?;
+        Table::print(writer, &rows, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+impl Table for ImapThreadRow<'_> {
+    fn head() -> Row {
+        ImapEnvelope::head()
+    }
+
+    fn row(&self) -> Row {
+        let envelope = self.envelope;
+        let unseen = !envelope.flags.contains(&ImapFlag::Seen);
+        // Replies are indented under the message they reply to.
+        let subject = match self.depth {
+            0 => envelope.subject.to_owned(),
+            depth => format!("{}└ {}", "  ".repeat(depth - 1), envelope.subject),
+        };
+        let mut row = envelope.row();
+        row.0[2] = Cell::new(subject).shrinkable().bold_if(unseen).green();
+        row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use himalaya_lib::{backends::ImapFlags, config::Format, msg::ThreadNode};
+    use std::io;
+    use termcolor::ColorSpec;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct StringWriter {
+        content: String,
+    }
+
+    impl io::Write for StringWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.content.push_str(&String::from_utf8_lossy(buf));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl termcolor::WriteColor for StringWriter {
+        fn supports_color(&self) -> bool {
+            false
+        }
+
+        fn set_color(&mut self, _spec: &ColorSpec) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn reset(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl WriteColor for StringWriter {}
+
+    fn node(
+        id: u32,
+        subject: &str,
+        children: Vec<ThreadNode<ImapEnvelope>>,
+    ) -> ThreadNode<ImapEnvelope> {
+        let envelope = ImapEnvelope {
+            id,
+            flags: ImapFlags(vec![ImapFlag::Seen]),
+            subject: subject.into(),
+            sender: "alice".into(),
+            ..ImapEnvelope::default()
+        };
+        ThreadNode::new(Some(envelope), children)
+    }
+
+    #[test]
+    fn it_should_print_threads() {
+        let threads = ImapThreads {
+            threads: vec![
+                node(
+                    1,
+                    "Hello",
+                    vec![
+                        node(2, "Re: Hello", vec![node(4, "Re: Re: Hello", vec![])]),
+                        node(3, "Re: Hello", vec![]),
+                    ],
+                ),
+                ThreadNode::new(None, vec![node(5, "Bye", vec![])]),
+            ],
+        };
+        let mut writer = StringWriter::default();
+        let opts = PrintTableOpts {
+            format: &Format::Flowed,
+            max_width: None,
+            columns: &[],
+        };
+        threads.print_table(&mut writer, opts).unwrap();
+
+        assert_eq!(
+            concat![
+                "\n",
+                "ID │FLAGS │SUBJECT           │SENDER │DATE \n",
+                "1  │      │Hello             │alice  │     \n",
+                "2  │      │└ Re: Hello       │alice  │     \n",
+                "4  │      │  └ Re: Re: Hello │alice  │     \n",
+                "3  │      │└ Re: Hello       │alice  │     \n",
+                "5  │      │Bye               │alice  │     \n",
+                "\n",
+            ],
+            writer.content
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! In-memory envelope module.
+//!
+//! This module provides the table representation of in-memory
+//! envelopes.
+
+use anyhow::Result;
+use himalaya_lib::{
+    backends::{MemoryEnvelope, MemoryEnvelopes},
+    msg::Flag,
+};
+
+use crate::{
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+impl PrintTable for MemoryEnvelopes {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+impl Table for MemoryEnvelope {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("ID").bold().underline().white())
+            .cell(Cell::new("FLAGS").bold().underline().white())
+            .cell(Cell::new("SUBJECT").shrinkable().bold().underline().white())
+            .cell(Cell::new("SENDER").bold().underline().white())
+            .cell(Cell::new("DATE").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        let id = self.id.to_string();
+        let flags = self.flags.to_symbols_string();
+        let unseen = !self.flags.contains(&Flag::Seen);
+        let subject = &self.subject;
+        let sender = &self.sender;
+        let date = &self.date;
+        Row::new()
+            .cell(Cell::new(id).bold_if(unseen).red())
+            .cell(Cell::new(flags).bold_if(unseen).white())
+            .cell(Cell::new(subject).shrinkable().bold_if(unseen).green())
+            .cell(Cell::new(sender).bold_if(unseen).blue())
+            .cell(Cell::new(date).bold_if(unseen).yellow())
+    }
+}
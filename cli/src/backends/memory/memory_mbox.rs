@@ -0,0 +1,35 @@
+//! In-memory mailbox module.
+//!
+//! This module provides the table representation of in-memory
+//! mailboxes.
+
+use anyhow::Result;
+use himalaya_lib::backends::{MemoryMbox, MemoryMboxes};
+
+use crate::{
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+impl PrintTable for MemoryMboxes {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+impl Table for MemoryMbox {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("NAME").bold().underline().white())
+            .cell(Cell::new("MESSAGES").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        Row::new()
+            .cell(Cell::new(&self.name).green())
+            .cell(Cell::new(&self.len.to_string()).white())
+    }
+}
@@ -12,8 +12,9 @@ use std::{
     ops::Deref,
 };
 
+use himalaya_lib::config::DeserializedAccountConfig;
+
 use crate::{
-    config::DeserializedAccountConfig,
     output::{PrintTable, PrintTableOpts, WriteColor},
     ui::{Cell, Row, Table},
 };
@@ -101,6 +102,9 @@ impl From<Iter<'_, String, DeserializedAccountConfig>> for Accounts {
                 DeserializedAccountConfig::Notmuch(config) => {
                     Account::new(name, "notmuch", config.default.unwrap_or_default())
                 }
+                DeserializedAccountConfig::Custom(config) => {
+                    Account::new(name, &config.backend, config.default.unwrap_or_default())
+                }
             })
             .collect();
         accounts.sort_by(|a, b| b.name.partial_cmp(&a.name).unwrap());
@@ -5,8 +5,10 @@
 use anyhow::Result;
 use log::{info, trace};
 
+use himalaya_lib::config::{AccountConfig, DeserializedConfig};
+
 use crate::{
-    config::{AccountConfig, Accounts, DeserializedConfig},
+    config::Accounts,
     output::{PrintTableOpts, PrinterService},
 };
 
@@ -27,6 +29,7 @@ pub fn list<'a, P: PrinterService>(
         PrintTableOpts {
             format: &account_config.format,
             max_width,
+            columns: &[],
         },
     )?;
 
@@ -39,10 +42,9 @@ mod tests {
     use std::{collections::HashMap, fmt::Debug, io, iter::FromIterator};
     use termcolor::ColorSpec;
 
-    use crate::{
-        config::{DeserializedAccountConfig, DeserializedImapAccountConfig},
-        output::{Print, PrintTable, WriteColor},
-    };
+    use himalaya_lib::config::{DeserializedAccountConfig, DeserializedImapAccountConfig};
+
+    use crate::output::{Print, PrintTable, WriteColor};
 
     use super::*;
 
@@ -11,3 +11,13 @@ pub fn path_arg<'a>() -> Arg<'a, 'a> {
         .help("Forces a specific config path")
         .value_name("PATH")
 }
+
+/// Represents the dry run argument.
+/// This argument runs the command against the account backend, printing
+/// the changes instead of applying them, and prevents messages from
+/// being sent.
+pub fn dry_run_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("dry-run")
+        .long("dry-run")
+        .help("Runs the command without touching the mailboxes nor sending messages")
+}
@@ -1,28 +1,25 @@
 use anyhow::Result;
+use himalaya_lib::{
+    backends::{AnyBackend, Backend, BackendBuilder, BoxedBackend, DryRunBackend},
+    config::{AccountConfig, DeserializedConfig, DEFAULT_INBOX_FOLDER},
+    msg::MsgId,
+    smtp::{LettreService, MemorySmtpService, SmtpService},
+};
 use std::{convert::TryFrom, env};
 use url::Url;
 
 use himalaya::{
-    backends::Backend,
     compl::{compl_args, compl_handlers},
-    config::{
-        account_args, account_handlers, config_args, AccountConfig, BackendConfig,
-        DeserializedConfig, DEFAULT_INBOX_FOLDER,
-    },
+    config::{account_args, account_handlers, config_args},
     mbox::{mbox_args, mbox_handlers},
     msg::{flag_args, flag_handlers, msg_args, msg_handlers, tpl_args, tpl_handlers},
     output::{output_args, OutputFmt, StdoutPrinter},
-    smtp::LettreService,
 };
 
 #[cfg(feature = "imap-backend")]
-use himalaya::backends::{imap_args, imap_handlers, ImapBackend};
-
-#[cfg(feature = "maildir-backend")]
-use himalaya::backends::MaildirBackend;
-
-#[cfg(feature = "notmuch-backend")]
-use himalaya::{backends::NotmuchBackend, config::MaildirBackendConfig};
+use himalaya::backends::{imap_args, imap_handlers};
+#[cfg(feature = "imap-backend")]
+use himalaya_lib::{backends::ImapBackend, config::BackendConfig};
 
 fn create_app<'a>() -> clap::App<'a, 'a> {
     let app = clap::App::new(env!("CARGO_PKG_NAME"))
@@ -31,6 +28,7 @@ fn create_app<'a>() -> clap::App<'a, 'a> {
         .author(env!("CARGO_PKG_AUTHORS"))
         .global_setting(clap::AppSettings::GlobalVersion)
         .arg(&config_args::path_arg())
+        .arg(&config_args::dry_run_arg())
         .arg(&account_args::name_arg())
         .args(&output_args::args())
         .arg(mbox_args::source_arg())
@@ -59,41 +57,16 @@ fn main() -> Result<()> {
         let mut printer = StdoutPrinter::from(OutputFmt::Plain);
         let url = Url::parse(&raw_args[1])?;
         let mut smtp = LettreService::from(&account_config);
-
-        #[cfg(feature = "imap-backend")]
-        let mut imap;
-
-        #[cfg(feature = "maildir-backend")]
-        let mut maildir;
-
-        #[cfg(feature = "notmuch-backend")]
-        let maildir_config: MaildirBackendConfig;
-        #[cfg(feature = "notmuch-backend")]
-        let mut notmuch;
-
-        let backend: Box<&mut dyn Backend> = match backend_config {
-            #[cfg(feature = "imap-backend")]
-            BackendConfig::Imap(ref imap_config) => {
-                imap = ImapBackend::new(&account_config, imap_config);
-                Box::new(&mut imap)
+        return match BackendBuilder::new().build(&account_config, &backend_config)? {
+            AnyBackend::Uid(mut backend) => {
+                let backend: Box<&mut dyn Backend<Id = _>> = Box::new(backend.as_mut());
+                msg_handlers::mailto(&url, &account_config, &mut printer, backend, &mut smtp)
             }
-            #[cfg(feature = "maildir-backend")]
-            BackendConfig::Maildir(ref maildir_config) => {
-                maildir = MaildirBackend::new(&account_config, maildir_config);
-                Box::new(&mut maildir)
-            }
-            #[cfg(feature = "notmuch-backend")]
-            BackendConfig::Notmuch(ref notmuch_config) => {
-                maildir_config = MaildirBackendConfig {
-                    maildir_dir: notmuch_config.notmuch_database_dir.clone(),
-                };
-                maildir = MaildirBackend::new(&account_config, &maildir_config);
-                notmuch = NotmuchBackend::new(&account_config, notmuch_config, &mut maildir)?;
-                Box::new(&mut notmuch)
+            AnyBackend::Hash(mut backend) => {
+                let backend: Box<&mut dyn Backend<Id = _>> = Box::new(backend.as_mut());
+                msg_handlers::mailto(&url, &account_config, &mut printer, backend, &mut smtp)
             }
         };
-
-        return msg_handlers::mailto(&url, &account_config, &mut printer, backend, &mut smtp);
     }
 
     let app = create_app();
@@ -117,40 +90,8 @@ fn main() -> Result<()> {
         .or_else(|| account_config.mailboxes.get("inbox").map(|s| s.as_str()))
         .unwrap_or(DEFAULT_INBOX_FOLDER);
     let mut printer = StdoutPrinter::try_from(m.value_of("output"))?;
-    #[cfg(feature = "imap-backend")]
-    let mut imap;
 
-    #[cfg(feature = "maildir-backend")]
-    let mut maildir;
-
-    #[cfg(feature = "notmuch-backend")]
-    let maildir_config: MaildirBackendConfig;
-    #[cfg(feature = "notmuch-backend")]
-    let mut notmuch;
-
-    let backend: Box<&mut dyn Backend> = match backend_config {
-        #[cfg(feature = "imap-backend")]
-        BackendConfig::Imap(ref imap_config) => {
-            imap = ImapBackend::new(&account_config, imap_config);
-            Box::new(&mut imap)
-        }
-        #[cfg(feature = "maildir-backend")]
-        BackendConfig::Maildir(ref maildir_config) => {
-            maildir = MaildirBackend::new(&account_config, maildir_config);
-            Box::new(&mut maildir)
-        }
-        #[cfg(feature = "notmuch-backend")]
-        BackendConfig::Notmuch(ref notmuch_config) => {
-            maildir_config = MaildirBackendConfig {
-                maildir_dir: notmuch_config.notmuch_database_dir.clone(),
-            };
-            maildir = MaildirBackend::new(&account_config, &maildir_config);
-            notmuch = NotmuchBackend::new(&account_config, notmuch_config, &mut maildir)?;
-            Box::new(&mut notmuch)
-        }
-    };
-
-    let mut smtp = LettreService::from(&account_config);
+    let dry_run = m.is_present("dry-run");
 
     // Check IMAP commands.
     #[allow(irrefutable_let_patterns)]
@@ -158,11 +99,41 @@ fn main() -> Result<()> {
     if let BackendConfig::Imap(ref imap_config) = backend_config {
         let mut imap = ImapBackend::new(&account_config, imap_config);
         match imap_args::matches(&m)? {
-            Some(imap_args::Command::Notify(keepalive)) => {
-                return imap_handlers::notify(keepalive, mbox, &mut imap);
+            Some(imap_args::Command::Notify(keepalive, mut mboxes, accounts)) => {
+                if mboxes.is_empty() {
+                    mboxes.push(mbox);
+                }
+                return imap_handlers::notify(
+                    keepalive,
+                    &mboxes,
+                    &accounts,
+                    &config,
+                    &account_config,
+                    imap_config,
+                );
+            }
+            Some(imap_args::Command::Watch(keepalive, mut mboxes, accounts)) => {
+                if mboxes.is_empty() {
+                    mboxes.push(mbox);
+                }
+                return imap_handlers::watch(
+                    keepalive,
+                    &mboxes,
+                    &accounts,
+                    &config,
+                    &account_config,
+                    imap_config,
+                );
             }
-            Some(imap_args::Command::Watch(keepalive)) => {
-                return imap_handlers::watch(keepalive, mbox, &mut imap);
+            Some(imap_args::Command::Info(mut mboxes)) => {
+                if mboxes.is_empty() {
+                    mboxes.push(mbox);
+                }
+                return imap_handlers::info(&mboxes, &account_config, &mut printer, &mut imap);
+            }
+            #[cfg(feature = "maildir-backend")]
+            Some(imap_args::Command::Sync(dir)) => {
+                return imap_handlers::sync(dir, dry_run, &mut printer, &mut imap);
             }
             _ => (),
         }
@@ -176,165 +147,225 @@ fn main() -> Result<()> {
         _ => (),
     }
 
+    // Message ids are parsed as the ids of the account backend.
+    let backend = BackendBuilder::new().build(&account_config, &backend_config)?;
+    match backend {
+        AnyBackend::Uid(backend) => run(&m, mbox, dry_run, &account_config, &mut printer, backend),
+        AnyBackend::Hash(backend) => run(&m, mbox, dry_run, &account_config, &mut printer, backend),
+    }
+}
+
+/// Runs the mailbox and message commands against the given backend.
+#[allow(clippy::single_match)]
+fn run<'a, I: MsgId + 'a>(
+    m: &clap::ArgMatches,
+    mbox: &str,
+    dry_run: bool,
+    account_config: &'a AccountConfig,
+    printer: &mut StdoutPrinter,
+    backend: BoxedBackend<'a, I>,
+) -> Result<()> {
+    // In dry run mode, changes are printed instead of being applied
+    // to the backend, and messages are not sent.
+    let mut backend: BoxedBackend<I> = if dry_run {
+        Box::new(
+            DryRunBackend::new(backend).with_reporter(|change| eprintln!("dry run: {}", change)),
+        )
+    } else {
+        backend
+    };
+    let backend: Box<&mut dyn Backend<Id = I>> = Box::new(backend.as_mut());
+
+    let mut smtp: Box<dyn SmtpService + '_> = if dry_run {
+        Box::new(MemorySmtpService::default())
+    } else {
+        Box::new(LettreService::from(account_config))
+    };
+
     // Check mailbox commands.
-    match mbox_args::matches(&m)? {
-        Some(mbox_args::Cmd::List(max_width)) => {
-            return mbox_handlers::list(max_width, &account_config, &mut printer, backend);
+    match mbox_args::matches(m)? {
+        Some(mbox_args::Cmd::List(max_width, subscribed)) => {
+            return mbox_handlers::list(max_width, subscribed, account_config, printer, backend);
+        }
+        Some(mbox_args::Cmd::Create(mbox)) => {
+            return mbox_handlers::create(mbox, printer, backend);
+        }
+        Some(mbox_args::Cmd::Delete(mbox)) => {
+            return mbox_handlers::delete(mbox, printer, backend);
+        }
+        Some(mbox_args::Cmd::Rename(mbox, new_mbox)) => {
+            return mbox_handlers::rename(mbox, new_mbox, printer, backend);
+        }
+        Some(mbox_args::Cmd::Subscribe(mbox)) => {
+            return mbox_handlers::subscribe(mbox, printer, backend);
+        }
+        Some(mbox_args::Cmd::Unsubscribe(mbox)) => {
+            return mbox_handlers::unsubscribe(mbox, printer, backend);
         }
         _ => (),
     }
 
     // Check message commands.
-    match msg_args::matches(&m)? {
-        Some(msg_args::Cmd::Attachments(seq)) => {
-            return msg_handlers::attachments(seq, mbox, &account_config, &mut printer, backend);
+    match msg_args::matches(m)? {
+        Some(msg_args::Cmd::Attachments(ref id, ref selectors, list, max_width)) => {
+            return msg_handlers::attachments(
+                id,
+                selectors,
+                list,
+                max_width,
+                mbox,
+                account_config,
+                printer,
+                backend,
+            );
         }
-        Some(msg_args::Cmd::Copy(seq, mbox_dst)) => {
-            return msg_handlers::copy(seq, mbox, mbox_dst, &mut printer, backend);
+        Some(msg_args::Cmd::Copy(ref ids, mbox_dst)) => {
+            return msg_handlers::copy(ids, mbox, mbox_dst, printer, backend);
         }
-        Some(msg_args::Cmd::Delete(seq)) => {
-            return msg_handlers::delete(seq, mbox, &mut printer, backend);
+        Some(msg_args::Cmd::Delete(ref ids)) => {
+            return msg_handlers::delete(ids, mbox, printer, backend);
         }
-        Some(msg_args::Cmd::Forward(seq, attachment_paths, encrypt)) => {
+        Some(msg_args::Cmd::Forward(ref id, attachment_paths, encrypt)) => {
             return msg_handlers::forward(
-                seq,
+                id,
                 attachment_paths,
                 encrypt,
                 mbox,
-                &account_config,
-                &mut printer,
+                account_config,
+                printer,
                 backend,
                 &mut smtp,
             );
         }
-        Some(msg_args::Cmd::List(max_width, page_size, page)) => {
+        Some(msg_args::Cmd::List(max_width, columns, page_size, page)) => {
             return msg_handlers::list(
                 max_width,
+                &columns,
                 page_size,
                 page,
                 mbox,
-                &account_config,
-                &mut printer,
+                account_config,
+                printer,
                 backend,
             );
         }
-        Some(msg_args::Cmd::Move(seq, mbox_dst)) => {
-            return msg_handlers::move_(seq, mbox, mbox_dst, &mut printer, backend);
+        Some(msg_args::Cmd::Move(ref ids, mbox_dst)) => {
+            return msg_handlers::move_(ids, mbox, mbox_dst, printer, backend);
         }
-        Some(msg_args::Cmd::Read(seq, text_mime, raw, headers)) => {
+        Some(msg_args::Cmd::Read(ref id, text_mime, raw, headers)) => {
             return msg_handlers::read(
-                seq,
+                id,
                 text_mime,
                 raw,
                 headers,
                 mbox,
-                &account_config,
-                &mut printer,
+                account_config,
+                printer,
                 backend,
             );
         }
-        Some(msg_args::Cmd::Reply(seq, all, attachment_paths, encrypt)) => {
+        Some(msg_args::Cmd::Reply(ref id, all, attachment_paths, encrypt)) => {
             return msg_handlers::reply(
-                seq,
+                id,
                 all,
                 attachment_paths,
                 encrypt,
                 mbox,
-                &account_config,
-                &mut printer,
+                account_config,
+                printer,
                 backend,
                 &mut smtp,
             );
         }
         Some(msg_args::Cmd::Save(raw_msg)) => {
-            return msg_handlers::save(mbox, raw_msg, &mut printer, backend);
+            return msg_handlers::save(mbox, raw_msg, printer, backend);
         }
-        Some(msg_args::Cmd::Search(query, max_width, page_size, page)) => {
+        Some(msg_args::Cmd::Search(query, max_width, columns, page_size, page)) => {
             return msg_handlers::search(
                 query,
                 max_width,
+                &columns,
                 page_size,
                 page,
                 mbox,
-                &account_config,
-                &mut printer,
+                account_config,
+                printer,
                 backend,
             );
         }
-        Some(msg_args::Cmd::Sort(criteria, query, max_width, page_size, page)) => {
+        Some(msg_args::Cmd::Thread(query, max_width, columns, page_size, page)) => {
+            return msg_handlers::thread(
+                query,
+                max_width,
+                &columns,
+                page_size,
+                page,
+                mbox,
+                account_config,
+                printer,
+                backend,
+            );
+        }
+        Some(msg_args::Cmd::Sort(criteria, query, max_width, columns, page_size, page)) => {
             return msg_handlers::sort(
                 criteria,
                 query,
                 max_width,
+                &columns,
                 page_size,
                 page,
                 mbox,
-                &account_config,
-                &mut printer,
+                account_config,
+                printer,
                 backend,
             );
         }
         Some(msg_args::Cmd::Send(raw_msg)) => {
-            return msg_handlers::send(raw_msg, &account_config, &mut printer, backend, &mut smtp);
+            return msg_handlers::send(raw_msg, account_config, printer, backend, &mut smtp);
         }
         Some(msg_args::Cmd::Write(tpl, atts, encrypt)) => {
             return msg_handlers::write(
                 tpl,
                 atts,
                 encrypt,
-                &account_config,
-                &mut printer,
+                account_config,
+                printer,
                 backend,
                 &mut smtp,
             );
         }
         Some(msg_args::Cmd::Flag(m)) => match m {
-            Some(flag_args::Cmd::Set(seq_range, ref flags)) => {
-                return flag_handlers::set(seq_range, flags, mbox, &mut printer, backend);
+            Some(flag_args::Cmd::Set(ref ids, ref flags)) => {
+                return flag_handlers::set(ids, flags, mbox, printer, backend);
             }
-            Some(flag_args::Cmd::Add(seq_range, ref flags)) => {
-                return flag_handlers::add(seq_range, flags, mbox, &mut printer, backend);
+            Some(flag_args::Cmd::Add(ref ids, ref flags)) => {
+                return flag_handlers::add(ids, flags, mbox, printer, backend);
             }
-            Some(flag_args::Cmd::Remove(seq_range, ref flags)) => {
-                return flag_handlers::remove(seq_range, flags, mbox, &mut printer, backend);
+            Some(flag_args::Cmd::Remove(ref ids, ref flags)) => {
+                return flag_handlers::remove(ids, flags, mbox, printer, backend);
             }
             _ => (),
         },
         Some(msg_args::Cmd::Tpl(m)) => match m {
             Some(tpl_args::Cmd::New(tpl)) => {
-                return tpl_handlers::new(tpl, &account_config, &mut printer);
+                return tpl_handlers::new(tpl, account_config, printer);
             }
-            Some(tpl_args::Cmd::Reply(seq, all, tpl)) => {
-                return tpl_handlers::reply(
-                    seq,
-                    all,
-                    tpl,
-                    mbox,
-                    &account_config,
-                    &mut printer,
-                    backend,
-                );
+            Some(tpl_args::Cmd::Reply(ref id, all, tpl)) => {
+                return tpl_handlers::reply(id, all, tpl, mbox, account_config, printer, backend);
             }
-            Some(tpl_args::Cmd::Forward(seq, tpl)) => {
-                return tpl_handlers::forward(
-                    seq,
-                    tpl,
-                    mbox,
-                    &account_config,
-                    &mut printer,
-                    backend,
-                );
+            Some(tpl_args::Cmd::Forward(ref id, tpl)) => {
+                return tpl_handlers::forward(id, tpl, mbox, account_config, printer, backend);
             }
             Some(tpl_args::Cmd::Save(atts, tpl)) => {
-                return tpl_handlers::save(mbox, &account_config, atts, tpl, &mut printer, backend);
+                return tpl_handlers::save(mbox, account_config, atts, tpl, printer, backend);
             }
             Some(tpl_args::Cmd::Send(atts, tpl)) => {
                 return tpl_handlers::send(
                     mbox,
-                    &account_config,
+                    account_config,
                     atts,
                     tpl,
-                    &mut printer,
+                    printer,
                     backend,
                     &mut smtp,
                 );
@@ -1,7 +1,36 @@
-use std::fmt;
+use anyhow::{anyhow, Result};
+use himalaya_lib::{backends::MemoryMboxes, mbox::Mboxes};
 
-use crate::output::PrintTable;
+use crate::output::{PrintTable, PrintTableOpts, WriteColor};
 
-pub trait Mboxes: fmt::Debug + erased_serde::Serialize + PrintTable {
-    //
+#[cfg(feature = "imap-backend")]
+use himalaya_lib::backends::ImapMboxes;
+#[cfg(feature = "maildir-backend")]
+use himalaya_lib::backends::MaildirMboxes;
+#[cfg(feature = "notmuch-backend")]
+use himalaya_lib::backends::NotmuchMboxes;
+
+impl PrintTable for dyn Mboxes {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        #[cfg(feature = "imap-backend")]
+        if let Some(mboxes) = self.as_any().downcast_ref::<ImapMboxes>() {
+            return mboxes.print_table(writer, opts);
+        }
+
+        #[cfg(feature = "maildir-backend")]
+        if let Some(mboxes) = self.as_any().downcast_ref::<MaildirMboxes>() {
+            return mboxes.print_table(writer, opts);
+        }
+
+        #[cfg(feature = "notmuch-backend")]
+        if let Some(mboxes) = self.as_any().downcast_ref::<NotmuchMboxes>() {
+            return mboxes.print_table(writer, opts);
+        }
+
+        if let Some(mboxes) = self.as_any().downcast_ref::<MemoryMboxes>() {
+            return mboxes.print_table(writer, opts);
+        }
+
+        Err(anyhow!("cannot print mailboxes: unsupported backend"))
+    }
 }
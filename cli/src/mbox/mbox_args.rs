@@ -10,25 +10,77 @@ use log::{debug, info};
 use crate::ui::table_arg;
 
 type MaxTableWidth = Option<usize>;
+type Subscribed = bool;
+type Mbox<'a> = &'a str;
 
 /// Represents the mailbox commands.
 #[derive(Debug, PartialEq, Eq)]
-pub enum Cmd {
+pub enum Cmd<'a> {
     /// Represents the list mailboxes command.
-    List(MaxTableWidth),
+    List(MaxTableWidth, Subscribed),
+    /// Represents the create mailbox command.
+    Create(Mbox<'a>),
+    /// Represents the delete mailbox command.
+    Delete(Mbox<'a>),
+    /// Represents the rename mailbox command.
+    Rename(Mbox<'a>, Mbox<'a>),
+    /// Represents the subscribe mailbox command.
+    Subscribe(Mbox<'a>),
+    /// Represents the unsubscribe mailbox command.
+    Unsubscribe(Mbox<'a>),
 }
 
 /// Defines the mailbox command matcher.
-pub fn matches(m: &clap::ArgMatches) -> Result<Option<Cmd>> {
+pub fn matches<'a>(m: &'a clap::ArgMatches) -> Result<Option<Cmd<'a>>> {
     info!("entering mailbox command matcher");
 
     if let Some(m) = m.subcommand_matches("mailboxes") {
         info!("mailboxes command matched");
+
+        if let Some(m) = m.subcommand_matches("create") {
+            info!("create subcommand matched");
+            let mbox = m.value_of("mbox").unwrap();
+            debug!("mailbox: {}", mbox);
+            return Ok(Some(Cmd::Create(mbox)));
+        }
+
+        if let Some(m) = m.subcommand_matches("delete") {
+            info!("delete subcommand matched");
+            let mbox = m.value_of("mbox").unwrap();
+            debug!("mailbox: {}", mbox);
+            return Ok(Some(Cmd::Delete(mbox)));
+        }
+
+        if let Some(m) = m.subcommand_matches("rename") {
+            info!("rename subcommand matched");
+            let mbox = m.value_of("mbox").unwrap();
+            debug!("mailbox: {}", mbox);
+            let new_mbox = m.value_of("new-mbox").unwrap();
+            debug!("new mailbox: {}", new_mbox);
+            return Ok(Some(Cmd::Rename(mbox, new_mbox)));
+        }
+
+        if let Some(m) = m.subcommand_matches("subscribe") {
+            info!("subscribe subcommand matched");
+            let mbox = m.value_of("mbox").unwrap();
+            debug!("mailbox: {}", mbox);
+            return Ok(Some(Cmd::Subscribe(mbox)));
+        }
+
+        if let Some(m) = m.subcommand_matches("unsubscribe") {
+            info!("unsubscribe subcommand matched");
+            let mbox = m.value_of("mbox").unwrap();
+            debug!("mailbox: {}", mbox);
+            return Ok(Some(Cmd::Unsubscribe(mbox)));
+        }
+
         let max_table_width = m
             .value_of("max-table-width")
             .and_then(|width| width.parse::<usize>().ok());
         debug!("max table width: {:?}", max_table_width);
-        return Ok(Some(Cmd::List(max_table_width)));
+        let subscribed = m.is_present("subscribed");
+        debug!("subscribed: {}", subscribed);
+        return Ok(Some(Cmd::List(max_table_width, subscribed)));
     }
 
     Ok(None)
@@ -39,7 +91,49 @@ pub fn subcmds<'a>() -> Vec<clap::App<'a, 'a>> {
     vec![clap::SubCommand::with_name("mailboxes")
         .aliases(&["mailbox", "mboxes", "mbox", "mb", "m"])
         .about("Lists mailboxes")
-        .arg(table_arg::max_width())]
+        .arg(table_arg::max_width())
+        .arg(
+            clap::Arg::with_name("subscribed")
+                .long("subscribed")
+                .short("s")
+                .help("Lists only subscribed mailboxes"),
+        )
+        .subcommands(vec![
+            clap::SubCommand::with_name("create")
+                .aliases(&["add", "new", "c"])
+                .about("Creates a mailbox")
+                .arg(mbox_arg()),
+            clap::SubCommand::with_name("delete")
+                .aliases(&["remove", "rm", "d"])
+                .about("Deletes a mailbox")
+                .arg(mbox_arg()),
+            clap::SubCommand::with_name("rename")
+                .aliases(&["mv", "r"])
+                .about("Renames a mailbox")
+                .arg(mbox_arg())
+                .arg(
+                    clap::Arg::with_name("new-mbox")
+                        .help("Specifies the new name of the mailbox")
+                        .value_name("NEW_MAILBOX")
+                        .required(true),
+                ),
+            clap::SubCommand::with_name("subscribe")
+                .aliases(&["sub"])
+                .about("Subscribes to a mailbox")
+                .arg(mbox_arg()),
+            clap::SubCommand::with_name("unsubscribe")
+                .aliases(&["unsub"])
+                .about("Unsubscribes from a mailbox")
+                .arg(mbox_arg()),
+        ])]
+}
+
+/// Defines the mailbox argument of the mailbox subcommands.
+fn mbox_arg<'a>() -> clap::Arg<'a, 'a> {
+    clap::Arg::with_name("mbox")
+        .help("Specifies the mailbox")
+        .value_name("MAILBOX")
+        .required(true)
 }
 
 /// Defines the source mailbox argument.
@@ -68,12 +162,42 @@ mod tests {
         let arg = clap::App::new("himalaya")
             .subcommands(subcmds())
             .get_matches_from(&["himalaya", "mailboxes"]);
-        assert_eq!(Some(Cmd::List(None)), matches(&arg).unwrap());
+        assert_eq!(Some(Cmd::List(None, false)), matches(&arg).unwrap());
 
         let arg = clap::App::new("himalaya")
             .subcommands(subcmds())
             .get_matches_from(&["himalaya", "mailboxes", "--max-width", "20"]);
-        assert_eq!(Some(Cmd::List(Some(20))), matches(&arg).unwrap());
+        assert_eq!(Some(Cmd::List(Some(20), false)), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "mailboxes", "--subscribed"]);
+        assert_eq!(Some(Cmd::List(None, true)), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "mailboxes", "create", "Foo"]);
+        assert_eq!(Some(Cmd::Create("Foo")), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "mailboxes", "delete", "Foo"]);
+        assert_eq!(Some(Cmd::Delete("Foo")), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "mailboxes", "rename", "Foo", "Bar"]);
+        assert_eq!(Some(Cmd::Rename("Foo", "Bar")), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "mailboxes", "subscribe", "Foo"]);
+        assert_eq!(Some(Cmd::Subscribe("Foo")), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "mailboxes", "unsubscribe", "Foo"]);
+        assert_eq!(Some(Cmd::Unsubscribe("Foo")), matches(&arg).unwrap());
     }
 
     #[test]
@@ -5,43 +5,110 @@
 use anyhow::Result;
 use log::{info, trace};
 
-use crate::{
-    backends::Backend,
-    config::AccountConfig,
-    output::{PrintTableOpts, PrinterService},
-};
+use himalaya_lib::{backends::Backend, config::AccountConfig};
 
-/// Lists all mailboxes.
+use crate::output::{PrintTableOpts, PrinterService};
+
+/// Lists all mailboxes, or only the subscribed ones.
 pub fn list<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
     max_width: Option<usize>,
+    subscribed: bool,
     config: &AccountConfig,
     printer: &mut P,
     backend: Box<&'a mut B>,
 ) -> Result<()> {
     info!("entering list mailbox handler");
-    let mboxes = backend.get_mboxes()?;
+    let mboxes = if subscribed {
+        backend.get_subscribed_mboxes()?
+    } else {
+        backend.get_mboxes()?
+    };
     trace!("mailboxes: {:?}", mboxes);
     printer.print_table(
         mboxes,
         PrintTableOpts {
             format: &config.format,
             max_width,
+            columns: &[],
         },
     )
 }
 
+/// Creates the given mailbox.
+pub fn create<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
+    mbox: &str,
+    printer: &mut P,
+    backend: Box<&'a mut B>,
+) -> Result<()> {
+    info!("entering create mailbox handler");
+    backend.add_mbox(mbox)?;
+    printer.print_struct(format!(r#"Mailbox "{}" successfully created"#, mbox))
+}
+
+/// Deletes the given mailbox.
+pub fn delete<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
+    mbox: &str,
+    printer: &mut P,
+    backend: Box<&'a mut B>,
+) -> Result<()> {
+    info!("entering delete mailbox handler");
+    backend.del_mbox(mbox)?;
+    printer.print_struct(format!(r#"Mailbox "{}" successfully deleted"#, mbox))
+}
+
+/// Renames the given mailbox.
+pub fn rename<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
+    mbox: &str,
+    new_mbox: &str,
+    printer: &mut P,
+    backend: Box<&'a mut B>,
+) -> Result<()> {
+    info!("entering rename mailbox handler");
+    backend.rename_mbox(mbox, new_mbox)?;
+    printer.print_struct(format!(
+        r#"Mailbox "{}" successfully renamed to "{}""#,
+        mbox, new_mbox
+    ))
+}
+
+/// Subscribes to the given mailbox.
+pub fn subscribe<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
+    mbox: &str,
+    printer: &mut P,
+    backend: Box<&'a mut B>,
+) -> Result<()> {
+    info!("entering subscribe mailbox handler");
+    backend.subscribe_mbox(mbox)?;
+    printer.print_struct(format!(r#"Successfully subscribed to mailbox "{}""#, mbox))
+}
+
+/// Unsubscribes from the given mailbox.
+pub fn unsubscribe<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
+    mbox: &str,
+    printer: &mut P,
+    backend: Box<&'a mut B>,
+) -> Result<()> {
+    info!("entering unsubscribe mailbox handler");
+    backend.unsubscribe_mbox(mbox)?;
+    printer.print_struct(format!(
+        r#"Successfully unsubscribed from mailbox "{}""#,
+        mbox
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fmt::Debug, io};
     use termcolor::ColorSpec;
 
-    use crate::{
+    use himalaya_lib::{
         backends::{ImapMbox, ImapMboxAttr, ImapMboxAttrs, ImapMboxes},
-        mbox::Mboxes,
-        msg::{Envelopes, Msg},
-        output::{Print, PrintTable, WriteColor},
+        mbox::{MboxCounts, Mboxes},
+        msg::{Envelopes, Flags, IdSet, Msg, SortCriteria, Uid},
     };
 
+    use crate::output::{Print, PrintTable, WriteColor};
+
     use super::*;
 
     #[test]
@@ -111,6 +178,8 @@ mod tests {
         struct TestBackend;
 
         impl<'a> Backend<'a> for TestBackend {
+            type Id = Uid;
+
             fn add_mbox(&mut self, _: &str) -> Result<()> {
                 unimplemented!();
             }
@@ -121,6 +190,7 @@ mod tests {
                             delim: "/".into(),
                             name: "INBOX".into(),
                             attrs: ImapMboxAttrs(vec![ImapMboxAttr::NoSelect]),
+                            counts: None,
                         },
                         ImapMbox {
                             delim: "/".into(),
@@ -129,6 +199,12 @@ mod tests {
                                 ImapMboxAttr::NoInferiors,
                                 ImapMboxAttr::Custom("HasNoChildren".into()),
                             ]),
+                            counts: Some(MboxCounts {
+                                messages: 10,
+                                unseen: 2,
+                                recent: Some(0),
+                                size: None,
+                            }),
                         },
                     ],
                 }))
@@ -143,34 +219,34 @@ mod tests {
                 &mut self,
                 _: &str,
                 _: &str,
-                _: &str,
+                _: &SortCriteria,
                 _: usize,
                 _: usize,
             ) -> Result<Box<dyn Envelopes>> {
                 unimplemented!()
             }
-            fn add_msg(&mut self, _: &str, _: &[u8], _: &str) -> Result<Box<dyn ToString>> {
+            fn add_msg(&mut self, _: &str, _: &[u8], _: &Flags) -> Result<Uid> {
                 unimplemented!()
             }
-            fn get_msg(&mut self, _: &str, _: &str) -> Result<Msg> {
+            fn get_msg(&mut self, _: &str, _: &Uid) -> Result<Msg> {
                 unimplemented!()
             }
-            fn copy_msg(&mut self, _: &str, _: &str, _: &str) -> Result<()> {
+            fn copy_msg(&mut self, _: &str, _: &str, _: &IdSet<Uid>) -> Result<()> {
                 unimplemented!()
             }
-            fn move_msg(&mut self, _: &str, _: &str, _: &str) -> Result<()> {
+            fn move_msg(&mut self, _: &str, _: &str, _: &IdSet<Uid>) -> Result<()> {
                 unimplemented!()
             }
-            fn del_msg(&mut self, _: &str, _: &str) -> Result<()> {
+            fn del_msg(&mut self, _: &str, _: &IdSet<Uid>) -> Result<()> {
                 unimplemented!()
             }
-            fn add_flags(&mut self, _: &str, _: &str, _: &str) -> Result<()> {
+            fn add_flags(&mut self, _: &str, _: &IdSet<Uid>, _: &Flags) -> Result<()> {
                 unimplemented!()
             }
-            fn set_flags(&mut self, _: &str, _: &str, _: &str) -> Result<()> {
+            fn set_flags(&mut self, _: &str, _: &IdSet<Uid>, _: &Flags) -> Result<()> {
                 unimplemented!()
             }
-            fn del_flags(&mut self, _: &str, _: &str, _: &str) -> Result<()> {
+            fn del_flags(&mut self, _: &str, _: &IdSet<Uid>, _: &Flags) -> Result<()> {
                 unimplemented!()
             }
         }
@@ -180,13 +256,13 @@ mod tests {
         let mut backend = TestBackend {};
         let backend = Box::new(&mut backend);
 
-        assert!(list(None, &config, &mut printer, backend).is_ok());
+        assert!(list(None, false, &config, &mut printer, backend).is_ok());
         assert_eq!(
             concat![
                 "\n",
-                "DELIM │NAME  │ATTRIBUTES                 \n",
-                "/     │INBOX │NoSelect                   \n",
-                "/     │Sent  │NoInferiors, HasNoChildren \n",
+                "DELIM │NAME  │UNSEEN │TOTAL │RECENT │ATTRIBUTES                 \n",
+                "/     │INBOX │       │      │       │NoSelect                   \n",
+                "/     │Sent  │2      │10    │0      │NoInferiors, HasNoChildren \n",
                 "\n"
             ],
             printer.writer.content
@@ -0,0 +1,47 @@
+//! Attachment module.
+//!
+//! This module provides the table representation of attachments.
+
+use anyhow::Result;
+use himalaya_lib::{
+    mbox::size_to_string,
+    msg::{Attachment, Attachments},
+};
+
+use crate::{
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+impl PrintTable for Attachments {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+impl Table for Attachment {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("INDEX").bold().underline().white())
+            .cell(
+                Cell::new("FILENAME")
+                    .shrinkable()
+                    .bold()
+                    .underline()
+                    .white(),
+            )
+            .cell(Cell::new("MIME").bold().underline().white())
+            .cell(Cell::new("SIZE").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        Row::new()
+            .cell(Cell::new(self.index.to_string()).red())
+            .cell(Cell::new(&self.filename).shrinkable().green())
+            .cell(Cell::new(&self.mime).blue())
+            .cell(Cell::new(size_to_string(self.size as u64)).yellow())
+    }
+}
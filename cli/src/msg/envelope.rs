@@ -1,13 +1,36 @@
-use std::{any, fmt};
+use anyhow::{anyhow, Result};
+use himalaya_lib::{backends::MemoryEnvelopes, msg::Envelopes};
 
-use crate::output::PrintTable;
+use crate::output::{PrintTable, PrintTableOpts, WriteColor};
 
-pub trait Envelopes: fmt::Debug + erased_serde::Serialize + PrintTable + any::Any {
-    fn as_any(&self) -> &dyn any::Any;
-}
+#[cfg(feature = "imap-backend")]
+use himalaya_lib::backends::ImapEnvelopes;
+#[cfg(feature = "maildir-backend")]
+use himalaya_lib::backends::MaildirEnvelopes;
+#[cfg(feature = "notmuch-backend")]
+use himalaya_lib::backends::NotmuchEnvelopes;
+
+impl PrintTable for dyn Envelopes {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        #[cfg(feature = "imap-backend")]
+        if let Some(envelopes) = self.as_any().downcast_ref::<ImapEnvelopes>() {
+            return envelopes.print_table(writer, opts);
+        }
+
+        #[cfg(feature = "maildir-backend")]
+        if let Some(envelopes) = self.as_any().downcast_ref::<MaildirEnvelopes>() {
+            return envelopes.print_table(writer, opts);
+        }
+
+        #[cfg(feature = "notmuch-backend")]
+        if let Some(envelopes) = self.as_any().downcast_ref::<NotmuchEnvelopes>() {
+            return envelopes.print_table(writer, opts);
+        }
+
+        if let Some(envelopes) = self.as_any().downcast_ref::<MemoryEnvelopes>() {
+            return envelopes.print_table(writer, opts);
+        }
 
-impl<T: fmt::Debug + erased_serde::Serialize + PrintTable + any::Any> Envelopes for T {
-    fn as_any(&self) -> &dyn any::Any {
-        self
+        Err(anyhow!("cannot print envelopes: unsupported backend"))
     }
 }
@@ -5,65 +5,67 @@
 
 use anyhow::Result;
 use clap::{self, App, AppSettings, Arg, ArgMatches, SubCommand};
+use himalaya_lib::msg::{Flags, IdSet, MsgId};
 use log::{debug, info};
+use std::convert::TryFrom;
 
 use crate::msg::msg_args;
 
-type SeqRange<'a> = &'a str;
-type Flags = String;
-
 /// Represents the flag commands.
 #[derive(Debug, PartialEq, Eq)]
-pub enum Cmd<'a> {
+pub enum Cmd<I> {
     /// Represents the add flags command.
-    Add(SeqRange<'a>, Flags),
+    Add(IdSet<I>, Flags),
     /// Represents the set flags command.
-    Set(SeqRange<'a>, Flags),
+    Set(IdSet<I>, Flags),
     /// Represents the remove flags command.
-    Remove(SeqRange<'a>, Flags),
+    Remove(IdSet<I>, Flags),
 }
 
 /// Defines the flag command matcher.
-pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
+pub fn matches<I: MsgId>(m: &ArgMatches) -> Result<Option<Cmd<I>>> {
     info!("entering message flag command matcher");
 
     if let Some(m) = m.subcommand_matches("add") {
         info!("add subcommand matched");
-        let seq_range = m.value_of("seq-range").unwrap();
-        debug!("seq range: {}", seq_range);
-        let flags: String = m
+        let ids = IdSet::try_from(m.value_of("seq-range").unwrap())?;
+        debug!("ids: {}", ids);
+        let flags = m
             .values_of("flags")
             .unwrap_or_default()
             .collect::<Vec<_>>()
             .join(" ");
-        debug!("flags: {:?}", flags);
-        return Ok(Some(Cmd::Add(seq_range, flags)));
+        let flags = Flags::try_from(flags.as_str())?;
+        debug!("flags: {}", flags);
+        return Ok(Some(Cmd::Add(ids, flags)));
     }
 
     if let Some(m) = m.subcommand_matches("set") {
         info!("set subcommand matched");
-        let seq_range = m.value_of("seq-range").unwrap();
-        debug!("seq range: {}", seq_range);
-        let flags: String = m
+        let ids = IdSet::try_from(m.value_of("seq-range").unwrap())?;
+        debug!("ids: {}", ids);
+        let flags = m
             .values_of("flags")
             .unwrap_or_default()
             .collect::<Vec<_>>()
             .join(" ");
-        debug!("flags: {:?}", flags);
-        return Ok(Some(Cmd::Set(seq_range, flags)));
+        let flags = Flags::try_from(flags.as_str())?;
+        debug!("flags: {}", flags);
+        return Ok(Some(Cmd::Set(ids, flags)));
     }
 
     if let Some(m) = m.subcommand_matches("remove") {
         info!("remove subcommand matched");
-        let seq_range = m.value_of("seq-range").unwrap();
-        debug!("seq range: {}", seq_range);
-        let flags: String = m
+        let ids = IdSet::try_from(m.value_of("seq-range").unwrap())?;
+        debug!("ids: {}", ids);
+        let flags = m
             .values_of("flags")
             .unwrap_or_default()
             .collect::<Vec<_>>()
             .join(" ");
-        debug!("flags: {:?}", flags);
-        return Ok(Some(Cmd::Remove(seq_range, flags)));
+        let flags = Flags::try_from(flags.as_str())?;
+        debug!("flags: {}", flags);
+        return Ok(Some(Cmd::Remove(ids, flags)));
     }
 
     Ok(None)
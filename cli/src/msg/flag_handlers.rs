@@ -4,52 +4,145 @@
 
 use anyhow::Result;
 
-use crate::{backends::Backend, output::PrinterService};
+use himalaya_lib::{
+    backends::Backend,
+    msg::{Flags, IdSet},
+};
 
-/// Adds flags to all messages matching the given sequence range.
+use crate::output::PrinterService;
+
+/// Adds flags to all messages matching the given id set.
 /// Flags are case-insensitive, and they do not need to be prefixed with `\`.
 pub fn add<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq_range: &'a str,
-    flags: &'a str,
+    ids: &IdSet<B::Id>,
+    flags: &Flags,
     mbox: &'a str,
     printer: &'a mut P,
     backend: Box<&'a mut B>,
 ) -> Result<()> {
-    backend.add_flags(mbox, seq_range, flags)?;
+    backend.add_flags(mbox, ids, flags)?;
     printer.print_struct(format!(
         "Flag(s) {:?} successfully added to message(s) {:?}",
-        flags, seq_range
+        flags.to_string(),
+        ids.to_string()
     ))
 }
 
-/// Removes flags from all messages matching the given sequence range.
+/// Removes flags from all messages matching the given id set.
 /// Flags are case-insensitive, and they do not need to be prefixed with `\`.
 pub fn remove<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq_range: &'a str,
-    flags: &'a str,
+    ids: &IdSet<B::Id>,
+    flags: &Flags,
     mbox: &'a str,
     printer: &'a mut P,
     backend: Box<&'a mut B>,
 ) -> Result<()> {
-    backend.del_flags(mbox, seq_range, flags)?;
+    backend.del_flags(mbox, ids, flags)?;
     printer.print_struct(format!(
         "Flag(s) {:?} successfully removed from message(s) {:?}",
-        flags, seq_range
+        flags.to_string(),
+        ids.to_string()
     ))
 }
 
-/// Replaces flags of all messages matching the given sequence range.
+/// Replaces flags of all messages matching the given id set.
 /// Flags are case-insensitive, and they do not need to be prefixed with `\`.
 pub fn set<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq_range: &'a str,
-    flags: &'a str,
+    ids: &IdSet<B::Id>,
+    flags: &Flags,
     mbox: &'a str,
     printer: &'a mut P,
     backend: Box<&'a mut B>,
 ) -> Result<()> {
-    backend.set_flags(mbox, seq_range, flags)?;
+    backend.set_flags(mbox, ids, flags)?;
     printer.print_struct(format!(
         "Flag(s) {:?} successfully set for message(s) {:?}",
-        flags, seq_range
+        flags.to_string(),
+        ids.to_string()
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use himalaya_lib::{
+        backends::{MemoryBackend, MemoryEnvelopes},
+        config::AccountConfig,
+    };
+    use std::{convert::TryFrom, fmt::Debug};
+
+    use crate::output::{Print, PrintTable, PrintTableOpts};
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct PrinterServiceTest {
+        pub prints: usize,
+    }
+
+    impl PrinterService for PrinterServiceTest {
+        fn print_str<T: Debug + Print>(&mut self, _data: T) -> Result<()> {
+            unimplemented!()
+        }
+        fn print_struct<T: Debug + Print + serde::Serialize>(&mut self, _data: T) -> Result<()> {
+            self.prints += 1;
+            Ok(())
+        }
+        fn print_table<T: Debug + PrintTable + erased_serde::Serialize + ?Sized>(
+            &mut self,
+            _data: Box<T>,
+            _opts: PrintTableOpts,
+        ) -> Result<()> {
+            unimplemented!()
+        }
+        fn is_json(&self) -> bool {
+            unimplemented!()
+        }
+    }
+
+    fn flags(backend: &mut MemoryBackend) -> Vec<String> {
+        backend
+            .get_envelopes("INBOX", 10, 0)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<MemoryEnvelopes>()
+            .unwrap()
+            .iter()
+            .map(|envelope| envelope.flags.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn it_should_add_set_and_remove_flags() {
+        let account_config = AccountConfig::default();
+        let mut backend = MemoryBackend::new(&account_config);
+        let mut printer = PrinterServiceTest::default();
+        for _ in 0..2 {
+            backend
+                .add_msg("INBOX", b"Subject: test\r\n\r\n", &Flags::default())
+                .unwrap();
+        }
+        let ids = IdSet::try_from("2").unwrap();
+
+        let seen = Flags::try_from("seen flagged").unwrap();
+        add(&ids, &seen, "INBOX", &mut printer, Box::new(&mut backend)).unwrap();
+        assert_eq!(vec!["seen flagged", ""], flags(&mut backend));
+
+        let seen = Flags::try_from("seen").unwrap();
+        remove(&ids, &seen, "INBOX", &mut printer, Box::new(&mut backend)).unwrap();
+        assert_eq!(vec!["flagged", ""], flags(&mut backend));
+
+        let answered = Flags::try_from("answered").unwrap();
+        let ids = IdSet::try_from("1:*").unwrap();
+        set(
+            &ids,
+            &answered,
+            "INBOX",
+            &mut printer,
+            Box::new(&mut backend),
+        )
+        .unwrap();
+        assert_eq!(vec!["answered", "answered"], flags(&mut backend));
+
+        assert_eq!(3, printer.prints);
+    }
+}
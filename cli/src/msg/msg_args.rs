@@ -4,7 +4,9 @@
 
 use anyhow::Result;
 use clap::{self, App, Arg, ArgMatches, SubCommand};
+use himalaya_lib::msg::{AttachmentSelector, IdSet, MsgId, SearchQuery, SortCriteria, TplOverride};
 use log::{debug, info, trace};
+use std::convert::TryFrom;
 
 use crate::{
     mbox::mbox_args,
@@ -12,7 +14,6 @@ use crate::{
     ui::table_arg,
 };
 
-type Seq<'a> = &'a str;
 type PageSize = usize;
 type Page = usize;
 type Mbox<'a> = &'a str;
@@ -23,67 +24,90 @@ type RawMsg<'a> = &'a str;
 type Query = String;
 type AttachmentPaths<'a> = Vec<&'a str>;
 type MaxTableWidth = Option<usize>;
+type Columns<'a> = Vec<&'a str>;
+type AttachmentSelectors = Vec<AttachmentSelector>;
+type ListAttachments = bool;
 type Encrypt = bool;
-type Criteria = String;
 type Headers<'a> = Vec<&'a str>;
 
-/// Message commands.
+/// Message commands. Message ids are parsed as the ids of the
+/// backend the commands are run against.
 #[derive(Debug, PartialEq, Eq)]
-pub enum Cmd<'a> {
-    Attachments(Seq<'a>),
-    Copy(Seq<'a>, Mbox<'a>),
-    Delete(Seq<'a>),
-    Forward(Seq<'a>, AttachmentPaths<'a>, Encrypt),
-    List(MaxTableWidth, Option<PageSize>, Page),
-    Move(Seq<'a>, Mbox<'a>),
-    Read(Seq<'a>, TextMime<'a>, Raw, Headers<'a>),
-    Reply(Seq<'a>, All, AttachmentPaths<'a>, Encrypt),
+pub enum Cmd<'a, I> {
+    Attachments(I, AttachmentSelectors, ListAttachments, MaxTableWidth),
+    Copy(IdSet<I>, Mbox<'a>),
+    Delete(IdSet<I>),
+    Forward(I, AttachmentPaths<'a>, Encrypt),
+    List(MaxTableWidth, Columns<'a>, Option<PageSize>, Page),
+    Move(IdSet<I>, Mbox<'a>),
+    Read(I, TextMime<'a>, Raw, Headers<'a>),
+    Reply(I, All, AttachmentPaths<'a>, Encrypt),
     Save(RawMsg<'a>),
-    Search(Query, MaxTableWidth, Option<PageSize>, Page),
-    Sort(Criteria, Query, MaxTableWidth, Option<PageSize>, Page),
+    Search(Query, MaxTableWidth, Columns<'a>, Option<PageSize>, Page),
+    Sort(
+        SortCriteria,
+        Query,
+        MaxTableWidth,
+        Columns<'a>,
+        Option<PageSize>,
+        Page,
+    ),
     Send(RawMsg<'a>),
-    Write(tpl_args::TplOverride<'a>, AttachmentPaths<'a>, Encrypt),
+    Thread(Query, MaxTableWidth, Columns<'a>, Option<PageSize>, Page),
+    Write(TplOverride<'a>, AttachmentPaths<'a>, Encrypt),
 
-    Flag(Option<flag_args::Cmd<'a>>),
-    Tpl(Option<tpl_args::Cmd<'a>>),
+    Flag(Option<flag_args::Cmd<I>>),
+    Tpl(Option<tpl_args::Cmd<'a, I>>),
 }
 
 /// Message command matcher.
-pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
+pub fn matches<'a, I: MsgId>(m: &'a ArgMatches) -> Result<Option<Cmd<'a, I>>> {
     info!("entering message command matcher");
 
     if let Some(m) = m.subcommand_matches("attachments") {
         info!("attachments command matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("seq: {}", seq);
-        return Ok(Some(Cmd::Attachments(seq)));
+        let id = I::try_from(m.value_of("seq").unwrap())?;
+        debug!("id: {}", id);
+        let selectors = m
+            .values_of("selectors")
+            .unwrap_or_default()
+            .map(AttachmentSelector::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        debug!("selectors: {:?}", selectors);
+        let list = m.is_present("list");
+        debug!("list: {}", list);
+        let max_table_width = m
+            .value_of("max-table-width")
+            .and_then(|width| width.parse::<usize>().ok());
+        debug!("max table width: {:?}", max_table_width);
+        return Ok(Some(Cmd::Attachments(id, selectors, list, max_table_width)));
     }
 
     if let Some(m) = m.subcommand_matches("copy") {
         info!("copy command matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("seq: {}", seq);
+        let ids = IdSet::try_from(m.value_of("seq").unwrap())?;
+        debug!("ids: {}", ids);
         let mbox = m.value_of("mbox-target").unwrap();
         debug!(r#"target mailbox: "{:?}""#, mbox);
-        return Ok(Some(Cmd::Copy(seq, mbox)));
+        return Ok(Some(Cmd::Copy(ids, mbox)));
     }
 
     if let Some(m) = m.subcommand_matches("delete") {
         info!("copy command matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("seq: {}", seq);
-        return Ok(Some(Cmd::Delete(seq)));
+        let ids = IdSet::try_from(m.value_of("seq").unwrap())?;
+        debug!("ids: {}", ids);
+        return Ok(Some(Cmd::Delete(ids)));
     }
 
     if let Some(m) = m.subcommand_matches("forward") {
         info!("forward command matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("seq: {}", seq);
+        let id = I::try_from(m.value_of("seq").unwrap())?;
+        debug!("id: {}", id);
         let paths: Vec<&str> = m.values_of("attachments").unwrap_or_default().collect();
         debug!("attachments paths: {:?}", paths);
         let encrypt = m.is_present("encrypt");
         debug!("encrypt: {}", encrypt);
-        return Ok(Some(Cmd::Forward(seq, paths, encrypt)));
+        return Ok(Some(Cmd::Forward(id, paths, encrypt)));
     }
 
     if let Some(m) = m.subcommand_matches("list") {
@@ -92,6 +116,8 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
             .value_of("max-table-width")
             .and_then(|width| width.parse::<usize>().ok());
         debug!("max table width: {:?}", max_table_width);
+        let columns: Vec<&str> = m.values_of("columns").unwrap_or_default().collect();
+        debug!("columns: {:?}", columns);
         let page_size = m.value_of("page-size").and_then(|s| s.parse().ok());
         debug!("page size: {:?}", page_size);
         let page = m
@@ -102,35 +128,35 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
             .map(|page| 1.max(page) - 1)
             .unwrap_or_default();
         debug!("page: {}", page);
-        return Ok(Some(Cmd::List(max_table_width, page_size, page)));
+        return Ok(Some(Cmd::List(max_table_width, columns, page_size, page)));
     }
 
     if let Some(m) = m.subcommand_matches("move") {
         info!("move command matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("seq: {}", seq);
+        let ids = IdSet::try_from(m.value_of("seq").unwrap())?;
+        debug!("ids: {}", ids);
         let mbox = m.value_of("mbox-target").unwrap();
         debug!("target mailbox: {:?}", mbox);
-        return Ok(Some(Cmd::Move(seq, mbox)));
+        return Ok(Some(Cmd::Move(ids, mbox)));
     }
 
     if let Some(m) = m.subcommand_matches("read") {
         info!("read command matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("seq: {}", seq);
+        let id = I::try_from(m.value_of("seq").unwrap())?;
+        debug!("id: {}", id);
         let mime = m.value_of("mime-type").unwrap();
         debug!("text mime: {}", mime);
         let raw = m.is_present("raw");
         debug!("raw: {}", raw);
         let headers: Vec<&str> = m.values_of("headers").unwrap_or_default().collect();
         debug!("headers: {:?}", headers);
-        return Ok(Some(Cmd::Read(seq, mime, raw, headers)));
+        return Ok(Some(Cmd::Read(id, mime, raw, headers)));
     }
 
     if let Some(m) = m.subcommand_matches("reply") {
         info!("reply command matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("seq: {}", seq);
+        let id = I::try_from(m.value_of("seq").unwrap())?;
+        debug!("id: {}", id);
         let all = m.is_present("reply-all");
         debug!("reply all: {}", all);
         let paths: Vec<&str> = m.values_of("attachments").unwrap_or_default().collect();
@@ -138,7 +164,7 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
         let encrypt = m.is_present("encrypt");
         debug!("encrypt: {}", encrypt);
 
-        return Ok(Some(Cmd::Reply(seq, all, paths, encrypt)));
+        return Ok(Some(Cmd::Reply(id, all, paths, encrypt)));
     }
 
     if let Some(m) = m.subcommand_matches("save") {
@@ -154,6 +180,8 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
             .value_of("max-table-width")
             .and_then(|width| width.parse::<usize>().ok());
         debug!("max table width: {:?}", max_table_width);
+        let columns: Vec<&str> = m.values_of("columns").unwrap_or_default().collect();
+        debug!("columns: {:?}", columns);
         let page_size = m.value_of("page-size").and_then(|s| s.parse().ok());
         debug!("page size: {:?}", page_size);
         let page = m
@@ -164,32 +192,15 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
             .map(|page| 1.max(page) - 1)
             .unwrap_or_default();
         debug!("page: {}", page);
-        let query = m
-            .values_of("query")
-            .unwrap_or_default()
-            .fold((false, vec![]), |(escape, mut cmds), cmd| {
-                match (cmd, escape) {
-                    // Next command is an arg and needs to be escaped
-                    ("subject", _) | ("body", _) | ("text", _) => {
-                        cmds.push(cmd.to_string());
-                        (true, cmds)
-                    }
-                    // Escaped arg commands
-                    (_, true) => {
-                        cmds.push(format!("\"{}\"", cmd));
-                        (false, cmds)
-                    }
-                    // Regular commands
-                    (_, false) => {
-                        cmds.push(cmd.to_string());
-                        (false, cmds)
-                    }
-                }
-            })
-            .1
-            .join(" ");
+        let query = query_from_args(m.values_of("query").unwrap_or_default())?;
         debug!("query: {}", query);
-        return Ok(Some(Cmd::Search(query, max_table_width, page_size, page)));
+        return Ok(Some(Cmd::Search(
+            query,
+            max_table_width,
+            columns,
+            page_size,
+            page,
+        )));
     }
 
     if let Some(m) = m.subcommand_matches("sort") {
@@ -198,6 +209,8 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
             .value_of("max-table-width")
             .and_then(|width| width.parse::<usize>().ok());
         debug!("max table width: {:?}", max_table_width);
+        let columns: Vec<&str> = m.values_of("columns").unwrap_or_default().collect();
+        debug!("columns: {:?}", columns);
         let page_size = m.value_of("page-size").and_then(|s| s.parse().ok());
         debug!("page size: {:?}", page_size);
         let page = m
@@ -213,36 +226,15 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
             .unwrap_or_default()
             .collect::<Vec<_>>()
             .join(" ");
-        debug!("criteria: {:?}", criteria);
-        let query = m
-            .values_of("query")
-            .unwrap_or_default()
-            .fold((false, vec![]), |(escape, mut cmds), cmd| {
-                match (cmd, escape) {
-                    // Next command is an arg and needs to be escaped
-                    ("subject", _) | ("body", _) | ("text", _) => {
-                        cmds.push(cmd.to_string());
-                        (true, cmds)
-                    }
-                    // Escaped arg commands
-                    (_, true) => {
-                        cmds.push(format!("\"{}\"", cmd));
-                        (false, cmds)
-                    }
-                    // Regular commands
-                    (_, false) => {
-                        cmds.push(cmd.to_string());
-                        (false, cmds)
-                    }
-                }
-            })
-            .1
-            .join(" ");
+        let criteria = SortCriteria::try_from(criteria.as_str())?;
+        debug!("criteria: {}", criteria);
+        let query = query_from_args(m.values_of("query").unwrap_or_default())?;
         debug!("query: {:?}", query);
         return Ok(Some(Cmd::Sort(
             criteria,
             query,
             max_table_width,
+            columns,
             page_size,
             page,
         )));
@@ -255,13 +247,42 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
         return Ok(Some(Cmd::Send(msg)));
     }
 
+    if let Some(m) = m.subcommand_matches("thread") {
+        info!("thread command matched");
+        let max_table_width = m
+            .value_of("max-table-width")
+            .and_then(|width| width.parse::<usize>().ok());
+        debug!("max table width: {:?}", max_table_width);
+        let columns: Vec<&str> = m.values_of("columns").unwrap_or_default().collect();
+        debug!("columns: {:?}", columns);
+        let page_size = m.value_of("page-size").and_then(|s| s.parse().ok());
+        debug!("page size: {:?}", page_size);
+        let page = m
+            .value_of("page")
+            .unwrap()
+            .parse()
+            .ok()
+            .map(|page| 1.max(page) - 1)
+            .unwrap_or_default();
+        debug!("page: {}", page);
+        let query = query_from_args(m.values_of("query").unwrap_or_default())?;
+        debug!("query: {}", query);
+        return Ok(Some(Cmd::Thread(
+            query,
+            max_table_width,
+            columns,
+            page_size,
+            page,
+        )));
+    }
+
     if let Some(m) = m.subcommand_matches("write") {
         info!("write command matched");
         let attachment_paths: Vec<&str> = m.values_of("attachments").unwrap_or_default().collect();
         debug!("attachments paths: {:?}", attachment_paths);
         let encrypt = m.is_present("encrypt");
         debug!("encrypt: {}", encrypt);
-        let tpl = tpl_args::TplOverride::from(m);
+        let tpl = tpl_args::tpl_override(m);
         return Ok(Some(Cmd::Write(tpl, attachment_paths, encrypt)));
     }
 
@@ -274,10 +295,34 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
     }
 
     info!("default list command matched");
-    Ok(Some(Cmd::List(None, None, 0)))
+    Ok(Some(Cmd::List(None, vec![], None, 0)))
 }
 
 /// Message sequence number argument.
+/// Builds the search query from the given args. The shell already
+/// removed the quotes around values containing whitespaces, so they
+/// are quoted back. The query is validated before being sent to the
+/// backend.
+fn query_from_args<'a>(args: impl Iterator<Item = &'a str>) -> Result<String> {
+    let query = args
+        .map(|arg| {
+            if !arg.contains(char::is_whitespace) || arg.starts_with('"') {
+                return arg.to_owned();
+            }
+            let quote = |value: &str| format!("\"{}\"", value.replace('"', "\\\""));
+            match arg.split_once(':') {
+                Some((key, value)) if key.chars().all(char::is_alphabetic) => {
+                    format!("{}:{}", key, quote(value))
+                }
+                _ => quote(arg),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    SearchQuery::try_from(query.as_str())?;
+    Ok(query)
+}
+
 pub fn seq_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("seq")
         .help("Specifies the targetted message")
@@ -289,7 +334,7 @@ pub fn seq_arg<'a>() -> Arg<'a, 'a> {
 pub fn seq_range_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("seq-range")
         .help("Specifies targetted message(s)")
-        .long_help("Specifies a range of targetted messages. The range follows the [RFC3501](https://datatracker.ietf.org/doc/html/rfc3501#section-9) format: `1:5` matches messages with sequence number between 1 and 5, `1,5` matches messages with sequence number 1 or 5, * matches all messages.")
+        .long_help("Specifies a range of targetted messages. The range follows the [RFC3501](https://datatracker.ietf.org/doc/html/rfc3501#section-9) format: `1:5` matches messages with id between 1 and 5, `1,5` matches messages with id 1 or 5, `1:*` matches all messages. IMAP ids are UIDs, as shown by the list command.")
         .value_name("SEQ")
         .required(true)
 }
@@ -349,6 +394,10 @@ pub fn encrypt_arg<'a>() -> Arg<'a, 'a> {
         .long("encrypt")
 }
 
+/// Search query long help, shared by the search, sort and thread
+/// commands.
+const QUERY_LONG_HELP: &str = "Terms are either `key:value` where key is one of from, to, cc, subject, body, flag, before or after, or a bare value matching the subject, the sender or the body. Terms can be combined with and, or, not (or -) and parentheses, they are combined with and by default. Dates follow the YYYY-MM-DD format. The query is case-insensitive, and works the same way for all backends.";
+
 /// Message subcommands.
 pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
     vec![
@@ -357,33 +406,50 @@ pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
         vec![
             SubCommand::with_name("attachments")
                 .aliases(&["attachment", "att", "a"])
-                .about("Downloads all message attachments")
-                .arg(msg_args::seq_arg()),
+                .about("Downloads or lists message attachments")
+                .arg(msg_args::seq_arg())
+                .arg(
+                    Arg::with_name("selectors")
+                        .help("Selects attachments, all of them by default")
+                        .long_help("Selects attachments by index (starting at 1), by MIME type (like `image/*`) or by file name (like `*.pdf`). MIME types and file names can contain `*` and `?` wildcards, and are matched case-insensitively. All attachments are selected by default.")
+                        .value_name("SELECTOR")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("list")
+                        .help("Lists attachments instead of downloading them")
+                        .short("l")
+                        .long("list"),
+                )
+                .arg(table_arg::max_width()),
             SubCommand::with_name("list")
                 .aliases(&["lst", "l"])
                 .about("Lists all messages")
                 .arg(page_size_arg())
                 .arg(page_arg())
-                .arg(table_arg::max_width()),
+                .arg(table_arg::max_width())
+                .arg(table_arg::columns()),
             SubCommand::with_name("search")
                 .aliases(&["s", "query", "q"])
-                .about("Lists messages matching the given IMAP query")
+                .about("Lists messages matching the given query")
                 .arg(page_size_arg())
                 .arg(page_arg())
                 .arg(table_arg::max_width())
+                .arg(table_arg::columns())
                 .arg(
                     Arg::with_name("query")
-                        .help("IMAP query")
-                        .long_help("The IMAP query format follows the [RFC3501](https://tools.ietf.org/html/rfc3501#section-6.4.4). The query is case-insensitive.")
+                        .help("Search query")
+                        .long_help(QUERY_LONG_HELP)
                         .value_name("QUERY")
                         .multiple(true)
                         .required(true),
                 ),
             SubCommand::with_name("sort")
-                .about("Sorts messages by the given criteria and matching the given IMAP query")
+                .about("Sorts messages by the given criteria and matching the given query")
                 .arg(page_size_arg())
                 .arg(page_arg())
                 .arg(table_arg::max_width())
+                .arg(table_arg::columns())
 		.arg(
 		    Arg::with_name("criterion")
 			.long("criterion")
@@ -405,10 +471,23 @@ pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
 		)
                 .arg(
                     Arg::with_name("query")
-                        .help("IMAP query")
-                        .long_help("The IMAP query format follows the [RFC3501](https://tools.ietf.org/html/rfc3501#section-6.4.4). The query is case-insensitive.")
+                        .help("Search query, all messages by default")
+                        .long_help(QUERY_LONG_HELP)
+                        .value_name("QUERY")
+                        .raw(true),
+                ),
+            SubCommand::with_name("thread")
+                .aliases(&["threads", "t"])
+                .about("Lists messages grouped by thread")
+                .arg(page_size_arg())
+                .arg(page_arg())
+                .arg(table_arg::max_width())
+                .arg(table_arg::columns())
+                .arg(
+                    Arg::with_name("query")
+                        .help("Search query, all messages by default")
+                        .long_help(QUERY_LONG_HELP)
                         .value_name("QUERY")
-			.default_value("ALL")
                         .raw(true),
                 ),
             SubCommand::with_name("write")
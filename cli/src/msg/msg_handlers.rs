@@ -4,6 +4,15 @@
 
 use anyhow::{Context, Result};
 use atty::Stream;
+use himalaya_lib::{
+    backends::Backend,
+    config::{AccountConfig, DEFAULT_SENT_FOLDER},
+    msg::{
+        AttachmentSelector, Flag, Flags, IdSet, Msg, Part, Parts, SortCriteria, TextPlainPart,
+        TplOverride,
+    },
+    smtp::SmtpService,
+};
 use log::{debug, info, trace};
 use mailparse::addrparse;
 use std::{
@@ -14,41 +23,68 @@ use std::{
 use url::Url;
 
 use crate::{
-    backends::Backend,
-    config::{AccountConfig, DEFAULT_SENT_FOLDER},
-    msg::{Msg, Part, Parts, TextPlainPart},
     output::{PrintTableOpts, PrinterService},
-    smtp::SmtpService,
+    ui::editor,
 };
 
-use super::tpl_args;
-
-/// Downloads all message attachments to the user account downloads directory.
+/// Downloads the selected message attachments to the user account
+/// downloads directory, or lists them. All attachments are selected
+/// when no selector is given. Only the selected attachments are
+/// fetched, when the backend supports it.
 pub fn attachments<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq: &str,
+    id: &B::Id,
+    selectors: &[AttachmentSelector],
+    list: bool,
+    max_width: Option<usize>,
     mbox: &str,
     config: &AccountConfig,
     printer: &mut P,
     backend: Box<&'a mut B>,
 ) -> Result<()> {
-    let attachments = backend.get_msg(mbox, seq)?.attachments();
+    let attachments = backend.get_attachments(mbox, id)?;
+    trace!("attachments: {:?}", attachments);
+
+    if list {
+        return printer.print_table(
+            Box::new(attachments),
+            PrintTableOpts {
+                format: &config.format,
+                max_width,
+                columns: &[],
+            },
+        );
+    }
+
+    let attachments: Vec<_> = attachments
+        .iter()
+        .filter(|attachment| {
+            selectors.is_empty()
+                || selectors
+                    .iter()
+                    .any(|selector| selector.matches(attachment))
+        })
+        .collect();
     let attachments_len = attachments.len();
 
     if attachments_len == 0 {
-        return printer.print_struct(format!("No attachment found for message {:?}", seq));
+        return printer.print_struct(format!(
+            "No attachment found for message {:?}",
+            id.to_string()
+        ));
     }
 
     printer.print_str(format!(
         "Found {:?} attachment{} for message {:?}",
         attachments_len,
         if attachments_len > 1 { "s" } else { "" },
-        seq
+        id.to_string()
     ))?;
 
     for attachment in attachments {
         let file_path = config.get_download_file_path(&attachment.filename)?;
         printer.print_str(format!("Downloading {:?}…", file_path))?;
-        fs::write(&file_path, &attachment.content)
+        let content = backend.get_attachment_content(mbox, id, attachment)?;
+        fs::write(&file_path, &content)
             .context(format!("cannot download attachment {:?}", file_path))?;
     }
 
@@ -61,33 +97,33 @@ pub fn attachments<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
 
 /// Copy a message from a mailbox to another.
 pub fn copy<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq: &str,
+    ids: &IdSet<B::Id>,
     mbox_src: &str,
     mbox_dst: &str,
     printer: &mut P,
     backend: Box<&mut B>,
 ) -> Result<()> {
-    backend.copy_msg(mbox_src, mbox_dst, seq)?;
+    backend.copy_msg(mbox_src, mbox_dst, ids)?;
     printer.print_struct(format!(
         r#"Message {} successfully copied to folder "{}""#,
-        seq, mbox_dst
+        ids, mbox_dst
     ))
 }
 
-/// Delete messages matching the given sequence range.
+/// Delete messages matching the given id set.
 pub fn delete<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq: &str,
+    ids: &IdSet<B::Id>,
     mbox: &str,
     printer: &mut P,
     backend: Box<&'a mut B>,
 ) -> Result<()> {
-    backend.del_msg(mbox, seq)?;
-    printer.print_struct(format!(r#"Message(s) {} successfully deleted"#, seq))
+    backend.del_msg(mbox, ids)?;
+    printer.print_struct(format!(r#"Message(s) {} successfully deleted"#, ids))
 }
 
 /// Forward the given message UID from the selected mailbox.
 pub fn forward<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
-    seq: &str,
+    id: &B::Id,
     attachments_paths: Vec<&str>,
     encrypt: bool,
     mbox: &str,
@@ -96,24 +132,19 @@ pub fn forward<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
     backend: Box<&'a mut B>,
     smtp: &mut S,
 ) -> Result<()> {
-    backend
-        .get_msg(mbox, seq)?
+    let msg = backend
+        .get_msg(mbox, id)?
         .into_forward(config)?
         .add_attachments(attachments_paths)?
-        .encrypt(encrypt)
-        .edit_with_editor(
-            tpl_args::TplOverride::default(),
-            config,
-            printer,
-            backend,
-            smtp,
-        )?;
+        .encrypt(encrypt);
+    editor::edit_msg_with_editor(msg, TplOverride::default(), config, printer, backend, smtp)?;
     Ok(())
 }
 
 /// List paginated messages from the selected mailbox.
 pub fn list<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
     max_width: Option<usize>,
+    columns: &[&str],
     page_size: Option<usize>,
     page: usize,
     mbox: &str,
@@ -130,6 +161,7 @@ pub fn list<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
         PrintTableOpts {
             format: &config.format,
             max_width,
+            columns,
         },
     )
 }
@@ -191,34 +223,28 @@ pub fn mailto<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
     };
     trace!("message: {:?}", msg);
 
-    msg.edit_with_editor(
-        tpl_args::TplOverride::default(),
-        config,
-        printer,
-        backend,
-        smtp,
-    )?;
+    editor::edit_msg_with_editor(msg, TplOverride::default(), config, printer, backend, smtp)?;
     Ok(())
 }
 
 /// Move a message from a mailbox to another.
 pub fn move_<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq: &str,
+    ids: &IdSet<B::Id>,
     mbox_src: &str,
     mbox_dst: &str,
     printer: &mut P,
     backend: Box<&'a mut B>,
 ) -> Result<()> {
-    backend.move_msg(mbox_src, mbox_dst, seq)?;
+    backend.move_msg(mbox_src, mbox_dst, ids)?;
     printer.print_struct(format!(
         r#"Message {} successfully moved to folder "{}""#,
-        seq, mbox_dst
+        ids, mbox_dst
     ))
 }
 
-/// Read a message by its sequence number.
+/// Read a message by its id.
 pub fn read<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq: &str,
+    id: &B::Id,
     text_mime: &str,
     raw: bool,
     headers: Vec<&str>,
@@ -227,7 +253,12 @@ pub fn read<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
     printer: &mut P,
     backend: Box<&'a mut B>,
 ) -> Result<()> {
-    let msg = backend.get_msg(mbox, seq)?;
+    let msg = backend.get_msg(mbox, id)?;
+    backend.add_flags(
+        mbox,
+        &IdSet::from(id.to_owned()),
+        &Flags::from(vec![Flag::Seen]),
+    )?;
 
     printer.print_struct(if raw {
         // Emails don't always have valid utf8. Using "lossy" to display what we can.
@@ -239,7 +270,7 @@ pub fn read<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
 
 /// Reply to the given message UID.
 pub fn reply<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
-    seq: &str,
+    id: &B::Id,
     all: bool,
     attachments_paths: Vec<&str>,
     encrypt: bool,
@@ -249,19 +280,17 @@ pub fn reply<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
     backend: Box<&'a mut B>,
     smtp: &mut S,
 ) -> Result<()> {
-    backend
-        .get_msg(mbox, seq)?
+    let msg = backend
+        .get_msg(mbox, id)?
         .into_reply(all, config)?
         .add_attachments(attachments_paths)?
-        .encrypt(encrypt)
-        .edit_with_editor(
-            tpl_args::TplOverride::default(),
-            config,
-            printer,
-            backend,
-            smtp,
-        )?
-        .add_flags(mbox, seq, "replied")
+        .encrypt(encrypt);
+    editor::edit_msg_with_editor(msg, TplOverride::default(), config, printer, backend, smtp)?
+        .add_flags(
+            mbox,
+            &IdSet::from(id.to_owned()),
+            &Flags::from(vec![Flag::Answered]),
+        )
 }
 
 /// Saves a raw message to the targetted mailbox.
@@ -290,7 +319,7 @@ pub fn save<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
             .collect::<Vec<String>>()
             .join("\r\n")
     };
-    backend.add_msg(mbox, raw_msg.as_bytes(), "seen")?;
+    backend.add_msg(mbox, raw_msg.as_bytes(), &Flags::from(vec![Flag::Seen]))?;
     Ok(())
 }
 
@@ -298,6 +327,7 @@ pub fn save<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
 pub fn search<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
     query: String,
     max_width: Option<usize>,
+    columns: &[&str],
     page_size: Option<usize>,
     page: usize,
     mbox: &str,
@@ -307,22 +337,24 @@ pub fn search<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
 ) -> Result<()> {
     let page_size = page_size.unwrap_or(config.default_page_size);
     debug!("page size: {}", page_size);
-    let msgs = backend.search_envelopes(mbox, &query, "", page_size, page)?;
+    let msgs = backend.search_envelopes(mbox, &query, &SortCriteria::default(), page_size, page)?;
     trace!("messages: {:#?}", msgs);
     printer.print_table(
         msgs,
         PrintTableOpts {
             format: &config.format,
             max_width,
+            columns,
         },
     )
 }
 
 /// Paginates messages from the selected mailbox matching the specified query, sorted by the given criteria.
 pub fn sort<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    sort: String,
+    sort: SortCriteria,
     query: String,
     max_width: Option<usize>,
+    columns: &[&str],
     page_size: Option<usize>,
     page: usize,
     mbox: &str,
@@ -339,6 +371,34 @@ pub fn sort<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
         PrintTableOpts {
             format: &config.format,
             max_width,
+            columns,
+        },
+    )
+}
+
+/// Paginates threads of messages from the selected mailbox matching
+/// the specified query.
+pub fn thread<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
+    query: String,
+    max_width: Option<usize>,
+    columns: &[&str],
+    page_size: Option<usize>,
+    page: usize,
+    mbox: &str,
+    config: &AccountConfig,
+    printer: &mut P,
+    backend: Box<&'a mut B>,
+) -> Result<()> {
+    let page_size = page_size.unwrap_or(config.default_page_size);
+    debug!("page size: {}", page_size);
+    let threads = backend.get_threads(mbox, &query, page_size, page)?;
+    trace!("threads: {:#?}", threads);
+    printer.print_table(
+        threads,
+        PrintTableOpts {
+            format: &config.format,
+            max_width,
+            columns,
         },
     )
 }
@@ -378,13 +438,17 @@ pub fn send<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
     trace!("raw message: {:?}", raw_msg);
     let msg = Msg::from_tpl(&raw_msg)?;
     smtp.send(&config, &msg)?;
-    backend.add_msg(&sent_folder, raw_msg.as_bytes(), "seen")?;
+    backend.add_msg(
+        &sent_folder,
+        raw_msg.as_bytes(),
+        &Flags::from(vec![Flag::Seen]),
+    )?;
     Ok(())
 }
 
 /// Compose a new message.
 pub fn write<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
-    tpl: tpl_args::TplOverride,
+    tpl: TplOverride,
     attachments_paths: Vec<&str>,
     encrypt: bool,
     config: &AccountConfig,
@@ -392,9 +456,9 @@ pub fn write<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
     backend: Box<&'a mut B>,
     smtp: &mut S,
 ) -> Result<()> {
-    Msg::default()
+    let msg = Msg::default()
         .add_attachments(attachments_paths)?
-        .encrypt(encrypt)
-        .edit_with_editor(tpl, config, printer, backend, smtp)?;
+        .encrypt(encrypt);
+    editor::edit_msg_with_editor(msg, tpl, config, printer, backend, smtp)?;
     Ok(())
 }
@@ -0,0 +1,18 @@
+use anyhow::{anyhow, Result};
+use himalaya_lib::msg::Threads;
+
+use crate::output::{PrintTable, PrintTableOpts, WriteColor};
+
+#[cfg(feature = "imap-backend")]
+use himalaya_lib::backends::ImapThreads;
+
+impl PrintTable for dyn Threads {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        #[cfg(feature = "imap-backend")]
+        if let Some(threads) = self.as_any().downcast_ref::<ImapThreads>() {
+            return threads.print_table(writer, opts);
+        }
+
+        Err(anyhow!("cannot print threads: unsupported backend"))
+    }
+}
@@ -4,81 +4,68 @@
 
 use anyhow::Result;
 use clap::{self, App, AppSettings, Arg, ArgMatches, SubCommand};
+use himalaya_lib::msg::{MsgId, TplOverride};
 use log::{debug, info, trace};
 
 use crate::msg::msg_args;
 
-type Seq<'a> = &'a str;
 type ReplyAll = bool;
 type AttachmentPaths<'a> = Vec<&'a str>;
 type Tpl<'a> = &'a str;
 
-#[derive(Debug, Default, PartialEq, Eq, Clone)]
-pub struct TplOverride<'a> {
-    pub subject: Option<&'a str>,
-    pub from: Option<Vec<&'a str>>,
-    pub to: Option<Vec<&'a str>>,
-    pub cc: Option<Vec<&'a str>>,
-    pub bcc: Option<Vec<&'a str>>,
-    pub headers: Option<Vec<&'a str>>,
-    pub body: Option<&'a str>,
-    pub sig: Option<&'a str>,
-}
-
-impl<'a> From<&'a ArgMatches<'a>> for TplOverride<'a> {
-    fn from(matches: &'a ArgMatches<'a>) -> Self {
-        Self {
-            subject: matches.value_of("subject"),
-            from: matches.values_of("from").map(|v| v.collect()),
-            to: matches.values_of("to").map(|v| v.collect()),
-            cc: matches.values_of("cc").map(|v| v.collect()),
-            bcc: matches.values_of("bcc").map(|v| v.collect()),
-            headers: matches.values_of("headers").map(|v| v.collect()),
-            body: matches.value_of("body"),
-            sig: matches.value_of("signature"),
-        }
+/// Builds the template override from the given matches.
+pub fn tpl_override<'a>(matches: &'a ArgMatches<'a>) -> TplOverride<'a> {
+    TplOverride {
+        subject: matches.value_of("subject"),
+        from: matches.values_of("from").map(|v| v.collect()),
+        to: matches.values_of("to").map(|v| v.collect()),
+        cc: matches.values_of("cc").map(|v| v.collect()),
+        bcc: matches.values_of("bcc").map(|v| v.collect()),
+        headers: matches.values_of("headers").map(|v| v.collect()),
+        body: matches.value_of("body"),
+        sig: matches.value_of("signature"),
     }
 }
 
 /// Message template commands.
 #[derive(Debug, PartialEq, Eq)]
-pub enum Cmd<'a> {
+pub enum Cmd<'a, I> {
     New(TplOverride<'a>),
-    Reply(Seq<'a>, ReplyAll, TplOverride<'a>),
-    Forward(Seq<'a>, TplOverride<'a>),
+    Reply(I, ReplyAll, TplOverride<'a>),
+    Forward(I, TplOverride<'a>),
     Save(AttachmentPaths<'a>, Tpl<'a>),
     Send(AttachmentPaths<'a>, Tpl<'a>),
 }
 
 /// Message template command matcher.
-pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
+pub fn matches<'a, I: MsgId>(m: &'a ArgMatches) -> Result<Option<Cmd<'a, I>>> {
     info!("entering message template command matcher");
 
     if let Some(m) = m.subcommand_matches("new") {
         info!("new subcommand matched");
-        let tpl = TplOverride::from(m);
+        let tpl = tpl_override(m);
         trace!("template override: {:?}", tpl);
         return Ok(Some(Cmd::New(tpl)));
     }
 
     if let Some(m) = m.subcommand_matches("reply") {
         info!("reply subcommand matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("sequence: {}", seq);
+        let id = I::try_from(m.value_of("seq").unwrap())?;
+        debug!("id: {}", id);
         let all = m.is_present("reply-all");
         debug!("reply all: {}", all);
-        let tpl = TplOverride::from(m);
+        let tpl = tpl_override(m);
         trace!("template override: {:?}", tpl);
-        return Ok(Some(Cmd::Reply(seq, all, tpl)));
+        return Ok(Some(Cmd::Reply(id, all, tpl)));
     }
 
     if let Some(m) = m.subcommand_matches("forward") {
         info!("forward subcommand matched");
-        let seq = m.value_of("seq").unwrap();
-        debug!("sequence: {}", seq);
-        let tpl = TplOverride::from(m);
+        let id = I::try_from(m.value_of("seq").unwrap())?;
+        debug!("id: {}", id);
+        let tpl = tpl_override(m);
         trace!("template args: {:?}", tpl);
-        return Ok(Some(Cmd::Forward(seq, tpl)));
+        return Ok(Some(Cmd::Forward(id, tpl)));
     }
 
     if let Some(m) = m.subcommand_matches("save") {
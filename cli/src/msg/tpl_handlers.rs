@@ -4,15 +4,15 @@
 
 use anyhow::Result;
 use atty::Stream;
-use std::io::{self, BufRead};
-
-use crate::{
+use himalaya_lib::{
     backends::Backend,
     config::AccountConfig,
-    msg::{Msg, TplOverride},
-    output::PrinterService,
+    msg::{Flag, Flags, Msg, TplOverride},
     smtp::SmtpService,
 };
+use std::io::{self, BufRead};
+
+use crate::output::PrinterService;
 
 /// Generate a new message template.
 pub fn new<'a, P: PrinterService>(
@@ -26,7 +26,7 @@ pub fn new<'a, P: PrinterService>(
 
 /// Generate a reply message template.
 pub fn reply<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq: &str,
+    id: &B::Id,
     all: bool,
     opts: TplOverride<'a>,
     mbox: &str,
@@ -35,7 +35,7 @@ pub fn reply<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
     backend: Box<&'a mut B>,
 ) -> Result<()> {
     let tpl = backend
-        .get_msg(mbox, seq)?
+        .get_msg(mbox, id)?
         .into_reply(all, config)?
         .to_tpl(opts, config)?;
     printer.print_struct(tpl)
@@ -43,7 +43,7 @@ pub fn reply<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
 
 /// Generate a forward message template.
 pub fn forward<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
-    seq: &str,
+    id: &B::Id,
     opts: TplOverride<'a>,
     mbox: &str,
     config: &'a AccountConfig,
@@ -51,7 +51,7 @@ pub fn forward<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
     backend: Box<&'a mut B>,
 ) -> Result<()> {
     let tpl = backend
-        .get_msg(mbox, seq)?
+        .get_msg(mbox, id)?
         .into_forward(config)?
         .to_tpl(opts, config)?;
     printer.print_struct(tpl)
@@ -78,7 +78,7 @@ pub fn save<'a, P: PrinterService, B: Backend<'a> + ?Sized>(
     };
     let msg = Msg::from_tpl(&tpl)?.add_attachments(attachments_paths)?;
     let raw_msg = msg.into_sendable_msg(config)?.formatted();
-    backend.add_msg(mbox, &raw_msg, "seen")?;
+    backend.add_msg(mbox, &raw_msg, &Flags::from(vec![Flag::Seen]))?;
     printer.print_struct("Template successfully saved")
 }
 
@@ -104,6 +104,6 @@ pub fn send<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
     };
     let msg = Msg::from_tpl(&tpl)?.add_attachments(attachments_paths)?;
     let sent_msg = smtp.send(account, &msg)?;
-    backend.add_msg(mbox, &sent_msg, "seen")?;
+    backend.add_msg(mbox, &sent_msg, &Flags::from(vec![Flag::Seen]))?;
     printer.print_struct("Template successfully sent")
 }
@@ -2,9 +2,6 @@
 
 pub mod output_args;
 
-pub mod output_utils;
-pub use output_utils::*;
-
 pub mod output_entity;
 pub use output_entity::*;
 
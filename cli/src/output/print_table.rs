@@ -1,9 +1,8 @@
 use anyhow::Result;
+use himalaya_lib::config::Format;
 use std::io;
 use termcolor::{self, StandardStream};
 
-use crate::config::Format;
-
 pub trait WriteColor: io::Write + termcolor::WriteColor {}
 
 impl WriteColor for StandardStream {}
@@ -15,4 +14,7 @@ pub trait PrintTable {
 pub struct PrintTableOpts<'a> {
     pub format: &'a Format,
     pub max_width: Option<usize>,
+    /// Represents the optional columns to show, see
+    /// [`crate::ui::Cell::optional`].
+    pub columns: &'a [&'a str],
 }
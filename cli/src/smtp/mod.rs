@@ -1 +0,0 @@
-//! Module related to SMTP.
@@ -1,8 +1,19 @@
 use anyhow::{Context, Result};
-use log::debug;
+use himalaya_lib::{
+    backends::Backend,
+    config::AccountConfig,
+    mbox::SpecialUse,
+    msg::{Flag, Flags, Msg, TplOverride},
+    smtp::SmtpService,
+};
+use log::{debug, info};
 use std::{env, fs, process::Command};
 
-use crate::msg::msg_utils;
+use crate::{
+    msg::msg_utils,
+    output::PrinterService,
+    ui::choice::{self, PostEditChoice, PreEditChoice},
+};
 
 pub fn open_with_tpl(tpl: String) -> Result<String> {
     let path = msg_utils::local_draft_path();
@@ -29,3 +40,93 @@ pub fn open_with_draft() -> Result<String> {
         fs::read_to_string(&path).context(format!("cannot read local draft at {:?}", path))?;
     open_with_tpl(tpl)
 }
+
+fn _edit_msg_with_editor(msg: &Msg, tpl: TplOverride, account: &AccountConfig) -> Result<Msg> {
+    let tpl = msg.to_tpl(tpl, account)?;
+    let tpl = open_with_tpl(tpl)?;
+    Msg::from_tpl(&tpl)
+}
+
+pub fn edit_msg_with_editor<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
+    mut msg: Msg,
+    tpl: TplOverride,
+    account: &AccountConfig,
+    printer: &mut P,
+    backend: Box<&'a mut B>,
+    smtp: &mut S,
+) -> Result<Box<&'a mut B>> {
+    info!("start editing with editor");
+
+    let draft = msg_utils::local_draft_path();
+    if draft.exists() {
+        loop {
+            match choice::pre_edit() {
+                Ok(choice) => match choice {
+                    PreEditChoice::Edit => {
+                        let tpl = open_with_draft()?;
+                        msg.merge_with(Msg::from_tpl(&tpl)?);
+                        break;
+                    }
+                    PreEditChoice::Discard => {
+                        msg.merge_with(_edit_msg_with_editor(&msg, tpl.clone(), account)?);
+                        break;
+                    }
+                    PreEditChoice::Quit => return Ok(backend),
+                },
+                Err(err) => {
+                    println!("{}", err);
+                    continue;
+                }
+            }
+        }
+    } else {
+        msg.merge_with(_edit_msg_with_editor(&msg, tpl.clone(), account)?);
+    }
+
+    loop {
+        match choice::post_edit() {
+            Ok(PostEditChoice::Send) => {
+                // The sent folder is resolved before sending, so that
+                // a failure does not leave a sent message unsaved.
+                let sent_folder = account.get_special_mbox(&mut **backend, SpecialUse::Sent)?;
+                printer.print_str("Sending message…")?;
+                let sent_msg = smtp.send(account, &msg)?;
+                printer.print_str(format!("Adding message to the {:?} folder…", sent_folder))?;
+                backend.add_msg(&sent_folder, &sent_msg, &Flags::from(vec![Flag::Seen]))?;
+                msg_utils::remove_local_draft()?;
+                printer.print_struct("Done!")?;
+                break;
+            }
+            Ok(PostEditChoice::Edit) => {
+                msg.merge_with(_edit_msg_with_editor(&msg, tpl.clone(), account)?);
+                continue;
+            }
+            Ok(PostEditChoice::LocalDraft) => {
+                printer.print_struct("Message successfully saved locally")?;
+                break;
+            }
+            Ok(PostEditChoice::RemoteDraft) => {
+                let tpl = msg.to_tpl(TplOverride::default(), account)?;
+                let draft_folder = account.get_special_mbox(&mut **backend, SpecialUse::Drafts)?;
+                backend.add_msg(
+                    &draft_folder,
+                    tpl.as_bytes(),
+                    &Flags::from(vec![Flag::Seen, Flag::Draft]),
+                )?;
+                msg_utils::remove_local_draft()?;
+                printer.print_struct(format!("Message successfully saved to {}", draft_folder))?;
+                break;
+            }
+            Ok(PostEditChoice::Discard) => {
+                msg_utils::remove_local_draft()?;
+                break;
+            }
+            Err(err) => {
+                println!("{}", err);
+                continue;
+            }
+        }
+    }
+
+    Ok(backend)
+}
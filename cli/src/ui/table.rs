@@ -10,10 +10,9 @@ use termcolor::{Color, ColorSpec};
 use terminal_size;
 use unicode_width::UnicodeWidthStr;
 
-use crate::{
-    config::Format,
-    output::{Print, PrintTableOpts, WriteColor},
-};
+use himalaya_lib::config::Format;
+
+use crate::output::{Print, PrintTableOpts, WriteColor};
 
 /// Defines the default terminal size.
 /// This is used when the size cannot be determined by the `terminal_size` crate.
@@ -33,6 +32,8 @@ pub struct Cell {
     value: String,
     /// (Dis)allowes the cell to shrink when the table exceeds the container width.
     shrinkable: bool,
+    /// Represents the name of the optional column the cell heads.
+    column: Option<&'static str>,
 }
 
 impl Cell {
@@ -67,6 +68,22 @@ impl Cell {
         self.shrinkable
     }
 
+    /// Makes the header cell head an optional column. The column is
+    /// only shown when its name is part of the selected columns.
+    pub fn optional(mut self, column: &'static str) -> Self {
+        self.column = Some(column);
+        self
+    }
+
+    /// Returns true if the header cell heads a column to show among
+    /// the given selected columns.
+    pub fn is_shown(&self, columns: &[&str]) -> bool {
+        match self.column {
+            Some(column) => columns.contains(&column),
+            None => true,
+        }
+    }
+
     /// Applies the bold style to the cell.
     pub fn bold(mut self) -> Self {
         self.style.set_bold(true);
@@ -155,6 +172,18 @@ impl Row {
         self.0.push(cell);
         self
     }
+
+    /// Keeps only the cells of the shown columns.
+    fn retain(self, shown: &[bool]) -> Self {
+        Self(
+            self.0
+                .into_iter()
+                .zip(shown)
+                .filter(|(_, shown)| **shown)
+                .map(|(cell, _)| cell)
+                .collect(),
+        )
+    }
 }
 
 /// Represents a table abstraction.
@@ -179,14 +208,20 @@ where
                 .or_else(|| terminal_size::terminal_size().map(|(w, _)| w.0 as usize))
                 .unwrap_or(DEFAULT_TERM_WIDTH),
         };
-        let mut table = vec![Self::head()];
+        let head = Self::head();
+        let shown: Vec<bool> = head
+            .0
+            .iter()
+            .map(|cell| cell.is_shown(opts.columns))
+            .collect();
+        let mut table = vec![head.retain(&shown)];
         let mut cell_widths: Vec<usize> =
             table[0].0.iter().map(|cell| cell.unicode_width()).collect();
         table.extend(
             items
                 .iter()
                 .map(|item| {
-                    let row = item.row();
+                    let row = item.row().retain(&shown);
                     row.0.iter().enumerate().for_each(|(i, cell)| {
                         cell_widths[i] = cell_widths[i].max(cell.unicode_width());
                     });
@@ -340,7 +375,7 @@ mod tests {
 
     macro_rules! write_items {
         ($writer:expr, $($item:expr),*) => {
-            Table::print($writer, &[$($item,)*], PrintTableOpts { format: &Format::Auto, max_width: Some(20) }).unwrap();
+            Table::print($writer, &[$($item,)*], PrintTableOpts { format: &Format::Auto, max_width: Some(20), columns: &[] }).unwrap();
         };
     }
 
@@ -443,4 +478,40 @@ mod tests {
         ];
         assert_eq!(expected, writer.content);
     }
+
+    struct OptionalItem(u16);
+
+    impl Table for OptionalItem {
+        fn head() -> Row {
+            Row::new()
+                .cell(Cell::new("ID"))
+                .cell(Cell::new("HIDDEN").optional("hidden"))
+                .cell(Cell::new("SHOWN").optional("shown"))
+        }
+
+        fn row(&self) -> Row {
+            Row::new()
+                .cell(Cell::new(self.0.to_string()))
+                .cell(Cell::new("hidden"))
+                .cell(Cell::new("shown"))
+        }
+    }
+
+    #[test]
+    fn optional_columns() {
+        let mut writer = StringWriter::default();
+        let opts = PrintTableOpts {
+            format: &Format::Auto,
+            max_width: Some(20),
+            columns: &["shown"],
+        };
+        Table::print(&mut writer, &[OptionalItem(1), OptionalItem(2)], opts).unwrap();
+
+        let expected = concat![
+            "ID │SHOWN \n",
+            "1  │shown \n",
+            "2  │shown \n",
+        ];
+        assert_eq!(expected, writer.content);
+    }
 }
@@ -8,3 +8,14 @@ pub fn max_width<'a>() -> Arg<'a, 'a> {
         .long("max-width")
         .value_name("INT")
 }
+
+/// Defines the optional envelope columns argument.
+pub fn columns<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("columns")
+        .help("Shows the given optional columns")
+        .long("columns")
+        .value_name("COLUMN")
+        .multiple(true)
+        .require_delimiter(true)
+        .possible_values(&["to", "cc", "reply-to", "message-id", "size", "attachment"])
+}
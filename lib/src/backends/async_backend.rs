@@ -0,0 +1,300 @@
+//! Async backend module.
+//!
+//! This module exposes the async variant of the backend trait, and a
+//! wrapper turning any async backend into a blocking one.
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use tokio::runtime::{self, Runtime};
+
+use crate::{
+    backends::Backend,
+    mbox::Mboxes,
+    msg::{Attachment, Attachments, Envelopes, Flags, IdSet, Msg, MsgId, SortCriteria, Threads},
+};
+
+#[async_trait]
+pub trait AsyncBackend: Send {
+    /// Represents the kind of identifier of the backend messages.
+    type Id: MsgId;
+
+    async fn connect(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn add_mbox(&mut self, mbox: &str) -> Result<()>;
+    async fn get_mboxes(&mut self) -> Result<Box<dyn Mboxes>>;
+
+    /// Gets the mailboxes the user subscribed to. Backends without
+    /// subscriptions consider all their mailboxes subscribed.
+    async fn get_subscribed_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        self.get_mboxes().await
+    }
+
+    async fn del_mbox(&mut self, mbox: &str) -> Result<()>;
+
+    async fn rename_mbox(&mut self, mbox: &str, _new_mbox: &str) -> Result<()> {
+        Err(anyhow!(
+            "cannot rename mailbox {:?}: feature not implemented",
+            mbox
+        ))
+    }
+
+    async fn subscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        Err(anyhow!(
+            "cannot subscribe to mailbox {:?}: feature not implemented",
+            mbox
+        ))
+    }
+
+    async fn unsubscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        Err(anyhow!(
+            "cannot unsubscribe from mailbox {:?}: feature not implemented",
+            mbox
+        ))
+    }
+    async fn get_envelopes(
+        &mut self,
+        mbox: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>>;
+    async fn search_envelopes(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>>;
+    async fn get_threads(
+        &mut self,
+        mbox: &str,
+        _query: &str,
+        _page_size: usize,
+        _page: usize,
+    ) -> Result<Box<dyn Threads>> {
+        Err(anyhow!(
+            "cannot get threads of mailbox {:?}: feature not implemented",
+            mbox
+        ))
+    }
+    async fn add_msg(&mut self, mbox: &str, msg: &[u8], flags: &Flags) -> Result<Self::Id>;
+    async fn get_msg(&mut self, mbox: &str, id: &Self::Id) -> Result<Msg>;
+    async fn get_attachments(&mut self, mbox: &str, id: &Self::Id) -> Result<Attachments> {
+        let attachments = self
+            .get_msg(mbox, id)
+            .await?
+            .attachments()
+            .into_iter()
+            .enumerate()
+            .map(|(i, attachment)| Attachment {
+                index: i + 1,
+                size: attachment.content.len(),
+                filename: attachment.filename,
+                mime: attachment.mime,
+                ..Attachment::default()
+            })
+            .collect();
+        Ok(Attachments { attachments })
+    }
+    async fn get_attachment_content(
+        &mut self,
+        mbox: &str,
+        id: &Self::Id,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>> {
+        let attachments = self.get_msg(mbox, id).await?.attachments();
+        attachment
+            .index
+            .checked_sub(1)
+            .and_then(|i| attachments.into_iter().nth(i))
+            .map(|attachment| attachment.content)
+            .ok_or_else(|| {
+                anyhow!(
+                    "cannot find attachment {} of message {}",
+                    attachment.index,
+                    id
+                )
+            })
+    }
+    async fn copy_msg(
+        &mut self,
+        mbox_src: &str,
+        mbox_dst: &str,
+        ids: &IdSet<Self::Id>,
+    ) -> Result<()>;
+    async fn move_msg(
+        &mut self,
+        mbox_src: &str,
+        mbox_dst: &str,
+        ids: &IdSet<Self::Id>,
+    ) -> Result<()>;
+    async fn del_msg(&mut self, mbox: &str, ids: &IdSet<Self::Id>) -> Result<()>;
+    async fn add_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()>;
+    async fn set_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()>;
+    async fn del_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()>;
+
+    async fn disconnect(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Wraps an async backend into a blocking one. Each call is run to
+/// completion on a dedicated single-threaded runtime.
+pub struct BlockingBackend<B: AsyncBackend> {
+    backend: B,
+    runtime: Runtime,
+}
+
+impl<B: AsyncBackend> BlockingBackend<B> {
+    pub fn new(backend: B) -> Result<Self> {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("cannot build async runtime")?;
+        Ok(Self { backend, runtime })
+    }
+
+    /// Runs the given future on the wrapper runtime. Useful to call
+    /// async methods that are not part of the backend trait.
+    pub fn block_on<'b, T, F>(&'b mut self, f: impl FnOnce(&'b mut B) -> F) -> T
+    where
+        F: std::future::Future<Output = T> + 'b,
+    {
+        self.runtime.block_on(f(&mut self.backend))
+    }
+
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+}
+
+impl<'a, B: AsyncBackend> Backend<'a> for BlockingBackend<B> {
+    type Id = B::Id;
+
+    fn connect(&mut self) -> Result<()> {
+        self.runtime.block_on(self.backend.connect())
+    }
+
+    fn add_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.runtime.block_on(self.backend.add_mbox(mbox))
+    }
+
+    fn get_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        self.runtime.block_on(self.backend.get_mboxes())
+    }
+
+    fn get_subscribed_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        self.runtime.block_on(self.backend.get_subscribed_mboxes())
+    }
+
+    fn del_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.runtime.block_on(self.backend.del_mbox(mbox))
+    }
+
+    fn rename_mbox(&mut self, mbox: &str, new_mbox: &str) -> Result<()> {
+        self.runtime
+            .block_on(self.backend.rename_mbox(mbox, new_mbox))
+    }
+
+    fn subscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.runtime.block_on(self.backend.subscribe_mbox(mbox))
+    }
+
+    fn unsubscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.runtime.block_on(self.backend.unsubscribe_mbox(mbox))
+    }
+
+    fn get_envelopes(
+        &mut self,
+        mbox: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        self.runtime
+            .block_on(self.backend.get_envelopes(mbox, page_size, page))
+    }
+
+    fn search_envelopes(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        self.runtime.block_on(
+            self.backend
+                .search_envelopes(mbox, query, sort, page_size, page),
+        )
+    }
+
+    fn get_threads(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Threads>> {
+        self.runtime
+            .block_on(self.backend.get_threads(mbox, query, page_size, page))
+    }
+
+    fn add_msg(&mut self, mbox: &str, msg: &[u8], flags: &Flags) -> Result<Self::Id> {
+        self.runtime
+            .block_on(self.backend.add_msg(mbox, msg, flags))
+    }
+
+    fn get_msg(&mut self, mbox: &str, id: &Self::Id) -> Result<Msg> {
+        self.runtime.block_on(self.backend.get_msg(mbox, id))
+    }
+
+    fn get_attachments(&mut self, mbox: &str, id: &Self::Id) -> Result<Attachments> {
+        self.runtime
+            .block_on(self.backend.get_attachments(mbox, id))
+    }
+
+    fn get_attachment_content(
+        &mut self,
+        mbox: &str,
+        id: &Self::Id,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>> {
+        self.runtime
+            .block_on(self.backend.get_attachment_content(mbox, id, attachment))
+    }
+
+    fn copy_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Self::Id>) -> Result<()> {
+        self.runtime
+            .block_on(self.backend.copy_msg(mbox_src, mbox_dst, ids))
+    }
+
+    fn move_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Self::Id>) -> Result<()> {
+        self.runtime
+            .block_on(self.backend.move_msg(mbox_src, mbox_dst, ids))
+    }
+
+    fn del_msg(&mut self, mbox: &str, ids: &IdSet<Self::Id>) -> Result<()> {
+        self.runtime.block_on(self.backend.del_msg(mbox, ids))
+    }
+
+    fn add_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()> {
+        self.runtime
+            .block_on(self.backend.add_flags(mbox, ids, flags))
+    }
+
+    fn set_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()> {
+        self.runtime
+            .block_on(self.backend.set_flags(mbox, ids, flags))
+    }
+
+    fn del_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()> {
+        self.runtime
+            .block_on(self.backend.del_flags(mbox, ids, flags))
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        self.runtime.block_on(self.backend.disconnect())
+    }
+}
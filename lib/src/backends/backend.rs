@@ -0,0 +1,145 @@
+//! Backend module.
+//!
+//! This module exposes the backend trait, which can be used to create
+//! custom backend implementations.
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    mbox::{Mboxes, SpecialUse},
+    msg::{Attachment, Attachments, Envelopes, Flags, IdSet, Msg, MsgId, SortCriteria, Threads},
+};
+
+pub trait Backend<'a> {
+    /// Represents the kind of identifier of the backend messages.
+    type Id: MsgId;
+
+    fn connect(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn add_mbox(&mut self, mbox: &str) -> Result<()>;
+    fn get_mboxes(&mut self) -> Result<Box<dyn Mboxes>>;
+
+    /// Gets the mailboxes the user subscribed to. Backends without
+    /// subscriptions consider all their mailboxes subscribed.
+    fn get_subscribed_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        self.get_mboxes()
+    }
+
+    fn del_mbox(&mut self, mbox: &str) -> Result<()>;
+
+    fn rename_mbox(&mut self, mbox: &str, _new_mbox: &str) -> Result<()> {
+        Err(anyhow!(
+            "cannot rename mailbox {:?}: feature not implemented",
+            mbox
+        ))
+    }
+
+    fn subscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        Err(anyhow!(
+            "cannot subscribe to mailbox {:?}: feature not implemented",
+            mbox
+        ))
+    }
+
+    fn unsubscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        Err(anyhow!(
+            "cannot unsubscribe from mailbox {:?}: feature not implemented",
+            mbox
+        ))
+    }
+
+    /// Finds the mailbox flagged by the backend with the given
+    /// special use. Backends without such flags return `None`, their
+    /// special mailboxes come from the account config.
+    fn find_special_mbox(&mut self, _special_use: SpecialUse) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn get_envelopes(
+        &mut self,
+        mbox: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>>;
+    fn search_envelopes(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>>;
+
+    /// Gets the threads of the messages matching the given query.
+    /// Pages are made of threads, the most recent ones first.
+    fn get_threads(
+        &mut self,
+        mbox: &str,
+        _query: &str,
+        _page_size: usize,
+        _page: usize,
+    ) -> Result<Box<dyn Threads>> {
+        Err(anyhow!(
+            "cannot get threads of mailbox {:?}: feature not implemented",
+            mbox
+        ))
+    }
+
+    fn add_msg(&mut self, mbox: &str, msg: &[u8], flags: &Flags) -> Result<Self::Id>;
+    fn get_msg(&mut self, mbox: &str, id: &Self::Id) -> Result<Msg>;
+
+    /// Gets the attachments of the given message, without their
+    /// content. The default implementation reads the whole message.
+    fn get_attachments(&mut self, mbox: &str, id: &Self::Id) -> Result<Attachments> {
+        let attachments = self
+            .get_msg(mbox, id)?
+            .attachments()
+            .into_iter()
+            .enumerate()
+            .map(|(i, attachment)| Attachment {
+                index: i + 1,
+                size: attachment.content.len(),
+                filename: attachment.filename,
+                mime: attachment.mime,
+                ..Attachment::default()
+            })
+            .collect();
+        Ok(Attachments { attachments })
+    }
+
+    /// Gets the content of the given attachment. The default
+    /// implementation reads the whole message.
+    fn get_attachment_content(
+        &mut self,
+        mbox: &str,
+        id: &Self::Id,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>> {
+        let attachments = self.get_msg(mbox, id)?.attachments();
+        attachment
+            .index
+            .checked_sub(1)
+            .and_then(|i| attachments.into_iter().nth(i))
+            .map(|attachment| attachment.content)
+            .ok_or_else(|| {
+                anyhow!(
+                    "cannot find attachment {} of message {}",
+                    attachment.index,
+                    id
+                )
+            })
+    }
+
+    fn copy_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Self::Id>) -> Result<()>;
+    fn move_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Self::Id>) -> Result<()>;
+    fn del_msg(&mut self, mbox: &str, ids: &IdSet<Self::Id>) -> Result<()>;
+    fn add_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()>;
+    fn set_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()>;
+    fn del_flags(&mut self, mbox: &str, ids: &IdSet<Self::Id>, flags: &Flags) -> Result<()>;
+
+    fn disconnect(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+//! Backend builder module.
+//!
+//! This module contains the backend builder, which turns an account
+//! config and a backend config into a backend. Library users can
+//! register their own backend kinds, selected from the account config
+//! with the `backend` key.
+
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::collections::HashMap;
+
+use crate::{
+    backends::{Backend, MemoryBackend},
+    config::{AccountConfig, BackendConfig},
+    msg::{Hash, Uid},
+};
+
+#[cfg(feature = "imap-backend")]
+use crate::backends::ImapBackend;
+
+#[cfg(feature = "maildir-backend")]
+use crate::backends::MaildirBackend;
+
+#[cfg(feature = "notmuch-backend")]
+use crate::backends::NotmuchBackend;
+
+/// Represents a boxed backend identifying messages with `I`. The
+/// backend may borrow the configs, but it has to implement the
+/// backend trait for any lifetime so that it can be lent to handlers.
+pub type BoxedBackend<'a, I> = Box<dyn for<'b> Backend<'b, Id = I> + 'a>;
+
+/// Represents a backend built by the builder. The kind of message
+/// identifier is only known at runtime, so callers match on it once
+/// and then work with a backend of known identifier type.
+pub enum AnyBackend<'a> {
+    /// Represents a backend identifying messages by number, like
+    /// IMAP or in-memory backends.
+    Uid(BoxedBackend<'a, Uid>),
+    /// Represents a backend identifying messages by hash, like
+    /// Maildir or Notmuch backends.
+    Hash(BoxedBackend<'a, Hash>),
+}
+
+/// Represents a function building a custom backend from the account
+/// config and the backend specific config.
+pub type BackendFactory =
+    Box<dyn for<'a> Fn(&'a AccountConfig, &'a toml::Value) -> Result<AnyBackend<'a>>>;
+
+/// Builds backends from configs.
+#[derive(Default)]
+pub struct BackendBuilder {
+    factories: HashMap<String, BackendFactory>,
+}
+
+impl BackendBuilder {
+    /// Creates a builder knowing the built-in backend kinds. The
+    /// in-memory backend is selected with `backend = "memory"`.
+    pub fn new() -> Self {
+        Self::default().register("memory", |account_config, _| {
+            Ok(AnyBackend::Uid(Box::new(MemoryBackend::new(
+                account_config,
+            ))))
+        })
+    }
+
+    /// Registers a custom backend kind. Accounts having a `backend`
+    /// key equal to the given kind are built using the given factory.
+    pub fn register<F>(mut self, kind: &str, factory: F) -> Self
+    where
+        F: for<'a> Fn(&'a AccountConfig, &'a toml::Value) -> Result<AnyBackend<'a>> + 'static,
+    {
+        self.factories.insert(kind.to_owned(), Box::new(factory));
+        self
+    }
+
+    /// Builds the backend matching the given configs.
+    pub fn build<'a>(
+        &self,
+        account_config: &'a AccountConfig,
+        backend_config: &'a BackendConfig,
+    ) -> Result<AnyBackend<'a>> {
+        info!(">> build backend");
+
+        let backend = match backend_config {
+            #[cfg(feature = "imap-backend")]
+            BackendConfig::Imap(imap_config) => {
+                debug!("kind: imap");
+                AnyBackend::Uid(Box::new(ImapBackend::new(account_config, imap_config)))
+            }
+            #[cfg(feature = "maildir-backend")]
+            BackendConfig::Maildir(maildir_config) => {
+                debug!("kind: maildir");
+                AnyBackend::Hash(Box::new(MaildirBackend::new(
+                    account_config,
+                    maildir_config,
+                )))
+            }
+            #[cfg(feature = "notmuch-backend")]
+            BackendConfig::Notmuch(notmuch_config) => {
+                debug!("kind: notmuch");
+                AnyBackend::Hash(Box::new(NotmuchBackend::new(
+                    account_config,
+                    notmuch_config,
+                )?))
+            }
+            BackendConfig::Custom(custom_config) => {
+                debug!("kind: {}", custom_config.backend);
+                let factory = self.factories.get(&custom_config.backend).ok_or_else(|| {
+                    anyhow!("cannot find backend kind {:?}", custom_config.backend)
+                })?;
+                factory(account_config, &custom_config.backend_config)?
+            }
+        };
+
+        info!("<< build backend");
+        Ok(backend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CustomBackendConfig;
+
+    fn uid_backend(backend: AnyBackend) -> BoxedBackend<Uid> {
+        match backend {
+            AnyBackend::Uid(backend) => backend,
+            AnyBackend::Hash(_) => panic!("expected backend identifying messages by number"),
+        }
+    }
+
+    #[test]
+    fn it_should_build_custom_backend() {
+        // The custom backend is an in-memory backend containing an
+        // extra mailbox named after its config.
+        let builder = BackendBuilder::new().register("test", |account_config, config| {
+            let mut backend = MemoryBackend::new(account_config);
+            let name = config
+                .get("name")
+                .and_then(|name| name.as_str())
+                .ok_or_else(|| anyhow!("cannot find mailbox name"))?;
+            backend.add_mbox(name)?;
+            Ok(AnyBackend::Uid(Box::new(backend)))
+        });
+        let account_config = AccountConfig::default();
+
+        let backend_config = BackendConfig::Custom(CustomBackendConfig {
+            backend: "test".into(),
+            backend_config: toml::from_str(r#"name = "custom""#).unwrap(),
+        });
+        let mut backend = uid_backend(builder.build(&account_config, &backend_config).unwrap());
+        let err = backend.add_mbox("custom").unwrap_err();
+        assert_eq!(
+            "cannot add in-memory mailbox \"custom\": already exists",
+            err.to_string()
+        );
+
+        let backend_config = BackendConfig::Custom(CustomBackendConfig {
+            backend: "test".into(),
+            backend_config: toml::Value::Table(Default::default()),
+        });
+        assert!(builder.build(&account_config, &backend_config).is_err());
+
+        let backend_config = BackendConfig::Custom(CustomBackendConfig {
+            backend: "memory".into(),
+            backend_config: toml::Value::Table(Default::default()),
+        });
+        let mut backend = uid_backend(builder.build(&account_config, &backend_config).unwrap());
+        assert!(backend.add_mbox("custom").is_ok());
+
+        let backend_config = BackendConfig::Custom(CustomBackendConfig {
+            backend: "unknown".into(),
+            backend_config: toml::Value::Table(Default::default()),
+        });
+        assert!(builder.build(&account_config, &backend_config).is_err());
+    }
+}
@@ -0,0 +1,330 @@
+//! Dry run backend module.
+//!
+//! This module contains the definition of the dry run backend. It
+//! wraps a real backend: reads go through it, whereas changes are
+//! recorded and reported instead of being applied.
+
+use anyhow::Result;
+use log::info;
+use std::fmt;
+
+use crate::{
+    backends::{Backend, BoxedBackend},
+    mbox::{Mboxes, SpecialUse},
+    msg::{Attachment, Attachments, Envelopes, Flags, IdSet, Msg, MsgId, SortCriteria, Threads},
+};
+
+/// Represents a change a dry run backend did not apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DryRunChange<I> {
+    AddMbox(String),
+    DelMbox(String),
+    RenameMbox(String, String),
+    SubscribeMbox(String),
+    UnsubscribeMbox(String),
+    AddMsg(String, Flags),
+    CopyMsg(String, String, IdSet<I>),
+    MoveMsg(String, String, IdSet<I>),
+    DelMsg(String, IdSet<I>),
+    AddFlags(String, IdSet<I>, Flags),
+    SetFlags(String, IdSet<I>, Flags),
+    DelFlags(String, IdSet<I>, Flags),
+}
+
+impl<I: MsgId> fmt::Display for DryRunChange<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AddMbox(mbox) => write!(f, "add mailbox {:?}", mbox),
+            Self::DelMbox(mbox) => write!(f, "delete mailbox {:?}", mbox),
+            Self::RenameMbox(mbox, new_mbox) => {
+                write!(f, "rename mailbox {:?} to {:?}", mbox, new_mbox)
+            }
+            Self::SubscribeMbox(mbox) => write!(f, "subscribe to mailbox {:?}", mbox),
+            Self::UnsubscribeMbox(mbox) => write!(f, "unsubscribe from mailbox {:?}", mbox),
+            Self::AddMsg(mbox, flags) => {
+                write!(f, "add message to {:?} with flags ({})", mbox, flags)
+            }
+            Self::CopyMsg(mbox_src, mbox_dst, ids) => write!(
+                f,
+                "copy messages {} from {:?} to {:?}",
+                ids, mbox_src, mbox_dst
+            ),
+            Self::MoveMsg(mbox_src, mbox_dst, ids) => write!(
+                f,
+                "move messages {} from {:?} to {:?}",
+                ids, mbox_src, mbox_dst
+            ),
+            Self::DelMsg(mbox, ids) => write!(f, "delete messages {} from {:?}", ids, mbox),
+            Self::AddFlags(mbox, ids, flags) => {
+                write!(f, "add flags ({}) to messages {} of {:?}", flags, ids, mbox)
+            }
+            Self::SetFlags(mbox, ids, flags) => {
+                write!(f, "set flags ({}) of messages {} of {:?}", flags, ids, mbox)
+            }
+            Self::DelFlags(mbox, ids, flags) => write!(
+                f,
+                "remove flags ({}) from messages {} of {:?}",
+                flags, ids, mbox
+            ),
+        }
+    }
+}
+
+/// Represents the function the changes are reported to.
+type DryRunReporter<'a, I> = Box<dyn FnMut(&DryRunChange<I>) + 'a>;
+
+/// Represents the dry run backend. Changes are kept in order, and
+/// passed to the optional reporter as soon as they are recorded.
+pub struct DryRunBackend<'a, I> {
+    backend: BoxedBackend<'a, I>,
+    changes: Vec<DryRunChange<I>>,
+    reporter: Option<DryRunReporter<'a, I>>,
+}
+
+impl<'a, I: MsgId> DryRunBackend<'a, I> {
+    pub fn new(backend: BoxedBackend<'a, I>) -> Self {
+        Self {
+            backend,
+            changes: Vec::new(),
+            reporter: None,
+        }
+    }
+
+    /// Sets the function called with each recorded change, for
+    /// example to print it.
+    pub fn with_reporter(mut self, reporter: impl FnMut(&DryRunChange<I>) + 'a) -> Self {
+        self.reporter = Some(Box::new(reporter));
+        self
+    }
+
+    /// Gets the changes recorded so far.
+    pub fn changes(&self) -> &[DryRunChange<I>] {
+        &self.changes
+    }
+
+    fn record(&mut self, change: DryRunChange<I>) -> Result<()> {
+        info!("dry run: {}", change);
+        if let Some(reporter) = self.reporter.as_mut() {
+            reporter(&change);
+        }
+        self.changes.push(change);
+        Ok(())
+    }
+}
+
+impl<'a, 'b, I: MsgId> Backend<'b> for DryRunBackend<'a, I> {
+    type Id = I;
+
+    fn connect(&mut self) -> Result<()> {
+        self.backend.connect()
+    }
+
+    fn add_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.record(DryRunChange::AddMbox(mbox.to_owned()))
+    }
+
+    fn get_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        self.backend.get_mboxes()
+    }
+
+    fn get_subscribed_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        self.backend.get_subscribed_mboxes()
+    }
+
+    fn del_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.record(DryRunChange::DelMbox(mbox.to_owned()))
+    }
+
+    fn rename_mbox(&mut self, mbox: &str, new_mbox: &str) -> Result<()> {
+        self.record(DryRunChange::RenameMbox(
+            mbox.to_owned(),
+            new_mbox.to_owned(),
+        ))
+    }
+
+    fn subscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.record(DryRunChange::SubscribeMbox(mbox.to_owned()))
+    }
+
+    fn unsubscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.record(DryRunChange::UnsubscribeMbox(mbox.to_owned()))
+    }
+
+    fn find_special_mbox(&mut self, special_use: SpecialUse) -> Result<Option<String>> {
+        self.backend.find_special_mbox(special_use)
+    }
+
+    fn get_envelopes(
+        &mut self,
+        mbox: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        self.backend.get_envelopes(mbox, page_size, page)
+    }
+
+    fn search_envelopes(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        self.backend
+            .search_envelopes(mbox, query, sort, page_size, page)
+    }
+
+    fn get_threads(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Threads>> {
+        self.backend.get_threads(mbox, query, page_size, page)
+    }
+
+    /// Records the message instead of adding it. The returned id does
+    /// not belong to any message.
+    fn add_msg(&mut self, mbox: &str, _msg: &[u8], flags: &Flags) -> Result<I> {
+        self.record(DryRunChange::AddMsg(mbox.to_owned(), flags.to_owned()))?;
+        Ok(I::default())
+    }
+
+    fn get_msg(&mut self, mbox: &str, id: &I) -> Result<Msg> {
+        self.backend.get_msg(mbox, id)
+    }
+
+    fn get_attachments(&mut self, mbox: &str, id: &I) -> Result<Attachments> {
+        self.backend.get_attachments(mbox, id)
+    }
+
+    fn get_attachment_content(
+        &mut self,
+        mbox: &str,
+        id: &I,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>> {
+        self.backend.get_attachment_content(mbox, id, attachment)
+    }
+
+    fn copy_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<I>) -> Result<()> {
+        self.record(DryRunChange::CopyMsg(
+            mbox_src.to_owned(),
+            mbox_dst.to_owned(),
+            ids.to_owned(),
+        ))
+    }
+
+    fn move_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<I>) -> Result<()> {
+        self.record(DryRunChange::MoveMsg(
+            mbox_src.to_owned(),
+            mbox_dst.to_owned(),
+            ids.to_owned(),
+        ))
+    }
+
+    fn del_msg(&mut self, mbox: &str, ids: &IdSet<I>) -> Result<()> {
+        self.record(DryRunChange::DelMsg(mbox.to_owned(), ids.to_owned()))
+    }
+
+    fn add_flags(&mut self, mbox: &str, ids: &IdSet<I>, flags: &Flags) -> Result<()> {
+        self.record(DryRunChange::AddFlags(
+            mbox.to_owned(),
+            ids.to_owned(),
+            flags.to_owned(),
+        ))
+    }
+
+    fn set_flags(&mut self, mbox: &str, ids: &IdSet<I>, flags: &Flags) -> Result<()> {
+        self.record(DryRunChange::SetFlags(
+            mbox.to_owned(),
+            ids.to_owned(),
+            flags.to_owned(),
+        ))
+    }
+
+    fn del_flags(&mut self, mbox: &str, ids: &IdSet<I>, flags: &Flags) -> Result<()> {
+        self.record(DryRunChange::DelFlags(
+            mbox.to_owned(),
+            ids.to_owned(),
+            flags.to_owned(),
+        ))
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        self.backend.disconnect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, convert::TryFrom};
+
+    use crate::{
+        backends::{MemoryBackend, MemoryEnvelopes},
+        config::AccountConfig,
+        msg::{Flag, Uid},
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_should_record_changes_over_real_backend() {
+        let account_config = AccountConfig::default();
+        let mut memory = MemoryBackend::new(&account_config);
+        let raw = b"From: alice@localhost\r\nSubject: A\r\n\r\na\r\n";
+        memory.add_msg("INBOX", raw, &Flags::default()).unwrap();
+        memory.add_msg("INBOX", raw, &Flags::default()).unwrap();
+
+        let reported = RefCell::new(Vec::new());
+        let mut backend = DryRunBackend::new(Box::new(memory))
+            .with_reporter(|change| reported.borrow_mut().push(change.to_string()));
+
+        // Reads go through the real backend.
+        let envelopes = backend.get_envelopes("INBOX", 10, 0).unwrap();
+        let envelopes = envelopes
+            .as_any()
+            .downcast_ref::<MemoryEnvelopes>()
+            .unwrap();
+        assert_eq!(2, envelopes.len());
+        assert_eq!("A", backend.get_msg("INBOX", &Uid(1)).unwrap().subject);
+
+        // Changes are recorded, not applied.
+        let ids = IdSet::try_from("1:2").unwrap();
+        let seen = Flags::from(vec![Flag::Seen]);
+        backend.move_msg("INBOX", "Sent", &ids).unwrap();
+        backend.add_flags("INBOX", &ids, &seen).unwrap();
+        backend.del_mbox("INBOX").unwrap();
+        assert_eq!(
+            vec![
+                DryRunChange::MoveMsg("INBOX".into(), "Sent".into(), ids.clone()),
+                DryRunChange::AddFlags("INBOX".into(), ids, seen),
+                DryRunChange::DelMbox("INBOX".into()),
+            ],
+            backend.changes()
+        );
+
+        let envelopes = backend.get_envelopes("INBOX", 10, 0).unwrap();
+        let envelopes = envelopes
+            .as_any()
+            .downcast_ref::<MemoryEnvelopes>()
+            .unwrap();
+        assert_eq!(2, envelopes.len());
+        assert!(envelopes.iter().all(|envelope| envelope.flags.is_empty()));
+        let envelopes = backend.get_envelopes("Sent", 10, 0).unwrap();
+        let envelopes = envelopes
+            .as_any()
+            .downcast_ref::<MemoryEnvelopes>()
+            .unwrap();
+        assert!(envelopes.is_empty());
+
+        drop(backend);
+        assert_eq!(3, reported.borrow().len());
+        assert_eq!(
+            r#"move messages 1:2 from "INBOX" to "Sent""#,
+            reported.borrow()[0]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Async IMAP backend module.
+//!
+//! This module contains the definition of the async IMAP backend. It
+//! runs the blocking IMAP backend on a dedicated worker thread, the
+//! same way the watcher does, so that the async runtime is never
+//! blocked and both APIs share a single IMAP implementation.
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use log::debug;
+use std::{sync::mpsc, thread};
+
+use crate::{
+    backends::{run_watch_cmds, AsyncBackend, Backend, ImapBackend},
+    config::{AccountConfig, ImapBackendConfig},
+    mbox::Mboxes,
+    msg::{Attachment, Attachments, Envelopes, Flags, IdSet, Msg, SortCriteria, Threads, Uid},
+};
+
+/// Represents a function run against the blocking IMAP backend by the
+/// worker thread.
+type ImapJob = Box<dyn for<'a> FnOnce(&mut ImapBackend<'a>) + Send>;
+
+/// Represents the async IMAP backend. Calls are queued to a worker
+/// thread owning the IMAP connection, which stops once the backend is
+/// dropped.
+pub struct AsyncImapBackend {
+    account_config: AccountConfig,
+    jobs: mpsc::Sender<ImapJob>,
+}
+
+impl AsyncImapBackend {
+    /// Spawns the worker thread of the given account. The connection
+    /// is lazily opened by the first call, like the blocking backend
+    /// does.
+    pub fn new(account_config: &AccountConfig, imap_config: &ImapBackendConfig) -> Result<Self> {
+        let (jobs, receiver) = mpsc::channel::<ImapJob>();
+        let name = format!("{}:imap", account_config.name);
+        debug!("spawn IMAP worker {}", name);
+
+        let worker_account_config = account_config.clone();
+        let imap_config = imap_config.clone();
+        thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || {
+                let mut imap = ImapBackend::new(&worker_account_config, &imap_config);
+                for job in receiver {
+                    job(&mut imap);
+                }
+                debug!("IMAP worker stopped");
+            })
+            .with_context(|| format!("cannot spawn IMAP worker {}", name))?;
+
+        Ok(Self {
+            account_config: account_config.clone(),
+            jobs,
+        })
+    }
+
+    /// Runs the given function against the blocking IMAP backend, on
+    /// the worker thread. Gives access to the IMAP features which are
+    /// not part of the backend trait, like the synchronization or the
+    /// server info.
+    pub async fn run<T, F>(&mut self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: for<'a> FnOnce(&mut ImapBackend<'a>) -> Result<T> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let job: ImapJob = Box::new(move |imap| {
+            // The receiver is gone if the caller stopped waiting, in
+            // which case the result is not needed anymore.
+            let _ = sender.send(f(imap));
+        });
+        self.jobs
+            .send(job)
+            .map_err(|_| anyhow!("cannot run IMAP command: worker stopped"))?;
+        receiver
+            .await
+            .map_err(|_| anyhow!("cannot run IMAP command: worker stopped"))?
+    }
+
+    /// Runs the watch commands of the account each time the given
+    /// mailbox changes, see [`ImapBackend::watch`]. The worker is busy
+    /// until the watch fails, so watching is better done from a
+    /// dedicated backend.
+    pub async fn watch(&mut self, keepalive: u64, mbox: &str) -> Result<()> {
+        let account_config = self.account_config.clone();
+        let mbox = mbox.to_owned();
+        self.run(move |imap| {
+            imap.watch(keepalive, &mbox, |event| {
+                run_watch_cmds(&account_config, &event)
+            })
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl AsyncBackend for AsyncImapBackend {
+    type Id = Uid;
+
+    async fn connect(&mut self) -> Result<()> {
+        self.run(|imap| imap.connect()).await
+    }
+
+    async fn add_mbox(&mut self, mbox: &str) -> Result<()> {
+        let mbox = mbox.to_owned();
+        self.run(move |imap| imap.add_mbox(&mbox)).await
+    }
+
+    async fn get_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        self.run(|imap| imap.get_mboxes()).await
+    }
+
+    async fn get_subscribed_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        self.run(|imap| imap.get_subscribed_mboxes()).await
+    }
+
+    async fn del_mbox(&mut self, mbox: &str) -> Result<()> {
+        let mbox = mbox.to_owned();
+        self.run(move |imap| imap.del_mbox(&mbox)).await
+    }
+
+    async fn rename_mbox(&mut self, mbox: &str, new_mbox: &str) -> Result<()> {
+        let (mbox, new_mbox) = (mbox.to_owned(), new_mbox.to_owned());
+        self.run(move |imap| imap.rename_mbox(&mbox, &new_mbox))
+            .await
+    }
+
+    async fn subscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        let mbox = mbox.to_owned();
+        self.run(move |imap| imap.subscribe_mbox(&mbox)).await
+    }
+
+    async fn unsubscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        let mbox = mbox.to_owned();
+        self.run(move |imap| imap.unsubscribe_mbox(&mbox)).await
+    }
+
+    async fn get_envelopes(
+        &mut self,
+        mbox: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        let mbox = mbox.to_owned();
+        self.run(move |imap| imap.get_envelopes(&mbox, page_size, page))
+            .await
+    }
+
+    async fn search_envelopes(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        let (mbox, query, sort) = (mbox.to_owned(), query.to_owned(), sort.to_owned());
+        self.run(move |imap| imap.search_envelopes(&mbox, &query, &sort, page_size, page))
+            .await
+    }
+
+    async fn get_threads(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Threads>> {
+        let (mbox, query) = (mbox.to_owned(), query.to_owned());
+        self.run(move |imap| imap.get_threads(&mbox, &query, page_size, page))
+            .await
+    }
+
+    async fn add_msg(&mut self, mbox: &str, msg: &[u8], flags: &Flags) -> Result<Uid> {
+        let (mbox, msg, flags) = (mbox.to_owned(), msg.to_owned(), flags.to_owned());
+        self.run(move |imap| imap.add_msg(&mbox, &msg, &flags))
+            .await
+    }
+
+    async fn get_msg(&mut self, mbox: &str, id: &Uid) -> Result<Msg> {
+        let (mbox, id) = (mbox.to_owned(), id.to_owned());
+        self.run(move |imap| imap.get_msg(&mbox, &id)).await
+    }
+
+    async fn get_attachments(&mut self, mbox: &str, id: &Uid) -> Result<Attachments> {
+        let (mbox, id) = (mbox.to_owned(), id.to_owned());
+        self.run(move |imap| imap.get_attachments(&mbox, &id)).await
+    }
+
+    async fn get_attachment_content(
+        &mut self,
+        mbox: &str,
+        id: &Uid,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>> {
+        let (mbox, id, attachment) = (mbox.to_owned(), id.to_owned(), attachment.to_owned());
+        self.run(move |imap| imap.get_attachment_content(&mbox, &id, &attachment))
+            .await
+    }
+
+    async fn copy_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Uid>) -> Result<()> {
+        let (mbox_src, mbox_dst, ids) = (mbox_src.to_owned(), mbox_dst.to_owned(), ids.to_owned());
+        self.run(move |imap| imap.copy_msg(&mbox_src, &mbox_dst, &ids))
+            .await
+    }
+
+    async fn move_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Uid>) -> Result<()> {
+        let (mbox_src, mbox_dst, ids) = (mbox_src.to_owned(), mbox_dst.to_owned(), ids.to_owned());
+        self.run(move |imap| imap.move_msg(&mbox_src, &mbox_dst, &ids))
+            .await
+    }
+
+    async fn del_msg(&mut self, mbox: &str, ids: &IdSet<Uid>) -> Result<()> {
+        let (mbox, ids) = (mbox.to_owned(), ids.to_owned());
+        self.run(move |imap| imap.del_msg(&mbox, &ids)).await
+    }
+
+    async fn add_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        let (mbox, ids, flags) = (mbox.to_owned(), ids.to_owned(), flags.to_owned());
+        self.run(move |imap| imap.add_flags(&mbox, &ids, &flags))
+            .await
+    }
+
+    async fn set_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        let (mbox, ids, flags) = (mbox.to_owned(), ids.to_owned(), flags.to_owned());
+        self.run(move |imap| imap.set_flags(&mbox, &ids, &flags))
+            .await
+    }
+
+    async fn del_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        let (mbox, ids, flags) = (mbox.to_owned(), ids.to_owned(), flags.to_owned());
+        self.run(move |imap| imap.del_flags(&mbox, &ids, &flags))
+            .await
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.run(|imap| imap.disconnect()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn it_should_run_on_worker() {
+        let account_config = AccountConfig::default();
+        let imap_config = ImapBackendConfig::default();
+        let mut imap = AsyncImapBackend::new(&account_config, &imap_config).unwrap();
+
+        let name = block_on(imap.run(|_| Ok(thread::current().name().map(String::from))));
+        assert_eq!(Some(":imap".to_owned()), name.unwrap());
+
+        let err = block_on(imap.run(|_| -> Result<()> { Err(anyhow!("error")) }));
+        assert_eq!("error", err.unwrap_err().to_string());
+    }
+}
@@ -0,0 +1,261 @@
+//! IMAP attachment module.
+//!
+//! This module provides IMAP conversion utilities related to
+//! attachments. Attachments are listed from the body structure of the
+//! message ([RFC3501 §7.4.2]), so that they can be fetched one by one
+//! using their section instead of fetching the whole message.
+//!
+//! [RFC3501 §7.4.2]: https://datatracker.ietf.org/doc/html/rfc3501#section-7.4.2
+
+use anyhow::{Context, Result};
+use imap_proto::{BodyStructure, ContentEncoding};
+use std::borrow::Cow;
+
+use crate::msg::{Attachment, Attachments};
+
+/// Represents the raw body structure returned by the `imap` crate.
+pub type RawImapBodyStructure<'a> = BodyStructure<'a>;
+
+impl From<&RawImapBodyStructure<'_>> for Attachments {
+    fn from(body: &RawImapBodyStructure) -> Self {
+        let mut attachments = vec![];
+        collect_attachments(body, "", &mut attachments);
+        Self { attachments }
+    }
+}
+
+/// Collects the attachments of the given body structure, `section`
+/// being the section of the body (empty for the message itself).
+fn collect_attachments(
+    body: &RawImapBodyStructure,
+    section: &str,
+    attachments: &mut Vec<Attachment>,
+) {
+    match body {
+        BodyStructure::Multipart { bodies, .. } => {
+            for (i, body) in bodies.iter().enumerate() {
+                let section = if section.is_empty() {
+                    (i + 1).to_string()
+                } else {
+                    format!("{}.{}", section, i + 1)
+                };
+                collect_attachments(body, &section, attachments);
+            }
+        }
+        BodyStructure::Basic { common, other, .. }
+        | BodyStructure::Text { common, other, .. }
+        | BodyStructure::Message { common, other, .. } => {
+            if !is_attachment(body) {
+                return;
+            }
+
+            // A message which is not multipart has a single part, its
+            // section is 1.
+            let section = if section.is_empty() { "1" } else { section };
+
+            let filename = common
+                .disposition
+                .as_ref()
+                .and_then(|disposition| find_param(&disposition.params, "filename"))
+                .or_else(|| find_param(&common.ty.params, "name"))
+                .unwrap_or_else(|| String::from("noname"));
+            let mime = format!("{}/{}", common.ty.ty, common.ty.subtype).to_lowercase();
+            let octets = other.octets as usize;
+            let (size, encoding) = match &other.transfer_encoding {
+                ContentEncoding::SevenBit => (octets, "7bit".into()),
+                ContentEncoding::EightBit => (octets, "8bit".into()),
+                ContentEncoding::Binary => (octets, "binary".into()),
+                // Base64 encodes 3 bytes in 4 characters.
+                ContentEncoding::Base64 => (octets / 4 * 3, "base64".into()),
+                ContentEncoding::QuotedPrintable => (octets, "quoted-printable".into()),
+                ContentEncoding::Other(encoding) => (octets, encoding.to_lowercase()),
+            };
+
+            attachments.push(Attachment {
+                index: attachments.len() + 1,
+                filename,
+                mime,
+                size,
+                section: Some(section.to_owned()),
+                encoding: Some(encoding),
+            });
+        }
+    }
+}
+
+/// Decodes the content of a fetched section according to its content
+/// transfer encoding.
+pub fn decode_section(section: &[u8], encoding: Option<&str>) -> Result<Vec<u8>> {
+    let encoding = match encoding {
+        Some(encoding @ ("base64" | "quoted-printable")) => encoding,
+        _ => return Ok(section.to_vec()),
+    };
+    // `mailparse` decodes bodies according to their headers, so the
+    // section gets back the header it was fetched without.
+    let mut part = format!("Content-Transfer-Encoding: {}\r\n\r\n", encoding).into_bytes();
+    part.extend_from_slice(section);
+    mailparse::parse_mail(&part)
+        .context("cannot parse attachment")?
+        .get_body_raw()
+        .context("cannot decode attachment")
+}
+
+/// Tells if the given body structure contains an attachment.
+pub fn has_attachment(body: &RawImapBodyStructure) -> bool {
+    match body {
+        BodyStructure::Multipart { bodies, .. } => bodies.iter().any(has_attachment),
+        part => is_attachment(part),
+    }
+}
+
+/// Tells if the given single part body structure is an attachment:
+/// a part with an `attachment` disposition, or a non-text part with a
+/// file name which is not explicitly inline.
+fn is_attachment(body: &RawImapBodyStructure) -> bool {
+    let common = match body {
+        BodyStructure::Multipart { .. } => return false,
+        BodyStructure::Basic { common, .. }
+        | BodyStructure::Text { common, .. }
+        | BodyStructure::Message { common, .. } => common,
+    };
+    match &common.disposition {
+        Some(disposition) => disposition.ty.eq_ignore_ascii_case("attachment"),
+        None => {
+            matches!(body, BodyStructure::Basic { .. })
+                && find_param(&common.ty.params, "name").is_some()
+        }
+    }
+}
+
+/// Finds the value of the given body parameter. Values can be
+/// encoded words ([RFC2047]) or extended values like
+/// `utf-8''na%C3%AFve.pdf` ([RFC2231]), parameter continuations are
+/// not supported.
+///
+/// [RFC2047]: https://datatracker.ietf.org/doc/html/rfc2047
+/// [RFC2231]: https://datatracker.ietf.org/doc/html/rfc2231
+fn find_param(params: &Option<Vec<(Cow<str>, Cow<str>)>>, key: &str) -> Option<String> {
+    let params = params.as_ref()?;
+
+    if let Some((_, value)) = params.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+        return Some(
+            rfc2047_decoder::decode(value.as_bytes()).unwrap_or_else(|_| value.to_string()),
+        );
+    }
+
+    let key = format!("{}*", key);
+    let (_, value) = params.iter().find(|(k, _)| k.eq_ignore_ascii_case(&key))?;
+    // The charset and the language come first, separated by quotes.
+    let value: &str = value;
+    let value = value.splitn(3, '\'').nth(2).unwrap_or(value).as_bytes();
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        let byte = match value.get(i + 1..i + 3) {
+            Some(hex) if value[i] == b'%' => std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match byte {
+            Some(byte) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            None => {
+                bytes.push(value[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use imap_proto::{parser::parse_response, AttributeValue, Response};
+
+    use super::*;
+
+    fn attachments(res: &[u8]) -> Vec<Attachment> {
+        match parse_response(res).unwrap().1 {
+            Response::Fetch(_, attrs) => {
+                attrs
+                    .iter()
+                    .find_map(|attr| match attr {
+                        AttributeValue::BodyStructure(body) => Some(Attachments::from(body)),
+                        _ => None,
+                    })
+                    .unwrap()
+                    .attachments
+            }
+            res => panic!("unexpected response {:?}", res),
+        }
+    }
+
+    #[test]
+    fn it_should_list_attachments() {
+        let res = concat!(
+            r#"* 1 FETCH (UID 1 BODYSTRUCTURE ("#,
+            r#"(("TEXT" "PLAIN" ("CHARSET" "utf-8") NIL NIL "7BIT" 12 1 NIL NIL NIL)"#,
+            r#"("TEXT" "HTML" ("CHARSET" "utf-8") NIL NIL "QUOTED-PRINTABLE" 34 2 NIL NIL NIL) "ALTERNATIVE" ("BOUNDARY" "b2") NIL NIL)"#,
+            r#"("APPLICATION" "PDF" ("NAME" "report.pdf") NIL NIL "BASE64" 4000 NIL ("ATTACHMENT" ("FILENAME" "report.pdf")) NIL)"#,
+            r#"("IMAGE" "PNG" ("NAME" "=?utf-8?q?na=C3=AFve.png?=") NIL NIL "BASE64" 400 NIL NIL NIL)"#,
+            r#"("IMAGE" "JPEG" NIL NIL NIL "BASE64" 40 NIL ("ATTACHMENT" ("FILENAME*" "utf-8''caf%C3%A9.jpg")) NIL)"#,
+            r#"("IMAGE" "GIF" ("NAME" "inline.gif") NIL NIL "BASE64" 40 NIL ("INLINE" NIL) NIL)"#,
+            r#" "MIXED" ("BOUNDARY" "b1") NIL NIL))"#,
+            "\r\n",
+        );
+        let attachments = attachments(res.as_bytes());
+
+        assert_eq!(
+            vec![
+                (1, "report.pdf", "application/pdf", 3000, "2"),
+                (2, "naïve.png", "image/png", 300, "3"),
+                (3, "café.jpg", "image/jpeg", 30, "4"),
+            ],
+            attachments
+                .iter()
+                .map(|attachment| (
+                    attachment.index,
+                    attachment.filename.as_str(),
+                    attachment.mime.as_str(),
+                    attachment.size,
+                    attachment.section.as_deref().unwrap(),
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some("base64"), attachments[0].encoding.as_deref());
+    }
+
+    #[test]
+    fn it_should_decode_sections() {
+        assert_eq!(b"hello".to_vec(), decode_section(b"hello", None).unwrap());
+        assert_eq!(
+            b"hello".to_vec(),
+            decode_section(b"hello", Some("7bit")).unwrap()
+        );
+        assert_eq!(
+            b"hello".to_vec(),
+            decode_section(b"aGVs\r\nbG8=\r\n", Some("base64")).unwrap()
+        );
+        assert_eq!(
+            "café".as_bytes().to_vec(),
+            decode_section(b"caf=C3=A9", Some("quoted-printable")).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_should_list_single_part_attachment() {
+        let res = concat!(
+            r#"* 1 FETCH (UID 1 BODYSTRUCTURE ("#,
+            r#""APPLICATION" "OCTET-STREAM" NIL NIL NIL "BASE64" 8 NIL ("ATTACHMENT" NIL) NIL))"#,
+            "\r\n",
+        );
+        let attachments = attachments(res.as_bytes());
+
+        assert_eq!(1, attachments.len());
+        assert_eq!("noname", attachments[0].filename);
+        assert_eq!(Some("1"), attachments[0].section.as_deref());
+    }
+}
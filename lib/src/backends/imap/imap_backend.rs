@@ -0,0 +1,1730 @@
+//! IMAP backend module.
+//!
+//! This module contains the definition of the IMAP backend.
+
+use anyhow::{anyhow, Context, Result};
+use imap::{extensions::idle::SetReadTimeout, types::UnsolicitedResponse};
+use imap_proto::{Capability, SectionPath};
+use log::{debug, log_enabled, trace, warn, Level};
+use mailparse::MailHeaderMap;
+use native_tls::{TlsConnector, TlsStream};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::{TryFrom, TryInto},
+    io::{self, Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    backends::{
+        appended_msg_query, appended_msg_uid, decode_mbox_name, decode_section, encode_utf7,
+        imap::msg_sort_criterion::to_imap_sort_criteria, parse_esearch_partial_response,
+        parse_id_response, parse_namespace_response, parse_quota_root_response,
+        parse_thread_response, partial_range, Backend, ImapEnvelope, ImapEnvelopeCache,
+        ImapEnvelopeCachePlan, ImapEnvelopes, ImapMboxUids, ImapMboxes, ImapOAuth2Authenticator,
+        ImapServerInfo, ImapSortKeys, ImapThreads, ImapUidValidityCache, ImapWatchEvent,
+        ImapWatchEventKind, ENVELOPE_FETCH_ITEMS, SORT_KEYS_FETCH_ITEMS,
+    },
+    config::{AccountConfig, ImapBackendConfig, TlsMode},
+    mbox::{MboxCounts, Mboxes, SpecialUse},
+    msg::{
+        imap_quote, thread_msgs, Attachment, Attachments, Envelopes, Flag, Flags, IdSet, Msg,
+        SearchQuery, SortCriteria, ThreadNode, ThreadRefs, Threads, Uid,
+    },
+};
+
+use super::ImapFlags;
+
+/// Represents the stream of an IMAP connection, which is encrypted
+/// unless TLS is disabled.
+#[derive(Debug)]
+pub enum ImapStream {
+    Tcp(TcpStream),
+    Tls(TlsStream<TcpStream>),
+}
+
+impl Read for ImapStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ImapStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+impl SetReadTimeout for ImapStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> imap::error::Result<()> {
+        match self {
+            Self::Tcp(stream) => SetReadTimeout::set_read_timeout(stream, timeout),
+            Self::Tls(stream) => SetReadTimeout::set_read_timeout(stream, timeout),
+        }
+    }
+}
+
+type ImapSess = imap::Session<ImapStream>;
+
+/// Represents the threads of UIDs built on the client side, along
+/// with the envelopes they point to.
+type ClientThreads = (Vec<ThreadNode<u32>>, HashMap<u32, ImapEnvelope>);
+
+pub struct ImapBackend<'a> {
+    account_config: &'a AccountConfig,
+    imap_config: &'a ImapBackendConfig,
+    sess: Option<ImapSess>,
+    condstore: Option<bool>,
+    backoff: ImapBackoff,
+    special_mboxes: Option<HashMap<SpecialUse, String>>,
+    capabilities: Option<Vec<String>>,
+}
+
+impl<'a> ImapBackend<'a> {
+    pub fn new(account_config: &'a AccountConfig, imap_config: &'a ImapBackendConfig) -> Self {
+        Self {
+            account_config,
+            imap_config,
+            sess: None,
+            condstore: None,
+            backoff: ImapBackoff::default(),
+            special_mboxes: None,
+            capabilities: None,
+        }
+    }
+
+    fn sess(&mut self) -> Result<&mut ImapSess> {
+        if self.sess.is_none() {
+            let tls = &self.imap_config.imap_tls;
+            debug!("create client");
+            debug!("host: {}", self.imap_config.imap_host);
+            debug!("port: {}", self.imap_config.imap_port);
+            debug!("tls: {}", tls.mode);
+            debug!("insecure: {}", tls.insecure);
+            let client = if tls.mode == TlsMode::None {
+                let tcp = TcpStream::connect((
+                    self.imap_config.imap_host.as_str(),
+                    self.imap_config.imap_port,
+                ))
+                .context("cannot connect to IMAP server")?;
+                let mut client = imap::Client::new(ImapStream::Tcp(tcp));
+                client
+                    .read_greeting()
+                    .context("cannot read IMAP server greeting")?;
+                client
+            } else {
+                let connector = tls.connector()?;
+                let mut client_builder = imap::ClientBuilder::new(
+                    &self.imap_config.imap_host,
+                    self.imap_config.imap_port,
+                );
+                if tls.mode == TlsMode::Starttls {
+                    client_builder.starttls();
+                }
+                client_builder
+                    .connect(|domain, tcp| {
+                        let stream = TlsConnector::connect(&connector, domain, tcp)?;
+                        tls.check_fingerprint(stream.peer_certificate()?.as_ref())
+                            .map_err(|err| io::Error::other(format!("{:#}", err)))?;
+                        Ok(ImapStream::Tls(stream))
+                    })
+                    .context("cannot connect to IMAP server")?
+            };
+
+            debug!("create session");
+            debug!("login: {}", self.imap_config.imap_login);
+            let mut sess = match self.imap_config.imap_oauth2 {
+                Some(ref oauth2_config) => {
+                    debug!("auth: {}", oauth2_config.method);
+                    let mechanism = oauth2_config.method.to_string();
+                    let authenticator =
+                        ImapOAuth2Authenticator::new(self.imap_config, oauth2_config)?;
+                    match client.authenticate(&mechanism, &authenticator) {
+                        Ok(sess) => sess,
+                        Err((err, client)) => {
+                            debug!("cannot authenticate, retry with a fresh token: {}", err);
+                            ImapOAuth2Authenticator::expire(self.imap_config)?;
+                            let authenticator =
+                                ImapOAuth2Authenticator::new(self.imap_config, oauth2_config)?;
+                            client
+                                .authenticate(&mechanism, &authenticator)
+                                .map_err(|res| res.0)
+                                .context("cannot authenticate to IMAP server using OAuth2")?
+                        }
+                    }
+                }
+                None => {
+                    debug!("passwd cmd: {}", self.imap_config.imap_passwd_cmd);
+                    client
+                        .login(
+                            &self.imap_config.imap_login,
+                            &self.imap_config.imap_passwd()?,
+                        )
+                        .map_err(|res| res.0)
+                        .context("cannot login to IMAP server")?
+                }
+            };
+            sess.debug = log_enabled!(Level::Trace);
+            self.sess = Some(sess);
+        }
+
+        match self.sess {
+            Some(ref mut sess) => Ok(sess),
+            None => Err(anyhow!("cannot get IMAP session")),
+        }
+    }
+
+    fn search_new_msgs(&mut self, query: &str) -> Result<Vec<u32>> {
+        let uids: Vec<u32> = self
+            .sess()?
+            .uid_search(query)
+            .context("cannot search new messages")?
+            .into_iter()
+            .collect();
+        debug!("found {} new messages", uids.len());
+        trace!("uids: {:?}", uids);
+
+        Ok(uids)
+    }
+
+    /// Searches messages matching the given query. When the highest
+    /// mod-sequence of a previous search is given, the mailbox is
+    /// examined again and only messages changed since then are
+    /// searched. The given mod-sequence is then updated.
+    fn search_changed_msgs(
+        &mut self,
+        mbox: &str,
+        mod_seq: &mut Option<u64>,
+        query: &str,
+    ) -> Result<Vec<u32>> {
+        let prev_mod_seq = match *mod_seq {
+            Some(prev_mod_seq) => prev_mod_seq,
+            None => return self.search_new_msgs(query),
+        };
+
+        *mod_seq = self
+            .sess()?
+            .examine(encode_utf7(mbox))
+            .context(format!("cannot examine mailbox {:?}", mbox))?
+            .highest_mod_seq;
+        debug!("HIGHESTMODSEQ: {:?}", mod_seq);
+
+        match *mod_seq {
+            Some(next_mod_seq) if next_mod_seq == prev_mod_seq => {
+                debug!("no message changed since mod-sequence {}", prev_mod_seq);
+                Ok(vec![])
+            }
+            Some(_) => {
+                let uids = self.fetch_changed_uids(prev_mod_seq)?;
+                if uids.is_empty() {
+                    return Ok(vec![]);
+                }
+                self.search_new_msgs(&format!("UID {} {}", uid_set(&uids), query))
+            }
+            None => self.search_new_msgs(query),
+        }
+    }
+
+    /// Fetches the UIDs of the messages of the selected mailbox
+    /// changed since the given mod-sequence.
+    fn fetch_changed_uids(&mut self, mod_seq: u64) -> Result<Vec<u32>> {
+        let uids: Vec<u32> = self
+            .sess()?
+            .uid_fetch("1:*", format!("(UID) (CHANGEDSINCE {})", mod_seq))
+            .context(format!("cannot fetch messages changed since {}", mod_seq))?
+            .iter()
+            .filter_map(|fetch| fetch.uid)
+            .collect();
+        debug!("found {} changed messages", uids.len());
+        trace!("uids: {:?}", uids);
+        Ok(uids)
+    }
+
+    /// Enables QRESYNC, or CONDSTORE if QRESYNC is not available, so
+    /// that selected mailboxes expose their highest mod-sequence
+    /// ([RFC7162]).
+    /// QRESYNC implies CONDSTORE, and also makes expunges increase
+    /// the mod-sequence. Returns whether mod-sequences are available.
+    ///
+    /// [RFC7162]: https://datatracker.ietf.org/doc/html/rfc7162
+    fn enable_condstore(&mut self) -> Result<bool> {
+        if let Some(enabled) = self.condstore {
+            return Ok(enabled);
+        }
+
+        let ext = if self.has_capability("QRESYNC")? {
+            Some("QRESYNC")
+        } else if self.has_capability("CONDSTORE")? {
+            Some("CONDSTORE")
+        } else {
+            None
+        };
+        let enabled = match ext {
+            Some(ext) if self.has_capability("ENABLE")? => {
+                self.sess()?
+                    .run_command_and_check_ok(format!("ENABLE {}", ext))
+                    .context(format!("cannot enable {}", ext))?;
+                true
+            }
+            _ => false,
+        };
+        debug!("mod-sequences enabled: {}", enabled);
+
+        self.condstore = Some(enabled);
+        Ok(enabled)
+    }
+
+    /// Fetches the envelopes within the given range of sequence
+    /// numbers. Envelopes are taken from the envelope cache, so only
+    /// the flags changed since the last listing and the envelopes
+    /// never listed before are actually fetched.
+    fn fetch_envelopes_with_cache(
+        &mut self,
+        mbox: &str,
+        range: &str,
+        uid_validity: u32,
+        highest_mod_seq: u64,
+    ) -> Result<ImapEnvelopes> {
+        let key = ImapUidValidityCache::key(self.imap_config, mbox);
+        let mut cache = ImapEnvelopeCache::from_default_path(&key)?;
+        cache.check_uid_validity(uid_validity);
+
+        let mut uids = vec![];
+        for fetch in self
+            .sess()?
+            .fetch(range, "UID")
+            .context(format!("cannot fetch UIDs within range {:?}", range))?
+            .iter()
+        {
+            uids.push(
+                fetch
+                    .uid
+                    .ok_or_else(|| anyhow!("cannot get UID of message {}", fetch.message))?,
+            );
+        }
+        let pruned = cache.prune(&uids);
+
+        let plan = cache.plan(&uids, highest_mod_seq);
+        debug!(
+            "{} outdated envelope(s), {} missing envelope(s)",
+            plan.outdated.len(),
+            plan.missing.len()
+        );
+        trace!("plan: {:?}", plan);
+
+        if let Some(changed_since) = plan.changed_since {
+            let query = format!("(UID FLAGS) (CHANGEDSINCE {})", changed_since);
+            let fetches = self
+                .sess()?
+                .uid_fetch(uid_set(&plan.outdated), &query)
+                .context("cannot fetch changed flags")?;
+            for fetch in fetches.iter() {
+                if let Some(uid) = fetch.uid {
+                    cache.set_flags(uid, ImapFlags::try_from(fetch.flags())?);
+                }
+            }
+            cache.touch(&plan.outdated, highest_mod_seq);
+        }
+
+        if !plan.missing.is_empty() {
+            let fetches = self
+                .sess()?
+                .uid_fetch(uid_set(&plan.missing), ENVELOPE_FETCH_ITEMS)
+                .context("cannot fetch missing envelopes")?;
+            for fetch in fetches.iter() {
+                cache.insert(ImapEnvelope::try_from(fetch)?, highest_mod_seq);
+            }
+        }
+
+        if pruned > 0 || plan != ImapEnvelopeCachePlan::default() {
+            cache.write()?;
+        }
+
+        uids.reverse();
+        Ok(ImapEnvelopes {
+            envelopes: cache.envelopes(&uids),
+        })
+    }
+
+    /// Selects the given mailbox, then remembers its UIDVALIDITY so
+    /// that the UIDs about to be listed can be checked later on.
+    fn select_for_listing(&mut self, mbox: &str) -> Result<imap::types::Mailbox> {
+        let mailbox = self
+            .sess()?
+            .select(encode_utf7(mbox))
+            .context(format!("cannot select mailbox {:?}", mbox))?;
+        debug!("UIDVALIDITY: {:?}", mailbox.uid_validity);
+        if let Some(uid_validity) = mailbox.uid_validity {
+            let key = ImapUidValidityCache::key(self.imap_config, mbox);
+            ImapUidValidityCache::from_default_path()?.update(&key, uid_validity)?;
+        }
+        Ok(mailbox)
+    }
+
+    /// Selects the given mailbox, then checks that its UIDVALIDITY
+    /// did not change since the last listing.
+    fn select_for_uids(&mut self, mbox: &str) -> Result<()> {
+        let mailbox = self
+            .sess()?
+            .select(encode_utf7(mbox))
+            .context(format!("cannot select mailbox {:?}", mbox))?;
+        debug!("UIDVALIDITY: {:?}", mailbox.uid_validity);
+        if let Some(uid_validity) = mailbox.uid_validity {
+            let key = ImapUidValidityCache::key(self.imap_config, mbox);
+            ImapUidValidityCache::from_default_path()?.check(&key, uid_validity)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the capabilities of the IMAP server. They are fetched
+    /// once per session, then cached.
+    pub fn capabilities(&mut self) -> Result<&[String]> {
+        if self.capabilities.is_none() {
+            let mut caps: Vec<String> = self
+                .sess()?
+                .capabilities()
+                .context("cannot get IMAP server capabilities")?
+                .iter()
+                .map(|cap| match cap {
+                    Capability::Imap4rev1 => String::from("IMAP4rev1"),
+                    Capability::Auth(mechanism) => format!("AUTH={}", mechanism),
+                    Capability::Atom(cap) => cap.to_string(),
+                })
+                .collect();
+            caps.sort();
+            debug!("capabilities: {:?}", caps);
+            self.capabilities = Some(caps);
+        }
+
+        match self.capabilities {
+            Some(ref caps) => Ok(caps),
+            None => Err(anyhow!("cannot get IMAP server capabilities")),
+        }
+    }
+
+    fn has_capability(&mut self, cap: &str) -> Result<bool> {
+        let has_cap = self
+            .capabilities()?
+            .iter()
+            .any(|server_cap| server_cap.eq_ignore_ascii_case(cap));
+        debug!("capability {}: {}", cap, has_cap);
+        Ok(has_cap)
+    }
+
+    /// Fetches the information the server gives about itself: its
+    /// capabilities, its identity, its namespaces and the quotas of
+    /// the given mailboxes. Parts the server does not support are
+    /// left empty.
+    pub fn server_info(&mut self, mboxes: &[&str]) -> Result<ImapServerInfo> {
+        let mut info = ImapServerInfo {
+            capabilities: self.capabilities()?.to_vec(),
+            ..ImapServerInfo::default()
+        };
+
+        // The `imap` crate does not support the ID, NAMESPACE and
+        // QUOTA extensions, so the commands are sent raw.
+        if self.has_capability("ID")? {
+            let res = self
+                .sess()?
+                .run_command_and_read_response("ID NIL")
+                .context("cannot get IMAP server identity")?;
+            info.id = parse_id_response(&res)?;
+        }
+
+        if self.has_capability("NAMESPACE")? {
+            let res = self
+                .sess()?
+                .run_command_and_read_response("NAMESPACE")
+                .context("cannot get IMAP server namespaces")?;
+            info.namespaces = Some(parse_namespace_response(&res)?);
+        }
+
+        if self.has_capability("QUOTA")? {
+            for mbox in mboxes {
+                let cmd = format!("GETQUOTAROOT {}", imap_quote(&encode_utf7(mbox)));
+                let res = self
+                    .sess()?
+                    .run_command_and_read_response(&cmd)
+                    .context(format!("cannot get quota of mailbox {:?}", mbox))?;
+                info.quotas.push(parse_quota_root_response(mbox, &res)?);
+            }
+        }
+
+        trace!("server info: {:?}", info);
+        Ok(info)
+    }
+
+    /// Fetches the message counts of the given mailboxes, one STATUS
+    /// command per mailbox. Mailboxes that cannot be selected, or
+    /// whose status cannot be fetched, are left without counts.
+    fn fetch_mbox_counts(&mut self, mboxes: &mut ImapMboxes) -> Result<()> {
+        for mbox in mboxes.mboxes.iter_mut().filter(|mbox| mbox.is_selectable()) {
+            match self
+                .sess()?
+                .status(encode_utf7(&mbox.name), "(MESSAGES UNSEEN RECENT)")
+            {
+                Ok(status) => mbox.counts = Some(MboxCounts::from(&status)),
+                Err(err) => warn!("cannot get status of mailbox {:?}: {}", mbox.name, err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the mailboxes along with their message counts, in a
+    /// single round trip thanks to the LIST-STATUS extension
+    /// ([RFC5819]).
+    ///
+    /// [RFC5819]: https://datatracker.ietf.org/doc/html/rfc5819
+    fn list_mboxes_with_status(&mut self) -> Result<ImapMboxes> {
+        let sess = self.sess()?;
+        // The `imap` crate sends the pattern as is, and queues the
+        // STATUS responses as unsolicited ones.
+        let mut mboxes: ImapMboxes = sess
+            .list(Some(""), Some("* RETURN (STATUS (MESSAGES UNSEEN RECENT))"))
+            .context("cannot list mailboxes with status")?
+            .into();
+        let mut counts = HashMap::new();
+        for res in sess.unsolicited_responses.try_iter() {
+            match res {
+                UnsolicitedResponse::Status {
+                    mailbox,
+                    attributes,
+                } => {
+                    counts.insert(
+                        decode_mbox_name(&mailbox),
+                        MboxCounts::from(&attributes[..]),
+                    );
+                }
+                res => trace!("skip response: {:?}", res),
+            }
+        }
+        trace!("counts: {:?}", counts);
+        for mbox in mboxes.mboxes.iter_mut() {
+            mbox.counts = counts.remove(&mbox.name);
+        }
+        Ok(mboxes)
+    }
+
+    /// Threads the messages matching the given IMAP query on the
+    /// client side, for servers lacking the THREAD extension. Returns
+    /// the threads of UIDs along with the envelopes fetched on the
+    /// way.
+    fn thread_envelopes(&mut self, mbox: &str, query: &str) -> Result<ClientThreads> {
+        let mut uids = self.search_uids(mbox, query)?;
+        if uids.is_empty() {
+            return Ok((vec![], HashMap::new()));
+        }
+        // UIDs follow the arrival order, which threads keep among
+        // siblings.
+        uids.sort_unstable();
+
+        let uid_set = uid_set(&uids);
+        let fetches = self
+            .sess()?
+            .uid_fetch(
+                &uid_set,
+                "(UID ENVELOPE FLAGS INTERNALDATE RFC822.SIZE BODYSTRUCTURE BODY.PEEK[HEADER.FIELDS (REFERENCES)])",
+            )
+            .context(format!("cannot fetch messages {:?}", uid_set))?;
+
+        let mut msgs = vec![];
+        let mut envelopes = HashMap::new();
+        for fetch in fetches.iter() {
+            let envelope = ImapEnvelope::try_from(fetch).context("cannot parse envelope")?;
+            let raw_envelope = fetch.envelope();
+            let message_id = raw_envelope
+                .and_then(|envelope| envelope.message_id.as_ref())
+                .map(|id| String::from_utf8_lossy(id).into_owned());
+            let in_reply_to = raw_envelope
+                .and_then(|envelope| envelope.in_reply_to.as_ref())
+                .map(|id| String::from_utf8_lossy(id).into_owned());
+            let references = fetch
+                .header()
+                .and_then(|header| mailparse::parse_headers(header).ok())
+                .and_then(|(headers, _)| headers.get_first_value("References"));
+            let refs = ThreadRefs::new(
+                message_id.as_deref(),
+                in_reply_to.as_deref(),
+                references.as_deref(),
+            );
+            trace!("thread refs of message {}: {:?}", envelope.id, refs);
+            msgs.push((envelope.id, refs));
+            envelopes.insert(envelope.id, envelope);
+        }
+        msgs.sort_by_key(|(uid, _)| *uid);
+
+        Ok((thread_msgs(msgs), envelopes))
+    }
+
+    /// Searches the UIDs of the messages matching the given IMAP
+    /// query, in no particular order.
+    fn search_uids(&mut self, mbox: &str, query: &str) -> Result<Vec<u32>> {
+        Ok(self
+            .sess()?
+            .uid_search(with_charset(query))
+            .context(format!(
+                "cannot find envelopes in {:?} with query {:?}",
+                mbox, query
+            ))?
+            .into_iter()
+            .collect())
+    }
+
+    /// Tells if the server can return a single page of search
+    /// results, which needs both the ESEARCH and PARTIAL extensions.
+    fn has_partial_search(&mut self) -> Result<bool> {
+        Ok(self.has_capability("ESEARCH")? && self.has_capability("PARTIAL")?)
+    }
+
+    /// Searches the UIDs of the given page of messages matching the
+    /// given IMAP query, most recent messages first. The server only
+    /// returns the UIDs of the page instead of all the matching ones.
+    fn search_uids_page(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Vec<u32>> {
+        // The `imap` crate does not support the ESEARCH extension, so
+        // the command is sent raw.
+        let cmd = format!(
+            "UID SEARCH RETURN (PARTIAL {}) {}",
+            partial_range(page_size, page),
+            with_charset(query)
+        );
+        let res = self
+            .sess()?
+            .run_command_and_read_response(&cmd)
+            .context(format!(
+                "cannot find envelopes in {:?} with query {:?}",
+                mbox, query
+            ))?;
+        parse_esearch_partial_response(&res)
+    }
+
+    /// Sorts the messages matching the given IMAP query on the client
+    /// side, for servers lacking the SORT extension. The sort keys of
+    /// all the matching messages need to be fetched, which is slower
+    /// than letting the server sort them.
+    fn sort_uids(&mut self, mbox: &str, query: &str, sort: &SortCriteria) -> Result<Vec<u32>> {
+        let uids = self.search_uids(mbox, query)?;
+        if uids.is_empty() {
+            return Ok(uids);
+        }
+
+        let uid_set = uid_set(&uids);
+        let fetches = self
+            .sess()?
+            .uid_fetch(&uid_set, SORT_KEYS_FETCH_ITEMS)
+            .context(format!("cannot fetch sort keys of messages {:?}", uid_set))?;
+        let mut keys = fetches
+            .iter()
+            .map(ImapSortKeys::try_from)
+            .collect::<Result<Vec<_>>>()
+            .context("cannot parse sort keys")?;
+        keys.sort_by(|a, b| a.cmp_by(b, sort));
+        Ok(keys.into_iter().map(|keys| keys.uid).collect())
+    }
+
+    /// Selects the given mailbox, then fetches the UID and the flags
+    /// of all its messages. Returns the UIDVALIDITY of the mailbox as
+    /// well, since UIDs are meaningless without it.
+    pub fn fetch_flags(&mut self, mbox: &str) -> Result<(u32, Vec<(u32, ImapFlags)>)> {
+        let mailbox = self.select_for_listing(mbox)?;
+        let uid_validity = mailbox
+            .uid_validity
+            .ok_or_else(|| anyhow!("cannot find UIDVALIDITY of mailbox {:?}", mbox))?;
+        if mailbox.exists == 0 {
+            return Ok((uid_validity, vec![]));
+        }
+
+        let fetches = self
+            .sess()?
+            .fetch("1:*", "(UID FLAGS)")
+            .context(format!("cannot fetch flags of mailbox {:?}", mbox))?;
+        let mut flags = vec![];
+        for fetch in fetches.iter() {
+            let uid = fetch
+                .uid
+                .ok_or_else(|| anyhow!("cannot get UID of message {}", fetch.message))?;
+            flags.push((uid, ImapFlags::try_from(fetch.flags())?));
+        }
+        Ok((uid_validity, flags))
+    }
+
+    /// Fetches the raw message matching the given UID, without
+    /// marking it as seen.
+    pub fn fetch_raw_msg(&mut self, mbox: &str, uid: u32) -> Result<Vec<u8>> {
+        self.select_for_uids(mbox)?;
+        let fetches = self
+            .sess()?
+            .uid_fetch(uid.to_string(), "BODY.PEEK[]")
+            .context(format!("cannot fetch message {:?}", uid))?;
+        let fetch = fetches
+            .first()
+            .ok_or_else(|| anyhow!("cannot find message {:?}", uid))?;
+        Ok(fetch.body().unwrap_or_default().to_owned())
+    }
+
+    /// Fetches the Message-ID of the messages matching the given
+    /// UIDs, indexed by UID. Messages without Message-ID are left
+    /// out.
+    pub fn fetch_msg_ids(&mut self, mbox: &str, uids: &[u32]) -> Result<BTreeMap<u32, String>> {
+        let uid_set = uid_set(uids);
+        self.select_for_uids(mbox)?;
+        let fetches = self
+            .sess()?
+            .uid_fetch(&uid_set, "(UID ENVELOPE)")
+            .context(format!("cannot fetch envelopes of messages {:?}", uid_set))?;
+        let mut msg_ids = BTreeMap::new();
+        for fetch in fetches.iter() {
+            let msg_id = fetch
+                .envelope()
+                .and_then(|envelope| envelope.message_id.as_ref())
+                .map(|id| String::from_utf8_lossy(id).trim().to_owned());
+            if let (Some(uid), Some(msg_id)) = (fetch.uid, msg_id) {
+                msg_ids.insert(uid, msg_id);
+            }
+        }
+        Ok(msg_ids)
+    }
+
+    fn store(&mut self, mbox: &str, ids: &IdSet<Uid>, query: String) -> Result<()> {
+        let uid_set = ids.to_seq_set();
+        self.select_for_uids(mbox)?;
+        self.sess()?
+            .uid_store(&uid_set, &query)
+            .context(format!("cannot store {:?}", query))?;
+        Ok(())
+    }
+
+    /// Watches the given mailbox for new messages matching the
+    /// notify query, and passes them to the given handler. Dropped
+    /// connections are reestablished, see [`ImapBackoff`]. Runs until
+    /// the handler fails or a non-connection error occurs.
+    pub fn notify<H>(&mut self, keepalive: u64, mbox: &str, mut handler: H) -> Result<()>
+    where
+        H: FnMut(ImapWatchEvent) -> Result<()>,
+    {
+        debug!("notify");
+
+        // The known UIDs survive reconnections, so that messages
+        // received in between are notified exactly once.
+        let mut msgs_set: Option<(Option<u32>, HashSet<u32>)> = None;
+        self.run_with_reconnect(|imap| {
+            imap.notify_session(keepalive, mbox, &mut msgs_set, &mut handler)
+        })
+    }
+
+    /// Runs the notify loop over the current session. The known UIDs
+    /// are initialized on the first run, then resynchronized with
+    /// the mailbox on the following ones.
+    fn notify_session<H>(
+        &mut self,
+        keepalive: u64,
+        mbox: &str,
+        msgs_set: &mut Option<(Option<u32>, HashSet<u32>)>,
+        handler: &mut H,
+    ) -> Result<()>
+    where
+        H: FnMut(ImapWatchEvent) -> Result<()>,
+    {
+        let condstore = self.enable_condstore()?;
+
+        debug!("examine mailbox {:?}", mbox);
+        let mailbox = self
+            .sess()?
+            .examine(encode_utf7(mbox))
+            .context(format!("cannot examine mailbox {}", mbox))?;
+        let mut mod_seq = mailbox.highest_mod_seq.filter(|_| condstore);
+        debug!("HIGHESTMODSEQ: {:?}", mod_seq);
+
+        let query = self.account_config.notify_query.clone();
+        let uids = self.search_new_msgs(&query)?;
+        let msgs_set = match msgs_set {
+            Some((uid_validity, msgs_set)) if *uid_validity == mailbox.uid_validity => {
+                debug!("resynchronize messages hashset");
+                let uids: Vec<u32> = uids
+                    .into_iter()
+                    .filter(|uid| !msgs_set.contains(uid))
+                    .collect();
+                self.notify_new_msgs(mbox, &uids, msgs_set, handler)?;
+                msgs_set
+            }
+            _ => {
+                debug!("init messages hashset");
+                let (_, msgs_set) =
+                    msgs_set.insert((mailbox.uid_validity, uids.into_iter().collect()));
+                msgs_set
+            }
+        };
+        trace!("messages hashset: {:?}", msgs_set);
+
+        loop {
+            debug!("begin loop");
+            self.idle(keepalive)?;
+
+            let uids: Vec<u32> = self
+                .search_changed_msgs(mbox, &mut mod_seq, &query)?
+                .into_iter()
+                .filter(|uid| -> bool { msgs_set.get(uid).is_none() })
+                .collect();
+            debug!("found {} new messages not in hashset", uids.len());
+            trace!("messages hashet: {:?}", msgs_set);
+            self.notify_new_msgs(mbox, &uids, msgs_set, handler)?;
+
+            debug!("end loop");
+        }
+    }
+
+    /// Fetches the envelopes of the given new messages, passes them
+    /// to the given handler, then adds them to the known UIDs.
+    fn notify_new_msgs<H>(
+        &mut self,
+        mbox: &str,
+        uids: &[u32],
+        msgs_set: &mut HashSet<u32>,
+        handler: &mut H,
+    ) -> Result<()>
+    where
+        H: FnMut(ImapWatchEvent) -> Result<()>,
+    {
+        if uids.is_empty() {
+            return Ok(());
+        }
+
+        let fetches = self
+            .sess()?
+            .uid_fetch(uid_set(uids), "(UID ENVELOPE)")
+            .context("cannot fetch new messages enveloppe")?;
+
+        for fetch in fetches.iter() {
+            let msg = ImapEnvelope::try_from(fetch)?;
+            let uid = fetch
+                .uid
+                .ok_or_else(|| anyhow!("cannot retrieve message {}'s UID", fetch.message))?;
+
+            debug!("notify message: {}", uid);
+            trace!("message: {:?}", msg);
+            handler(ImapWatchEvent::new_msg(
+                &self.account_config.name,
+                mbox,
+                uid,
+                &msg,
+            ))?;
+
+            debug!("insert message {} in hashset", uid);
+            msgs_set.insert(uid);
+            trace!("messages hashset: {:?}", msgs_set);
+        }
+
+        Ok(())
+    }
+
+    /// Watches the given mailbox for changes, and passes them to the
+    /// given handler. Dropped connections are reestablished, see
+    /// [`ImapBackoff`]. Runs until the handler fails or a
+    /// non-connection error occurs.
+    pub fn watch<H>(&mut self, keepalive: u64, mbox: &str, mut handler: H) -> Result<()>
+    where
+        H: FnMut(ImapWatchEvent) -> Result<()>,
+    {
+        // The mailbox state survives reconnections, so that changes
+        // made in between are reported.
+        let mut state = None;
+        self.run_with_reconnect(|imap| {
+            imap.watch_session(keepalive, mbox, &mut state, &mut handler)
+        })
+    }
+
+    /// Runs the watch loop over the current session. The responses
+    /// sent by the server during the idle mode are turned into new
+    /// message, expunge and flag change events.
+    fn watch_session<H>(
+        &mut self,
+        keepalive: u64,
+        mbox: &str,
+        state: &mut Option<ImapWatchState>,
+        handler: &mut H,
+    ) -> Result<()>
+    where
+        H: FnMut(ImapWatchEvent) -> Result<()>,
+    {
+        let condstore = self.enable_condstore()?;
+
+        debug!("examine mailbox: {}", mbox);
+        let mailbox = self
+            .sess()?
+            .examine(encode_utf7(mbox))
+            .context(format!("cannot examine mailbox `{}`", mbox))?;
+        let next_state = ImapWatchState {
+            uid_validity: mailbox.uid_validity,
+            uids: self.fetch_mbox_uids(mailbox.exists)?,
+            mod_seq: mailbox.highest_mod_seq.filter(|_| condstore),
+        };
+
+        // Responses received before the mailbox state are already
+        // part of it.
+        let sess = self.sess()?;
+        sess.unsolicited_responses
+            .try_iter()
+            .for_each(|res| trace!("skip response: {:?}", res));
+
+        let state = match state.take() {
+            Some(prev_state) if prev_state.uid_validity != next_state.uid_validity => {
+                debug!("UIDVALIDITY changed while disconnected");
+                let event = ImapWatchEvent::new(
+                    ImapWatchEventKind::Changed,
+                    &self.account_config.name,
+                    mbox,
+                    vec![],
+                );
+                handler(event)?;
+                state.insert(next_state)
+            }
+            Some(prev_state) => {
+                debug!("resynchronize mailbox state");
+                self.watch_resync(mbox, &prev_state, &next_state, handler)?;
+                state.insert(next_state)
+            }
+            None => state.insert(next_state),
+        };
+
+        loop {
+            debug!("begin loop");
+            let responses = self.idle(keepalive)?;
+            let (expunged, flagged) = state.uids.apply(&responses);
+
+            let seqs = state.uids.unknown_seqs();
+            if !seqs.is_empty() {
+                let fetches = self
+                    .sess()?
+                    .fetch(uid_set(&seqs), "(UID ENVELOPE)")
+                    .context("cannot fetch new messages enveloppe")?;
+                for fetch in fetches.iter() {
+                    let uid = fetch.uid.ok_or_else(|| {
+                        anyhow!("cannot retrieve message {}'s UID", fetch.message)
+                    })?;
+                    state.uids.set(fetch.message, uid);
+                    let envelope = ImapEnvelope::try_from(fetch)?;
+                    handler(ImapWatchEvent::new_msg(
+                        &self.account_config.name,
+                        mbox,
+                        uid,
+                        &envelope,
+                    ))?;
+                }
+            }
+
+            for (kind, uids) in [
+                (ImapWatchEventKind::Expunged, expunged),
+                (ImapWatchEventKind::Flags, flagged),
+            ] {
+                if !uids.is_empty() {
+                    handler(ImapWatchEvent::new(
+                        kind,
+                        &self.account_config.name,
+                        mbox,
+                        uids,
+                    ))?;
+                }
+            }
+
+            debug!("end loop");
+        }
+    }
+
+    /// Reports the changes made to the watched mailbox while
+    /// disconnected, by comparing its previous and next states. Flag
+    /// changes can only be reported when mod-sequences are
+    /// available.
+    fn watch_resync<H>(
+        &mut self,
+        mbox: &str,
+        prev_state: &ImapWatchState,
+        next_state: &ImapWatchState,
+        handler: &mut H,
+    ) -> Result<()>
+    where
+        H: FnMut(ImapWatchEvent) -> Result<()>,
+    {
+        let prev_uids: HashSet<u32> = prev_state.uids.uids().into_iter().collect();
+        let next_uids: HashSet<u32> = next_state.uids.uids().into_iter().collect();
+
+        let new_uids: Vec<u32> = next_state
+            .uids
+            .uids()
+            .into_iter()
+            .filter(|uid| !prev_uids.contains(uid))
+            .collect();
+        if !new_uids.is_empty() {
+            let fetches = self
+                .sess()?
+                .uid_fetch(uid_set(&new_uids), "(UID ENVELOPE)")
+                .context("cannot fetch new messages enveloppe")?;
+            for fetch in fetches.iter() {
+                let uid = fetch
+                    .uid
+                    .ok_or_else(|| anyhow!("cannot retrieve message {}'s UID", fetch.message))?;
+                let envelope = ImapEnvelope::try_from(fetch)?;
+                handler(ImapWatchEvent::new_msg(
+                    &self.account_config.name,
+                    mbox,
+                    uid,
+                    &envelope,
+                ))?;
+            }
+        }
+
+        let expunged: Vec<u32> = prev_state
+            .uids
+            .uids()
+            .into_iter()
+            .filter(|uid| !next_uids.contains(uid))
+            .collect();
+        if !expunged.is_empty() {
+            handler(ImapWatchEvent::new(
+                ImapWatchEventKind::Expunged,
+                &self.account_config.name,
+                mbox,
+                expunged,
+            ))?;
+        }
+
+        if let (Some(prev_mod_seq), Some(next_mod_seq)) = (prev_state.mod_seq, next_state.mod_seq) {
+            if prev_mod_seq != next_mod_seq {
+                let flagged: Vec<u32> = self
+                    .fetch_changed_uids(prev_mod_seq)?
+                    .into_iter()
+                    .filter(|uid| prev_uids.contains(uid))
+                    .collect();
+                if !flagged.is_empty() {
+                    handler(ImapWatchEvent::new(
+                        ImapWatchEventKind::Flags,
+                        &self.account_config.name,
+                        mbox,
+                        flagged,
+                    ))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the UIDs of the messages of the selected mailbox,
+    /// which contains the given number of messages.
+    fn fetch_mbox_uids(&mut self, exists: u32) -> Result<ImapMboxUids> {
+        let mut uids = ImapMboxUids::new(exists);
+        if exists == 0 {
+            return Ok(uids);
+        }
+
+        let fetches = self
+            .sess()?
+            .fetch("1:*", "(UID)")
+            .context("cannot fetch messages UID")?;
+        for fetch in fetches.iter() {
+            if let Some(uid) = fetch.uid {
+                uids.set(fetch.message, uid);
+            }
+        }
+        trace!("mailbox uids: {:?}", uids);
+
+        Ok(uids)
+    }
+
+    /// Sets the delays between reconnection attempts.
+    pub fn set_backoff(&mut self, backoff: ImapBackoff) {
+        self.backoff = backoff;
+    }
+
+    /// Runs the given function until it succeeds or fails with a
+    /// non-connection error. When the connection drops, the session
+    /// is dropped as well, then the function is run again after a
+    /// delay growing with the number of consecutive failed attempts.
+    fn run_with_reconnect<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&mut Self) -> Result<()>,
+    {
+        let mut attempt = 0;
+        loop {
+            let err = match f(self) {
+                Ok(()) => return Ok(()),
+                Err(err) if is_connection_error(&err) => err,
+                Err(err) => return Err(err),
+            };
+
+            // A session existed, so the connection worked before
+            // dropping: this is a fresh failure.
+            if self.sess.take().is_some() {
+                attempt = 0;
+            }
+            self.condstore = None;
+            self.capabilities = None;
+
+            let delay = self.backoff.delay(attempt);
+            warn!("connection lost: {:#}", err);
+            warn!("reconnecting in {:?} (attempt {})", delay, attempt + 1);
+            thread::sleep(delay);
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    /// Waits in the idle mode until the server sends a response or
+    /// the keepalive duration elapses, then returns the responses
+    /// received.
+    fn idle(&mut self, keepalive: u64) -> Result<Vec<UnsolicitedResponse>> {
+        let mut responses = vec![];
+        self.sess()?
+            .idle()
+            .and_then(|mut idle| {
+                idle.set_keepalive(Duration::from_secs(keepalive));
+                idle.wait_keepalive_while(|res| {
+                    trace!("idle response: {:?}", res);
+                    responses.push(res);
+                    false
+                })
+            })
+            .context("cannot start the idle mode")?;
+
+        // Responses sent along with the end of the idle mode, like
+        // the RECENT following an EXISTS, are queued by the session.
+        responses.extend(self.sess()?.unsolicited_responses.try_iter());
+        Ok(responses)
+    }
+}
+
+impl<'a, 'b> Backend<'b> for ImapBackend<'a> {
+    type Id = Uid;
+
+    fn add_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.sess()?
+            .create(encode_utf7(mbox))
+            .context(format!("cannot create imap mailbox {:?}", mbox))
+    }
+
+    fn get_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        if self.has_capability("LIST-STATUS")? {
+            return Ok(Box::new(self.list_mboxes_with_status()?));
+        }
+        let mut mboxes: ImapMboxes = self
+            .sess()?
+            .list(Some(""), Some("*"))
+            .context("cannot list mailboxes")?
+            .into();
+        self.fetch_mbox_counts(&mut mboxes)?;
+        Ok(Box::new(mboxes))
+    }
+
+    fn get_subscribed_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        let mut mboxes: ImapMboxes = self
+            .sess()?
+            .lsub(Some(""), Some("*"))
+            .context("cannot list subscribed mailboxes")?
+            .into();
+        self.fetch_mbox_counts(&mut mboxes)?;
+        Ok(Box::new(mboxes))
+    }
+
+    fn del_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.special_mboxes = None;
+        self.sess()?
+            .delete(encode_utf7(mbox))
+            .context(format!("cannot delete imap mailbox {:?}", mbox))
+    }
+
+    fn rename_mbox(&mut self, mbox: &str, new_mbox: &str) -> Result<()> {
+        self.special_mboxes = None;
+        self.sess()?
+            .rename(encode_utf7(mbox), encode_utf7(new_mbox))
+            .context(format!(
+                "cannot rename imap mailbox {:?} to {:?}",
+                mbox, new_mbox
+            ))
+    }
+
+    fn subscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.sess()?
+            .subscribe(encode_utf7(mbox))
+            .context(format!("cannot subscribe to imap mailbox {:?}", mbox))
+    }
+
+    fn unsubscribe_mbox(&mut self, mbox: &str) -> Result<()> {
+        self.sess()?
+            .unsubscribe(encode_utf7(mbox))
+            .context(format!("cannot unsubscribe from imap mailbox {:?}", mbox))
+    }
+
+    fn find_special_mbox(&mut self, special_use: SpecialUse) -> Result<Option<String>> {
+        if self.special_mboxes.is_none() {
+            let mboxes: ImapMboxes = self
+                .sess()?
+                .list(Some(""), Some("*"))
+                .context("cannot list mailboxes")?
+                .into();
+            let mut special_mboxes = HashMap::new();
+            for mbox in mboxes.iter() {
+                if let Some(special_use) = mbox.special_use() {
+                    special_mboxes
+                        .entry(special_use)
+                        .or_insert_with(|| mbox.name.clone());
+                }
+            }
+            debug!("special-use mailboxes: {:?}", special_mboxes);
+            self.special_mboxes = Some(special_mboxes);
+        }
+
+        Ok(self
+            .special_mboxes
+            .as_ref()
+            .and_then(|special_mboxes| special_mboxes.get(&special_use))
+            .cloned())
+    }
+
+    fn get_envelopes(
+        &mut self,
+        mbox: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        let condstore = self.enable_condstore()?;
+        let mailbox = self.select_for_listing(mbox)?;
+        let last_seq = mailbox.exists as usize;
+        debug!("last sequence number: {:?}", last_seq);
+        if last_seq == 0 {
+            return Ok(Box::new(ImapEnvelopes::default()));
+        }
+
+        // Pages are computed from sequence numbers, but envelopes are
+        // identified by UID.
+        let range = if page_size > 0 {
+            let cursor = page * page_size;
+            let begin = 1.max(last_seq - cursor);
+            let end = begin - begin.min(page_size) + 1;
+            format!("{}:{}", end, begin)
+        } else {
+            String::from("1:*")
+        };
+        debug!("range: {:?}", range);
+
+        debug!("HIGHESTMODSEQ: {:?}", mailbox.highest_mod_seq);
+        if let (true, Some(uid_validity), Some(highest_mod_seq)) =
+            (condstore, mailbox.uid_validity, mailbox.highest_mod_seq)
+        {
+            let envelopes =
+                self.fetch_envelopes_with_cache(mbox, &range, uid_validity, highest_mod_seq)?;
+            return Ok(Box::new(envelopes));
+        }
+
+        let fetches = self
+            .sess()?
+            .fetch(&range, ENVELOPE_FETCH_ITEMS)
+            .context(format!("cannot fetch messages within range {:?}", range))?;
+        let envelopes: ImapEnvelopes = fetches.try_into()?;
+        Ok(Box::new(envelopes))
+    }
+
+    fn search_envelopes(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        let last_seq = self.select_for_listing(mbox)?.exists;
+        debug!("last sequence number: {:?}", last_seq);
+        if last_seq == 0 {
+            return Ok(Box::new(ImapEnvelopes::default()));
+        }
+
+        let query = SearchQuery::try_from(query)?.to_imap_query();
+        debug!("IMAP query: {:?}", query);
+
+        let uids = if sort.is_empty() && page_size > 0 && self.has_partial_search()? {
+            // Only the requested page is returned, so there is nothing
+            // left to paginate.
+            self.search_uids_page(mbox, &query, page_size, page)?
+        } else {
+            let uids = if sort.is_empty() {
+                let mut uids = self.search_uids(mbox, &query)?;
+                // Search results are unordered, most recent messages
+                // come first.
+                uids.sort_unstable_by(|a, b| b.cmp(a));
+                uids
+            } else if self.has_capability("SORT")? {
+                let sort = to_imap_sort_criteria(sort);
+                let charset = imap::extensions::sort::SortCharset::Utf8;
+                self.sess()?
+                    .uid_sort(&sort, charset, &query)
+                    .context(format!(
+                        "cannot find envelopes in {:?} with query {:?}",
+                        mbox, query
+                    ))?
+            } else {
+                debug!("SORT not supported, sorting on the client side");
+                self.sort_uids(mbox, &query, sort)?
+            };
+            debug!("uids: {:?}", uids);
+
+            let begin = page * page_size;
+            if begin >= uids.len() {
+                return Ok(Box::new(ImapEnvelopes::default()));
+            }
+            let end = if page_size > 0 {
+                uids.len().min(begin + page_size)
+            } else {
+                uids.len()
+            };
+            uids[begin..end].to_vec()
+        };
+        debug!("page uids: {:?}", uids);
+        if uids.is_empty() {
+            return Ok(Box::new(ImapEnvelopes::default()));
+        }
+
+        let uid_set = uid_set(&uids);
+        let fetches = self
+            .sess()?
+            .uid_fetch(&uid_set, ENVELOPE_FETCH_ITEMS)
+            .context(format!("cannot fetch messages {:?}", uid_set))?;
+        let mut envelopes: ImapEnvelopes = fetches.try_into()?;
+        // Fetches come in the mailbox order, so the search order needs
+        // to be restored.
+        envelopes
+            .envelopes
+            .sort_by_key(|envelope| uids.iter().position(|uid| *uid == envelope.id));
+        Ok(Box::new(envelopes))
+    }
+
+    fn get_threads(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Threads>> {
+        let last_seq = self.select_for_listing(mbox)?.exists;
+        debug!("last sequence number: {:?}", last_seq);
+        if last_seq == 0 {
+            return Ok(Box::new(ImapThreads::default()));
+        }
+
+        let query = SearchQuery::try_from(query)?.to_imap_query();
+        debug!("IMAP query: {:?}", query);
+
+        let (mut threads, mut envelopes) = if self.has_capability("THREAD=REFERENCES")? {
+            // The `imap` crate does not support the THREAD extension,
+            // so the command is sent raw.
+            let cmd = format!("UID THREAD REFERENCES UTF-8 {}", query);
+            let res = self
+                .sess()?
+                .run_command_and_read_response(&cmd)
+                .context(format!(
+                    "cannot thread envelopes in {:?} with query {:?}",
+                    mbox, query
+                ))?;
+            (parse_thread_response(&res)?, HashMap::new())
+        } else {
+            debug!("THREAD=REFERENCES not supported, threading on the client side");
+            self.thread_envelopes(mbox, &query)?
+        };
+        // Threads come oldest first, most recent ones are listed
+        // first.
+        threads.reverse();
+        trace!("threads: {:?}", threads);
+
+        let begin = page * page_size;
+        if begin >= threads.len() {
+            return Ok(Box::new(ImapThreads::default()));
+        }
+        let end = if page_size > 0 {
+            threads.len().min(begin + page_size)
+        } else {
+            threads.len()
+        };
+        let threads: Vec<_> = threads.drain(begin..end).collect();
+
+        let uids: Vec<_> = threads
+            .iter()
+            .flat_map(|thread| thread.msgs())
+            .filter(|uid| !envelopes.contains_key(*uid))
+            .copied()
+            .collect();
+        if !uids.is_empty() {
+            let uid_set = uid_set(&uids);
+            let fetches = self
+                .sess()?
+                .uid_fetch(&uid_set, ENVELOPE_FETCH_ITEMS)
+                .context(format!("cannot fetch messages {:?}", uid_set))?;
+            for fetch in fetches.iter() {
+                let envelope = ImapEnvelope::try_from(fetch).context("cannot parse envelope")?;
+                envelopes.insert(envelope.id, envelope);
+            }
+        }
+
+        let threads = threads
+            .into_iter()
+            .map(|thread| thread.map(&mut |uid| envelopes.remove(&uid)))
+            .collect();
+        Ok(Box::new(ImapThreads { threads }))
+    }
+
+    fn add_msg(&mut self, mbox: &str, msg: &[u8], flags: &Flags) -> Result<Uid> {
+        let flags = ImapFlags::from(flags);
+        // The `imap` crate does not expose the APPENDUID response
+        // code, so the appended message is searched back among the
+        // messages arriving from the current UIDNEXT.
+        let uid_next = self
+            .sess()?
+            .select(encode_utf7(mbox))
+            .context(format!("cannot select mailbox {:?}", mbox))?
+            .uid_next
+            .unwrap_or(1);
+        self.sess()?
+            .append(&encode_utf7(mbox), msg)
+            .flags(<ImapFlags as Into<Vec<imap::types::Flag<'b>>>>::into(flags))
+            .finish()
+            .context(format!("cannot append message to {:?}", mbox))?;
+
+        self.sess()?
+            .select(encode_utf7(mbox))
+            .context(format!("cannot select mailbox {:?}", mbox))?;
+        let uids = self
+            .sess()?
+            .uid_search(appended_msg_query(msg, uid_next))
+            .context(format!("cannot search message appended to {:?}", mbox))?;
+        let uid = appended_msg_uid(uids, uid_next)
+            .context(format!("cannot get UID of message appended to {:?}", mbox))?;
+        Ok(Uid(uid))
+    }
+
+    fn get_msg(&mut self, mbox: &str, id: &Uid) -> Result<Msg> {
+        let uid = id.to_string();
+        self.select_for_uids(mbox)?;
+        let fetches = self
+            .sess()?
+            .uid_fetch(&uid, "(UID FLAGS INTERNALDATE BODY.PEEK[])")
+            .context(format!("cannot fetch messages {:?}", uid))?;
+        let fetch = fetches
+            .first()
+            .ok_or_else(|| anyhow!("cannot find message {:?}", uid))?;
+        let msg_raw = fetch.body().unwrap_or_default().to_owned();
+        let mut msg = Msg::from_parsed_mail(
+            mailparse::parse_mail(&msg_raw).context("cannot parse message")?,
+            self.account_config,
+        )?;
+        msg.raw = msg_raw;
+        Ok(msg)
+    }
+
+    fn get_attachments(&mut self, mbox: &str, id: &Uid) -> Result<Attachments> {
+        let uid = id.to_string();
+        self.select_for_uids(mbox)?;
+        let fetches = self
+            .sess()?
+            .uid_fetch(&uid, "(UID BODYSTRUCTURE)")
+            .context(format!("cannot fetch body structure of message {:?}", uid))?;
+        let body = fetches
+            .first()
+            .ok_or_else(|| anyhow!("cannot find message {:?}", uid))?
+            .bodystructure()
+            .ok_or_else(|| anyhow!("cannot get body structure of message {:?}", uid))?;
+        let attachments = Attachments::from(body);
+        trace!("attachments: {:?}", attachments);
+        Ok(attachments)
+    }
+
+    fn get_attachment_content(
+        &mut self,
+        mbox: &str,
+        id: &Uid,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>> {
+        let section = attachment
+            .section
+            .as_deref()
+            .ok_or_else(|| anyhow!("cannot get section of attachment {:?}", attachment.filename))?;
+        let path = section
+            .split('.')
+            .map(|part| part.parse())
+            .collect::<Result<Vec<u32>, _>>()
+            .context(format!("cannot parse section {:?}", section))?;
+
+        let uid = id.to_string();
+        self.select_for_uids(mbox)?;
+        let fetches = self
+            .sess()?
+            .uid_fetch(&uid, format!("BODY.PEEK[{}]", section))
+            .context(format!(
+                "cannot fetch section {} of message {:?}",
+                section, uid
+            ))?;
+        let content = fetches
+            .first()
+            .ok_or_else(|| anyhow!("cannot find message {:?}", uid))?
+            .section(&SectionPath::Part(path, None))
+            .ok_or_else(|| anyhow!("cannot get section {} of message {:?}", section, uid))?;
+        decode_section(content, attachment.encoding.as_deref())
+    }
+
+    fn copy_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Uid>) -> Result<()> {
+        let uid_set = ids.to_seq_set();
+        self.select_for_uids(mbox_src)?;
+        self.sess()?
+            .uid_copy(&uid_set, encode_utf7(mbox_dst))
+            .context(format!(
+                "cannot copy messages {:?} to {:?}",
+                uid_set, mbox_dst
+            ))?;
+        Ok(())
+    }
+
+    fn move_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Uid>) -> Result<()> {
+        if !self.has_capability("MOVE")? {
+            debug!("MOVE not supported, falling back to COPY then delete");
+            self.copy_msg(mbox_src, mbox_dst, ids)?;
+            return self.del_msg(mbox_src, ids);
+        }
+
+        let uid_set = ids.to_seq_set();
+        self.select_for_uids(mbox_src)?;
+        self.sess()?
+            .uid_mv(&uid_set, encode_utf7(mbox_dst))
+            .context(format!(
+                "cannot move messages {:?} to {:?}",
+                uid_set, mbox_dst
+            ))?;
+        Ok(())
+    }
+
+    fn del_msg(&mut self, mbox: &str, ids: &IdSet<Uid>) -> Result<()> {
+        let uid_set = ids.to_seq_set();
+        self.add_flags(mbox, ids, &Flags::from(vec![Flag::Deleted]))?;
+
+        // Without UIDPLUS, a plain EXPUNGE would also remove the
+        // other messages flagged as deleted, so the messages are only
+        // flagged.
+        if self.has_capability("UIDPLUS")? {
+            self.sess()?
+                .uid_expunge(&uid_set)
+                .context(format!("cannot expunge messages {:?}", uid_set))?;
+        } else {
+            warn!(
+                "UIDPLUS not supported, messages {:?} of {:?} flagged as deleted but not expunged",
+                uid_set, mbox
+            );
+        }
+        Ok(())
+    }
+
+    fn add_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        let flags = ImapFlags::from(flags);
+        self.store(mbox, ids, format!("+FLAGS ({})", flags))
+            .context(format!("cannot add flags {:?}", &flags))
+    }
+
+    fn set_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        let flags = ImapFlags::from(flags);
+        self.store(mbox, ids, format!("FLAGS ({})", flags))
+            .context(format!("cannot set flags {:?}", &flags))
+    }
+
+    fn del_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        let flags = ImapFlags::from(flags);
+        self.store(mbox, ids, format!("-FLAGS ({})", flags))
+            .context(format!("cannot remove flags {:?}", &flags))
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        if let Some(ref mut sess) = self.sess {
+            debug!("logout from IMAP server");
+            sess.logout().context("cannot logout from IMAP server")?;
+        }
+        Ok(())
+    }
+}
+
+/// Represents the state of a watched mailbox, kept across
+/// reconnections.
+#[derive(Debug)]
+struct ImapWatchState {
+    uid_validity: Option<u32>,
+    uids: ImapMboxUids,
+    mod_seq: Option<u64>,
+}
+
+/// Represents the delays between reconnection attempts of the
+/// notify and watch modes. The delay starts at `min` and doubles
+/// after each failed attempt, up to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImapBackoff {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Default for ImapBackoff {
+    fn default() -> Self {
+        Self {
+            min: Duration::from_secs(1),
+            max: Duration::from_secs(300),
+        }
+    }
+}
+
+impl ImapBackoff {
+    /// Gets the delay before the given attempt, starting from 0.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.min
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max)
+            .min(self.max)
+    }
+}
+
+/// Returns `true` if the given error comes from a dropped or
+/// unreachable connection, in which case reconnecting may help.
+fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|err| {
+        if let Some(err) = err.downcast_ref::<imap::Error>() {
+            // Servers closing the connection send an unsolicited
+            // BYE first, then the next read fails with
+            // `ConnectionLost`.
+            return matches!(err, imap::Error::Io(_) | imap::Error::ConnectionLost);
+        }
+        matches!(
+            err.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(
+                io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::UnexpectedEof
+            )
+        )
+    })
+}
+
+/// Builds the UID set matching the given UIDs.
+fn uid_set(uids: &[u32]) -> String {
+    uids.iter()
+        .map(|uid| uid.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Announces the charset of the given search query when it contains
+/// non-ASCII strings. SORT and THREAD announce it on their own.
+fn with_charset(query: &str) -> String {
+    if query.is_ascii() {
+        query.to_owned()
+    } else {
+        format!("CHARSET UTF-8 {}", query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread,
+    };
+
+    use crate::config::TlsConfig;
+
+    use super::*;
+
+    /// Serves a single IMAP connection over a mailbox made of the
+    /// given messages, mapping UIDs to their deleted state. The
+    /// mailbox is given back once the client disconnects.
+    fn serve(mut msgs: BTreeMap<u32, bool>) -> (u16, thread::JoinHandle<BTreeMap<u32, bool>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            write!(stream, "* OK ready\r\n").unwrap();
+            for line in reader.lines() {
+                let line = line.unwrap();
+                let mut words = line.split_whitespace();
+                let tag = words.next().unwrap_or_default();
+                match words.collect::<Vec<_>>().as_slice() {
+                    ["SELECT", ..] => write!(stream, "* {} EXISTS\r\n", msgs.len()).unwrap(),
+                    ["UID", "STORE", uids, "+FLAGS", flags] if flags.contains("\\Deleted") => {
+                        for uid in uids.split(',') {
+                            msgs.insert(uid.parse().unwrap(), true);
+                        }
+                    }
+                    ["UID", "EXPUNGE", uids] => {
+                        let uids: Vec<u32> =
+                            uids.split(',').map(|uid| uid.parse().unwrap()).collect();
+                        msgs.retain(|uid, deleted| !(*deleted && uids.contains(uid)));
+                    }
+                    ["EXPUNGE"] => msgs.retain(|_, deleted| !*deleted),
+                    _ => (),
+                }
+                write!(stream, "{} OK done\r\n", tag).unwrap();
+            }
+            msgs
+        });
+        (port, server)
+    }
+
+    fn del_msg(caps: &[&str], msgs: BTreeMap<u32, bool>) -> BTreeMap<u32, bool> {
+        let (port, server) = serve(msgs);
+        let account_config = AccountConfig::default();
+        let imap_config = ImapBackendConfig {
+            imap_host: "127.0.0.1".into(),
+            imap_port: port,
+            imap_tls: TlsConfig {
+                mode: TlsMode::None,
+                ..TlsConfig::default()
+            },
+            imap_login: "user".into(),
+            imap_passwd_cmd: "echo passwd".into(),
+            ..ImapBackendConfig::default()
+        };
+        let mut imap = ImapBackend::new(&account_config, &imap_config);
+        imap.capabilities = Some(caps.iter().map(|cap| cap.to_string()).collect());
+        imap.del_msg("INBOX", &IdSet::from(Uid(1))).unwrap();
+        drop(imap);
+        server.join().unwrap()
+    }
+
+    #[test]
+    fn it_should_keep_other_deleted_msgs() {
+        let msgs = BTreeMap::from([(1, false), (2, true)]);
+
+        // Without UIDPLUS, the message is only flagged.
+        assert_eq!(
+            BTreeMap::from([(1, true), (2, true)]),
+            del_msg(&["IMAP4rev1"], msgs.clone())
+        );
+
+        // With UIDPLUS, only the message is expunged.
+        assert_eq!(
+            BTreeMap::from([(2, true)]),
+            del_msg(&["IMAP4rev1", "UIDPLUS"], msgs)
+        );
+    }
+}
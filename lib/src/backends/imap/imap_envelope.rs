@@ -0,0 +1,263 @@
+//! IMAP envelope module.
+//!
+//! This module provides IMAP types and conversion utilities related
+//! to the envelope.
+
+use anyhow::{anyhow, Context, Error, Result};
+use imap_proto::Address;
+use std::{convert::TryFrom, ops::Deref};
+
+use super::{has_attachment, ImapFlags};
+
+/// Represents the items to fetch in order to build envelopes.
+pub const ENVELOPE_FETCH_ITEMS: &str =
+    "(UID ENVELOPE FLAGS INTERNALDATE RFC822.SIZE BODYSTRUCTURE)";
+
+/// Represents a list of IMAP envelopes.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ImapEnvelopes {
+    #[serde(rename = "response")]
+    pub envelopes: Vec<ImapEnvelope>,
+}
+
+impl Deref for ImapEnvelopes {
+    type Target = Vec<ImapEnvelope>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.envelopes
+    }
+}
+
+/// Represents the IMAP envelope. The envelope is just a message
+/// subset, and is mostly used for listings.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImapEnvelope {
+    /// Represents the UID of the message.
+    ///
+    /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-2.3.1.1
+    pub id: u32,
+
+    /// Represents the flags attached to the message.
+    pub flags: ImapFlags,
+
+    /// Represents the subject of the message.
+    pub subject: String,
+
+    /// Represents the first sender of the message.
+    pub sender: String,
+
+    /// Represents the recipients of the message.
+    pub to: Vec<String>,
+
+    /// Represents the carbon copy recipients of the message.
+    pub cc: Vec<String>,
+
+    /// Represents the addresses replies should be sent to.
+    pub reply_to: Vec<String>,
+
+    /// Represents the Message-ID header of the message.
+    pub message_id: Option<String>,
+
+    /// Represents the size of the message, in bytes.
+    pub size: Option<u32>,
+
+    /// Tells if the message has at least one attachment.
+    pub has_attachment: bool,
+
+    /// Represents the internal date of the message.
+    ///
+    /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-2.3.3
+    pub date: Option<String>,
+}
+
+/// Represents a list of raw envelopes returned by the `imap` crate.
+pub type RawImapEnvelopes = imap::types::ZeroCopy<Vec<RawImapEnvelope>>;
+
+impl TryFrom<RawImapEnvelopes> for ImapEnvelopes {
+    type Error = Error;
+
+    fn try_from(raw_envelopes: RawImapEnvelopes) -> Result<Self, Self::Error> {
+        let mut envelopes = vec![];
+        for raw_envelope in raw_envelopes.iter().rev() {
+            envelopes.push(ImapEnvelope::try_from(raw_envelope).context("cannot parse envelope")?);
+        }
+        Ok(Self { envelopes })
+    }
+}
+
+/// Represents the raw envelope returned by the `imap` crate.
+pub type RawImapEnvelope = imap::types::Fetch;
+
+impl TryFrom<&RawImapEnvelope> for ImapEnvelope {
+    type Error = Error;
+
+    fn try_from(fetch: &RawImapEnvelope) -> Result<ImapEnvelope> {
+        let envelope = fetch
+            .envelope()
+            .ok_or_else(|| anyhow!("cannot get envelope of message {}", fetch.message))?;
+
+        // Get the UID
+        let id = fetch
+            .uid
+            .ok_or_else(|| anyhow!("cannot get UID of message {}", fetch.message))?;
+
+        // Get the flags
+        let flags = ImapFlags::try_from(fetch.flags())?;
+
+        // Get the subject
+        let subject = envelope
+            .subject
+            .as_ref()
+            .map(|subj| {
+                rfc2047_decoder::decode(subj).context(format!(
+                    "cannot decode subject of message {}",
+                    fetch.message
+                ))
+            })
+            .unwrap_or_else(|| Ok(String::default()))?;
+
+        // Get the sender
+        let sender = envelope
+            .sender
+            .as_ref()
+            .and_then(|addrs| addrs.get(0))
+            .or_else(|| envelope.from.as_ref().and_then(|addrs| addrs.get(0)))
+            .ok_or_else(|| anyhow!("cannot get sender of message {}", fetch.message))?;
+        let sender = if let Some(ref name) = sender.name {
+            rfc2047_decoder::decode(&name.to_vec()).context(format!(
+                "cannot decode sender's name of message {}",
+                fetch.message,
+            ))?
+        } else {
+            let mbox = sender
+                .mailbox
+                .as_ref()
+                .ok_or_else(|| anyhow!("cannot get sender's mailbox of message {}", fetch.message))
+                .and_then(|mbox| {
+                    rfc2047_decoder::decode(&mbox.to_vec()).context(format!(
+                        "cannot decode sender's mailbox of message {}",
+                        fetch.message,
+                    ))
+                })?;
+            let host = sender
+                .host
+                .as_ref()
+                .ok_or_else(|| anyhow!("cannot get sender's host of message {}", fetch.message))
+                .and_then(|host| {
+                    rfc2047_decoder::decode(&host.to_vec()).context(format!(
+                        "cannot decode sender's host of message {}",
+                        fetch.message,
+                    ))
+                })?;
+            format!("{}@{}", mbox, host)
+        };
+
+        // Get the recipients
+        let to = decode_addrs(&envelope.to).context(format!(
+            "cannot decode recipients of message {}",
+            fetch.message
+        ))?;
+        let cc = decode_addrs(&envelope.cc).context(format!(
+            "cannot decode carbon copy recipients of message {}",
+            fetch.message
+        ))?;
+        let reply_to = decode_addrs(&envelope.reply_to).context(format!(
+            "cannot decode reply-to addresses of message {}",
+            fetch.message
+        ))?;
+
+        // Get the message id
+        let message_id = envelope
+            .message_id
+            .as_ref()
+            .map(|id| String::from_utf8_lossy(id).into_owned());
+
+        // Get the size and the attachment indicator
+        let size = fetch.size;
+        let has_attachment = matches!(fetch.bodystructure(), Some(body) if has_attachment(body));
+
+        // Get the internal date
+        let date = fetch
+            .internal_date()
+            .map(|date| date.naive_local().to_string());
+
+        Ok(Self {
+            id,
+            flags,
+            subject,
+            sender,
+            to,
+            cc,
+            reply_to,
+            message_id,
+            size,
+            has_attachment,
+            date,
+        })
+    }
+}
+
+/// Decodes the given envelope addresses, formatted as
+/// `name <mailbox@host>` when they have a name. Group delimiters,
+/// which have no host, are skipped.
+fn decode_addrs(addrs: &Option<Vec<Address>>) -> Result<Vec<String>> {
+    let mut decoded = vec![];
+    for addr in addrs.iter().flatten() {
+        let (mbox, host) = match (&addr.mailbox, &addr.host) {
+            (Some(mbox), Some(host)) => (mbox, host),
+            _ => continue,
+        };
+        let email = format!(
+            "{}@{}",
+            String::from_utf8_lossy(mbox),
+            String::from_utf8_lossy(host)
+        );
+        match &addr.name {
+            Some(name) => {
+                let name = rfc2047_decoder::decode(&name.to_vec())
+                    .context(format!("cannot decode name of address {:?}", email))?;
+                decoded.push(format!("{} <{}>", name, email));
+            }
+            None => decoded.push(email),
+        }
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+
+    fn addr(
+        name: Option<&'static str>,
+        mbox: &'static str,
+        host: Option<&'static str>,
+    ) -> Address<'static> {
+        Address {
+            name: name.map(|name| Cow::Borrowed(name.as_bytes())),
+            adl: None,
+            mailbox: Some(Cow::Borrowed(mbox.as_bytes())),
+            host: host.map(|host| Cow::Borrowed(host.as_bytes())),
+        }
+    }
+
+    #[test]
+    fn it_should_decode_addrs() {
+        assert!(decode_addrs(&None).unwrap().is_empty());
+        assert_eq!(
+            vec!["alice@localhost", "Béb <bob@localhost>"]
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>(),
+            decode_addrs(&Some(vec![
+                addr(None, "alice", Some("localhost")),
+                addr(None, "friends", None),
+                addr(Some("=?utf-8?q?B=C3=A9b?="), "bob", Some("localhost")),
+                addr(None, "", None),
+            ]))
+            .unwrap()
+        );
+    }
+}
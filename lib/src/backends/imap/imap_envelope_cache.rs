@@ -0,0 +1,305 @@
+//! IMAP envelope cache module.
+//!
+//! Fetching envelopes is expensive on large mailboxes. When the
+//! server supports CONDSTORE ([RFC7162]), every message carries a
+//! mod-sequence which increases each time the message changes. This
+//! module contains a cache remembering the envelopes already listed
+//! together with the mod-sequence they were fetched at, so that
+//! following listings only need to fetch what changed since then.
+//!
+//! [RFC7162]: https://datatracker.ietf.org/doc/html/rfc7162
+
+use anyhow::{Context, Result};
+use log::{debug, trace, warn};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+use crate::{
+    backends::{ImapEnvelope, ImapFlags},
+    config::DeserializedConfig,
+};
+
+/// Represents a cached envelope.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImapEnvelopeCacheEntry {
+    /// Represents the highest mod-sequence of the mailbox at the time
+    /// the envelope was last fetched or checked.
+    pub mod_seq: u64,
+
+    /// Represents the cached envelope.
+    pub envelope: ImapEnvelope,
+}
+
+/// Represents what needs to be fetched in order to list the given
+/// UIDs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImapEnvelopeCachePlan {
+    /// Represents the lowest mod-sequence of the outdated entries,
+    /// to be used as `CHANGEDSINCE` modifier.
+    pub changed_since: Option<u64>,
+
+    /// Represents the cached UIDs whose flags may have changed.
+    pub outdated: Vec<u32>,
+
+    /// Represents the UIDs not cached yet, which need a full fetch.
+    pub missing: Vec<u32>,
+}
+
+/// Represents the envelope cache of one mailbox.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ImapEnvelopeCache {
+    #[serde(skip)]
+    path: PathBuf,
+
+    /// Represents the key identifying the account and the mailbox,
+    /// see [`crate::backends::ImapUidValidityCache::key`].
+    pub key: String,
+
+    /// Represents the UIDVALIDITY the cached UIDs belong to.
+    pub uid_validity: u32,
+
+    /// Represents the cached entries, indexed by UID.
+    pub entries: BTreeMap<u32, ImapEnvelopeCacheEntry>,
+}
+
+impl ImapEnvelopeCache {
+    /// Reads the cache of the given key from the default cache
+    /// directory.
+    pub fn from_default_path(key: &str) -> Result<Self> {
+        let file_name: String = key
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || "@.-".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = DeserializedConfig::cache_dir()?
+            .join("imap-envelopes")
+            .join(file_name + ".json");
+        Self::from_path(path, key)
+    }
+
+    /// Reads the cache of the given key from the given file. A
+    /// missing file, a file belonging to another key or a file that
+    /// cannot be parsed (for example written by an older version
+    /// with fewer envelope fields) is considered as an empty cache.
+    pub fn from_path(path: PathBuf, key: &str) -> Result<Self> {
+        let mut cache: Self = if path.is_file() {
+            let content = fs::read(&path)
+                .with_context(|| format!("cannot read envelope cache file {:?}", path))?;
+            serde_json::from_slice(&content).unwrap_or_else(|err| {
+                warn!(
+                    "cannot parse envelope cache file {:?}, ignoring it: {}",
+                    path, err
+                );
+                Self::default()
+            })
+        } else {
+            Self::default()
+        };
+        if cache.key != key {
+            cache = Self {
+                key: key.to_owned(),
+                ..Self::default()
+            };
+        }
+        cache.path = path;
+        trace!("envelope cache entries: {:?}", cache.entries.len());
+        Ok(cache)
+    }
+
+    /// Clears the cache if the given UIDVALIDITY differs from the
+    /// cached one, since cached UIDs may then point to other
+    /// messages.
+    pub fn check_uid_validity(&mut self, uid_validity: u32) {
+        if self.uid_validity != uid_validity {
+            debug!(
+                "UIDVALIDITY changed from {} to {}, clear envelope cache",
+                self.uid_validity, uid_validity
+            );
+            self.uid_validity = uid_validity;
+            self.entries.clear();
+        }
+    }
+
+    /// Computes what needs to be fetched in order to list the given
+    /// UIDs, knowing the current highest mod-sequence of the
+    /// mailbox.
+    pub fn plan(&self, uids: &[u32], highest_mod_seq: u64) -> ImapEnvelopeCachePlan {
+        let mut plan = ImapEnvelopeCachePlan::default();
+        for uid in uids {
+            match self.entries.get(uid) {
+                None => plan.missing.push(*uid),
+                Some(entry) if entry.mod_seq < highest_mod_seq => {
+                    plan.outdated.push(*uid);
+                    plan.changed_since = Some(
+                        plan.changed_since
+                            .map_or(entry.mod_seq, |mod_seq| mod_seq.min(entry.mod_seq)),
+                    );
+                }
+                Some(_) => (),
+            }
+        }
+        plan
+    }
+
+    /// Inserts or replaces the given envelope.
+    pub fn insert(&mut self, envelope: ImapEnvelope, mod_seq: u64) {
+        self.entries
+            .insert(envelope.id, ImapEnvelopeCacheEntry { mod_seq, envelope });
+    }
+
+    /// Replaces the flags of the given cached UID.
+    pub fn set_flags(&mut self, uid: u32, flags: ImapFlags) {
+        if let Some(entry) = self.entries.get_mut(&uid) {
+            entry.envelope.flags = flags;
+        }
+    }
+
+    /// Marks the given cached UIDs as up to date.
+    pub fn touch(&mut self, uids: &[u32], mod_seq: u64) {
+        for uid in uids {
+            if let Some(entry) = self.entries.get_mut(uid) {
+                entry.mod_seq = mod_seq;
+            }
+        }
+    }
+
+    /// Removes the cached entries lying within the given listed UIDs
+    /// without being part of them. Listed UIDs come from a contiguous
+    /// range of sequence numbers, so these entries have been
+    /// expunged. Returns the number of removed entries.
+    pub fn prune(&mut self, uids: &[u32]) -> usize {
+        let (min, max) = match (uids.iter().min(), uids.iter().max()) {
+            (Some(min), Some(max)) => (*min, *max),
+            _ => return 0,
+        };
+        let listed: HashSet<&u32> = uids.iter().collect();
+        let expunged: Vec<u32> = self
+            .entries
+            .range(min..=max)
+            .map(|(uid, _)| *uid)
+            .filter(|uid| !listed.contains(uid))
+            .collect();
+        debug!("prune {} expunged envelopes", expunged.len());
+        for uid in &expunged {
+            self.entries.remove(uid);
+        }
+        expunged.len()
+    }
+
+    /// Gets the cached envelopes matching the given UIDs, in the same
+    /// order.
+    pub fn envelopes(&self, uids: &[u32]) -> Vec<ImapEnvelope> {
+        uids.iter()
+            .filter_map(|uid| self.entries.get(uid))
+            .map(|entry| entry.envelope.clone())
+            .collect()
+    }
+
+    /// Writes the cache file.
+    pub fn write(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create cache directory {:?}", dir))?;
+        }
+        let content = serde_json::to_vec(self).context("cannot serialize envelope cache")?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("cannot write envelope cache file {:?}", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::backends::ImapFlag;
+
+    fn envelope(id: u32) -> ImapEnvelope {
+        ImapEnvelope {
+            id,
+            subject: format!("subject {}", id),
+            ..ImapEnvelope::default()
+        }
+    }
+
+    #[test]
+    fn it_should_plan_fetches() {
+        let mut cache = ImapEnvelopeCache::default();
+        cache.insert(envelope(1), 10);
+        cache.insert(envelope(2), 20);
+        cache.insert(envelope(3), 30);
+
+        let plan = cache.plan(&[1, 2, 3, 4], 30);
+        assert_eq!(Some(10), plan.changed_since);
+        assert_eq!(vec![1, 2], plan.outdated);
+        assert_eq!(vec![4], plan.missing);
+
+        cache.touch(&[1, 2], 30);
+        let plan = cache.plan(&[1, 2, 3], 30);
+        assert_eq!(ImapEnvelopeCachePlan::default(), plan);
+    }
+
+    #[test]
+    fn it_should_prune_expunged_envelopes() {
+        let mut cache = ImapEnvelopeCache::default();
+        for id in 1..=5 {
+            cache.insert(envelope(id), 1);
+        }
+
+        assert_eq!(1, cache.prune(&[2, 4]));
+        assert_eq!(
+            vec![1, 2, 4, 5],
+            cache.entries.keys().cloned().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![4, 2],
+            cache
+                .envelopes(&[4, 2])
+                .iter()
+                .map(|e| e.id)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn it_should_read_and_write_cache() {
+        let path = env::temp_dir().join(format!(
+            "himalaya-envelope-cache-{}.json",
+            uuid::Uuid::new_v4()
+        ));
+
+        let mut cache = ImapEnvelopeCache::from_path(path.clone(), "INBOX").unwrap();
+        cache.check_uid_validity(42);
+        cache.insert(envelope(1), 10);
+        cache.set_flags(1, ImapFlags(vec![ImapFlag::Seen]));
+        cache.write().unwrap();
+
+        let mut cache = ImapEnvelopeCache::from_path(path.clone(), "INBOX").unwrap();
+        assert_eq!(vec![ImapFlag::Seen], cache.entries[&1].envelope.flags.0);
+        cache.check_uid_validity(42);
+        assert_eq!(1, cache.entries.len());
+        cache.check_uid_validity(43);
+        assert!(cache.entries.is_empty());
+
+        let cache = ImapEnvelopeCache::from_path(path.clone(), "Sent").unwrap();
+        assert!(cache.entries.is_empty());
+
+        fs::write(
+            &path,
+            r#"{"key":"INBOX","uid_validity":42,"entries":{"1":{}}}"#,
+        )
+        .unwrap();
+        let cache = ImapEnvelopeCache::from_path(path.clone(), "INBOX").unwrap();
+        assert!(cache.entries.is_empty());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -5,8 +5,10 @@ use std::{
     ops::Deref,
 };
 
+use crate::msg::{Flag, Flags};
+
 /// Represents the imap flag variants.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum ImapFlag {
     Seen,
     Answered,
@@ -18,17 +20,16 @@ pub enum ImapFlag {
     Custom(String),
 }
 
-impl From<&str> for ImapFlag {
-    fn from(flag_str: &str) -> Self {
-        match flag_str {
-            "seen" => ImapFlag::Seen,
-            "answered" => ImapFlag::Answered,
-            "flagged" => ImapFlag::Flagged,
-            "deleted" => ImapFlag::Deleted,
-            "draft" => ImapFlag::Draft,
-            "recent" => ImapFlag::Recent,
-            "maycreate" | "may-create" => ImapFlag::MayCreate,
-            flag_str => ImapFlag::Custom(flag_str.into()),
+impl From<&Flag> for ImapFlag {
+    fn from(flag: &Flag) -> Self {
+        match flag {
+            Flag::Seen => ImapFlag::Seen,
+            Flag::Answered => ImapFlag::Answered,
+            Flag::Flagged => ImapFlag::Flagged,
+            Flag::Deleted => ImapFlag::Deleted,
+            Flag::Draft => ImapFlag::Draft,
+            Flag::Recent => ImapFlag::Recent,
+            Flag::Custom(custom) => ImapFlag::Custom(custom.to_owned()),
         }
     }
 }
@@ -52,7 +53,7 @@ impl TryFrom<&imap::types::Flag<'_>> for ImapFlag {
 }
 
 /// Represents the imap flags.
-#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct ImapFlags(pub Vec<ImapFlag>);
 
 impl ImapFlags {
@@ -127,14 +128,9 @@ impl<'a> Into<Vec<imap::types::Flag<'a>>> for ImapFlags {
     }
 }
 
-impl From<&str> for ImapFlags {
-    fn from(flags_str: &str) -> Self {
-        ImapFlags(
-            flags_str
-                .split_whitespace()
-                .map(|flag_str| flag_str.trim().into())
-                .collect(),
-        )
+impl From<&Flags> for ImapFlags {
+    fn from(flags: &Flags) -> Self {
+        ImapFlags(flags.iter().map(ImapFlag::from).collect())
     }
 }
 
@@ -0,0 +1,388 @@
+//! IMAP server info module.
+//!
+//! This module provides IMAP types and parsing utilities related to
+//! the information a server gives about itself: its identity
+//! ([RFC2971]), its namespaces ([RFC2342]) and the quotas of its
+//! mailboxes ([RFC9208]).
+//!
+//! [RFC2971]: https://datatracker.ietf.org/doc/html/rfc2971
+//! [RFC2342]: https://datatracker.ietf.org/doc/html/rfc2342
+//! [RFC9208]: https://datatracker.ietf.org/doc/html/rfc9208
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+/// Represents the information an IMAP server gives about itself.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ImapServerInfo {
+    /// Represents the capabilities of the server.
+    pub capabilities: Vec<String>,
+
+    /// Represents the identity of the server, empty if the server
+    /// does not support the ID extension.
+    pub id: BTreeMap<String, String>,
+
+    /// Represents the namespaces of the server, if the server
+    /// supports the NAMESPACE extension.
+    pub namespaces: Option<ImapNamespaces>,
+
+    /// Represents the quotas of the requested mailboxes, empty if
+    /// the server does not support the QUOTA extension.
+    pub quotas: Vec<ImapMboxQuota>,
+}
+
+/// Represents the namespaces of an IMAP server, by kind.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ImapNamespaces {
+    /// Represents the namespaces of the user's own mailboxes.
+    pub personal: Vec<ImapNamespace>,
+
+    /// Represents the namespaces of the mailboxes of other users.
+    pub other: Vec<ImapNamespace>,
+
+    /// Represents the namespaces of the shared mailboxes.
+    pub shared: Vec<ImapNamespace>,
+}
+
+/// Represents an IMAP namespace.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ImapNamespace {
+    /// Represents the prefix of the mailboxes of the namespace.
+    pub prefix: String,
+
+    /// Represents the hierarchy delimiter of the namespace, if any.
+    pub delim: Option<String>,
+}
+
+/// Represents the quota roots of a mailbox.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ImapMboxQuota {
+    /// Represents the mailbox name.
+    pub mbox: String,
+
+    /// Represents the quota roots the mailbox belongs to.
+    pub roots: Vec<ImapQuotaRoot>,
+}
+
+/// Represents a quota root, shared by one or several mailboxes.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ImapQuotaRoot {
+    /// Represents the name of the quota root.
+    pub name: String,
+
+    /// Represents the resources limited by the quota root.
+    pub resources: Vec<ImapQuotaResource>,
+}
+
+/// Represents the usage and the limit of a resource, like `STORAGE`
+/// (in units of 1024 octets) or `MESSAGE`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ImapQuotaResource {
+    pub name: String,
+    pub usage: u64,
+    pub limit: u64,
+}
+
+/// Parses the server identity out of a raw `ID` response, like
+/// `* ID ("name" "Dovecot" "version" NIL)`. Fields without value are
+/// skipped.
+pub fn parse_id_response(res: &[u8]) -> Result<BTreeMap<String, String>> {
+    let res = String::from_utf8_lossy(res);
+    let mut id = BTreeMap::new();
+    let fields = match res.lines().find_map(|line| line.strip_prefix("* ID ")) {
+        Some(fields) => parse_values(fields)?,
+        None => return Ok(id),
+    };
+    if let Some(Value::List(fields)) = fields.into_iter().next() {
+        for field in fields.chunks(2) {
+            if let [Value::Str(key), Value::Str(value)] = field {
+                id.insert(key.to_owned(), value.to_owned());
+            }
+        }
+    }
+    Ok(id)
+}
+
+/// Parses the namespaces out of a raw `NAMESPACE` response, like
+/// `* NAMESPACE (("" "/")) NIL (("#shared/" "/"))`.
+pub fn parse_namespace_response(res: &[u8]) -> Result<ImapNamespaces> {
+    let res = String::from_utf8_lossy(res);
+    let kinds = match res
+        .lines()
+        .find_map(|line| line.strip_prefix("* NAMESPACE "))
+    {
+        Some(kinds) => parse_values(kinds)?,
+        None => return Ok(ImapNamespaces::default()),
+    };
+
+    // Each kind is either NIL or a list of namespaces, a namespace
+    // being a prefix, a delimiter and optional extensions.
+    let mut kinds = kinds.into_iter().map(|kind| match kind {
+        Value::List(namespaces) => namespaces
+            .into_iter()
+            .filter_map(|namespace| match namespace {
+                Value::List(namespace) => match namespace.as_slice() {
+                    [Value::Str(prefix), delim, ..] => Some(ImapNamespace {
+                        prefix: prefix.to_owned(),
+                        delim: match delim {
+                            Value::Str(delim) => Some(delim.to_owned()),
+                            _ => None,
+                        },
+                    }),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    });
+    Ok(ImapNamespaces {
+        personal: kinds.next().unwrap_or_default(),
+        other: kinds.next().unwrap_or_default(),
+        shared: kinds.next().unwrap_or_default(),
+    })
+}
+
+/// Parses the quota roots of the given mailbox out of a raw
+/// `GETQUOTAROOT` response, made of a `QUOTAROOT` line followed by
+/// one `QUOTA` line per root, like `* QUOTA "" (STORAGE 10 512)`.
+pub fn parse_quota_root_response(mbox: &str, res: &[u8]) -> Result<ImapMboxQuota> {
+    let res = String::from_utf8_lossy(res);
+
+    let mut roots = vec![];
+    for line in res.lines() {
+        if let Some(values) = line.strip_prefix("* QUOTAROOT ") {
+            // The mailbox name comes first.
+            for root in parse_values(values)?.into_iter().skip(1) {
+                if let Value::Str(name) = root {
+                    roots.push(ImapQuotaRoot {
+                        name,
+                        resources: vec![],
+                    });
+                }
+            }
+        }
+    }
+
+    for line in res.lines() {
+        let values = match line.strip_prefix("* QUOTA ") {
+            Some(values) => parse_values(values)?,
+            None => continue,
+        };
+        let (name, resources) = match values.as_slice() {
+            [Value::Str(name), Value::List(resources)] => (name, resources),
+            _ => return Err(anyhow!("cannot parse quota response {:?}", line)),
+        };
+        let mut parsed_resources = vec![];
+        for resource in resources.chunks(3) {
+            match resource {
+                [Value::Str(name), Value::Str(usage), Value::Str(limit)] => {
+                    parsed_resources.push(ImapQuotaResource {
+                        name: name.to_owned(),
+                        usage: usage.parse().map_err(|_| {
+                            anyhow!("cannot parse usage {:?} of quota {:?}", usage, name)
+                        })?,
+                        limit: limit.parse().map_err(|_| {
+                            anyhow!("cannot parse limit {:?} of quota {:?}", limit, name)
+                        })?,
+                    })
+                }
+                _ => return Err(anyhow!("cannot parse quota response {:?}", line)),
+            }
+        }
+        match roots.iter_mut().find(|root| root.name == *name) {
+            Some(root) => root.resources = parsed_resources,
+            None => roots.push(ImapQuotaRoot {
+                name: name.to_owned(),
+                resources: parsed_resources,
+            }),
+        }
+    }
+
+    Ok(ImapMboxQuota {
+        mbox: mbox.to_owned(),
+        roots,
+    })
+}
+
+/// Represents the values of an IMAP response line. Atoms, numbers
+/// and quoted strings are all represented as strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Nil,
+    Str(String),
+    List(Vec<Value>),
+}
+
+/// Parses the values of the given response line, literals are not
+/// supported.
+fn parse_values(input: &str) -> Result<Vec<Value>> {
+    let mut parser = ValueParser {
+        input: input.trim().as_bytes(),
+        pos: 0,
+    };
+    let mut values = vec![];
+    loop {
+        parser.skip_spaces();
+        if parser.pos >= parser.input.len() {
+            return Ok(values);
+        }
+        values.push(parser.parse_value()?);
+    }
+}
+
+/// Represents the parser of the values of an IMAP response line.
+struct ValueParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ValueParser<'a> {
+    fn skip_spaces(&mut self) {
+        while self.input.get(self.pos) == Some(&b' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        self.skip_spaces();
+        match self.input.get(self.pos) {
+            Some(b'(') => {
+                self.pos += 1;
+                let mut values = vec![];
+                loop {
+                    self.skip_spaces();
+                    match self.input.get(self.pos) {
+                        Some(b')') => {
+                            self.pos += 1;
+                            return Ok(Value::List(values));
+                        }
+                        Some(_) => values.push(self.parse_value()?),
+                        None => return Err(anyhow!("cannot parse response: unclosed list")),
+                    }
+                }
+            }
+            Some(b'"') => {
+                self.pos += 1;
+                let mut value = vec![];
+                loop {
+                    match self.input.get(self.pos) {
+                        Some(b'"') => {
+                            self.pos += 1;
+                            return Ok(Value::Str(String::from_utf8_lossy(&value).into_owned()));
+                        }
+                        Some(b'\\') => {
+                            if let Some(c) = self.input.get(self.pos + 1) {
+                                value.push(*c);
+                            }
+                            self.pos += 2;
+                        }
+                        Some(c) => {
+                            value.push(*c);
+                            self.pos += 1;
+                        }
+                        None => return Err(anyhow!("cannot parse response: unclosed string")),
+                    }
+                }
+            }
+            Some(b'{') => Err(anyhow!("cannot parse response: literals not supported")),
+            Some(b')') => Err(anyhow!(
+                "cannot parse response: unexpected ')' at {}",
+                self.pos
+            )),
+            _ => {
+                let start = self.pos;
+                while matches!(self.input.get(self.pos), Some(c) if !b" ()\"".contains(c)) {
+                    self.pos += 1;
+                }
+                let atom = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
+                if atom.eq_ignore_ascii_case("NIL") {
+                    Ok(Value::Nil)
+                } else {
+                    Ok(Value::Str(atom))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_id_response() {
+        let res = b"* ID (\"name\" \"Dovecot\" \"version\" NIL \"vendor\" \"the \\\"best\\\"\")\r\nA1 OK done\r\n";
+        let id = parse_id_response(res).unwrap();
+        assert_eq!(2, id.len());
+        assert_eq!(Some("Dovecot"), id.get("name").map(String::as_str));
+        assert_eq!(Some("the \"best\""), id.get("vendor").map(String::as_str));
+
+        let res = b"* ID NIL\r\nA1 OK done\r\n";
+        assert!(parse_id_response(res).unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_should_parse_namespace_response() {
+        let res = b"* NAMESPACE ((\"\" \"/\")) NIL ((\"#shared/\" \"/\" \"X-PARAM\" (\"FLAG1\")) (\"#news.\" NIL))\r\nA1 OK done\r\n";
+        assert_eq!(
+            ImapNamespaces {
+                personal: vec![ImapNamespace {
+                    prefix: "".into(),
+                    delim: Some("/".into()),
+                }],
+                other: vec![],
+                shared: vec![
+                    ImapNamespace {
+                        prefix: "#shared/".into(),
+                        delim: Some("/".into()),
+                    },
+                    ImapNamespace {
+                        prefix: "#news.".into(),
+                        delim: None,
+                    },
+                ],
+            },
+            parse_namespace_response(res).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_should_parse_quota_root_response() {
+        let res = concat!(
+            "* QUOTAROOT INBOX \"\" \"#user/alice\"\r\n",
+            "* QUOTA \"\" (STORAGE 10 512 MESSAGE 3 1000)\r\n",
+            "A1 OK done\r\n",
+        );
+        assert_eq!(
+            ImapMboxQuota {
+                mbox: "INBOX".into(),
+                roots: vec![
+                    ImapQuotaRoot {
+                        name: "".into(),
+                        resources: vec![
+                            ImapQuotaResource {
+                                name: "STORAGE".into(),
+                                usage: 10,
+                                limit: 512,
+                            },
+                            ImapQuotaResource {
+                                name: "MESSAGE".into(),
+                                usage: 3,
+                                limit: 1000,
+                            },
+                        ],
+                    },
+                    ImapQuotaRoot {
+                        name: "#user/alice".into(),
+                        resources: vec![],
+                    },
+                ],
+            },
+            parse_quota_root_response("INBOX", res.as_bytes()).unwrap()
+        );
+
+        let res = b"* QUOTA \"\" (STORAGE 10)\r\nA1 OK done\r\n";
+        assert!(parse_quota_root_response("INBOX", res).is_err());
+    }
+}
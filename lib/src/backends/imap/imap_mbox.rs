@@ -0,0 +1,216 @@
+//! IMAP mailbox module.
+//!
+//! This module provides IMAP types and conversion utilities related
+//! to the mailbox.
+
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::ops::Deref;
+
+use crate::mbox::{MboxCounts, SpecialUse};
+
+use super::{decode_mbox_name, ImapMboxAttr, ImapMboxAttrs};
+
+/// Represents a list of IMAP mailboxes.
+#[derive(Debug, Default, Serialize)]
+pub struct ImapMboxes {
+    #[serde(rename = "response")]
+    pub mboxes: Vec<ImapMbox>,
+}
+
+impl Deref for ImapMboxes {
+    type Target = Vec<ImapMbox>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mboxes
+    }
+}
+
+/// Represents the IMAP mailbox.
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ImapMbox {
+    /// Represents the mailbox hierarchie delimiter.
+    pub delim: String,
+
+    /// Represents the mailbox name.
+    pub name: String,
+
+    /// Represents the mailbox attributes.
+    pub attrs: ImapMboxAttrs,
+
+    /// Represents the message counts of the mailbox, missing when the
+    /// mailbox cannot be selected.
+    #[serde(flatten)]
+    pub counts: Option<MboxCounts>,
+}
+
+impl ImapMbox {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Gets the special use of the mailbox from its attributes, if
+    /// any.
+    pub fn special_use(&self) -> Option<SpecialUse> {
+        self.attrs.iter().find_map(|attr| attr.special_use())
+    }
+
+    /// Checks if the mailbox can be selected, and therefore holds
+    /// messages.
+    pub fn is_selectable(&self) -> bool {
+        !self.attrs.iter().any(|attr| match attr {
+            ImapMboxAttr::NoSelect => true,
+            ImapMboxAttr::Custom(custom) => custom.eq_ignore_ascii_case("\\NonExistent"),
+            _ => false,
+        })
+    }
+}
+
+impl Display for ImapMbox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_create_new_mbox() {
+        assert_eq!(ImapMbox::default(), ImapMbox::new(""));
+        assert_eq!(
+            ImapMbox {
+                name: "INBOX".into(),
+                ..ImapMbox::default()
+            },
+            ImapMbox::new("INBOX")
+        );
+    }
+
+    #[test]
+    fn it_should_display_mbox() {
+        let default_mbox = ImapMbox::default();
+        assert_eq!("", default_mbox.to_string());
+
+        let new_mbox = ImapMbox::new("INBOX");
+        assert_eq!("INBOX", new_mbox.to_string());
+
+        let full_mbox = ImapMbox {
+            delim: ".".into(),
+            name: "Sent".into(),
+            attrs: ImapMboxAttrs(vec![ImapMboxAttr::NoSelect]),
+            counts: None,
+        };
+        assert_eq!("Sent", full_mbox.to_string());
+    }
+
+    #[test]
+    fn it_should_check_if_mbox_is_selectable() {
+        let mut mbox = ImapMbox::new("INBOX");
+        assert!(mbox.is_selectable());
+        mbox.attrs = ImapMboxAttrs(vec![ImapMboxAttr::Custom("\\HasChildren".into())]);
+        assert!(mbox.is_selectable());
+        mbox.attrs = ImapMboxAttrs(vec![ImapMboxAttr::NoSelect]);
+        assert!(!mbox.is_selectable());
+        mbox.attrs = ImapMboxAttrs(vec![ImapMboxAttr::Custom("\\NonExistent".into())]);
+        assert!(!mbox.is_selectable());
+    }
+
+    #[test]
+    fn it_should_get_counts_from_status_attrs() {
+        let attrs = [
+            RawImapStatusAttr::Messages(12),
+            RawImapStatusAttr::UidNext(42),
+            RawImapStatusAttr::Unseen(3),
+            RawImapStatusAttr::Recent(1),
+        ];
+        assert_eq!(
+            MboxCounts {
+                messages: 12,
+                unseen: 3,
+                recent: Some(1),
+                size: None,
+            },
+            MboxCounts::from(&attrs[..])
+        );
+    }
+
+    #[test]
+    fn it_should_get_special_use() {
+        let mbox = ImapMbox {
+            name: "[Gmail]/Sent Mail".into(),
+            attrs: ImapMboxAttrs(vec![
+                ImapMboxAttr::Custom("\\HasNoChildren".into()),
+                ImapMboxAttr::Custom("\\Sent".into()),
+            ]),
+            ..ImapMbox::default()
+        };
+        assert_eq!(Some(SpecialUse::Sent), mbox.special_use());
+        assert_eq!(None, ImapMbox::new("INBOX").special_use());
+    }
+}
+
+/// Represents a list of raw mailboxes returned by the `imap` crate.
+pub type RawImapMboxes = imap::types::ZeroCopy<Vec<RawImapMbox>>;
+
+impl<'a> From<RawImapMboxes> for ImapMboxes {
+    fn from(raw_mboxes: RawImapMboxes) -> Self {
+        Self {
+            mboxes: raw_mboxes.iter().map(ImapMbox::from).collect(),
+        }
+    }
+}
+
+/// Represents the raw mailbox returned by the `imap` crate.
+pub type RawImapMbox = imap::types::Name;
+
+impl<'a> From<&'a RawImapMbox> for ImapMbox {
+    fn from(raw_mbox: &'a RawImapMbox) -> Self {
+        Self {
+            delim: raw_mbox.delimiter().unwrap_or_default().into(),
+            name: decode_mbox_name(raw_mbox.name()),
+            attrs: raw_mbox.attributes().into(),
+            counts: None,
+        }
+    }
+}
+
+/// Represents the raw mailbox status returned by the `imap` crate.
+pub type RawImapMboxStatus = imap::types::Mailbox;
+
+impl<'a> From<&'a RawImapMboxStatus> for MboxCounts {
+    fn from(status: &'a RawImapMboxStatus) -> Self {
+        Self {
+            messages: status.exists as usize,
+            unseen: status.unseen.unwrap_or_default() as usize,
+            recent: Some(status.recent as usize),
+            size: None,
+        }
+    }
+}
+
+/// Represents the raw status attribute returned by the `imap` crate
+/// along with LIST-STATUS responses.
+pub type RawImapStatusAttr = imap_proto::StatusAttribute;
+
+impl<'a> From<&'a [RawImapStatusAttr]> for MboxCounts {
+    fn from(attrs: &'a [RawImapStatusAttr]) -> Self {
+        let mut counts = Self {
+            recent: Some(0),
+            ..Self::default()
+        };
+        for attr in attrs {
+            match attr {
+                RawImapStatusAttr::Messages(n) => counts.messages = *n as usize,
+                RawImapStatusAttr::Unseen(n) => counts.unseen = *n as usize,
+                RawImapStatusAttr::Recent(n) => counts.recent = Some(*n as usize),
+                _ => (),
+            }
+        }
+        counts
+    }
+}
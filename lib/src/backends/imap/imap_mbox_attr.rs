@@ -10,6 +10,8 @@ use std::{
     ops::Deref,
 };
 
+use crate::mbox::SpecialUse;
+
 /// Represents the attributes of the mailbox.
 #[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
 pub struct ImapMboxAttrs(pub Vec<ImapMboxAttr>);
@@ -42,6 +44,19 @@ pub enum ImapMboxAttr {
     Custom(String),
 }
 
+impl ImapMboxAttr {
+    /// Gets the special use ([RFC6154]) the attribute stands for, if
+    /// any.
+    ///
+    /// [RFC6154]: https://datatracker.ietf.org/doc/html/rfc6154
+    pub fn special_use(&self) -> Option<SpecialUse> {
+        match self {
+            ImapMboxAttr::Custom(custom) => SpecialUse::from_attr(custom),
+            _ => None,
+        }
+    }
+}
+
 /// Makes the attribute displayable.
 impl Display for ImapMboxAttr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
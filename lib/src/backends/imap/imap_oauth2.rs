@@ -0,0 +1,74 @@
+//! IMAP OAuth 2.0 module.
+//!
+//! This module contains the SASL authenticator used to log in to
+//! IMAP servers with an OAuth 2.0 access token.
+
+use anyhow::{Context, Result};
+use log::debug;
+
+use crate::config::{ImapBackendConfig, OAuth2Config, OAuth2TokenCache};
+
+/// Represents the XOAUTH2 and OAUTHBEARER authenticator.
+#[derive(Debug, Clone)]
+pub struct ImapOAuth2Authenticator {
+    initial_response: String,
+    error_response: &'static str,
+}
+
+impl ImapOAuth2Authenticator {
+    /// Builds the authenticator from an access token matching the
+    /// given configs.
+    pub fn new(imap_config: &ImapBackendConfig, oauth2_config: &OAuth2Config) -> Result<Self> {
+        let access_token = oauth2_config
+            .access_token(&Self::key(imap_config), &imap_config.imap_tls)
+            .context("cannot get IMAP OAuth2 access token")?;
+        let method = oauth2_config.method;
+        Ok(Self {
+            initial_response: method.sasl_initial_response(
+                &imap_config.imap_login,
+                &imap_config.imap_host,
+                imap_config.imap_port,
+                &access_token,
+            ),
+            error_response: method.sasl_error_response(),
+        })
+    }
+
+    /// Expires the cached access token matching the given config.
+    /// Access tokens may be revoked before their expiry, in which
+    /// case the next authenticator should use a fresh one.
+    pub fn expire(imap_config: &ImapBackendConfig) -> Result<()> {
+        OAuth2TokenCache::from_default_path()?.expire(&Self::key(imap_config))
+    }
+
+    fn key(imap_config: &ImapBackendConfig) -> String {
+        OAuth2TokenCache::key(
+            "imap",
+            &imap_config.imap_login,
+            &imap_config.imap_host,
+            imap_config.imap_port,
+        )
+    }
+
+    /// Answers the given challenge. The first challenge is empty,
+    /// the next one can only be an error.
+    fn answer(&self, challenge: &[u8]) -> String {
+        if challenge.is_empty() {
+            self.initial_response.clone()
+        } else {
+            debug!(
+                "OAuth2 authentication error: {}",
+                String::from_utf8_lossy(challenge)
+            );
+            self.error_response.to_owned()
+        }
+    }
+}
+
+impl imap::Authenticator for ImapOAuth2Authenticator {
+    type Response = String;
+
+    fn process(&self, challenge: &[u8]) -> Self::Response {
+        self.answer(challenge)
+    }
+}
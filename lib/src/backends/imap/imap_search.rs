@@ -0,0 +1,347 @@
+//! IMAP search module.
+//!
+//! This module provides the utilities used to search envelopes on
+//! servers missing some search extensions. Servers without the SORT
+//! extension ([RFC5256]) get their envelopes sorted in-process, using
+//! the same semantics, and servers supporting the PARTIAL extension
+//! ([RFC9394]) only return the requested page of a search. Appended
+//! messages get their UID searched back, since the UIDPLUS extension
+//! ([RFC4315]) response codes are not exposed by the IMAP crates.
+//!
+//! [RFC5256]: https://datatracker.ietf.org/doc/html/rfc5256
+//! [RFC9394]: https://datatracker.ietf.org/doc/html/rfc9394
+//! [RFC4315]: https://datatracker.ietf.org/doc/html/rfc4315
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset};
+use imap_proto::{Address, Envelope};
+use mailparse::MailHeaderMap;
+use std::{cmp::Ordering, convert::TryFrom};
+
+use crate::msg::{
+    base_subject, imap_quote, parse_date, SortCriteria, SortCriterionKind, SortCriterionOrder,
+};
+
+use super::RawImapEnvelope;
+
+/// Represents the items to fetch in order to sort envelopes
+/// in-process.
+pub const SORT_KEYS_FETCH_ITEMS: &str = "(UID ENVELOPE INTERNALDATE RFC822.SIZE)";
+
+/// Represents the properties an IMAP message can be sorted by, for
+/// servers which cannot sort messages themselves.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImapSortKeys {
+    /// Represents the UID of the message, which breaks the ties.
+    pub uid: u32,
+
+    /// Represents the internal date of the message.
+    pub arrival: Option<DateTime<FixedOffset>>,
+
+    /// Represents the mailbox of the first Cc address, lowercased.
+    pub cc: String,
+
+    /// Represents the date of the message, or its internal date if
+    /// the date is missing or invalid.
+    pub date: Option<DateTime<FixedOffset>>,
+
+    /// Represents the mailbox of the first From address, lowercased.
+    pub from: String,
+
+    /// Represents the size of the message, in bytes.
+    pub size: u32,
+
+    /// Represents the base subject of the message, lowercased and
+    /// without reply or forward prefixes.
+    pub subject: String,
+
+    /// Represents the mailbox of the first To address, lowercased.
+    pub to: String,
+}
+
+impl ImapSortKeys {
+    /// Extracts the sort keys from the given fetched items.
+    pub fn new(
+        uid: u32,
+        envelope: Option<&Envelope>,
+        arrival: Option<DateTime<FixedOffset>>,
+        size: Option<u32>,
+    ) -> Self {
+        let date = envelope
+            .and_then(|envelope| envelope.date.as_ref())
+            .and_then(|date| parse_date(&String::from_utf8_lossy(date)))
+            .or(arrival);
+        let subject = envelope
+            .and_then(|envelope| envelope.subject.as_ref())
+            .map(|subject| {
+                rfc2047_decoder::decode(subject)
+                    .unwrap_or_else(|_| String::from_utf8_lossy(subject).into_owned())
+            })
+            .unwrap_or_default();
+        Self {
+            uid,
+            arrival,
+            cc: first_mbox(envelope.and_then(|envelope| envelope.cc.as_ref())),
+            date,
+            from: first_mbox(envelope.and_then(|envelope| envelope.from.as_ref())),
+            size: size.unwrap_or_default(),
+            subject: base_subject(&subject),
+            to: first_mbox(envelope.and_then(|envelope| envelope.to.as_ref())),
+        }
+    }
+
+    /// Compares the sort keys using the given criteria. Each
+    /// criterion breaks the ties of the previous one, and the UID
+    /// breaks the remaining ties, like the SORT extension does.
+    pub fn cmp_by(&self, other: &Self, criteria: &SortCriteria) -> Ordering {
+        criteria
+            .iter()
+            .fold(Ordering::Equal, |ord, criterion| {
+                ord.then_with(|| {
+                    let ord = match criterion.kind {
+                        SortCriterionKind::Arrival => self.arrival.cmp(&other.arrival),
+                        SortCriterionKind::Cc => self.cc.cmp(&other.cc),
+                        SortCriterionKind::Date => self.date.cmp(&other.date),
+                        SortCriterionKind::From => self.from.cmp(&other.from),
+                        SortCriterionKind::Size => self.size.cmp(&other.size),
+                        SortCriterionKind::Subject => self.subject.cmp(&other.subject),
+                        SortCriterionKind::To => self.to.cmp(&other.to),
+                    };
+                    match criterion.order {
+                        SortCriterionOrder::Asc => ord,
+                        SortCriterionOrder::Desc => ord.reverse(),
+                    }
+                })
+            })
+            .then_with(|| self.uid.cmp(&other.uid))
+    }
+}
+
+impl TryFrom<&RawImapEnvelope> for ImapSortKeys {
+    type Error = anyhow::Error;
+
+    fn try_from(fetch: &RawImapEnvelope) -> Result<Self> {
+        let uid = fetch
+            .uid
+            .ok_or_else(|| anyhow!("cannot get UID of message {}", fetch.message))?;
+        Ok(Self::new(
+            uid,
+            fetch.envelope(),
+            fetch.internal_date(),
+            fetch.size,
+        ))
+    }
+}
+
+/// Returns the mailbox of the first address, lowercased. Group
+/// delimiters, which have no host, are skipped.
+fn first_mbox(addrs: Option<&Vec<Address>>) -> String {
+    addrs
+        .into_iter()
+        .flatten()
+        .find(|addr| addr.host.is_some())
+        .and_then(|addr| addr.mailbox.as_ref())
+        .map(|mbox| String::from_utf8_lossy(mbox).to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Builds the range of the `PARTIAL` search return option matching
+/// the given page. Negative ranges count from the last match, so
+/// that the most recent messages come first.
+pub fn partial_range(page_size: usize, page: usize) -> String {
+    let begin = page * page_size + 1;
+    format!("-{}:-{}", begin, begin + page_size - 1)
+}
+
+/// Parses the UIDs out of a raw `ESEARCH` response to a search with
+/// the `PARTIAL` return option, like
+/// `* ESEARCH (TAG "A1") UID PARTIAL (-1:-10 41:44,46)`. UIDs are
+/// returned in descending order.
+pub fn parse_esearch_partial_response(res: &[u8]) -> Result<Vec<u32>> {
+    let res = String::from_utf8_lossy(res);
+    let partial = match res
+        .lines()
+        .filter(|line| line.starts_with("* ESEARCH"))
+        .find_map(|line| line.split_once("PARTIAL ("))
+    {
+        Some((_, partial)) => partial,
+        None => return Ok(vec![]),
+    };
+    let partial = partial
+        .split_once(')')
+        .map(|(partial, _)| partial)
+        .ok_or_else(|| anyhow!("cannot parse esearch response: unclosed partial result"))?;
+
+    let mut uids = vec![];
+    let uid_set = partial.split_whitespace().nth(1).unwrap_or("NIL");
+    if uid_set != "NIL" {
+        for range in uid_set.split(',') {
+            let parse = |uid: &str| {
+                uid.parse::<u32>()
+                    .map_err(|_| anyhow!("cannot parse esearch response: invalid uid {:?}", uid))
+            };
+            let (begin, end) = match range.split_once(':') {
+                Some((begin, end)) => (parse(begin)?, parse(end)?),
+                None => (parse(range)?, parse(range)?),
+            };
+            uids.extend(begin.min(end)..=begin.max(end));
+        }
+    }
+    uids.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(uids)
+}
+
+/// Builds the query searching the message just appended to a
+/// mailbox, among the messages which arrived from the given UID. The
+/// Message-ID header, when present, tells the message apart from the
+/// ones appended at the same time by other clients.
+pub fn appended_msg_query(msg: &[u8], uid_next: u32) -> String {
+    let msg_id = mailparse::parse_headers(msg)
+        .ok()
+        .and_then(|(headers, _)| headers.get_first_value("Message-ID"));
+    match msg_id {
+        Some(msg_id) => format!(
+            "UID {}:* HEADER Message-ID {}",
+            uid_next,
+            imap_quote(msg_id.trim())
+        ),
+        None => format!("UID {}:*", uid_next),
+    }
+}
+
+/// Picks the UID of the appended message out of the results of the
+/// [`appended_msg_query`]. The range `n:*` always matches the last
+/// message of the mailbox, so UIDs lower than the given one are
+/// discarded. Zero or several remaining UIDs are an error rather
+/// than a guess.
+pub fn appended_msg_uid(uids: impl IntoIterator<Item = u32>, uid_next: u32) -> Result<u32> {
+    let uids: Vec<u32> = uids.into_iter().filter(|uid| *uid >= uid_next).collect();
+    match uids.as_slice() {
+        [uid] => Ok(*uid),
+        [] => Err(anyhow!("cannot find appended message")),
+        uids => Err(anyhow!(
+            "cannot tell appended message apart from messages {}",
+            uids.iter()
+                .map(|uid| uid.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use imap_proto::{parser::parse_response, AttributeValue, Response};
+
+    use super::*;
+
+    fn keys(uid: u32, subject: &str, from: &str, date: &str, size: u32) -> ImapSortKeys {
+        let res = format!(
+            concat!(
+                r#"* {uid} FETCH (UID {uid} RFC822.SIZE {size} INTERNALDATE "01-Mar-2022 12:00:00 +0000" "#,
+                r#"ENVELOPE ("{date}" "{subject}" ((NIL NIL "{from}" "localhost")) NIL NIL "#,
+                r#"(("Bob" NIL "Bob" "localhost")) NIL NIL NIL NIL))"#,
+                "\r\n",
+            ),
+            uid = uid,
+            size = size,
+            date = date,
+            subject = subject,
+            from = from,
+        );
+        match parse_response(res.as_bytes()).unwrap().1 {
+            Response::Fetch(_, attrs) => {
+                let envelope = attrs.iter().find_map(|attr| match attr {
+                    AttributeValue::Envelope(envelope) => Some(envelope.as_ref()),
+                    _ => None,
+                });
+                let arrival = attrs.iter().find_map(|attr| match attr {
+                    AttributeValue::InternalDate(date) => {
+                        DateTime::parse_from_str(date, "%d-%b-%Y %H:%M:%S %z").ok()
+                    }
+                    _ => None,
+                });
+                ImapSortKeys::new(uid, envelope, arrival, Some(size))
+            }
+            res => panic!("unexpected response {:?}", res),
+        }
+    }
+
+    #[test]
+    fn it_should_extract_sort_keys() {
+        let keys = keys(
+            1,
+            "Re: =?utf-8?q?Caf=C3=A9?=",
+            "Alice",
+            "Tue, 1 Mar 2022 10:00:00 +0100 (CET)",
+            42,
+        );
+        assert_eq!("alice", keys.from);
+        assert_eq!("bob", keys.to);
+        assert_eq!("", keys.cc);
+        assert_eq!("café", keys.subject);
+        assert_eq!(parse_date("Tue, 1 Mar 2022 09:00:00 +0000"), keys.date);
+        assert_eq!(42, keys.size);
+    }
+
+    #[test]
+    fn it_should_sort_by_criteria() {
+        let mut msgs = [
+            keys(1, "Lunch", "bob", "Tue, 1 Mar 2022 10:00:00 +0100", 3),
+            keys(2, "Meeting", "alice", "Tue, 1 Mar 2022 10:30:00 +0200", 1),
+            keys(3, "Report", "bob", "invalid", 2),
+        ];
+        let mut sort = |criteria: &str| {
+            let criteria = SortCriteria::try_from(criteria).unwrap();
+            msgs.sort_by(|a, b| a.cmp_by(b, &criteria));
+            msgs.iter().map(|keys| keys.uid).collect::<Vec<_>>()
+        };
+
+        // The invalid date falls back to the internal date.
+        assert_eq!(vec![2, 1, 3], sort("date"));
+        assert_eq!(vec![3, 1, 2], sort("date:desc"));
+        assert_eq!(vec![1, 3, 2], sort("from:desc"));
+        assert_eq!(vec![3, 1, 2], sort("from:desc subject:desc"));
+        assert_eq!(vec![1, 2, 3], sort("arrival"));
+        assert_eq!(vec![2, 3, 1], sort("size"));
+    }
+
+    #[test]
+    fn it_should_parse_esearch_partial_response() {
+        assert_eq!("-1:-10", partial_range(10, 0));
+        assert_eq!("-21:-30", partial_range(10, 2));
+
+        let res = b"* ESEARCH (TAG \"A1\") UID PARTIAL (-1:-10 41:44,46)\r\nA1 OK done\r\n";
+        assert_eq!(
+            vec![46, 44, 43, 42, 41],
+            parse_esearch_partial_response(res).unwrap()
+        );
+
+        let res = b"* ESEARCH (TAG \"A1\") UID PARTIAL (-11:-20 NIL)\r\nA1 OK done\r\n";
+        assert!(parse_esearch_partial_response(res).unwrap().is_empty());
+
+        let res = b"* ESEARCH (TAG \"A1\") UID\r\nA1 OK done\r\n";
+        assert!(parse_esearch_partial_response(res).unwrap().is_empty());
+
+        let res = b"* ESEARCH (TAG \"A1\") UID PARTIAL (-1:-10 4:x)\r\nA1 OK done\r\n";
+        assert!(parse_esearch_partial_response(res).is_err());
+    }
+
+    #[test]
+    fn it_should_find_appended_msg_uid() {
+        let msg = b"Message-ID: <1@localhost>\r\nSubject: \"hello\"\r\n\r\nbody";
+        assert_eq!(
+            r#"UID 42:* HEADER Message-ID "<1@localhost>""#,
+            appended_msg_query(msg, 42)
+        );
+        assert_eq!(
+            "UID 42:*",
+            appended_msg_query(b"Subject: hello\r\n\r\n", 42)
+        );
+
+        assert_eq!(42, appended_msg_uid(vec![42], 42).unwrap());
+        // The last message matches `42:*` even if its UID is lower.
+        assert!(appended_msg_uid(vec![41], 42).is_err());
+        assert!(appended_msg_uid(vec![42, 43], 42).is_err());
+    }
+}
@@ -0,0 +1,866 @@
+//! IMAP sync module.
+//!
+//! This module contains the two-way synchronization between an IMAP
+//! account and a local Maildir tree. Each IMAP mailbox is mirrored
+//! into a Maildir folder, next to a state file remembering the
+//! UIDVALIDITY of the mailbox, the UID of each synchronized message
+//! with its Maildir id and its flags, and the last sync point. This
+//! state is what allows to tell new messages from deleted ones, and
+//! which side changed the flags of a message since the last sync.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use log::{debug, info, trace};
+use mailparse::MailHeaderMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    backends::{encode_utf7, Backend, ImapBackend, ImapFlag, ImapFlags, ImapMboxAttr, ImapMboxes},
+    msg::{Flag, Flags, IdRange, IdSet, Uid},
+};
+
+/// Represents the name of the state file, stored in each Maildir
+/// folder.
+const STATE_FILE_NAME: &str = ".himalaya-sync-state.toml";
+
+/// Represents the flags kept in sync, as Maildir flag characters
+/// (draft, flagged, replied, seen and trashed). Other flags cannot be
+/// represented on both sides.
+const SYNC_FLAGS: &str = "DFRST";
+
+/// Represents a synchronized message.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ImapSyncEntry {
+    /// Represents the UID of the IMAP message.
+    pub uid: u32,
+    /// Represents the id of the Maildir message.
+    pub maildir_id: String,
+    /// Represents the flags of the message at the last sync, as
+    /// Maildir flag characters.
+    pub flags: String,
+}
+
+/// Represents the sync state of a mailbox.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ImapSyncState {
+    /// Represents the UIDVALIDITY of the mailbox at the last sync.
+    pub uid_validity: Option<u32>,
+    /// Represents the highest UID seen at the last sync.
+    pub last_uid: u32,
+    /// Represents the date of the last sync.
+    pub last_sync: Option<String>,
+    /// Represents the synchronized messages.
+    #[serde(default)]
+    pub entries: Vec<ImapSyncEntry>,
+}
+
+impl ImapSyncState {
+    /// Reads the state from the given file. A missing file is
+    /// considered as an empty state.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("cannot read sync state file {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("cannot parse sync state file {:?}", path))
+    }
+
+    /// Writes the state to the given file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = toml::to_string(self).context("cannot serialize sync state")?;
+        fs::write(path, content).with_context(|| format!("cannot write sync state file {:?}", path))
+    }
+}
+
+/// Represents the flags to add and to remove from a message.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImapSyncFlagsDiff {
+    pub add: String,
+    pub remove: String,
+}
+
+/// Represents the changes to apply on both sides in order to
+/// synchronize a mailbox.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImapSyncPlan {
+    /// Represents the entries still synchronized, with their merged
+    /// flags.
+    pub entries: Vec<ImapSyncEntry>,
+    /// Represents the new entries of the messages found on both
+    /// sides, matched by Message-ID, with their merged flags.
+    pub matched: Vec<ImapSyncEntry>,
+    /// Represents the UIDs of the new IMAP messages to download,
+    /// with their flags.
+    pub pull: Vec<(u32, String)>,
+    /// Represents the ids of the new Maildir messages to upload.
+    pub push: Vec<String>,
+    /// Represents the ids of the Maildir messages deleted on the IMAP
+    /// side.
+    pub del_local: Vec<String>,
+    /// Represents the UIDs of the IMAP messages deleted on the
+    /// Maildir side.
+    pub del_remote: Vec<u32>,
+    /// Represents the flag changes of the Maildir messages.
+    pub flags_local: Vec<(String, ImapSyncFlagsDiff)>,
+    /// Represents the flag changes of the IMAP messages.
+    pub flags_remote: Vec<(u32, ImapSyncFlagsDiff)>,
+}
+
+impl ImapSyncPlan {
+    /// Builds the plan from the last sync entries and from the
+    /// current flags of both sides, indexed by UID and by Maildir id.
+    /// The Message-IDs of the messages not synchronized yet, indexed
+    /// the same way, are used to match messages already present on
+    /// both sides, for example when the Maildir tree already mirrors
+    /// the mailbox, instead of duplicating them.
+    pub fn new(
+        entries: &[ImapSyncEntry],
+        remote: &BTreeMap<u32, String>,
+        local: &BTreeMap<String, String>,
+        remote_msg_ids: &BTreeMap<u32, String>,
+        local_msg_ids: &BTreeMap<String, String>,
+    ) -> Self {
+        let mut plan = Self::default();
+
+        for entry in entries {
+            match (remote.get(&entry.uid), local.get(&entry.maildir_id)) {
+                (Some(remote_flags), Some(local_flags)) => {
+                    let flags = plan.sync_flags(entry, remote_flags, local_flags);
+                    plan.entries.push(ImapSyncEntry {
+                        flags,
+                        ..entry.clone()
+                    });
+                }
+                (None, Some(_)) => plan.del_local.push(entry.maildir_id.clone()),
+                (Some(_), None) => plan.del_remote.push(entry.uid),
+                (None, None) => (),
+            }
+        }
+
+        let synced_ids: BTreeSet<&str> = entries
+            .iter()
+            .map(|entry| entry.maildir_id.as_str())
+            .collect();
+        let mut local_ids_by_msg_id: BTreeMap<&str, VecDeque<&str>> = BTreeMap::new();
+        for id in local.keys().filter(|id| !synced_ids.contains(id.as_str())) {
+            if let Some(msg_id) = local_msg_ids.get(id) {
+                local_ids_by_msg_id
+                    .entry(msg_id.as_str())
+                    .or_default()
+                    .push_back(id.as_str());
+            }
+        }
+
+        let synced_uids: BTreeSet<u32> = entries.iter().map(|entry| entry.uid).collect();
+        let mut matched_ids = BTreeSet::new();
+        for (uid, remote_flags) in remote {
+            if synced_uids.contains(uid) {
+                continue;
+            }
+            let id = remote_msg_ids
+                .get(uid)
+                .and_then(|msg_id| local_ids_by_msg_id.get_mut(msg_id.as_str()))
+                .and_then(|ids| ids.pop_front());
+            match id {
+                Some(id) => {
+                    let entry = ImapSyncEntry {
+                        uid: *uid,
+                        maildir_id: id.to_owned(),
+                        flags: String::new(),
+                    };
+                    let flags = plan.sync_flags(&entry, remote_flags, &local[id]);
+                    plan.matched.push(ImapSyncEntry { flags, ..entry });
+                    matched_ids.insert(id);
+                }
+                // Unknown messages flagged as deleted are about to be
+                // expunged, for example by a previous sync against a
+                // server without UIDPLUS, so they are not downloaded.
+                None if remote_flags.contains('T') => (),
+                None => plan.pull.push((*uid, remote_flags.clone())),
+            }
+        }
+
+        plan.push = local
+            .keys()
+            .filter(|id| !synced_ids.contains(id.as_str()) && !matched_ids.contains(id.as_str()))
+            .cloned()
+            .collect();
+
+        plan
+    }
+
+    /// Merges the flags of both sides of the given entry, and plans
+    /// the flag changes of the side which differs from the result.
+    fn sync_flags(&mut self, entry: &ImapSyncEntry, remote: &str, local: &str) -> String {
+        let flags = merge_flags(&entry.flags, remote, local);
+        if flags != remote {
+            self.flags_remote
+                .push((entry.uid, diff_flags(remote, &flags)));
+        }
+        if flags != local {
+            self.flags_local
+                .push((entry.maildir_id.clone(), diff_flags(local, &flags)));
+        }
+        flags
+    }
+}
+
+impl ImapSyncPlan {
+    /// Builds the report of the given mailbox as if the plan was
+    /// fully applied.
+    pub fn to_report(&self, mbox: &str) -> ImapSyncReport {
+        ImapSyncReport {
+            mbox: mbox.to_owned(),
+            pulled: self.pull.len(),
+            pushed: self.push.len(),
+            deleted_local: self.del_local.len(),
+            deleted_remote: self.del_remote.len(),
+            flagged_local: self.flags_local.len(),
+            flagged_remote: self.flags_remote.len(),
+        }
+    }
+}
+
+/// Merges the flags changed on both sides since the last sync. A flag
+/// added on one side is added, a flag removed on one side is removed.
+fn merge_flags(base: &str, remote: &str, local: &str) -> String {
+    SYNC_FLAGS
+        .chars()
+        .filter(|c| {
+            let in_base = base.contains(*c);
+            let changed_remote = remote.contains(*c) != in_base;
+            let changed_local = local.contains(*c) != in_base;
+            in_base != (changed_remote || changed_local)
+        })
+        .collect()
+}
+
+fn diff_flags(from: &str, to: &str) -> ImapSyncFlagsDiff {
+    ImapSyncFlagsDiff {
+        add: to.chars().filter(|c| !from.contains(*c)).collect(),
+        remove: from.chars().filter(|c| !to.contains(*c)).collect(),
+    }
+}
+
+fn flags_from_imap(flags: &ImapFlags) -> String {
+    let flags: Vec<char> = flags
+        .iter()
+        .filter_map(|flag| match flag {
+            ImapFlag::Draft => Some('D'),
+            ImapFlag::Flagged => Some('F'),
+            ImapFlag::Answered => Some('R'),
+            ImapFlag::Seen => Some('S'),
+            ImapFlag::Deleted => Some('T'),
+            _ => None,
+        })
+        .collect();
+    SYNC_FLAGS.chars().filter(|c| flags.contains(c)).collect()
+}
+
+fn flags_from_maildir(flags: &str) -> String {
+    SYNC_FLAGS.chars().filter(|c| flags.contains(*c)).collect()
+}
+
+fn flags_to_imap(flags: &str) -> Flags {
+    Flags::from(
+        flags
+            .chars()
+            .filter_map(|c| match c {
+                'D' => Some(Flag::Draft),
+                'F' => Some(Flag::Flagged),
+                'R' => Some(Flag::Answered),
+                'S' => Some(Flag::Seen),
+                'T' => Some(Flag::Deleted),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Represents what a mailbox sync changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ImapSyncReport {
+    pub mbox: String,
+    pub pulled: usize,
+    pub pushed: usize,
+    pub deleted_local: usize,
+    pub deleted_remote: usize,
+    pub flagged_local: usize,
+    pub flagged_remote: usize,
+}
+
+/// Builds the Maildir folder path of the given IMAP mailbox, using
+/// the Maildir++ layout: the inbox is the root folder, other
+/// mailboxes are dot-prefixed subfolders.
+fn maildir_path(root: &Path, mbox: &str, delim: &str) -> PathBuf {
+    if mbox.eq_ignore_ascii_case("inbox") {
+        root.to_owned()
+    } else if delim.is_empty() || delim == "." {
+        root.join(format!(".{}", mbox))
+    } else {
+        root.join(format!(".{}", mbox.replace(delim, ".")))
+    }
+}
+
+/// Lists the flags of the given Maildir entries, indexed by id.
+fn list_local_flags(
+    entries: impl Iterator<Item = std::io::Result<maildir::MailEntry>>,
+) -> Result<BTreeMap<String, String>> {
+    let mut local = BTreeMap::new();
+    for entry in entries {
+        let entry = entry.context("cannot read maildir entry")?;
+        local.insert(entry.id().to_owned(), flags_from_maildir(entry.flags()));
+    }
+    Ok(local)
+}
+
+/// Lists the Message-ID of the given Maildir entries, indexed by
+/// id. Only the entries matching the given ids are read, messages
+/// without Message-ID are left out.
+fn list_local_msg_ids(
+    entries: impl Iterator<Item = std::io::Result<maildir::MailEntry>>,
+    ids: &BTreeSet<&str>,
+) -> Result<BTreeMap<String, String>> {
+    let mut msg_ids = BTreeMap::new();
+    for entry in entries {
+        let mut entry = entry.context("cannot read maildir entry")?;
+        let id = entry.id().to_owned();
+        if !ids.contains(id.as_str()) {
+            continue;
+        }
+        let headers = entry
+            .headers()
+            .with_context(|| format!("cannot parse maildir message {:?}", id))?;
+        if let Some(msg_id) = headers.get_first_value("Message-ID") {
+            msg_ids.insert(id, msg_id.trim().to_owned());
+        }
+    }
+    Ok(msg_ids)
+}
+
+impl<'a> ImapBackend<'a> {
+    /// Synchronizes all the selectable IMAP mailboxes with the
+    /// Maildir tree of the given directory. In dry run mode, nothing
+    /// is changed on either side and the reports tell what a sync
+    /// would change.
+    pub fn sync(&mut self, dir: &Path, dry_run: bool) -> Result<Vec<ImapSyncReport>> {
+        info!(">> sync IMAP account");
+        debug!("dir: {:?}", dir);
+        debug!("dry run: {}", dry_run);
+
+        let mboxes = self.get_mboxes()?;
+        let mboxes = mboxes
+            .as_any()
+            .downcast_ref::<ImapMboxes>()
+            .ok_or_else(|| anyhow!("cannot list IMAP mailboxes"))?;
+
+        let mut reports = vec![];
+        for mbox in mboxes.iter() {
+            if mbox.attrs.contains(&ImapMboxAttr::NoSelect) {
+                debug!("skip mailbox {:?}", mbox.name);
+                continue;
+            }
+            // Maildir++ folder names are encoded like IMAP ones.
+            let path = maildir_path(dir, &encode_utf7(&mbox.name), &mbox.delim);
+            let mdir = maildir::Maildir::from(path);
+            let report = if dry_run {
+                self.plan_sync_mbox(&mbox.name, &mdir)?
+            } else {
+                self.sync_mbox(&mbox.name, &mdir)?
+            };
+            reports.push(report);
+        }
+
+        info!("<< sync IMAP account");
+        Ok(reports)
+    }
+
+    /// Synchronizes the given IMAP mailbox with the given Maildir
+    /// folder.
+    pub fn sync_mbox(&mut self, mbox: &str, mdir: &maildir::Maildir) -> Result<ImapSyncReport> {
+        info!(">> sync IMAP mailbox");
+        debug!("mailbox: {:?}", mbox);
+        debug!("maildir: {:?}", mdir.path());
+
+        mdir.create_dirs()
+            .with_context(|| format!("cannot create maildir {:?}", mdir.path()))?;
+        let state_path = mdir.path().join(STATE_FILE_NAME);
+        let mut state = ImapSyncState::from_path(&state_path)?;
+        trace!("state: {:?}", state);
+
+        let (uid_validity, remote) = self.fetch_sync_flags(mbox, &state, mdir)?;
+
+        // Messages delivered to "new" are moved to "cur", so that
+        // all messages live in the same folder.
+        let new_ids = mdir
+            .list_new()
+            .map(|entry| entry.map(|entry| entry.id().to_owned()))
+            .collect::<Result<Vec<_>, _>>()
+            .context("cannot read maildir entry")?;
+        for id in new_ids {
+            mdir.move_new_to_cur(&id)
+                .with_context(|| format!("cannot move maildir message {:?}", id))?;
+        }
+        let local = list_local_flags(mdir.list_cur())?;
+        let (remote_msg_ids, local_msg_ids) =
+            self.fetch_unsynced_msg_ids(mbox, &state, &remote, &local, mdir.list_cur())?;
+
+        let plan = ImapSyncPlan::new(
+            &state.entries,
+            &remote,
+            &local,
+            &remote_msg_ids,
+            &local_msg_ids,
+        );
+        trace!("plan: {:?}", plan);
+
+        // The state only records the changes actually applied, and is
+        // written even if the sync fails halfway, so that they are
+        // not applied twice. Entries gone from both sides need no
+        // change.
+        state.uid_validity = Some(uid_validity);
+        state.entries.retain(|entry| {
+            remote.contains_key(&entry.uid) || local.contains_key(&entry.maildir_id)
+        });
+        let res = self.apply_plan(mbox, mdir, &plan, &mut state);
+        state.last_uid = state
+            .entries
+            .iter()
+            .map(|entry| entry.uid)
+            .max()
+            .unwrap_or_default();
+        state.last_sync = Some(Local::now().to_rfc3339());
+        state.write(&state_path)?;
+        let report = res?;
+
+        info!("<< sync IMAP mailbox");
+        Ok(report)
+    }
+
+    /// Computes the changes a sync of the given IMAP mailbox with the
+    /// given Maildir folder would apply, without applying them nor
+    /// touching the sync state.
+    pub fn plan_sync_mbox(
+        &mut self,
+        mbox: &str,
+        mdir: &maildir::Maildir,
+    ) -> Result<ImapSyncReport> {
+        info!(">> plan IMAP mailbox sync");
+        debug!("mailbox: {:?}", mbox);
+        debug!("maildir: {:?}", mdir.path());
+
+        let state = ImapSyncState::from_path(&mdir.path().join(STATE_FILE_NAME))?;
+        trace!("state: {:?}", state);
+        let (_, remote) = self.fetch_sync_flags(mbox, &state, mdir)?;
+        let local = if mdir.path().is_dir() {
+            list_local_flags(mdir.list_new().chain(mdir.list_cur()))?
+        } else {
+            BTreeMap::new()
+        };
+        let (remote_msg_ids, local_msg_ids) = self.fetch_unsynced_msg_ids(
+            mbox,
+            &state,
+            &remote,
+            &local,
+            mdir.list_new().chain(mdir.list_cur()),
+        )?;
+
+        let plan = ImapSyncPlan::new(
+            &state.entries,
+            &remote,
+            &local,
+            &remote_msg_ids,
+            &local_msg_ids,
+        );
+        trace!("plan: {:?}", plan);
+
+        info!("<< plan IMAP mailbox sync");
+        Ok(plan.to_report(mbox))
+    }
+
+    /// Fetches the flags of the messages of the given IMAP mailbox,
+    /// indexed by UID, after checking that its UIDVALIDITY did not
+    /// change since the last sync.
+    fn fetch_sync_flags(
+        &mut self,
+        mbox: &str,
+        state: &ImapSyncState,
+        mdir: &maildir::Maildir,
+    ) -> Result<(u32, BTreeMap<u32, String>)> {
+        let (uid_validity, remote) = self.fetch_flags(mbox)?;
+        debug!("UIDVALIDITY: {}", uid_validity);
+        if let Some(prev_uid_validity) = state.uid_validity {
+            if prev_uid_validity != uid_validity {
+                return Err(anyhow!(
+                    "cannot sync mailbox {:?}: UIDVALIDITY changed from {} to {}, the maildir {:?} needs to be synchronized from scratch",
+                    mbox,
+                    prev_uid_validity,
+                    uid_validity,
+                    mdir.path()
+                ));
+            }
+        }
+        let remote = remote
+            .iter()
+            .map(|(uid, flags)| (*uid, flags_from_imap(flags)))
+            .collect();
+        Ok((uid_validity, remote))
+    }
+
+    /// Fetches the Message-IDs of the messages which are not
+    /// synchronized yet, on both sides. There is nothing to match
+    /// when one side has no such message.
+    fn fetch_unsynced_msg_ids(
+        &mut self,
+        mbox: &str,
+        state: &ImapSyncState,
+        remote: &BTreeMap<u32, String>,
+        local: &BTreeMap<String, String>,
+        local_entries: impl Iterator<Item = std::io::Result<maildir::MailEntry>>,
+    ) -> Result<(BTreeMap<u32, String>, BTreeMap<String, String>)> {
+        let synced_uids: BTreeSet<u32> = state.entries.iter().map(|entry| entry.uid).collect();
+        let uids: Vec<u32> = remote
+            .keys()
+            .filter(|uid| !synced_uids.contains(uid))
+            .copied()
+            .collect();
+        let synced_ids: BTreeSet<&str> = state
+            .entries
+            .iter()
+            .map(|entry| entry.maildir_id.as_str())
+            .collect();
+        let ids: BTreeSet<&str> = local
+            .keys()
+            .map(|id| id.as_str())
+            .filter(|id| !synced_ids.contains(id))
+            .collect();
+        if uids.is_empty() || ids.is_empty() {
+            return Ok((BTreeMap::new(), BTreeMap::new()));
+        }
+
+        debug!(
+            "match {} IMAP and {} maildir messages",
+            uids.len(),
+            ids.len()
+        );
+        let remote_msg_ids = self.fetch_msg_ids(mbox, &uids)?;
+        let local_msg_ids = list_local_msg_ids(local_entries, &ids)?;
+        Ok((remote_msg_ids, local_msg_ids))
+    }
+
+    /// Applies the given plan, step by step. The state is updated
+    /// after each successful step, so that it reflects what was
+    /// applied when a step fails.
+    fn apply_plan(
+        &mut self,
+        mbox: &str,
+        mdir: &maildir::Maildir,
+        plan: &ImapSyncPlan,
+        state: &mut ImapSyncState,
+    ) -> Result<ImapSyncReport> {
+        let mut report = ImapSyncReport {
+            mbox: mbox.to_owned(),
+            ..ImapSyncReport::default()
+        };
+
+        for id in &plan.del_local {
+            debug!("delete maildir message {:?}", id);
+            mdir.delete(id)
+                .with_context(|| format!("cannot delete maildir message {:?}", id))?;
+            state.entries.retain(|entry| entry.maildir_id != *id);
+            report.deleted_local += 1;
+        }
+
+        if !plan.del_remote.is_empty() {
+            debug!("delete IMAP messages {:?}", plan.del_remote);
+            let uids = IdSet(
+                plan.del_remote
+                    .iter()
+                    .map(|uid| IdRange::One(Uid(*uid)))
+                    .collect(),
+            );
+            self.del_msg(mbox, &uids)?;
+            state
+                .entries
+                .retain(|entry| !plan.del_remote.contains(&entry.uid));
+            report.deleted_remote += plan.del_remote.len();
+        }
+
+        for (id, diff) in &plan.flags_local {
+            debug!("update flags of maildir message {:?}: {:?}", id, diff);
+            if !diff.add.is_empty() {
+                mdir.add_flags(id, &diff.add)
+                    .with_context(|| format!("cannot add flags to maildir message {:?}", id))?;
+            }
+            if !diff.remove.is_empty() {
+                mdir.remove_flags(id, &diff.remove).with_context(|| {
+                    format!("cannot remove flags from maildir message {:?}", id)
+                })?;
+            }
+            report.flagged_local += 1;
+        }
+
+        for (uid, diff) in &plan.flags_remote {
+            debug!("update flags of IMAP message {}: {:?}", uid, diff);
+            let uids = IdSet::from(Uid(*uid));
+            if !diff.add.is_empty() {
+                self.add_flags(mbox, &uids, &flags_to_imap(&diff.add))?;
+            }
+            if !diff.remove.is_empty() {
+                self.del_flags(mbox, &uids, &flags_to_imap(&diff.remove))?;
+            }
+            report.flagged_remote += 1;
+        }
+
+        // Flags are merged from the last synced ones, which can only
+        // be replaced once both sides got the merged flags. Merging
+        // again after a partial update gives the same flags.
+        for planned_entry in &plan.entries {
+            if let Some(entry) = state
+                .entries
+                .iter_mut()
+                .find(|entry| entry.uid == planned_entry.uid)
+            {
+                entry.flags = planned_entry.flags.clone();
+            }
+        }
+
+        // Messages matched by Message-ID are synchronized as soon as
+        // both sides got the merged flags.
+        state.entries.extend(plan.matched.iter().cloned());
+
+        for (uid, flags) in &plan.pull {
+            debug!("download IMAP message {}", uid);
+            let raw_msg = self.fetch_raw_msg(mbox, *uid)?;
+            let maildir_id = mdir
+                .store_cur_with_flags(&raw_msg, flags)
+                .with_context(|| format!("cannot add maildir message to {:?}", mdir.path()))?;
+            state.entries.push(ImapSyncEntry {
+                uid: *uid,
+                maildir_id,
+                flags: flags.to_owned(),
+            });
+            report.pulled += 1;
+        }
+
+        for id in &plan.push {
+            debug!("upload maildir message {:?}", id);
+            let mut entry = mdir
+                .find(id)
+                .ok_or_else(|| anyhow!("cannot find maildir message {:?}", id))?;
+            let flags = flags_from_maildir(entry.flags());
+            let raw_msg = fs::read(entry.path())
+                .with_context(|| format!("cannot read maildir message {:?}", entry.path()))?;
+            // Parses the message so that invalid messages are not
+            // uploaded.
+            entry
+                .parsed()
+                .with_context(|| format!("cannot parse maildir message {:?}", id))?;
+            let Uid(uid) = self.add_msg(mbox, &raw_msg, &flags_to_imap(&flags))?;
+            state.entries.push(ImapSyncEntry {
+                uid,
+                maildir_id: id.to_owned(),
+                flags,
+            });
+            report.pushed += 1;
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn entry(uid: u32, maildir_id: &str, flags: &str) -> ImapSyncEntry {
+        ImapSyncEntry {
+            uid,
+            maildir_id: maildir_id.into(),
+            flags: flags.into(),
+        }
+    }
+
+    #[test]
+    fn it_should_merge_flags() {
+        assert_eq!("S", merge_flags("", "S", ""));
+        assert_eq!("S", merge_flags("", "", "S"));
+        assert_eq!("", merge_flags("S", "", "S"));
+        assert_eq!("", merge_flags("S", "S", ""));
+        assert_eq!("FS", merge_flags("S", "FS", "S"));
+        assert_eq!("FR", merge_flags("S", "FS", "R"));
+    }
+
+    #[test]
+    fn it_should_plan_sync() {
+        let entries = vec![
+            entry(1, "a", "S"),
+            entry(2, "b", "S"),
+            entry(3, "c", ""),
+            entry(4, "d", ""),
+        ];
+        let remote = BTreeMap::from_iter([(1, "FS".into()), (3, "".into()), (5, "S".into())]);
+        let local = BTreeMap::from_iter([
+            ("a".into(), "".into()),
+            ("b".into(), "S".into()),
+            ("e".into(), "R".into()),
+        ]);
+
+        let plan = ImapSyncPlan::new(
+            &entries,
+            &remote,
+            &local,
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+        );
+        assert_eq!(vec![entry(1, "a", "F")], plan.entries);
+        assert_eq!(vec![(5, String::from("S"))], plan.pull);
+        assert_eq!(vec![String::from("e")], plan.push);
+        assert_eq!(vec![String::from("b")], plan.del_local);
+        assert_eq!(vec![3], plan.del_remote);
+        assert_eq!(
+            vec![(
+                1,
+                ImapSyncFlagsDiff {
+                    add: "".into(),
+                    remove: "S".into()
+                }
+            )],
+            plan.flags_remote
+        );
+        assert_eq!(
+            vec![(
+                String::from("a"),
+                ImapSyncFlagsDiff {
+                    add: "F".into(),
+                    remove: "".into()
+                }
+            )],
+            plan.flags_local
+        );
+        assert_eq!(
+            ImapSyncReport {
+                mbox: "INBOX".into(),
+                pulled: 1,
+                pushed: 1,
+                deleted_local: 1,
+                deleted_remote: 1,
+                flagged_local: 1,
+                flagged_remote: 1,
+            },
+            plan.to_report("INBOX")
+        );
+    }
+
+    #[test]
+    fn it_should_match_msgs_by_msg_id() {
+        // The Maildir tree already mirrors the mailbox, but there is
+        // no sync state yet.
+        let remote = BTreeMap::from_iter([
+            (1, "S".into()),
+            (2, "".into()),
+            (3, "".into()),
+            (4, "T".into()),
+        ]);
+        let local = BTreeMap::from_iter([
+            ("a".into(), "S".into()),
+            ("b".into(), "F".into()),
+            ("c".into(), "".into()),
+        ]);
+        let remote_msg_ids =
+            BTreeMap::from_iter([(1, "<1@localhost>".into()), (2, "<2@localhost>".into())]);
+        let local_msg_ids = BTreeMap::from_iter([
+            ("a".into(), "<1@localhost>".into()),
+            ("b".into(), "<2@localhost>".into()),
+            ("c".into(), "<3@localhost>".into()),
+        ]);
+
+        let plan = ImapSyncPlan::new(&[], &remote, &local, &remote_msg_ids, &local_msg_ids);
+        assert_eq!(vec![entry(1, "a", "S"), entry(2, "b", "F")], plan.matched);
+        assert_eq!(vec![(3, String::new())], plan.pull);
+        assert_eq!(vec![String::from("c")], plan.push);
+        assert_eq!(
+            vec![(
+                2,
+                ImapSyncFlagsDiff {
+                    add: "F".into(),
+                    remove: "".into()
+                }
+            )],
+            plan.flags_remote
+        );
+        assert!(plan.flags_local.is_empty());
+        assert!(plan.del_local.is_empty());
+        assert!(plan.del_remote.is_empty());
+    }
+
+    #[test]
+    fn it_should_list_local_msg_ids() {
+        let path = env::temp_dir().join(format!("himalaya-sync-{}", uuid::Uuid::new_v4()));
+        let mdir = maildir::Maildir::from(path.clone());
+        mdir.create_dirs().unwrap();
+        let a = mdir
+            .store_cur_with_flags(b"Message-ID: <1@localhost>\r\n\r\na\r\n", "")
+            .unwrap();
+        let b = mdir
+            .store_cur_with_flags(b"Subject: b\r\n\r\nb\r\n", "")
+            .unwrap();
+        // Messages not listed are not read.
+        mdir.store_cur_with_flags(b"Message-ID: <3@localhost>\r\n\r\nc\r\n", "")
+            .unwrap();
+
+        let ids = BTreeSet::from_iter([a.as_str(), b.as_str()]);
+        assert_eq!(
+            BTreeMap::from_iter([(a.clone(), String::from("<1@localhost>"))]),
+            list_local_msg_ids(mdir.list_cur(), &ids).unwrap()
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn it_should_read_and_write_state() {
+        let path =
+            env::temp_dir().join(format!("himalaya-sync-state-{}.toml", uuid::Uuid::new_v4()));
+        assert_eq!(
+            ImapSyncState::default(),
+            ImapSyncState::from_path(&path).unwrap()
+        );
+
+        let state = ImapSyncState {
+            uid_validity: Some(42),
+            last_uid: 2,
+            last_sync: Some("2022-03-01T10:00:00+01:00".into()),
+            entries: vec![entry(1, "a", "S"), entry(2, "b", "")],
+        };
+        state.write(&path).unwrap();
+        assert_eq!(state, ImapSyncState::from_path(&path).unwrap());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_should_build_maildir_path() {
+        let root = Path::new("/mail");
+        assert_eq!(PathBuf::from("/mail"), maildir_path(root, "INBOX", "/"));
+        assert_eq!(
+            PathBuf::from("/mail/.Sent"),
+            maildir_path(root, "Sent", "/")
+        );
+        assert_eq!(
+            PathBuf::from("/mail/.Work.Todo"),
+            maildir_path(root, "Work/Todo", "/")
+        );
+        assert_eq!(
+            PathBuf::from("/mail/.Work.Todo"),
+            maildir_path(root, "Work.Todo", ".")
+        );
+    }
+}
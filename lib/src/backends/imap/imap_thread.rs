@@ -0,0 +1,169 @@
+//! IMAP thread module.
+//!
+//! This module provides IMAP types and parsing utilities related to
+//! message threads, as returned by the THREAD extension ([RFC5256]).
+//!
+//! [RFC5256]: https://datatracker.ietf.org/doc/html/rfc5256
+
+use anyhow::{anyhow, Result};
+use std::ops::Deref;
+
+use crate::msg::ThreadNode;
+
+use super::ImapEnvelope;
+
+/// Represents a list of IMAP message threads.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ImapThreads {
+    #[serde(rename = "response")]
+    pub threads: Vec<ThreadNode<ImapEnvelope>>,
+}
+
+impl Deref for ImapThreads {
+    type Target = Vec<ThreadNode<ImapEnvelope>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.threads
+    }
+}
+
+/// Parses the threads of message numbers out of a raw `THREAD` or
+/// `UID THREAD` response, like `* THREAD (2)(3 6 (4 23)(44 7 96))`.
+pub fn parse_thread_response(res: &[u8]) -> Result<Vec<ThreadNode<u32>>> {
+    let res = String::from_utf8_lossy(res);
+    let threads = match res.lines().find_map(|line| line.strip_prefix("* THREAD")) {
+        Some(threads) => threads,
+        None => return Ok(vec![]),
+    };
+
+    let mut parser = ThreadParser {
+        input: threads.trim().as_bytes(),
+        pos: 0,
+    };
+    let mut nodes = vec![];
+    while parser.pos < parser.input.len() {
+        nodes.push(parser.parse_thread()?);
+    }
+    Ok(nodes)
+}
+
+/// Represents the parser of the `thread-list` rule of the `THREAD`
+/// response grammar.
+struct ThreadParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ThreadParser<'a> {
+    fn skip_spaces(&mut self) {
+        while self.input.get(self.pos) == Some(&b' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        self.skip_spaces();
+        match self.input.get(self.pos) {
+            Some(next) if *next == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            next => Err(anyhow!(
+                "cannot parse thread response: expected {:?}, got {:?} at {}",
+                c as char,
+                next.map(|c| *c as char),
+                self.pos
+            )),
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        self.skip_spaces();
+        let start = self.pos;
+        while matches!(self.input.get(self.pos), Some(c) if c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Parses a parenthesized thread: a chain of messages, each one
+    /// replying to the previous one, followed by the nested threads
+    /// replying to the last message. A thread without message stands
+    /// for a missing parent of the nested threads.
+    fn parse_thread(&mut self) -> Result<ThreadNode<u32>> {
+        self.expect(b'(')?;
+
+        let mut chain = vec![];
+        while let Some(id) = self.parse_number() {
+            chain.push(id);
+        }
+
+        let mut children = vec![];
+        loop {
+            self.skip_spaces();
+            if self.input.get(self.pos) != Some(&b'(') {
+                break;
+            }
+            children.push(self.parse_thread()?);
+        }
+        self.expect(b')')?;
+
+        let mut node = None;
+        for id in chain.into_iter().rev() {
+            let children = match node.take() {
+                Some(node) => vec![node],
+                None => std::mem::take(&mut children),
+            };
+            node = Some(ThreadNode::new(Some(id), children));
+        }
+        Ok(node.unwrap_or_else(|| ThreadNode::new(None, children)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u32, children: Vec<ThreadNode<u32>>) -> ThreadNode<u32> {
+        ThreadNode::new(Some(id), children)
+    }
+
+    #[test]
+    fn it_should_parse_thread_response() {
+        let res = b"* THREAD (2)(3 6 (4 23)(44 7 96))\r\nA1 OK done\r\n";
+        assert_eq!(
+            vec![
+                node(2, vec![]),
+                node(
+                    3,
+                    vec![node(
+                        6,
+                        vec![
+                            node(4, vec![node(23, vec![])]),
+                            node(44, vec![node(7, vec![node(96, vec![])])]),
+                        ]
+                    )]
+                ),
+            ],
+            parse_thread_response(res).unwrap()
+        );
+
+        let res = b"* THREAD ((3)(5))\r\nA1 OK done\r\n";
+        assert_eq!(
+            vec![ThreadNode::new(
+                None,
+                vec![node(3, vec![]), node(5, vec![])]
+            )],
+            parse_thread_response(res).unwrap()
+        );
+
+        let res = b"* THREAD\r\nA1 OK done\r\n";
+        assert!(parse_thread_response(res).unwrap().is_empty());
+
+        let res = b"* THREAD (2)(3\r\nA1 OK done\r\n";
+        assert!(parse_thread_response(res).is_err());
+    }
+}
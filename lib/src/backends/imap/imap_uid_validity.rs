@@ -0,0 +1,114 @@
+//! IMAP UIDVALIDITY module.
+//!
+//! IMAP messages are identified by their UID, which only makes sense
+//! together with the UIDVALIDITY of their mailbox: when the server
+//! changes the UIDVALIDITY, all UIDs of the mailbox may point to
+//! other messages. This module contains a cache remembering the
+//! UIDVALIDITY seen by the last listing of each mailbox, so that ids
+//! coming from an outdated listing are rejected.
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, trace};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use crate::config::{DeserializedConfig, ImapBackendConfig};
+
+/// Represents the UIDVALIDITY cache. Entries are indexed by a key
+/// identifying the account and the mailbox.
+#[derive(Debug, Default)]
+pub struct ImapUidValidityCache {
+    path: PathBuf,
+    entries: BTreeMap<String, u32>,
+}
+
+impl ImapUidValidityCache {
+    /// Reads the cache from the default cache file.
+    pub fn from_default_path() -> Result<Self> {
+        Self::from_path(DeserializedConfig::cache_dir()?.join("imap-uid-validity.toml"))
+    }
+
+    /// Reads the cache from the given file. A missing file is
+    /// considered as an empty cache.
+    pub fn from_path(path: PathBuf) -> Result<Self> {
+        let entries = if path.is_file() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("cannot read UIDVALIDITY cache file {:?}", path))?;
+            toml::from_str(&content)
+                .with_context(|| format!("cannot parse UIDVALIDITY cache file {:?}", path))?
+        } else {
+            BTreeMap::new()
+        };
+        trace!("UIDVALIDITY cache entries: {:?}", entries);
+        Ok(Self { path, entries })
+    }
+
+    /// Builds the key identifying the given mailbox of the given
+    /// account.
+    pub fn key(imap_config: &ImapBackendConfig, mbox: &str) -> String {
+        format!(
+            "{}@{}:{}/{}",
+            imap_config.imap_login, imap_config.imap_host, imap_config.imap_port, mbox
+        )
+    }
+
+    /// Checks that the given UIDVALIDITY matches the cached one.
+    /// Unknown keys always match.
+    pub fn check(&self, key: &str, uid_validity: u32) -> Result<()> {
+        match self.entries.get(key) {
+            Some(cached) if *cached != uid_validity => Err(anyhow!(
+                "UIDVALIDITY of {:?} changed from {} to {}, please list messages again",
+                key,
+                cached,
+                uid_validity
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Updates the UIDVALIDITY of the given key, then writes the
+    /// cache file if it changed.
+    pub fn update(&mut self, key: &str, uid_validity: u32) -> Result<()> {
+        if self.entries.get(key) == Some(&uid_validity) {
+            return Ok(());
+        }
+        debug!("update UIDVALIDITY of {:?} to {}", key, uid_validity);
+        self.entries.insert(key.to_owned(), uid_validity);
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create cache directory {:?}", dir))?;
+        }
+        let content =
+            toml::to_string(&self.entries).context("cannot serialize UIDVALIDITY cache")?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("cannot write UIDVALIDITY cache file {:?}", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn it_should_check_uid_validity() {
+        let path = env::temp_dir().join(format!(
+            "himalaya-uid-validity-{}.toml",
+            uuid::Uuid::new_v4()
+        ));
+
+        let mut cache = ImapUidValidityCache::from_path(path.clone()).unwrap();
+        assert!(cache.check("user@localhost:993/INBOX", 42).is_ok());
+        cache.update("user@localhost:993/INBOX", 42).unwrap();
+
+        let mut cache = ImapUidValidityCache::from_path(path.clone()).unwrap();
+        assert!(cache.check("user@localhost:993/INBOX", 42).is_ok());
+        assert!(cache.check("user@localhost:993/INBOX", 43).is_err());
+        assert!(cache.check("user@localhost:993/Sent", 43).is_ok());
+        cache.update("user@localhost:993/INBOX", 43).unwrap();
+        assert!(cache.check("user@localhost:993/INBOX", 43).is_ok());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+//! IMAP modified UTF-7 module.
+//!
+//! IMAP mailbox names are 7-bit: other characters are encoded in a
+//! modified version of UTF-7, as defined in [RFC3501 §5.1.3].
+//! Printable ASCII characters represent themselves, except `&` which
+//! is written `&-`. Other characters are encoded in UTF-16, then in a
+//! modified base64 (`,` instead of `/`, no padding) enclosed between
+//! `&` and `-`.
+//!
+//! [RFC3501 §5.1.3]: https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3
+
+use anyhow::{anyhow, Result};
+use log::warn;
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+,";
+
+/// Encodes the given mailbox name in modified UTF-7.
+pub fn encode_utf7(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    let mut utf16 = vec![];
+
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            if !utf16.is_empty() {
+                encoded.push('&');
+                encoded.push_str(&encode_base64(&utf16));
+                encoded.push('-');
+                utf16.clear();
+            }
+            match c {
+                '&' => encoded.push_str("&-"),
+                c => encoded.push(c),
+            }
+        } else {
+            let mut buf = [0; 2];
+            for unit in c.encode_utf16(&mut buf) {
+                utf16.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+    }
+
+    if !utf16.is_empty() {
+        encoded.push('&');
+        encoded.push_str(&encode_base64(&utf16));
+        encoded.push('-');
+    }
+
+    encoded
+}
+
+/// Decodes the given mailbox name from modified UTF-7.
+pub fn decode_utf7(name: &str) -> Result<String> {
+    let mut decoded = String::with_capacity(name.len());
+    let mut rest = name;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest
+            .find('-')
+            .ok_or_else(|| anyhow!("cannot decode mailbox name {:?}: missing `-`", name))?;
+        if end == 0 {
+            decoded.push('&');
+        } else {
+            let utf16: Vec<u16> = decode_base64(&rest[..end])
+                .ok_or_else(|| anyhow!("cannot decode mailbox name {:?}: invalid base64", name))?
+                .chunks(2)
+                .map(|bytes| match bytes {
+                    [hi, lo] => Ok(u16::from_be_bytes([*hi, *lo])),
+                    _ => Err(anyhow!("cannot decode mailbox name {:?}: odd UTF-16", name)),
+                })
+                .collect::<Result<_>>()?;
+            let chars = String::from_utf16(&utf16)
+                .map_err(|_| anyhow!("cannot decode mailbox name {:?}: invalid UTF-16", name))?;
+            decoded.push_str(&chars);
+        }
+        rest = &rest[end + 1..];
+    }
+    decoded.push_str(rest);
+
+    Ok(decoded)
+}
+
+/// Decodes the given mailbox name returned by the server. Names that
+/// are not valid modified UTF-7 are returned as is, since some
+/// servers send them in UTF-8.
+pub fn decode_mbox_name(name: &str) -> String {
+    decode_utf7(name).unwrap_or_else(|err| {
+        warn!("{}, keeping it as is", err);
+        name.to_owned()
+    })
+}
+
+/// Encodes the given bytes in modified base64, without padding.
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len() * 4 / 3 + 2);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64_CHARS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// Decodes the given modified base64, without padding. Returns `None`
+/// if it contains an invalid character or an incomplete byte.
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for c in encoded.bytes() {
+        let value = BASE64_CHARS.iter().position(|b| *b == c)?;
+        n = n << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    // Leftover bits are padding, they must be zero.
+    if bits >= 6 || n != 0 {
+        return None;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_encode_and_decode_mbox_names() {
+        let names = [
+            ("INBOX", "INBOX"),
+            ("Sent Items", "Sent Items"),
+            ("Tom & Jerry", "Tom &- Jerry"),
+            ("été", "&AOk-t&AOk-"),
+            ("台北", "&U,BTFw-"),
+            ("~peter/mail/台北/日本語", "~peter/mail/&U,BTFw-/&ZeVnLIqe-"),
+            ("Entwürfe", "Entw&APw-rfe"),
+            ("😀", "&2D3eAA-"),
+        ];
+        for (decoded, encoded) in names {
+            assert_eq!(encoded, encode_utf7(decoded));
+            assert_eq!(decoded, decode_utf7(encoded).unwrap());
+            assert_eq!(decoded, decode_utf7(&encode_utf7(decoded)).unwrap());
+        }
+    }
+
+    #[test]
+    fn it_should_reject_invalid_mbox_names() {
+        assert!(decode_utf7("&AOk").is_err());
+        assert!(decode_utf7("&A.k-").is_err());
+        assert!(decode_utf7("&AO-").is_err());
+        assert!(decode_utf7("&2D0-").is_err());
+    }
+}
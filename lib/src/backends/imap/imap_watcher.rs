@@ -0,0 +1,425 @@
+//! IMAP watcher module.
+//!
+//! The idle mode only applies to the selected mailbox, so watching
+//! several mailboxes requires several connections. This module
+//! contains a watcher opening one connection per mailbox, possibly
+//! from different accounts, and feeding their events to a single
+//! pipeline which runs the notify and watch commands.
+
+use anyhow::{anyhow, Context, Result};
+use imap::types::UnsolicitedResponse;
+use log::{debug, warn};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    sync::mpsc,
+    thread,
+};
+
+use crate::{
+    backends::{ImapBackend, ImapEnvelope},
+    config::{AccountConfig, ImapBackendConfig},
+    process::run_cmd_with_env,
+};
+
+/// Represents the kind of change a watch event reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImapWatchEventKind {
+    /// Represents new messages.
+    New,
+    /// Represents deleted messages.
+    Expunged,
+    /// Represents messages whose flags changed.
+    Flags,
+    /// Represents a change that cannot be narrowed down to messages,
+    /// like a UIDVALIDITY change.
+    Changed,
+}
+
+impl Display for ImapWatchEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::New => write!(f, "new"),
+            Self::Expunged => write!(f, "expunged"),
+            Self::Flags => write!(f, "flags"),
+            Self::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+/// Represents an event emitted by a watched mailbox. Watch commands
+/// get it through `HIMALAYA_*` environment variables, see
+/// [`ImapWatchEvent::envs`], and as JSON on their standard input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImapWatchEvent {
+    #[serde(rename = "event")]
+    pub kind: ImapWatchEventKind,
+    pub account: String,
+    #[serde(rename = "mailbox")]
+    pub mbox: String,
+    /// Represents the UIDs of the messages concerned by the event.
+    pub uids: Vec<u32>,
+    /// Represents the sender of the new message.
+    pub sender: Option<String>,
+    /// Represents the subject of the new message.
+    pub subject: Option<String>,
+}
+
+impl ImapWatchEvent {
+    pub fn new(kind: ImapWatchEventKind, account: &str, mbox: &str, uids: Vec<u32>) -> Self {
+        Self {
+            kind,
+            account: account.to_owned(),
+            mbox: mbox.to_owned(),
+            uids,
+            sender: None,
+            subject: None,
+        }
+    }
+
+    /// Builds the event of a new message from its envelope.
+    pub fn new_msg(account: &str, mbox: &str, uid: u32, envelope: &ImapEnvelope) -> Self {
+        Self {
+            sender: Some(envelope.sender.clone()),
+            subject: Some(envelope.subject.clone()),
+            ..Self::new(ImapWatchEventKind::New, account, mbox, vec![uid])
+        }
+    }
+
+    /// Gets the environment variables describing the event. UIDs are
+    /// comma-separated, sender and subject are only set for new
+    /// messages.
+    pub fn envs(&self) -> Vec<(&'static str, String)> {
+        let uids = self.uids.iter().map(|uid| uid.to_string());
+        let mut envs = vec![
+            ("HIMALAYA_EVENT", self.kind.to_string()),
+            ("HIMALAYA_ACCOUNT", self.account.clone()),
+            ("HIMALAYA_MAILBOX", self.mbox.clone()),
+            ("HIMALAYA_UIDS", uids.collect::<Vec<_>>().join(",")),
+        ];
+        if let Some(ref sender) = self.sender {
+            envs.push(("HIMALAYA_SENDER", sender.clone()));
+        }
+        if let Some(ref subject) = self.subject {
+            envs.push(("HIMALAYA_SUBJECT", subject.clone()));
+        }
+        envs
+    }
+}
+
+/// Represents the UIDs of the selected mailbox, indexed by sequence
+/// number. Unsolicited responses only refer to messages by sequence
+/// number, so this mapping is needed to turn them into UIDs. UIDs of
+/// the messages announced by an EXISTS response are unknown until
+/// they are fetched.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImapMboxUids(Vec<Option<u32>>);
+
+impl ImapMboxUids {
+    /// Builds the mapping of a mailbox containing the given number of
+    /// messages, all of them with an unknown UID.
+    pub fn new(exists: u32) -> Self {
+        Self(vec![None; exists as usize])
+    }
+
+    /// Sets the UID of the message at the given sequence number.
+    pub fn set(&mut self, seq: u32, uid: u32) {
+        if let Some(entry) = (seq as usize)
+            .checked_sub(1)
+            .and_then(|i| self.0.get_mut(i))
+        {
+            *entry = Some(uid);
+        }
+    }
+
+    /// Gets the known UIDs, in sequence order.
+    pub fn uids(&self) -> Vec<u32> {
+        self.0.iter().flatten().copied().collect()
+    }
+
+    /// Gets the sequence numbers of the messages with an unknown UID.
+    pub fn unknown_seqs(&self) -> Vec<u32> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, uid)| uid.is_none())
+            .map(|(i, _)| i as u32 + 1)
+            .collect()
+    }
+
+    /// Applies the given unsolicited responses, in order. Returns the
+    /// UIDs of the expunged messages and of the messages whose flags
+    /// changed.
+    pub fn apply(&mut self, responses: &[UnsolicitedResponse]) -> (Vec<u32>, Vec<u32>) {
+        let mut expunged = vec![];
+        let mut flagged = vec![];
+
+        for res in responses {
+            match res {
+                UnsolicitedResponse::Exists(exists) => {
+                    let exists = *exists as usize;
+                    if exists > self.0.len() {
+                        self.0.resize(exists, None);
+                    }
+                }
+                UnsolicitedResponse::Expunge(seq) => {
+                    let i = (*seq as usize).wrapping_sub(1);
+                    if i < self.0.len() {
+                        expunged.extend(self.0.remove(i));
+                    }
+                }
+                // Earlier expunges happened before the mailbox was
+                // selected, they are not part of the mapping.
+                UnsolicitedResponse::Vanished {
+                    earlier: false,
+                    uids,
+                } => self.0.retain(|uid| match uid {
+                    Some(uid) if uids.iter().any(|range| range.contains(uid)) => {
+                        expunged.push(*uid);
+                        false
+                    }
+                    _ => true,
+                }),
+                UnsolicitedResponse::Fetch { id, .. } => {
+                    let uid = (*id as usize).checked_sub(1).and_then(|i| self.0.get(i));
+                    if let Some(Some(uid)) = uid {
+                        flagged.push(*uid);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // Messages expunged right after a flag change are reported as
+        // expunged only.
+        let expunged_set: HashSet<&u32> = expunged.iter().collect();
+        let mut seen = HashSet::new();
+        flagged.retain(|uid| !expunged_set.contains(uid) && seen.insert(*uid));
+
+        (expunged, flagged)
+    }
+}
+
+/// Represents the kind of events a watcher listens to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImapWatchMode {
+    Notify,
+    Watch,
+}
+
+/// Represents a mailbox to watch.
+#[derive(Debug, Clone)]
+struct ImapWatchTarget {
+    account_config: AccountConfig,
+    imap_config: ImapBackendConfig,
+    mbox: String,
+}
+
+/// Represents a watcher of several mailboxes.
+#[derive(Debug)]
+pub struct ImapWatcher {
+    keepalive: u64,
+    targets: Vec<ImapWatchTarget>,
+}
+
+impl ImapWatcher {
+    pub fn new(keepalive: u64) -> Self {
+        Self {
+            keepalive,
+            targets: vec![],
+        }
+    }
+
+    /// Adds the given mailbox of the given account to the watched
+    /// ones.
+    pub fn add(
+        &mut self,
+        account_config: &AccountConfig,
+        imap_config: &ImapBackendConfig,
+        mbox: &str,
+    ) -> &mut Self {
+        self.targets.push(ImapWatchTarget {
+            account_config: account_config.clone(),
+            imap_config: imap_config.clone(),
+            mbox: mbox.to_owned(),
+        });
+        self
+    }
+
+    /// Runs the notify command of the matching account for each new
+    /// message of the watched mailboxes.
+    pub fn notify(self) -> Result<()> {
+        self.run(ImapWatchMode::Notify, |account_config, event| {
+            if event.kind == ImapWatchEventKind::New {
+                account_config.run_notify_cmd(
+                    event.subject.as_deref().unwrap_or_default(),
+                    event.sender.as_deref().unwrap_or_default(),
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Runs the watch commands of the matching account each time one
+    /// of the watched mailboxes changes. The event is passed to the
+    /// commands through environment variables and as JSON on their
+    /// standard input.
+    pub fn watch(self) -> Result<()> {
+        self.run(ImapWatchMode::Watch, run_watch_cmds)
+    }
+
+    /// Spawns one connection per watched mailbox, then passes their
+    /// events to the given handler until one of them fails.
+    fn run<H>(self, mode: ImapWatchMode, mut handler: H) -> Result<()>
+    where
+        H: FnMut(&AccountConfig, &ImapWatchEvent) -> Result<()>,
+    {
+        if self.targets.is_empty() {
+            return Err(anyhow!("cannot watch mailboxes: no mailbox given"));
+        }
+
+        let accounts: HashMap<String, AccountConfig> = self
+            .targets
+            .iter()
+            .map(|target| {
+                let account_config = target.account_config.clone();
+                (account_config.name.clone(), account_config)
+            })
+            .collect();
+
+        let (sender, receiver) = mpsc::channel::<Result<ImapWatchEvent>>();
+        for target in self.targets {
+            let sender = sender.clone();
+            let keepalive = self.keepalive;
+            let name = format!("{}:{}", target.account_config.name, target.mbox);
+            debug!("spawn watcher {}", name);
+            thread::Builder::new()
+                .name(name.clone())
+                .spawn(move || {
+                    let mut imap = ImapBackend::new(&target.account_config, &target.imap_config);
+                    let forward = |event| {
+                        sender
+                            .send(Ok(event))
+                            .map_err(|_| anyhow!("cannot forward event: watcher stopped"))
+                    };
+                    let res = match mode {
+                        ImapWatchMode::Notify => imap.notify(keepalive, &target.mbox, forward),
+                        ImapWatchMode::Watch => imap.watch(keepalive, &target.mbox, forward),
+                    };
+                    if let Err(err) = res {
+                        let err = err.context(format!(
+                            "cannot watch mailbox {:?} of account {:?}",
+                            target.mbox, target.account_config.name
+                        ));
+                        if sender.send(Err(err)).is_err() {
+                            warn!(
+                                "cannot report error of watcher {}:{}: pipeline stopped",
+                                target.account_config.name, target.mbox
+                            );
+                        }
+                    }
+                })
+                .with_context(|| format!("cannot spawn watcher {}", name))?;
+        }
+        drop(sender);
+
+        for event in receiver {
+            let event = event?;
+            debug!("watch event: {:?}", event);
+            let account_config = accounts
+                .get(&event.account)
+                .ok_or_else(|| anyhow!("cannot find account {:?}", event.account))?;
+            handler(account_config, &event)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the watch commands of the given account in the background.
+/// The event is passed to the commands through environment variables
+/// and as JSON on their standard input.
+pub fn run_watch_cmds(account_config: &AccountConfig, event: &ImapWatchEvent) -> Result<()> {
+    let cmds = account_config.watch_cmds.clone();
+    let envs = event.envs();
+    let json = serde_json::to_vec(event).context("cannot serialize watch event")?;
+    thread::spawn(move || {
+        debug!("batch execution of {} cmd(s)", cmds.len());
+        cmds.iter().for_each(|cmd| {
+            debug!("running command {:?}…", cmd);
+            let res = run_cmd_with_env(cmd, &envs, &json);
+            debug!("{:?}", res);
+        })
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_apply_unsolicited_responses() {
+        let mut uids = ImapMboxUids::new(4);
+        [(1, 10), (2, 11), (3, 12), (4, 13)]
+            .into_iter()
+            .for_each(|(seq, uid)| uids.set(seq, uid));
+
+        let (expunged, flagged) = uids.apply(&[
+            UnsolicitedResponse::Fetch {
+                id: 3,
+                attributes: vec![],
+            },
+            UnsolicitedResponse::Fetch {
+                id: 1,
+                attributes: vec![],
+            },
+            UnsolicitedResponse::Expunge(2),
+            UnsolicitedResponse::Expunge(2),
+            UnsolicitedResponse::Exists(4),
+        ]);
+        assert_eq!(vec![11, 12], expunged);
+        assert_eq!(vec![10], flagged);
+        assert_eq!(vec![10, 13], uids.uids());
+        assert_eq!(vec![3, 4], uids.unknown_seqs());
+
+        uids.set(3, 14);
+        uids.set(4, 15);
+        let (expunged, flagged) = uids.apply(&[UnsolicitedResponse::Vanished {
+            earlier: false,
+            uids: vec![13..=14],
+        }]);
+        assert_eq!(vec![13, 14], expunged);
+        assert!(flagged.is_empty());
+        assert_eq!(vec![10, 15], uids.uids());
+    }
+
+    #[test]
+    fn it_should_describe_events() {
+        let envelope = ImapEnvelope {
+            sender: "Alice".into(),
+            subject: "Hello".into(),
+            ..ImapEnvelope::default()
+        };
+        let event = ImapWatchEvent::new_msg("perso", "INBOX", 42, &envelope);
+        assert_eq!(
+            vec![
+                ("HIMALAYA_EVENT", "new".to_owned()),
+                ("HIMALAYA_ACCOUNT", "perso".to_owned()),
+                ("HIMALAYA_MAILBOX", "INBOX".to_owned()),
+                ("HIMALAYA_UIDS", "42".to_owned()),
+                ("HIMALAYA_SENDER", "Alice".to_owned()),
+                ("HIMALAYA_SUBJECT", "Hello".to_owned()),
+            ],
+            event.envs()
+        );
+
+        let event = ImapWatchEvent::new(ImapWatchEventKind::Expunged, "perso", "INBOX", vec![1, 2]);
+        assert_eq!(
+            r#"{"event":"expunged","account":"perso","mailbox":"INBOX","uids":[1,2],"sender":null,"subject":null}"#,
+            serde_json::to_string(&event).unwrap()
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Message sort criteria module.
+//!
+//! This module regroups everything related to the conversion of
+//! message sort criteria into IMAP sort criteria.
+
+use crate::msg::{SortCriteria, SortCriterion, SortCriterionKind, SortCriterionOrder};
+
+type ImapSortCriterion<'a> = imap::extensions::sort::SortCriterion<'a>;
+
+impl From<&SortCriterion> for ImapSortCriterion<'static> {
+    fn from(criterion: &SortCriterion) -> Self {
+        match criterion.order {
+            SortCriterionOrder::Asc => match criterion.kind {
+                SortCriterionKind::Arrival => ImapSortCriterion::Arrival,
+                SortCriterionKind::Cc => ImapSortCriterion::Cc,
+                SortCriterionKind::Date => ImapSortCriterion::Date,
+                SortCriterionKind::From => ImapSortCriterion::From,
+                SortCriterionKind::Size => ImapSortCriterion::Size,
+                SortCriterionKind::Subject => ImapSortCriterion::Subject,
+                SortCriterionKind::To => ImapSortCriterion::To,
+            },
+            SortCriterionOrder::Desc => ImapSortCriterion::Reverse(match criterion.kind {
+                SortCriterionKind::Arrival => &ImapSortCriterion::Arrival,
+                SortCriterionKind::Cc => &ImapSortCriterion::Cc,
+                SortCriterionKind::Date => &ImapSortCriterion::Date,
+                SortCriterionKind::From => &ImapSortCriterion::From,
+                SortCriterionKind::Size => &ImapSortCriterion::Size,
+                SortCriterionKind::Subject => &ImapSortCriterion::Subject,
+                SortCriterionKind::To => &ImapSortCriterion::To,
+            }),
+        }
+    }
+}
+
+/// Converts message sort criteria into IMAP sort criteria.
+pub fn to_imap_sort_criteria(criteria: &SortCriteria) -> Vec<ImapSortCriterion<'static>> {
+    criteria.iter().map(ImapSortCriterion::from).collect()
+}
+
+/// Builds the sort program of the IMAP `SORT` command matching the
+/// given criteria, for clients that cannot build the command
+/// themselves.
+pub fn to_imap_sort_program(criteria: &SortCriteria) -> String {
+    to_imap_sort_criteria(criteria)
+        .iter()
+        .map(|criterion| criterion.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
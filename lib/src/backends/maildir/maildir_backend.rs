@@ -5,13 +5,20 @@
 
 use anyhow::{anyhow, Context, Result};
 use log::{debug, info, trace};
-use std::{convert::TryInto, env, fs, path::PathBuf};
+use std::{
+    convert::{TryFrom, TryInto},
+    env, fs,
+    path::PathBuf,
+};
 
 use crate::{
-    backends::{Backend, IdMapper, MaildirEnvelopes, MaildirFlags, MaildirMboxes},
+    backends::{Backend, IdMapper, MaildirEnvelopes, MaildirFlags, MaildirMboxes, MaildirSortKeys},
     config::{AccountConfig, MaildirBackendConfig},
     mbox::Mboxes,
-    msg::{Envelopes, Msg},
+    msg::{
+        Envelopes, Flags, Hash, IdSet, Msg, SearchQuery, SortCriteria, SortCriterion,
+        SortCriterionKind, SortCriterionOrder,
+    },
 };
 
 /// Represents the maildir backend.
@@ -21,10 +28,7 @@ pub struct MaildirBackend<'a> {
 }
 
 impl<'a> MaildirBackend<'a> {
-    pub fn new(
-        account_config: &'a AccountConfig,
-        maildir_config: &'a MaildirBackendConfig,
-    ) -> Self {
+    pub fn new(account_config: &'a AccountConfig, maildir_config: &MaildirBackendConfig) -> Self {
         Self {
             account_config,
             mdir: maildir_config.maildir_dir.clone().into(),
@@ -68,9 +72,115 @@ impl<'a> MaildirBackend<'a> {
             })
             .map(maildir::Maildir::from)
     }
+
+    /// Finds the maildir message identifiers matching the given
+    /// short hashes, using the id mapper cache file of the maildir.
+    fn find_ids(&self, mdir: &maildir::Maildir, ids: &IdSet<Hash>) -> Result<Vec<String>> {
+        let mapper = IdMapper::new(mdir.path())
+            .with_context(|| format!("cannot create id mapper instance for {:?}", mdir.path()))?;
+        ids.to_ids()?
+            .iter()
+            .map(|short_hash| {
+                mapper.find(&short_hash.to_string()).with_context(|| {
+                    format!(
+                        "cannot find maildir message by short hash {:?} at {:?}",
+                        short_hash.to_string(),
+                        mdir.path()
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the envelopes from the "cur" folder of the given maildir
+    /// matching the given query, sorts them by the given criteria
+    /// (most recent first by default), then applies pagination
+    /// boundaries and shortens their hash.
+    fn find_envelopes(
+        &self,
+        mdir: &maildir::Maildir,
+        query: &SearchQuery,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<MaildirEnvelopes> {
+        let default_sort = SortCriteria(vec![SortCriterion {
+            kind: SortCriterionKind::Date,
+            order: SortCriterionOrder::Desc,
+        }]);
+        let sort = if sort.is_empty() { &default_sort } else { sort };
+
+        // Only sort keys and ids are kept in memory, envelopes are
+        // built for the requested page only.
+        let mut entries = vec![];
+        for entry in mdir.list_cur() {
+            let mut entry = entry.context("cannot decode maildir mail entry")?;
+            let metadata = fs::metadata(entry.path()).with_context(|| {
+                format!("cannot read metadata of maildir entry {:?}", entry.path())
+            })?;
+            let flags = Flags::from(&MaildirFlags::from(&entry));
+            let parsed_mail = entry.parsed().context("cannot parse maildir mail entry")?;
+            if query.matches(&parsed_mail, &flags) {
+                let keys =
+                    MaildirSortKeys::new(&parsed_mail, metadata.len(), metadata.modified().ok());
+                entries.push((keys, entry.id().to_owned()));
+            }
+        }
+        debug!("envelopes len: {:?}", entries.len());
+        entries.sort_by(|(a, _), (b, _)| a.cmp_by(b, sort));
+
+        // Calculates pagination boundaries.
+        let page_begin = page * page_size;
+        debug!("page begin: {:?}", page_begin);
+        if page_begin > entries.len() {
+            return Err(anyhow!(
+                "cannot get maildir envelopes at page {:?} (out of bounds)",
+                page_begin + 1,
+            ));
+        }
+        let page_end = entries.len().min(page_begin + page_size);
+        debug!("page end: {:?}", page_end);
+
+        // Applies pagination boundaries.
+        let mut envelopes = MaildirEnvelopes::default();
+        for (_, id) in &entries[page_begin..page_end] {
+            let entry = mdir
+                .find(id)
+                .ok_or_else(|| anyhow!("cannot find maildir message {:?}", id))?;
+            envelopes.push(
+                entry
+                    .try_into()
+                    .context("cannot parse maildir mail entry")?,
+            );
+        }
+        trace!("envelopes: {:?}", envelopes);
+
+        // Appends envelopes hash to the id mapper cache file and
+        // calculates the new short hash length. The short hash length
+        // represents the minimum hash length possible to avoid
+        // conflicts.
+        let short_hash_len = {
+            let mut mapper = IdMapper::new(mdir.path())?;
+            let entries = envelopes
+                .iter()
+                .map(|env| (env.hash.to_owned(), env.id.to_owned()))
+                .collect();
+            mapper.append(entries)?
+        };
+        debug!("short hash length: {:?}", short_hash_len);
+
+        // Shorten envelopes hash.
+        envelopes
+            .iter_mut()
+            .for_each(|env| env.hash = env.hash[0..short_hash_len].to_owned());
+
+        Ok(envelopes)
+    }
 }
 
-impl<'a> Backend<'a> for MaildirBackend<'a> {
+impl<'a, 'b> Backend<'b> for MaildirBackend<'a> {
+    type Id = Hash;
+
     fn add_mbox(&mut self, subdir: &str) -> Result<()> {
         info!(">> add maildir subdir");
         debug!("subdir: {:?}", subdir);
@@ -80,6 +190,9 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
 
         fs::create_dir(&path)
             .with_context(|| format!("cannot create maildir subdir {:?} at {:?}", subdir, path))?;
+        maildir::Maildir::from(path.clone())
+            .create_dirs()
+            .with_context(|| format!("cannot create maildir subdir {:?} at {:?}", subdir, path))?;
 
         info!("<< add maildir subdir");
         Ok(())
@@ -112,6 +225,55 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
         Ok(())
     }
 
+    fn rename_mbox(&mut self, dir: &str, new_dir: &str) -> Result<()> {
+        info!(">> rename maildir dir");
+        debug!("dir: {:?}", dir);
+        debug!("new dir: {:?}", new_dir);
+
+        let root = self.mdir.path();
+        if !root.join(format!(".{}", dir)).is_dir() {
+            return Err(anyhow!("cannot find maildir dir {:?} in {:?}", dir, root));
+        }
+
+        // Maildir++ subfolders are siblings of their parent folder,
+        // named after it followed by a dot: they are renamed along.
+        let child_prefix = format!(".{}.", dir);
+        let mut renames = vec![(format!(".{}", dir), format!(".{}", new_dir))];
+        for entry in fs::read_dir(root)
+            .with_context(|| format!("cannot read maildir dirs from {:?}", root))?
+        {
+            let entry = entry.with_context(|| format!("cannot read maildir dir from {:?}", root))?;
+            if let Some(child) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(&child_prefix))
+            {
+                renames.push((
+                    format!("{}{}", child_prefix, child),
+                    format!(".{}.{}", new_dir, child),
+                ));
+            }
+        }
+        trace!("renames: {:?}", renames);
+
+        if let Some((_, path)) = renames.iter().find(|(_, path)| root.join(path).exists()) {
+            return Err(anyhow!(
+                "cannot rename maildir dir {:?}: {:?} already exists",
+                dir,
+                root.join(path)
+            ));
+        }
+        for (path, new_path) in renames {
+            let (path, new_path) = (root.join(path), root.join(new_path));
+            fs::rename(&path, &new_path).with_context(|| {
+                format!("cannot rename maildir dir {:?} to {:?}", path, new_path)
+            })?;
+        }
+
+        info!("<< rename maildir dir");
+        Ok(())
+    }
+
     fn get_envelopes(
         &mut self,
         dir: &str,
@@ -127,50 +289,13 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
             .get_mdir_from_dir(dir)
             .with_context(|| format!("cannot get maildir instance from {:?}", dir))?;
 
-        // Reads envelopes from the "cur" folder of the selected
-        // maildir.
-        let mut envelopes: MaildirEnvelopes = mdir.list_cur().try_into().with_context(|| {
-            format!("cannot parse maildir envelopes from {:?}", self.mdir.path())
-        })?;
-        debug!("envelopes len: {:?}", envelopes.len());
-        trace!("envelopes: {:?}", envelopes);
-
-        // Calculates pagination boundaries.
-        let page_begin = page * page_size;
-        debug!("page begin: {:?}", page_begin);
-        if page_begin > envelopes.len() {
-            return Err(anyhow!(
-                "cannot get maildir envelopes at page {:?} (out of bounds)",
-                page_begin + 1,
-            ));
-        }
-        let page_end = envelopes.len().min(page_begin + page_size);
-        debug!("page end: {:?}", page_end);
-
-        // Sorts envelopes by most recent date.
-        envelopes.sort_by(|a, b| b.date.partial_cmp(&a.date).unwrap());
-
-        // Applies pagination boundaries.
-        envelopes.envelopes = envelopes[page_begin..page_end].to_owned();
-
-        // Appends envelopes hash to the id mapper cache file and
-        // calculates the new short hash length. The short hash length
-        // represents the minimum hash length possible to avoid
-        // conflicts.
-        let short_hash_len = {
-            let mut mapper = IdMapper::new(mdir.path())?;
-            let entries = envelopes
-                .iter()
-                .map(|env| (env.hash.to_owned(), env.id.to_owned()))
-                .collect();
-            mapper.append(entries)?
-        };
-        debug!("short hash length: {:?}", short_hash_len);
-
-        // Shorten envelopes hash.
-        envelopes
-            .iter_mut()
-            .for_each(|env| env.hash = env.hash[0..short_hash_len].to_owned());
+        let envelopes = self.find_envelopes(
+            &mdir,
+            &SearchQuery::All,
+            &SortCriteria::default(),
+            page_size,
+            page,
+        )?;
 
         info!("<< get maildir envelopes");
         Ok(Box::new(envelopes))
@@ -178,20 +303,32 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
 
     fn search_envelopes(
         &mut self,
-        _dir: &str,
-        _query: &str,
-        _sort: &str,
-        _page_size: usize,
-        _page: usize,
+        dir: &str,
+        query: &str,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
     ) -> Result<Box<dyn Envelopes>> {
         info!(">> search maildir envelopes");
+        debug!("dir: {:?}", dir);
+        debug!("query: {:?}", query);
+        debug!("sort: {:?}", sort.to_string());
+        debug!("page size: {:?}", page_size);
+        debug!("page: {:?}", page);
+
+        let query = SearchQuery::try_from(query)?;
+
+        let mdir = self
+            .get_mdir_from_dir(dir)
+            .with_context(|| format!("cannot get maildir instance from {:?}", dir))?;
+
+        let envelopes = self.find_envelopes(&mdir, &query, sort, page_size, page)?;
+
         info!("<< search maildir envelopes");
-        Err(anyhow!(
-            "cannot find maildir envelopes: feature not implemented"
-        ))
+        Ok(Box::new(envelopes))
     }
 
-    fn add_msg(&mut self, dir: &str, msg: &[u8], flags: &str) -> Result<Box<dyn ToString>> {
+    fn add_msg(&mut self, dir: &str, msg: &[u8], flags: &Flags) -> Result<Hash> {
         info!(">> add maildir message");
         debug!("dir: {:?}", dir);
         debug!("flags: {:?}", flags);
@@ -201,7 +338,7 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
             .with_context(|| format!("cannot get maildir instance from {:?}", dir))?;
         let flags: MaildirFlags = flags
             .try_into()
-            .with_context(|| format!("cannot parse maildir flags {:?}", flags))?;
+            .with_context(|| format!("cannot parse maildir flags {:?}", flags.to_string()))?;
         let id = mdir
             .store_cur_with_flags(msg, &flags.to_string())
             .with_context(|| format!("cannot add maildir message to {:?}", mdir.path()))?;
@@ -222,10 +359,10 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
             })?;
 
         info!("<< add maildir message");
-        Ok(Box::new(hash))
+        Ok(Hash(hash))
     }
 
-    fn get_msg(&mut self, dir: &str, short_hash: &str) -> Result<Msg> {
+    fn get_msg(&mut self, dir: &str, short_hash: &Hash) -> Result<Msg> {
         info!(">> get maildir message");
         debug!("dir: {:?}", dir);
         debug!("short hash: {:?}", short_hash);
@@ -234,11 +371,11 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
             .get_mdir_from_dir(dir)
             .with_context(|| format!("cannot get maildir instance from {:?}", dir))?;
         let id = IdMapper::new(mdir.path())?
-            .find(short_hash)
+            .find(&short_hash.to_string())
             .with_context(|| {
                 format!(
                     "cannot find maildir message by short hash {:?} at {:?}",
-                    short_hash,
+                    short_hash.to_string(),
                     mdir.path()
                 )
             })?;
@@ -262,10 +399,11 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
         Ok(msg)
     }
 
-    fn copy_msg(&mut self, dir_src: &str, dir_dst: &str, short_hash: &str) -> Result<()> {
-        info!(">> copy maildir message");
+    fn copy_msg(&mut self, dir_src: &str, dir_dst: &str, short_hashes: &IdSet<Hash>) -> Result<()> {
+        info!(">> copy maildir messages");
         debug!("source dir: {:?}", dir_src);
         debug!("destination dir: {:?}", dir_dst);
+        debug!("short hashes: {:?}", short_hashes.to_string());
 
         let mdir_src = self
             .get_mdir_from_dir(dir_src)
@@ -273,49 +411,39 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
         let mdir_dst = self.get_mdir_from_dir(dir_dst).with_context(|| {
             format!("cannot get destination maildir instance from {:?}", dir_dst)
         })?;
-        let id = IdMapper::new(mdir_src.path())
-            .with_context(|| format!("cannot create id mapper instance for {:?}", mdir_src.path()))?
-            .find(short_hash)
-            .with_context(|| {
+        let ids = self.find_ids(&mdir_src, short_hashes)?;
+        debug!("ids: {:?}", ids);
+
+        let mut entries = vec![];
+        for id in ids {
+            mdir_src.copy_to(&id, &mdir_dst).with_context(|| {
                 format!(
-                    "cannot find maildir message by short hash {:?} at {:?}",
-                    short_hash,
-                    mdir_src.path()
+                    "cannot copy message {:?} from maildir {:?} to maildir {:?}",
+                    id,
+                    mdir_src.path(),
+                    mdir_dst.path()
                 )
             })?;
-        debug!("id: {:?}", id);
-
-        mdir_src.copy_to(&id, &mdir_dst).with_context(|| {
-            format!(
-                "cannot copy message {:?} from maildir {:?} to maildir {:?}",
-                id,
-                mdir_src.path(),
-                mdir_dst.path()
-            )
-        })?;
+            entries.push((format!("{:x}", md5::compute(&id)), id));
+        }
 
-        // Appends hash entry to the id mapper cache file.
+        // Appends hash entries to the id mapper cache file.
         let mut mapper = IdMapper::new(mdir_dst.path()).with_context(|| {
             format!("cannot create id mapper instance for {:?}", mdir_dst.path())
         })?;
-        let hash = format!("{:x}", md5::compute(&id));
         mapper
-            .append(vec![(hash.clone(), id.clone())])
-            .with_context(|| {
-                format!(
-                    "cannot append hash {:?} with id {:?} to id mapper",
-                    hash, id
-                )
-            })?;
+            .append(entries)
+            .context("cannot append copied messages to id mapper")?;
 
-        info!("<< copy maildir message");
+        info!("<< copy maildir messages");
         Ok(())
     }
 
-    fn move_msg(&mut self, dir_src: &str, dir_dst: &str, short_hash: &str) -> Result<()> {
-        info!(">> move maildir message");
+    fn move_msg(&mut self, dir_src: &str, dir_dst: &str, short_hashes: &IdSet<Hash>) -> Result<()> {
+        info!(">> move maildir messages");
         debug!("source dir: {:?}", dir_src);
         debug!("destination dir: {:?}", dir_dst);
+        debug!("short hashes: {:?}", short_hashes.to_string());
 
         let mdir_src = self
             .get_mdir_from_dir(dir_src)
@@ -323,80 +451,62 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
         let mdir_dst = self.get_mdir_from_dir(dir_dst).with_context(|| {
             format!("cannot get destination maildir instance from {:?}", dir_dst)
         })?;
-        let id = IdMapper::new(mdir_src.path())
-            .with_context(|| format!("cannot create id mapper instance for {:?}", mdir_src.path()))?
-            .find(short_hash)
-            .with_context(|| {
+        let ids = self.find_ids(&mdir_src, short_hashes)?;
+        debug!("ids: {:?}", ids);
+
+        let mut entries = vec![];
+        for id in ids {
+            mdir_src.move_to(&id, &mdir_dst).with_context(|| {
                 format!(
-                    "cannot find maildir message by short hash {:?} at {:?}",
-                    short_hash,
-                    mdir_src.path()
+                    "cannot move message {:?} from maildir {:?} to maildir {:?}",
+                    id,
+                    mdir_src.path(),
+                    mdir_dst.path()
                 )
             })?;
-        debug!("id: {:?}", id);
-
-        mdir_src.move_to(&id, &mdir_dst).with_context(|| {
-            format!(
-                "cannot move message {:?} from maildir {:?} to maildir {:?}",
-                id,
-                mdir_src.path(),
-                mdir_dst.path()
-            )
-        })?;
+            entries.push((format!("{:x}", md5::compute(&id)), id));
+        }
 
-        // Appends hash entry to the id mapper cache file.
+        // Appends hash entries to the id mapper cache file.
         let mut mapper = IdMapper::new(mdir_dst.path()).with_context(|| {
             format!("cannot create id mapper instance for {:?}", mdir_dst.path())
         })?;
-        let hash = format!("{:x}", md5::compute(&id));
         mapper
-            .append(vec![(hash.clone(), id.clone())])
-            .with_context(|| {
-                format!(
-                    "cannot append hash {:?} with id {:?} to id mapper",
-                    hash, id
-                )
-            })?;
+            .append(entries)
+            .context("cannot append moved messages to id mapper")?;
 
-        info!("<< move maildir message");
+        info!("<< move maildir messages");
         Ok(())
     }
 
-    fn del_msg(&mut self, dir: &str, short_hash: &str) -> Result<()> {
-        info!(">> delete maildir message");
+    fn del_msg(&mut self, dir: &str, short_hashes: &IdSet<Hash>) -> Result<()> {
+        info!(">> delete maildir messages");
         debug!("dir: {:?}", dir);
-        debug!("short hash: {:?}", short_hash);
+        debug!("short hashes: {:?}", short_hashes.to_string());
 
         let mdir = self
             .get_mdir_from_dir(dir)
             .with_context(|| format!("cannot get maildir instance from {:?}", dir))?;
-        let id = IdMapper::new(mdir.path())
-            .with_context(|| format!("cannot create id mapper instance for {:?}", mdir.path()))?
-            .find(short_hash)
-            .with_context(|| {
+        let ids = self.find_ids(&mdir, short_hashes)?;
+        debug!("ids: {:?}", ids);
+        for id in ids {
+            mdir.delete(&id).with_context(|| {
                 format!(
-                    "cannot find maildir message by short hash {:?} at {:?}",
-                    short_hash,
+                    "cannot delete message {:?} from maildir {:?}",
+                    id,
                     mdir.path()
                 )
             })?;
-        debug!("id: {:?}", id);
-        mdir.delete(&id).with_context(|| {
-            format!(
-                "cannot delete message {:?} from maildir {:?}",
-                id,
-                mdir.path()
-            )
-        })?;
+        }
 
-        info!("<< delete maildir message");
+        info!("<< delete maildir messages");
         Ok(())
     }
 
-    fn add_flags(&mut self, dir: &str, short_hash: &str, flags: &str) -> Result<()> {
+    fn add_flags(&mut self, dir: &str, short_hashes: &IdSet<Hash>, flags: &Flags) -> Result<()> {
         info!(">> add maildir message flags");
         debug!("dir: {:?}", dir);
-        debug!("short hash: {:?}", short_hash);
+        debug!("short hashes: {:?}", short_hashes.to_string());
         debug!("flags: {:?}", flags);
 
         let mdir = self
@@ -404,30 +514,24 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
             .with_context(|| format!("cannot get maildir instance from {:?}", dir))?;
         let flags: MaildirFlags = flags
             .try_into()
-            .with_context(|| format!("cannot parse maildir flags {:?}", flags))?;
+            .with_context(|| format!("cannot parse maildir flags {:?}", flags.to_string()))?;
         debug!("flags: {:?}", flags);
-        let id = IdMapper::new(mdir.path())
-            .with_context(|| format!("cannot create id mapper instance for {:?}", mdir.path()))?
-            .find(short_hash)
-            .with_context(|| {
-                format!(
-                    "cannot find maildir message by short hash {:?} at {:?}",
-                    short_hash,
-                    mdir.path()
-                )
+        let ids = self.find_ids(&mdir, short_hashes)?;
+        debug!("ids: {:?}", ids);
+        for id in ids {
+            mdir.add_flags(&id, &flags.to_string()).with_context(|| {
+                format!("cannot add flags {:?} to maildir message {:?}", flags, id)
             })?;
-        debug!("id: {:?}", id);
-        mdir.add_flags(&id, &flags.to_string())
-            .with_context(|| format!("cannot add flags {:?} to maildir message {:?}", flags, id))?;
+        }
 
         info!("<< add maildir message flags");
         Ok(())
     }
 
-    fn set_flags(&mut self, dir: &str, short_hash: &str, flags: &str) -> Result<()> {
+    fn set_flags(&mut self, dir: &str, short_hashes: &IdSet<Hash>, flags: &Flags) -> Result<()> {
         info!(">> set maildir message flags");
         debug!("dir: {:?}", dir);
-        debug!("short hash: {:?}", short_hash);
+        debug!("short hashes: {:?}", short_hashes.to_string());
         debug!("flags: {:?}", flags);
 
         let mdir = self
@@ -435,30 +539,24 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
             .with_context(|| format!("cannot get maildir instance from {:?}", dir))?;
         let flags: MaildirFlags = flags
             .try_into()
-            .with_context(|| format!("cannot parse maildir flags {:?}", flags))?;
+            .with_context(|| format!("cannot parse maildir flags {:?}", flags.to_string()))?;
         debug!("flags: {:?}", flags);
-        let id = IdMapper::new(mdir.path())
-            .with_context(|| format!("cannot create id mapper instance for {:?}", mdir.path()))?
-            .find(short_hash)
-            .with_context(|| {
-                format!(
-                    "cannot find maildir message by short hash {:?} at {:?}",
-                    short_hash,
-                    mdir.path()
-                )
+        let ids = self.find_ids(&mdir, short_hashes)?;
+        debug!("ids: {:?}", ids);
+        for id in ids {
+            mdir.set_flags(&id, &flags.to_string()).with_context(|| {
+                format!("cannot set flags {:?} to maildir message {:?}", flags, id)
             })?;
-        debug!("id: {:?}", id);
-        mdir.set_flags(&id, &flags.to_string())
-            .with_context(|| format!("cannot set flags {:?} to maildir message {:?}", flags, id))?;
+        }
 
         info!("<< set maildir message flags");
         Ok(())
     }
 
-    fn del_flags(&mut self, dir: &str, short_hash: &str, flags: &str) -> Result<()> {
+    fn del_flags(&mut self, dir: &str, short_hashes: &IdSet<Hash>, flags: &Flags) -> Result<()> {
         info!(">> delete maildir message flags");
         debug!("dir: {:?}", dir);
-        debug!("short hash: {:?}", short_hash);
+        debug!("short hashes: {:?}", short_hashes.to_string());
         debug!("flags: {:?}", flags);
 
         let mdir = self
@@ -466,26 +564,19 @@ impl<'a> Backend<'a> for MaildirBackend<'a> {
             .with_context(|| format!("cannot get maildir instance from {:?}", dir))?;
         let flags: MaildirFlags = flags
             .try_into()
-            .with_context(|| format!("cannot parse maildir flags {:?}", flags))?;
+            .with_context(|| format!("cannot parse maildir flags {:?}", flags.to_string()))?;
         debug!("flags: {:?}", flags);
-        let id = IdMapper::new(mdir.path())
-            .with_context(|| format!("cannot create id mapper instance for {:?}", mdir.path()))?
-            .find(short_hash)
-            .with_context(|| {
-                format!(
-                    "cannot find maildir message by short hash {:?} at {:?}",
-                    short_hash,
-                    mdir.path()
-                )
-            })?;
-        debug!("id: {:?}", id);
-        mdir.remove_flags(&id, &flags.to_string())
-            .with_context(|| {
-                format!(
-                    "cannot delete flags {:?} to maildir message {:?}",
-                    flags, id
-                )
-            })?;
+        let ids = self.find_ids(&mdir, short_hashes)?;
+        debug!("ids: {:?}", ids);
+        for id in ids {
+            mdir.remove_flags(&id, &flags.to_string())
+                .with_context(|| {
+                    format!(
+                        "cannot delete flags {:?} to maildir message {:?}",
+                        flags, id
+                    )
+                })?;
+        }
 
         info!("<< delete maildir message flags");
         Ok(())
@@ -4,6 +4,8 @@ use std::{
     ops::Deref,
 };
 
+use crate::msg::{Flag, Flags};
+
 /// Represents the maildir flag variants.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
 pub enum MaildirFlag {
@@ -68,15 +70,15 @@ impl ToString for MaildirFlags {
     }
 }
 
-impl TryFrom<&str> for MaildirFlags {
+impl TryFrom<&Flags> for MaildirFlags {
     type Error = Error;
 
-    fn try_from(flags_str: &str) -> Result<Self, Self::Error> {
-        let mut flags = vec![];
-        for flag_str in flags_str.split_whitespace() {
-            flags.push(flag_str.trim().try_into()?);
+    fn try_from(flags: &Flags) -> Result<Self, Self::Error> {
+        let mut maildir_flags = vec![];
+        for flag in flags.iter() {
+            maildir_flags.push(flag.try_into()?);
         }
-        Ok(MaildirFlags(flags))
+        Ok(MaildirFlags(maildir_flags))
     }
 }
 
@@ -98,6 +100,25 @@ impl From<&maildir::MailEntry> for MaildirFlags {
     }
 }
 
+impl From<&MaildirFlags> for Flags {
+    fn from(flags: &MaildirFlags) -> Self {
+        Flags(
+            flags
+                .iter()
+                .map(|flag| match flag {
+                    MaildirFlag::Passed => Flag::Custom(String::from("passed")),
+                    MaildirFlag::Replied => Flag::Answered,
+                    MaildirFlag::Seen => Flag::Seen,
+                    MaildirFlag::Trashed => Flag::Deleted,
+                    MaildirFlag::Draft => Flag::Draft,
+                    MaildirFlag::Flagged => Flag::Flagged,
+                    MaildirFlag::Custom(custom) => Flag::Custom(custom.to_string()),
+                })
+                .collect(),
+        )
+    }
+}
+
 impl Into<char> for &MaildirFlag {
     fn into(self) -> char {
         match self {
@@ -112,18 +133,23 @@ impl Into<char> for &MaildirFlag {
     }
 }
 
-impl TryFrom<&str> for MaildirFlag {
+impl TryFrom<&Flag> for MaildirFlag {
     type Error = Error;
 
-    fn try_from(flag_str: &str) -> Result<Self, Self::Error> {
-        match flag_str {
-            "passed" => Ok(MaildirFlag::Passed),
-            "replied" => Ok(MaildirFlag::Replied),
-            "seen" => Ok(MaildirFlag::Seen),
-            "trashed" => Ok(MaildirFlag::Trashed),
-            "draft" => Ok(MaildirFlag::Draft),
-            "flagged" => Ok(MaildirFlag::Flagged),
-            flag_str => Err(anyhow!("cannot parse maildir flag {:?}", flag_str)),
+    fn try_from(flag: &Flag) -> Result<Self, Self::Error> {
+        match flag {
+            Flag::Seen => Ok(MaildirFlag::Seen),
+            Flag::Answered => Ok(MaildirFlag::Replied),
+            Flag::Flagged => Ok(MaildirFlag::Flagged),
+            Flag::Deleted => Ok(MaildirFlag::Trashed),
+            Flag::Draft => Ok(MaildirFlag::Draft),
+            Flag::Custom(custom) if custom.eq_ignore_ascii_case("passed") => {
+                Ok(MaildirFlag::Passed)
+            }
+            flag => Err(anyhow!(
+                "cannot convert flag {:?} into maildir flag",
+                flag.to_string()
+            )),
         }
     }
 }
@@ -0,0 +1,159 @@
+//! Maildir mailbox module.
+//!
+//! This module provides Maildir types and conversion utilities
+//! related to the mailbox
+
+use anyhow::{anyhow, Context, Error, Result};
+use std::{
+    convert::{TryFrom, TryInto},
+    ffi::OsStr,
+    fmt::{self, Display},
+    fs,
+    ops::Deref,
+};
+
+use crate::mbox::MboxCounts;
+
+/// Represents a list of Maildir mailboxes.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct MaildirMboxes {
+    #[serde(rename = "response")]
+    pub mboxes: Vec<MaildirMbox>,
+}
+
+impl Deref for MaildirMboxes {
+    type Target = Vec<MaildirMbox>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mboxes
+    }
+}
+
+/// Represents the mailbox.
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct MaildirMbox {
+    /// Represents the mailbox name.
+    pub name: String,
+
+    /// Represents the message counts of the mailbox.
+    #[serde(flatten)]
+    pub counts: Option<MboxCounts>,
+}
+
+impl MaildirMbox {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            counts: None,
+        }
+    }
+}
+
+impl Display for MaildirMbox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_create_new_mbox() {
+        assert_eq!(MaildirMbox::default(), MaildirMbox::new(""));
+        assert_eq!(
+            MaildirMbox {
+                name: "INBOX".into(),
+                ..MaildirMbox::default()
+            },
+            MaildirMbox::new("INBOX")
+        );
+    }
+
+    #[test]
+    fn it_should_display_mbox() {
+        let default_mbox = MaildirMbox::default();
+        assert_eq!("", default_mbox.to_string());
+
+        let new_mbox = MaildirMbox::new("INBOX");
+        assert_eq!("INBOX", new_mbox.to_string());
+
+        let full_mbox = MaildirMbox {
+            name: "Sent".into(),
+            counts: None,
+        };
+        assert_eq!("Sent", full_mbox.to_string());
+    }
+}
+
+/// Represents a list of raw mailboxes returned by the `maildir` crate.
+pub type RawMaildirMboxes = maildir::MaildirEntries;
+
+impl TryFrom<RawMaildirMboxes> for MaildirMboxes {
+    type Error = Error;
+
+    fn try_from(mail_entries: RawMaildirMboxes) -> Result<Self, Self::Error> {
+        let mut mboxes = vec![];
+        for entry in mail_entries {
+            mboxes.push(entry?.try_into()?);
+        }
+        Ok(MaildirMboxes { mboxes })
+    }
+}
+
+/// Represents the raw mailbox returned by the `maildir` crate.
+pub type RawMaildirMbox = maildir::Maildir;
+
+impl TryFrom<RawMaildirMbox> for MaildirMbox {
+    type Error = Error;
+
+    fn try_from(mail_entry: RawMaildirMbox) -> Result<Self, Self::Error> {
+        let subdir_name = mail_entry.path().file_name();
+        let counts = count_msgs(&mail_entry)?;
+        Ok(Self {
+            name: subdir_name
+                .and_then(OsStr::to_str)
+                .and_then(|s| if s.len() < 2 { None } else { Some(&s[1..]) })
+                .ok_or_else(|| {
+                    anyhow!(
+                        "cannot parse maildir subdirectory name from path {:?}",
+                        subdir_name,
+                    )
+                })?
+                .into(),
+            counts: Some(counts),
+        })
+    }
+}
+
+/// Counts the messages of the given maildir. Messages of the `new`
+/// folder are recent and unseen, messages of the `cur` folder are
+/// unseen unless they have the seen flag.
+pub fn count_msgs(mdir: &RawMaildirMbox) -> Result<MboxCounts> {
+    let mut counts = MboxCounts::default();
+    let mut recent = 0;
+    let mut size = 0;
+
+    for (is_new, entries) in [(true, mdir.list_new()), (false, mdir.list_cur())] {
+        for entry in entries {
+            let entry = entry
+                .with_context(|| format!("cannot read maildir entry from {:?}", mdir.path()))?;
+            let metadata = fs::metadata(entry.path()).with_context(|| {
+                format!("cannot read metadata of maildir entry {:?}", entry.path())
+            })?;
+            counts.messages += 1;
+            size += metadata.len();
+            if is_new {
+                recent += 1;
+                counts.unseen += 1;
+            } else if !entry.flags().contains('S') {
+                counts.unseen += 1;
+            }
+        }
+    }
+
+    counts.recent = Some(recent);
+    counts.size = Some(size);
+    Ok(counts)
+}
@@ -0,0 +1,175 @@
+//! Maildir sort module.
+//!
+//! Maildir has no server to sort messages, so envelopes are sorted
+//! in-process. This module contains the sort keys extracted from each
+//! message, following the IMAP SORT semantics of [RFC5256] where it
+//! makes sense, so that both backends order messages the same way.
+//!
+//! [RFC5256]: https://datatracker.ietf.org/doc/html/rfc5256
+
+use chrono::{DateTime, FixedOffset, Utc};
+use mailparse::{MailAddr, MailHeaderMap, ParsedMail};
+use std::{cmp::Ordering, time::SystemTime};
+
+use crate::msg::{base_subject, parse_date, SortCriteria, SortCriterionKind, SortCriterionOrder};
+
+/// Represents the properties a maildir message can be sorted by.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MaildirSortKeys {
+    /// Represents the time the message arrived in the maildir.
+    pub arrival: Option<DateTime<FixedOffset>>,
+
+    /// Represents the first Cc address, lowercased.
+    pub cc: String,
+
+    /// Represents the date of the message, or its arrival time if
+    /// the date is missing or invalid.
+    pub date: Option<DateTime<FixedOffset>>,
+
+    /// Represents the first From address, lowercased.
+    pub from: String,
+
+    /// Represents the size of the message, in bytes.
+    pub size: u64,
+
+    /// Represents the base subject of the message, lowercased and
+    /// without reply or forward prefixes.
+    pub subject: String,
+
+    /// Represents the first To address, lowercased.
+    pub to: String,
+}
+
+impl MaildirSortKeys {
+    /// Extracts the sort keys from the given parsed message.
+    pub fn new(parsed_mail: &ParsedMail, size: u64, arrival: Option<SystemTime>) -> Self {
+        let arrival = arrival.map(|arrival| DateTime::<Utc>::from(arrival).into());
+        let date = parsed_mail
+            .headers
+            .get_first_value("date")
+            .and_then(|date| parse_date(&date))
+            .or(arrival);
+        Self {
+            arrival,
+            cc: first_addr(parsed_mail, "cc"),
+            date,
+            from: first_addr(parsed_mail, "from"),
+            size,
+            subject: base_subject(
+                &parsed_mail
+                    .headers
+                    .get_first_value("subject")
+                    .unwrap_or_default(),
+            ),
+            to: first_addr(parsed_mail, "to"),
+        }
+    }
+
+    /// Compares the sort keys using the given criteria. Each
+    /// criterion breaks the ties of the previous one.
+    pub fn cmp_by(&self, other: &Self, criteria: &SortCriteria) -> Ordering {
+        criteria.iter().fold(Ordering::Equal, |ord, criterion| {
+            ord.then_with(|| {
+                let ord = match criterion.kind {
+                    SortCriterionKind::Arrival => self.arrival.cmp(&other.arrival),
+                    SortCriterionKind::Cc => self.cc.cmp(&other.cc),
+                    SortCriterionKind::Date => self.date.cmp(&other.date),
+                    SortCriterionKind::From => self.from.cmp(&other.from),
+                    SortCriterionKind::Size => self.size.cmp(&other.size),
+                    SortCriterionKind::Subject => self.subject.cmp(&other.subject),
+                    SortCriterionKind::To => self.to.cmp(&other.to),
+                };
+                match criterion.order {
+                    SortCriterionOrder::Asc => ord,
+                    SortCriterionOrder::Desc => ord.reverse(),
+                }
+            })
+        })
+    }
+}
+
+/// Returns the first address of the given header, lowercased. Groups
+/// are represented by their first address, or by their name if they
+/// are empty.
+fn first_addr(parsed_mail: &ParsedMail, key: &str) -> String {
+    let value = match parsed_mail.headers.get_first_value(key) {
+        Some(value) => value,
+        None => return String::new(),
+    };
+    let addr = match mailparse::addrparse(&value) {
+        Ok(addrs) => match addrs.first() {
+            Some(MailAddr::Single(info)) => info.addr.clone(),
+            Some(MailAddr::Group(info)) => info
+                .addrs
+                .first()
+                .map(|info| info.addr.clone())
+                .unwrap_or_else(|| info.group_name.clone()),
+            None => String::new(),
+        },
+        Err(_) => value,
+    };
+    addr.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::TryFrom, time::Duration};
+
+    use super::*;
+
+    fn keys(from: &str, subject: &str, date: &str, size: u64) -> MaildirSortKeys {
+        let raw = format!(
+            "From: {}\r\nTo: bob@localhost\r\nSubject: {}\r\nDate: {}\r\n\r\nHello\r\n",
+            from, subject, date
+        );
+        let parsed_mail = mailparse::parse_mail(raw.as_bytes()).unwrap();
+        let arrival = SystemTime::UNIX_EPOCH + Duration::from_secs(size);
+        MaildirSortKeys::new(&parsed_mail, size, Some(arrival))
+    }
+
+    #[test]
+    fn it_should_extract_sort_keys() {
+        let keys = keys(
+            "Alice <Alice@Localhost>",
+            "Re: FWD: Meeting (fwd)",
+            "Tue, 1 Mar 2022 10:00:00 +0100 (CET)",
+            42,
+        );
+        assert_eq!("alice@localhost", keys.from);
+        assert_eq!("bob@localhost", keys.to);
+        assert_eq!("", keys.cc);
+        assert_eq!("meeting", keys.subject);
+        assert_eq!(parse_date("Tue, 1 Mar 2022 09:00:00 +0000"), keys.date);
+    }
+
+    #[test]
+    fn it_should_sort_by_criteria() {
+        let mut msgs = [
+            keys(
+                "bob@localhost",
+                "Lunch",
+                "Tue, 1 Mar 2022 10:00:00 +0100",
+                3,
+            ),
+            keys(
+                "alice@localhost",
+                "Meeting",
+                "Tue, 1 Mar 2022 10:30:00 +0200",
+                1,
+            ),
+            keys("bob@localhost", "Report", "invalid", 2),
+        ];
+        let mut sort = |criteria: &str| {
+            let criteria = SortCriteria::try_from(criteria).unwrap();
+            msgs.sort_by(|a, b| a.cmp_by(b, &criteria));
+            msgs.iter().map(|keys| keys.size).collect::<Vec<_>>()
+        };
+
+        // The invalid date falls back to the arrival time, 1970.
+        assert_eq!(vec![2, 1, 3], sort("date"));
+        assert_eq!(vec![3, 1, 2], sort("date:desc"));
+        assert_eq!(vec![3, 2, 1], sort("from:desc subject"));
+        assert_eq!(vec![1, 2, 3], sort("arrival"));
+        assert_eq!(vec![3, 2, 1], sort("size:desc"));
+    }
+}
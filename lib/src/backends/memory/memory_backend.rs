@@ -0,0 +1,715 @@
+//! In-memory backend module.
+//!
+//! This module contains the definition of the in-memory backend and
+//! its traits implementation. Nothing is persisted: mailboxes and
+//! messages live as long as the backend does, which makes it a good
+//! fit for tests and dry runs.
+//!
+//! Messages are identified by number. Numbers are never reused
+//! within a mailbox, like IMAP UIDs.
+//!
+//! Searches use the shared query language, matched in-process, see
+//! [`crate::msg::SearchQuery`].
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, FixedOffset};
+use log::{debug, info, trace};
+use mailparse::{MailHeaderMap, ParsedMail};
+use std::{cmp::Ordering, collections::BTreeMap, convert::TryFrom};
+
+use crate::{
+    backends::{Backend, MemoryEnvelope, MemoryEnvelopes, MemoryMbox, MemoryMboxes},
+    config::{AccountConfig, DEFAULT_DRAFT_FOLDER, DEFAULT_INBOX_FOLDER, DEFAULT_SENT_FOLDER},
+    mbox::Mboxes,
+    msg::{
+        Envelopes, Flag, Flags, IdSet, Msg, SearchQuery, SortCriteria, SortCriterionKind,
+        SortCriterionOrder, Uid,
+    },
+};
+
+/// Represents a message stored in memory.
+#[derive(Debug, Clone)]
+struct MemoryMsg {
+    id: u32,
+    flags: Vec<Flag>,
+    raw: Vec<u8>,
+}
+
+impl MemoryMsg {
+    fn to_envelope(&self) -> Result<MemoryEnvelope> {
+        MemoryEnvelope::from_raw(self.id, Flags::from(self.flags.clone()), &self.raw)
+            .with_context(|| format!("cannot build envelope of in-memory message {}", self.id))
+    }
+}
+
+/// Represents a mailbox stored in memory.
+#[derive(Debug, Default)]
+struct MemoryMboxStore {
+    next_id: u32,
+    msgs: Vec<MemoryMsg>,
+}
+
+impl MemoryMboxStore {
+    fn append(&mut self, raw: Vec<u8>, flags: Vec<Flag>) -> u32 {
+        self.next_id += 1;
+        self.msgs.push(MemoryMsg {
+            id: self.next_id,
+            flags,
+            raw,
+        });
+        self.next_id
+    }
+}
+
+/// Represents the in-memory backend.
+pub struct MemoryBackend<'a> {
+    account_config: &'a AccountConfig,
+    mboxes: BTreeMap<String, MemoryMboxStore>,
+}
+
+impl<'a> MemoryBackend<'a> {
+    /// Creates an in-memory backend containing the inbox, the sent
+    /// and the draft mailboxes of the given account.
+    pub fn new(account_config: &'a AccountConfig) -> Self {
+        let mut backend = Self {
+            account_config,
+            mboxes: BTreeMap::new(),
+        };
+
+        for (alias, default) in [
+            ("inbox", DEFAULT_INBOX_FOLDER),
+            ("sent", DEFAULT_SENT_FOLDER),
+            ("draft", DEFAULT_DRAFT_FOLDER),
+        ] {
+            let mbox = account_config
+                .mailboxes
+                .get(alias)
+                .map(|s| s.as_str())
+                .unwrap_or(default);
+            backend.mboxes.entry(mbox.to_owned()).or_default();
+        }
+
+        backend
+    }
+
+    /// Resolves the given mailbox alias, then finds the matching
+    /// mailbox.
+    fn mbox(&mut self, mbox: &str) -> Result<&mut MemoryMboxStore> {
+        let name = self.account_config.get_mbox_alias(mbox)?;
+        self.mboxes
+            .get_mut(&name)
+            .ok_or_else(|| anyhow!("cannot find in-memory mailbox {:?}", name))
+    }
+
+    /// Finds the messages of the given mailbox matching the given id
+    /// set.
+    fn msgs(&mut self, mbox: &str, ids: &IdSet<Uid>) -> Result<Vec<MemoryMsg>> {
+        Ok(self
+            .mbox(mbox)?
+            .msgs
+            .iter()
+            .filter(|msg| ids.contains(Uid(msg.id)))
+            .cloned()
+            .collect())
+    }
+
+    /// Applies the given function to the flags of the messages
+    /// matching the given id set.
+    fn update_flags(
+        &mut self,
+        mbox: &str,
+        ids: &IdSet<Uid>,
+        f: impl Fn(&mut Vec<Flag>),
+    ) -> Result<()> {
+        for msg in self.mbox(mbox)?.msgs.iter_mut() {
+            if ids.contains(Uid(msg.id)) {
+                f(&mut msg.flags);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, 'b> Backend<'b> for MemoryBackend<'a> {
+    type Id = Uid;
+
+    fn add_mbox(&mut self, mbox: &str) -> Result<()> {
+        info!(">> add in-memory mailbox");
+        debug!("mailbox: {:?}", mbox);
+
+        let name = self.account_config.get_mbox_alias(mbox)?;
+        if self.mboxes.contains_key(&name) {
+            return Err(anyhow!(
+                "cannot add in-memory mailbox {:?}: already exists",
+                name
+            ));
+        }
+        self.mboxes.insert(name, MemoryMboxStore::default());
+
+        info!("<< add in-memory mailbox");
+        Ok(())
+    }
+
+    fn get_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        info!(">> get in-memory mailboxes");
+
+        let mboxes = MemoryMboxes {
+            mboxes: self
+                .mboxes
+                .iter()
+                .map(|(name, mbox)| MemoryMbox {
+                    name: name.to_owned(),
+                    len: mbox.msgs.len(),
+                })
+                .collect(),
+        };
+        trace!("mailboxes: {:?}", mboxes);
+
+        info!("<< get in-memory mailboxes");
+        Ok(Box::new(mboxes))
+    }
+
+    fn del_mbox(&mut self, mbox: &str) -> Result<()> {
+        info!(">> delete in-memory mailbox");
+        debug!("mailbox: {:?}", mbox);
+
+        let name = self.account_config.get_mbox_alias(mbox)?;
+        self.mboxes
+            .remove(&name)
+            .ok_or_else(|| anyhow!("cannot find in-memory mailbox {:?}", name))?;
+
+        info!("<< delete in-memory mailbox");
+        Ok(())
+    }
+
+    fn rename_mbox(&mut self, mbox: &str, new_mbox: &str) -> Result<()> {
+        info!(">> rename in-memory mailbox");
+        debug!("mailbox: {:?}", mbox);
+        debug!("new mailbox: {:?}", new_mbox);
+
+        let name = self.account_config.get_mbox_alias(mbox)?;
+        let new_name = self.account_config.get_mbox_alias(new_mbox)?;
+        if self.mboxes.contains_key(&new_name) {
+            return Err(anyhow!(
+                "cannot rename in-memory mailbox {:?}: {:?} already exists",
+                name,
+                new_name
+            ));
+        }
+        let store = self
+            .mboxes
+            .remove(&name)
+            .ok_or_else(|| anyhow!("cannot find in-memory mailbox {:?}", name))?;
+        self.mboxes.insert(new_name, store);
+
+        info!("<< rename in-memory mailbox");
+        Ok(())
+    }
+
+    fn get_envelopes(
+        &mut self,
+        mbox: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        info!(">> get in-memory envelopes");
+        debug!("mailbox: {:?}", mbox);
+        debug!("page size: {:?}", page_size);
+        debug!("page: {:?}", page);
+
+        let msgs: Vec<&MemoryMsg> = self.mbox(mbox)?.msgs.iter().rev().collect();
+        let envelopes = paginate(msgs, page_size, page)?;
+        trace!("envelopes: {:?}", envelopes);
+
+        info!("<< get in-memory envelopes");
+        Ok(Box::new(envelopes))
+    }
+
+    fn search_envelopes(
+        &mut self,
+        mbox: &str,
+        query: &str,
+        sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        info!(">> search in-memory envelopes");
+        debug!("mailbox: {:?}", mbox);
+        debug!("query: {:?}", query);
+        debug!("sort: {:?}", sort.to_string());
+        debug!("page size: {:?}", page_size);
+        debug!("page: {:?}", page);
+
+        let query = SearchQuery::try_from(query)?;
+        let mut msgs = vec![];
+        for msg in self.mbox(mbox)?.msgs.iter().rev() {
+            let parsed_mail = mailparse::parse_mail(&msg.raw)
+                .with_context(|| format!("cannot parse in-memory message {}", msg.id))?;
+            if query.matches(&parsed_mail, &Flags(msg.flags.clone())) {
+                msgs.push((msg, parsed_mail));
+            }
+        }
+        msgs.sort_by(|(a, parsed_a), (b, parsed_b)| {
+            sort.iter().fold(Ordering::Equal, |ord, criterion| {
+                ord.then_with(|| {
+                    let ord = match criterion.kind {
+                        SortCriterionKind::Arrival => a.id.cmp(&b.id),
+                        SortCriterionKind::Cc => {
+                            header(parsed_a, "cc").cmp(&header(parsed_b, "cc"))
+                        }
+                        SortCriterionKind::Date => date(parsed_a).cmp(&date(parsed_b)),
+                        SortCriterionKind::From => {
+                            header(parsed_a, "from").cmp(&header(parsed_b, "from"))
+                        }
+                        SortCriterionKind::Size => a.raw.len().cmp(&b.raw.len()),
+                        SortCriterionKind::Subject => {
+                            header(parsed_a, "subject").cmp(&header(parsed_b, "subject"))
+                        }
+                        SortCriterionKind::To => {
+                            header(parsed_a, "to").cmp(&header(parsed_b, "to"))
+                        }
+                    };
+                    match criterion.order {
+                        SortCriterionOrder::Asc => ord,
+                        SortCriterionOrder::Desc => ord.reverse(),
+                    }
+                })
+            })
+        });
+        let msgs = msgs.into_iter().map(|(msg, _)| msg).collect();
+        let envelopes = paginate(msgs, page_size, page)?;
+        trace!("envelopes: {:?}", envelopes);
+
+        info!("<< search in-memory envelopes");
+        Ok(Box::new(envelopes))
+    }
+
+    fn add_msg(&mut self, mbox: &str, msg: &[u8], flags: &Flags) -> Result<Uid> {
+        info!(">> add in-memory message");
+        debug!("mailbox: {:?}", mbox);
+        debug!("flags: {:?}", flags.to_string());
+
+        let id = self.mbox(mbox)?.append(msg.to_vec(), flags.to_vec());
+        debug!("id: {:?}", id);
+
+        info!("<< add in-memory message");
+        Ok(Uid(id))
+    }
+
+    fn get_msg(&mut self, mbox: &str, id: &Uid) -> Result<Msg> {
+        info!(">> get in-memory message");
+        debug!("mailbox: {:?}", mbox);
+        debug!("id: {:?}", id);
+
+        let Uid(num) = *id;
+        let account_config = self.account_config;
+        let msg = self
+            .mbox(mbox)?
+            .msgs
+            .iter()
+            .find(|msg| msg.id == num)
+            .ok_or_else(|| anyhow!("cannot find in-memory message {}", num))?;
+        let parsed_mail = mailparse::parse_mail(&msg.raw)
+            .with_context(|| format!("cannot parse in-memory message {}", num))?;
+        let msg = Msg::from_parsed_mail(parsed_mail, account_config)
+            .with_context(|| format!("cannot parse in-memory message {}", num))?;
+        trace!("message: {:?}", msg);
+
+        info!("<< get in-memory message");
+        Ok(msg)
+    }
+
+    fn copy_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Uid>) -> Result<()> {
+        info!(">> copy in-memory messages");
+        debug!("source mailbox: {:?}", mbox_src);
+        debug!("destination mailbox: {:?}", mbox_dst);
+        debug!("ids: {:?}", ids.to_string());
+
+        let msgs = self.msgs(mbox_src, ids)?;
+        let mbox_dst = self.mbox(mbox_dst)?;
+        for msg in msgs {
+            mbox_dst.append(msg.raw, msg.flags);
+        }
+
+        info!("<< copy in-memory messages");
+        Ok(())
+    }
+
+    fn move_msg(&mut self, mbox_src: &str, mbox_dst: &str, ids: &IdSet<Uid>) -> Result<()> {
+        info!(">> move in-memory messages");
+        debug!("source mailbox: {:?}", mbox_src);
+        debug!("destination mailbox: {:?}", mbox_dst);
+        debug!("ids: {:?}", ids.to_string());
+
+        // Checks the destination first so that messages are not lost
+        // if it does not exist.
+        self.mbox(mbox_dst)?;
+        self.copy_msg(mbox_src, mbox_dst, ids)?;
+        self.del_msg(mbox_src, ids)?;
+
+        info!("<< move in-memory messages");
+        Ok(())
+    }
+
+    fn del_msg(&mut self, mbox: &str, ids: &IdSet<Uid>) -> Result<()> {
+        info!(">> delete in-memory messages");
+        debug!("mailbox: {:?}", mbox);
+        debug!("ids: {:?}", ids.to_string());
+
+        self.mbox(mbox)?
+            .msgs
+            .retain(|msg| !ids.contains(Uid(msg.id)));
+
+        info!("<< delete in-memory messages");
+        Ok(())
+    }
+
+    fn add_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        info!(">> add in-memory message flags");
+        debug!("mailbox: {:?}", mbox);
+        debug!("ids: {:?}", ids.to_string());
+        debug!("flags: {:?}", flags.to_string());
+
+        self.update_flags(mbox, ids, |msg_flags| {
+            for flag in flags.iter() {
+                if !msg_flags.contains(flag) {
+                    msg_flags.push(flag.to_owned());
+                }
+            }
+        })?;
+
+        info!("<< add in-memory message flags");
+        Ok(())
+    }
+
+    fn set_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        info!(">> set in-memory message flags");
+        debug!("mailbox: {:?}", mbox);
+        debug!("ids: {:?}", ids.to_string());
+        debug!("flags: {:?}", flags.to_string());
+
+        self.update_flags(mbox, ids, |msg_flags| *msg_flags = flags.to_vec())?;
+
+        info!("<< set in-memory message flags");
+        Ok(())
+    }
+
+    fn del_flags(&mut self, mbox: &str, ids: &IdSet<Uid>, flags: &Flags) -> Result<()> {
+        info!(">> delete in-memory message flags");
+        debug!("mailbox: {:?}", mbox);
+        debug!("ids: {:?}", ids.to_string());
+        debug!("flags: {:?}", flags.to_string());
+
+        self.update_flags(mbox, ids, |msg_flags| {
+            msg_flags.retain(|flag| !flags.contains(flag))
+        })?;
+
+        info!("<< delete in-memory message flags");
+        Ok(())
+    }
+}
+
+/// Builds the envelopes of the given page.
+fn paginate(msgs: Vec<&MemoryMsg>, page_size: usize, page: usize) -> Result<MemoryEnvelopes> {
+    let page_begin = page * page_size;
+    debug!("page begin: {:?}", page_begin);
+    if page_begin > msgs.len() {
+        return Err(anyhow!(
+            "cannot get in-memory envelopes at page {:?} (out of bounds)",
+            page_begin + 1,
+        ));
+    }
+    let page_end = msgs.len().min(page_begin + page_size);
+    debug!("page end: {:?}", page_end);
+
+    let mut envelopes = MemoryEnvelopes::default();
+    for msg in &msgs[page_begin..page_end] {
+        envelopes.push(msg.to_envelope()?);
+    }
+    Ok(envelopes)
+}
+
+/// Returns the lowercased value of the given header, or an empty
+/// string if the header does not exist.
+fn header(parsed_mail: &ParsedMail, key: &str) -> String {
+    parsed_mail
+        .headers
+        .get_first_value(key)
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Returns the parsed date of the message, if any.
+fn date(parsed_mail: &ParsedMail) -> Option<DateTime<FixedOffset>> {
+    let date = parsed_mail.headers.get_first_value("date")?;
+    DateTime::parse_from_rfc2822(date.split_at(date.find(" (").unwrap_or(date.len())).0).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::msg::Attachment;
+
+    use super::*;
+
+    fn raw_msg(from: &str, subject: &str, date: &str, body: &str) -> Vec<u8> {
+        format!(
+            "From: {}\r\nTo: bob@localhost\r\nSubject: {}\r\nDate: {}\r\n\r\n{}\r\n",
+            from, subject, date, body
+        )
+        .into_bytes()
+    }
+
+    fn ids(envelopes: Box<dyn Envelopes>) -> Vec<u32> {
+        envelopes
+            .as_any()
+            .downcast_ref::<MemoryEnvelopes>()
+            .unwrap()
+            .iter()
+            .map(|envelope| envelope.id)
+            .collect()
+    }
+
+    #[test]
+    fn it_should_manage_mboxes() {
+        let account_config = AccountConfig::default();
+        let mut backend = MemoryBackend::new(&account_config);
+
+        backend.add_mbox("Archives").unwrap();
+        assert!(backend.add_mbox("Archives").is_err());
+        backend.del_mbox("Drafts").unwrap();
+        assert!(backend.del_mbox("Drafts").is_err());
+
+        let mboxes = backend.get_mboxes().unwrap();
+        let mboxes = mboxes.as_any().downcast_ref::<MemoryMboxes>().unwrap();
+        let names: Vec<&str> = mboxes.iter().map(|mbox| mbox.name.as_str()).collect();
+        assert_eq!(vec!["Archives", "INBOX", "Sent"], names);
+    }
+
+    #[test]
+    fn it_should_manage_msgs() {
+        let account_config = AccountConfig::default();
+        let mut backend = MemoryBackend::new(&account_config);
+        let date = "Tue, 1 Mar 2022 10:00:00 +0100";
+
+        let id = backend
+            .add_msg(
+                "INBOX",
+                &raw_msg("alice@localhost", "A", date, "a"),
+                &Flags::default(),
+            )
+            .unwrap();
+        assert_eq!(Uid(1), id);
+        backend
+            .add_msg(
+                "INBOX",
+                &raw_msg("alice@localhost", "B", date, "b"),
+                &Flags::default(),
+            )
+            .unwrap();
+        backend
+            .add_msg(
+                "INBOX",
+                &raw_msg("alice@localhost", "C", date, "c"),
+                &Flags::default(),
+            )
+            .unwrap();
+        assert!(backend.add_msg("Unknown", b"", &Flags::default()).is_err());
+
+        assert_eq!(
+            vec![3, 2, 1],
+            ids(backend.get_envelopes("INBOX", 10, 0).unwrap())
+        );
+        assert_eq!(vec![1], ids(backend.get_envelopes("INBOX", 2, 1).unwrap()));
+        assert!(backend.get_envelopes("INBOX", 2, 2).is_err());
+
+        let ids_set = IdSet::try_from("1:2").unwrap();
+        backend.copy_msg("INBOX", "Sent", &ids_set).unwrap();
+        assert_eq!(
+            vec![2, 1],
+            ids(backend.get_envelopes("Sent", 10, 0).unwrap())
+        );
+
+        backend
+            .move_msg("INBOX", "Drafts", &IdSet::from(Uid(3)))
+            .unwrap();
+        assert_eq!(
+            vec![2, 1],
+            ids(backend.get_envelopes("INBOX", 10, 0).unwrap())
+        );
+        assert_eq!(
+            vec![1],
+            ids(backend.get_envelopes("Drafts", 10, 0).unwrap())
+        );
+        assert!(backend
+            .move_msg("INBOX", "Unknown", &IdSet::from(Uid(1)))
+            .is_err());
+
+        backend
+            .del_msg("INBOX", &IdSet::try_from("2:*").unwrap())
+            .unwrap();
+        assert_eq!(vec![1], ids(backend.get_envelopes("INBOX", 10, 0).unwrap()));
+
+        // Ids are never reused within a mailbox.
+        let id = backend
+            .add_msg(
+                "INBOX",
+                &raw_msg("alice@localhost", "D", date, "d"),
+                &Flags::default(),
+            )
+            .unwrap();
+        assert_eq!(Uid(4), id);
+
+        let msg = backend.get_msg("INBOX", &Uid(4)).unwrap();
+        assert_eq!("D", msg.subject);
+        assert!(backend.get_msg("INBOX", &Uid(2)).is_err());
+    }
+
+    #[test]
+    fn it_should_manage_flags() {
+        let account_config = AccountConfig::default();
+        let mut backend = MemoryBackend::new(&account_config);
+        let date = "Tue, 1 Mar 2022 10:00:00 +0100";
+        let flags = Flags::from(vec![Flag::Seen]);
+        backend
+            .add_msg("INBOX", &raw_msg("alice@localhost", "A", date, "a"), &flags)
+            .unwrap();
+        let ids_set = IdSet::from(Uid(1));
+        let envelope = |backend: &mut MemoryBackend| {
+            backend
+                .get_envelopes("INBOX", 10, 0)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<MemoryEnvelopes>()
+                .unwrap()[0]
+                .flags
+                .to_string()
+        };
+
+        backend
+            .add_flags("INBOX", &ids_set, &Flags::try_from("flagged seen").unwrap())
+            .unwrap();
+        assert_eq!("seen flagged", envelope(&mut backend));
+        backend
+            .del_flags("INBOX", &ids_set, &Flags::try_from("seen").unwrap())
+            .unwrap();
+        assert_eq!("flagged", envelope(&mut backend));
+        backend
+            .set_flags("INBOX", &ids_set, &Flags::try_from("answered").unwrap())
+            .unwrap();
+        assert_eq!("answered", envelope(&mut backend));
+    }
+
+    #[test]
+    fn it_should_search_and_sort_envelopes() {
+        let account_config = AccountConfig::default();
+        let mut backend = MemoryBackend::new(&account_config);
+        let msgs = [
+            (
+                "alice@localhost",
+                "Meeting",
+                "Tue, 1 Mar 2022 10:00:00 +0100",
+                "see you",
+            ),
+            (
+                "carol@localhost",
+                "Lunch",
+                "Wed, 2 Mar 2022 10:00:00 +0100",
+                "meeting room",
+            ),
+            (
+                "bob@localhost",
+                "Report",
+                "Mon, 28 Feb 2022 10:00:00 +0100",
+                "attached",
+            ),
+        ];
+        for (from, subject, date, body) in msgs {
+            backend
+                .add_msg(
+                    "INBOX",
+                    &raw_msg(from, subject, date, body),
+                    &Flags::default(),
+                )
+                .unwrap();
+        }
+        backend
+            .add_flags(
+                "INBOX",
+                &IdSet::from(Uid(2)),
+                &Flags::from(vec![Flag::Seen]),
+            )
+            .unwrap();
+        let mut search = |query: &str, sort: &str| {
+            let sort = SortCriteria::try_from(sort).unwrap();
+            ids(backend
+                .search_envelopes("INBOX", query, &sort, 10, 0)
+                .unwrap())
+        };
+
+        assert_eq!(vec![3, 2, 1], search("", ""));
+        assert_eq!(vec![2, 1], search("meeting", ""));
+        assert_eq!(vec![1], search("subject:MEETING", ""));
+        assert_eq!(vec![1], search("meeting -flag:seen", ""));
+        assert_eq!(vec![2], search("flag:seen", ""));
+        assert_eq!(vec![3], search("from:bob body:attached", ""));
+        assert_eq!(
+            vec![3, 1],
+            search("from:bob or (subject:meeting and not lunch)", "")
+        );
+        assert_eq!(vec![3, 1, 2], search("", "date"));
+        assert_eq!(vec![2, 1, 3], search("", "date:desc"));
+        assert_eq!(vec![1, 3, 2], search("", "from"));
+        assert_eq!(vec![2, 1, 3], search("", "subject"));
+        assert!(backend
+            .search_envelopes("INBOX", "flag:(seen)", &SortCriteria::default(), 10, 0)
+            .is_err());
+        assert!(backend
+            .search_envelopes("INBOX", "subject:", &SortCriteria::default(), 10, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn it_should_get_attachments() {
+        let account_config = AccountConfig::default();
+        let mut backend = MemoryBackend::new(&account_config);
+        let raw_msg = concat!(
+            "Subject: report\r\n",
+            "Content-Type: multipart/mixed; boundary=b\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "see attached\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "Content-Disposition: attachment; filename=report.txt\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "aGVsbG8=\r\n",
+            "--b--\r\n",
+        );
+        let id = backend
+            .add_msg("INBOX", raw_msg.as_bytes(), &Flags::default())
+            .unwrap();
+
+        let attachments = backend.get_attachments("INBOX", &id).unwrap();
+        assert_eq!(1, attachments.len());
+        assert_eq!(1, attachments[0].index);
+        assert_eq!("report.txt", attachments[0].filename);
+        assert_eq!(5, attachments[0].size);
+        assert_eq!(
+            b"hello".to_vec(),
+            backend
+                .get_attachment_content("INBOX", &id, &attachments[0])
+                .unwrap()
+        );
+
+        let unknown = Attachment {
+            index: 2,
+            ..Attachment::default()
+        };
+        assert!(backend
+            .get_attachment_content("INBOX", &id, &unknown)
+            .is_err());
+    }
+}
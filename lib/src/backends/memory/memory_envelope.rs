@@ -0,0 +1,133 @@
+//! In-memory envelope module.
+//!
+//! This module provides the in-memory envelope types and their
+//! parser.
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use log::trace;
+use mailparse::MailHeaderMap;
+use std::ops::{Deref, DerefMut};
+
+use crate::msg::{from_slice_to_addrs, Addr, Flags};
+
+/// Represents a list of envelopes.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct MemoryEnvelopes {
+    #[serde(rename = "response")]
+    pub envelopes: Vec<MemoryEnvelope>,
+}
+
+impl Deref for MemoryEnvelopes {
+    type Target = Vec<MemoryEnvelope>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.envelopes
+    }
+}
+
+impl DerefMut for MemoryEnvelopes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.envelopes
+    }
+}
+
+/// Represents the envelope. The envelope is just a message subset,
+/// and is mostly used for listings.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct MemoryEnvelope {
+    /// Represents the id of the message.
+    pub id: u32,
+
+    /// Represents the flags of the message.
+    pub flags: Flags,
+
+    /// Represents the subject of the message.
+    pub subject: String,
+
+    /// Represents the first sender of the message.
+    pub sender: String,
+
+    /// Represents the date of the message.
+    pub date: String,
+}
+
+impl MemoryEnvelope {
+    /// Builds an envelope from a raw message.
+    pub fn from_raw(id: u32, flags: Flags, raw_msg: &[u8]) -> Result<Self> {
+        trace!(">> build envelope from raw message");
+
+        let parsed_mail =
+            mailparse::parse_mail(raw_msg).context("cannot parse in-memory message")?;
+        let headers = parsed_mail.get_headers();
+
+        let subject = headers.get_first_value("subject").unwrap_or_default();
+        let sender = match headers.get_first_value("from") {
+            Some(from) => from_slice_to_addrs(&from)
+                .with_context(|| format!("cannot parse sender {:?}", from))?
+                .and_then(|senders| senders.first().cloned())
+                .map(|sender| match sender {
+                    Addr::Single(mailparse::SingleInfo { display_name, addr }) => {
+                        display_name.unwrap_or(addr)
+                    }
+                    Addr::Group(mailparse::GroupInfo { group_name, .. }) => group_name,
+                })
+                .unwrap_or_default(),
+            None => String::new(),
+        };
+        // Dates that cannot be parsed are kept as they are.
+        let date = headers
+            .get_first_value("date")
+            .map(|date| {
+                DateTime::parse_from_rfc2822(date.split_at(date.find(" (").unwrap_or(date.len())).0)
+                    .map(|date| date.naive_local().to_string())
+                    .unwrap_or(date)
+            })
+            .unwrap_or_default();
+
+        let envelope = Self {
+            id,
+            flags,
+            subject,
+            sender,
+            date,
+        };
+
+        trace!("envelope: {:?}", envelope);
+        trace!("<< build envelope from raw message");
+        Ok(envelope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::Flag;
+
+    #[test]
+    fn it_should_build_envelope_from_raw() {
+        let raw_msg = concat!(
+            "From: Alice <alice@localhost>\r\n",
+            "Subject: Hello\r\n",
+            "Date: Tue, 1 Mar 2022 10:00:00 +0100 (CET)\r\n",
+            "\r\n",
+            "Hello, world!\r\n",
+        );
+        let envelope =
+            MemoryEnvelope::from_raw(1, Flags::from(vec![Flag::Seen]), raw_msg.as_bytes()).unwrap();
+        assert_eq!(
+            MemoryEnvelope {
+                id: 1,
+                flags: Flags::from(vec![Flag::Seen]),
+                subject: "Hello".into(),
+                sender: "Alice".into(),
+                date: "2022-03-01 10:00:00".into(),
+            },
+            envelope
+        );
+
+        let envelope = MemoryEnvelope::from_raw(2, Flags::default(), b"\r\nempty").unwrap();
+        assert_eq!("", envelope.subject);
+        assert_eq!("", envelope.sender);
+    }
+}
@@ -0,0 +1,39 @@
+//! In-memory mailbox module.
+//!
+//! This module provides the in-memory mailbox types.
+
+use std::{
+    fmt::{self, Display},
+    ops::Deref,
+};
+
+/// Represents a list of in-memory mailboxes.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct MemoryMboxes {
+    #[serde(rename = "response")]
+    pub mboxes: Vec<MemoryMbox>,
+}
+
+impl Deref for MemoryMboxes {
+    type Target = Vec<MemoryMbox>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mboxes
+    }
+}
+
+/// Represents the mailbox.
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct MemoryMbox {
+    /// Represents the mailbox name.
+    pub name: String,
+
+    /// Represents the number of messages in the mailbox.
+    pub len: usize,
+}
+
+impl Display for MemoryMbox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
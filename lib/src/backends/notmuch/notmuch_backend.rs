@@ -0,0 +1,633 @@
+use std::{
+    collections::BTreeMap,
+    convert::{TryFrom, TryInto},
+    fs,
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, trace};
+
+use crate::{
+    backends::{Backend, IdMapper, MaildirBackend, NotmuchEnvelopes, NotmuchMbox, NotmuchMboxes},
+    config::{AccountConfig, MaildirBackendConfig, NotmuchBackendConfig},
+    mbox::{MboxCounts, Mboxes},
+    msg::{Envelopes, Flag, Flags, Hash, IdSet, Msg, SearchQuery, SortCriteria},
+};
+
+/// Represents the name of the file persisting the virtual mailboxes
+/// created with the backend, stored at the root of the database.
+const VIRT_MBOXES_FILE_NAME: &str = ".himalaya-mailboxes.toml";
+
+/// Represents the Notmuch backend.
+pub struct NotmuchBackend<'a> {
+    account_config: &'a AccountConfig,
+    notmuch_config: &'a NotmuchBackendConfig,
+    pub mdir: MaildirBackend<'a>,
+    db: notmuch::Database,
+}
+
+impl<'a> NotmuchBackend<'a> {
+    pub fn new(
+        account_config: &'a AccountConfig,
+        notmuch_config: &'a NotmuchBackendConfig,
+    ) -> Result<NotmuchBackend<'a>> {
+        info!(">> create new notmuch backend");
+
+        // The Notmuch backend relies on a Maildir backend living at
+        // the root of the database to manipulate message files.
+        let mdir_config = MaildirBackendConfig {
+            maildir_dir: notmuch_config.notmuch_database_dir.clone(),
+        };
+
+        let backend = Self {
+            account_config,
+            notmuch_config,
+            mdir: MaildirBackend::new(account_config, &mdir_config),
+            db: notmuch::Database::open(
+                notmuch_config.notmuch_database_dir.clone(),
+                notmuch::DatabaseMode::ReadWrite,
+            )
+            .with_context(|| {
+                format!(
+                    "cannot open notmuch database at {:?}",
+                    notmuch_config.notmuch_database_dir
+                )
+            })?,
+        };
+
+        info!("<< create new notmuch backend");
+        Ok(backend)
+    }
+
+    fn _search_envelopes(
+        &mut self,
+        query: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        // Gets envelopes matching the given Notmuch query.
+        let query_builder = self
+            .db
+            .create_query(query)
+            .with_context(|| format!("cannot create notmuch query from {:?}", query))?;
+        let mut envelopes: NotmuchEnvelopes = query_builder
+            .search_messages()
+            .with_context(|| format!("cannot find notmuch envelopes from query {:?}", query))?
+            .try_into()
+            .with_context(|| format!("cannot parse notmuch envelopes from query {:?}", query))?;
+        debug!("envelopes len: {:?}", envelopes.len());
+        trace!("envelopes: {:?}", envelopes);
+
+        // Calculates pagination boundaries.
+        let page_begin = page * page_size;
+        debug!("page begin: {:?}", page_begin);
+        if page_begin > envelopes.len() {
+            return Err(anyhow!(
+                "cannot get notmuch envelopes at page {:?} (out of bounds)",
+                page_begin + 1,
+            ));
+        }
+        let page_end = envelopes.len().min(page_begin + page_size);
+        debug!("page end: {:?}", page_end);
+
+        // Sorts envelopes by most recent date.
+        envelopes.sort_by(|a, b| b.date.partial_cmp(&a.date).unwrap());
+
+        // Applies pagination boundaries.
+        envelopes.envelopes = envelopes[page_begin..page_end].to_owned();
+
+        // Appends envelopes hash to the id mapper cache file and
+        // calculates the new short hash length. The short hash length
+        // represents the minimum hash length possible to avoid
+        // conflicts.
+        let short_hash_len = {
+            let mut mapper = IdMapper::new(&self.notmuch_config.notmuch_database_dir)?;
+            let entries = envelopes
+                .iter()
+                .map(|env| (env.hash.to_owned(), env.id.to_owned()))
+                .collect();
+            mapper.append(entries)?
+        };
+        debug!("short hash length: {:?}", short_hash_len);
+
+        // Shorten envelopes hash.
+        envelopes
+            .iter_mut()
+            .for_each(|env| env.hash = env.hash[0..short_hash_len].to_owned());
+
+        Ok(Box::new(envelopes))
+    }
+
+    /// Gets the path of the file persisting the virtual mailboxes.
+    fn virt_mboxes_path(&self) -> PathBuf {
+        self.notmuch_config
+            .notmuch_database_dir
+            .join(VIRT_MBOXES_FILE_NAME)
+    }
+
+    /// Reads the virtual mailboxes persisted in the database
+    /// directory, by name.
+    fn read_virt_mboxes(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.virt_mboxes_path();
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("cannot read notmuch virtual mailboxes at {:?}", path))?;
+        toml::from_str(&content)
+            .with_context(|| format!("cannot parse notmuch virtual mailboxes at {:?}", path))
+    }
+
+    /// Writes the given virtual mailboxes to the database directory.
+    fn write_virt_mboxes(&self, mboxes: &BTreeMap<String, String>) -> Result<()> {
+        let path = self.virt_mboxes_path();
+        let content =
+            toml::to_string(mboxes).context("cannot serialize notmuch virtual mailboxes")?;
+        fs::write(&path, content)
+            .with_context(|| format!("cannot write notmuch virtual mailboxes at {:?}", path))
+    }
+
+    /// Gets the query of the given virtual mailbox, from the config
+    /// first, then from the persisted virtual mailboxes.
+    fn get_virt_mbox_query(&self, virt_mbox: &str) -> Result<Option<String>> {
+        if let Some(query) = self.account_config.mailboxes.get(virt_mbox) {
+            return Ok(Some(query.to_owned()));
+        }
+        Ok(self.read_virt_mboxes()?.remove(virt_mbox))
+    }
+
+    /// Checks that the given virtual mailbox is persisted, so that it
+    /// can be modified. Virtual mailboxes of the config are read-only.
+    fn check_virt_mbox_persisted(
+        &self,
+        virt_mbox: &str,
+        mboxes: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        if self.account_config.mailboxes.contains_key(virt_mbox) {
+            return Err(anyhow!(
+                "cannot modify notmuch virtual mailbox {:?}: it is defined in the config",
+                virt_mbox
+            ));
+        }
+        if !mboxes.contains_key(virt_mbox) {
+            return Err(anyhow!(
+                "cannot find notmuch virtual mailbox {:?}",
+                virt_mbox
+            ));
+        }
+        Ok(())
+    }
+
+    /// Counts the messages matching the given query, and the ones
+    /// among them still tagged as unread.
+    fn count_msgs(&self, query: &str) -> Result<MboxCounts> {
+        let count = |query: &str| {
+            self.db
+                .create_query(query)
+                .with_context(|| format!("cannot create notmuch query from {:?}", query))?
+                .count_messages()
+                .with_context(|| format!("cannot count notmuch messages from query {:?}", query))
+        };
+        Ok(MboxCounts {
+            messages: count(query)? as usize,
+            unseen: count(&format!("({}) and tag:unread", query))? as usize,
+            ..MboxCounts::default()
+        })
+    }
+
+    /// Persists a virtual mailbox matching the given query.
+    pub fn add_virt_mbox(&mut self, virt_mbox: &str, query: &str) -> Result<()> {
+        let mut mboxes = self.read_virt_mboxes()?;
+        if self.account_config.mailboxes.contains_key(virt_mbox) || mboxes.contains_key(virt_mbox) {
+            return Err(anyhow!(
+                "cannot add notmuch virtual mailbox {:?}: already exists",
+                virt_mbox
+            ));
+        }
+        mboxes.insert(virt_mbox.to_owned(), query.to_owned());
+        self.write_virt_mboxes(&mboxes)
+    }
+
+    /// Finds the notmuch message identifiers matching the given short
+    /// hashes, using the id mapper cache file of the database.
+    fn find_ids(&self, short_hashes: &IdSet<Hash>) -> Result<Vec<String>> {
+        let dir = &self.notmuch_config.notmuch_database_dir;
+        let mapper = IdMapper::new(dir)
+            .with_context(|| format!("cannot create id mapper instance for {:?}", dir))?;
+        short_hashes
+            .to_ids()?
+            .iter()
+            .map(|short_hash| {
+                mapper.find(&short_hash.to_string()).with_context(|| {
+                    format!(
+                        "cannot find notmuch message from short hash {:?}",
+                        short_hash.to_string()
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'a, 'b> Backend<'b> for NotmuchBackend<'a> {
+    type Id = Hash;
+
+    fn add_mbox(&mut self, virt_mbox: &str) -> Result<()> {
+        info!(">> add notmuch virtual mailbox");
+        debug!("virtual mailbox: {:?}", virt_mbox);
+
+        // Virtual mailboxes created without query match the messages
+        // tagged with their name.
+        let query = format!("tag:\"{}\"", virt_mbox.replace('"', "\"\""));
+        debug!("query: {:?}", query);
+        self.add_virt_mbox(virt_mbox, &query)?;
+
+        info!("<< add notmuch virtual mailbox");
+        Ok(())
+    }
+
+    fn get_mboxes(&mut self) -> Result<Box<dyn Mboxes>> {
+        info!(">> get notmuch virtual mailboxes");
+
+        let mut virt_mboxes = self.read_virt_mboxes()?;
+        virt_mboxes.extend(self.account_config.mailboxes.clone());
+        let mut mboxes = vec![];
+        for (name, query) in virt_mboxes.iter() {
+            let mut mbox = NotmuchMbox::new(name, query);
+            mbox.counts = Some(self.count_msgs(query)?);
+            mboxes.push(mbox);
+        }
+        trace!("virtual mailboxes: {:?}", mboxes);
+        mboxes.sort_by(|a, b| b.name.partial_cmp(&a.name).unwrap());
+
+        info!("<< get notmuch virtual mailboxes");
+        Ok(Box::new(NotmuchMboxes { mboxes }))
+    }
+
+    fn del_mbox(&mut self, virt_mbox: &str) -> Result<()> {
+        info!(">> delete notmuch virtual mailbox");
+        debug!("virtual mailbox: {:?}", virt_mbox);
+
+        let mut mboxes = self.read_virt_mboxes()?;
+        self.check_virt_mbox_persisted(virt_mbox, &mboxes)?;
+        mboxes.remove(virt_mbox);
+        self.write_virt_mboxes(&mboxes)?;
+
+        info!("<< delete notmuch virtual mailbox");
+        Ok(())
+    }
+
+    fn rename_mbox(&mut self, virt_mbox: &str, new_virt_mbox: &str) -> Result<()> {
+        info!(">> rename notmuch virtual mailbox");
+        debug!("virtual mailbox: {:?}", virt_mbox);
+        debug!("new virtual mailbox: {:?}", new_virt_mbox);
+
+        let mut mboxes = self.read_virt_mboxes()?;
+        self.check_virt_mbox_persisted(virt_mbox, &mboxes)?;
+        if self.account_config.mailboxes.contains_key(new_virt_mbox)
+            || mboxes.contains_key(new_virt_mbox)
+        {
+            return Err(anyhow!(
+                "cannot rename notmuch virtual mailbox {:?}: {:?} already exists",
+                virt_mbox,
+                new_virt_mbox
+            ));
+        }
+        if let Some(query) = mboxes.remove(virt_mbox) {
+            mboxes.insert(new_virt_mbox.to_owned(), query);
+        }
+        self.write_virt_mboxes(&mboxes)?;
+
+        info!("<< rename notmuch virtual mailbox");
+        Ok(())
+    }
+
+    fn get_envelopes(
+        &mut self,
+        virt_mbox: &str,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        info!(">> get notmuch envelopes");
+        debug!("virtual mailbox: {:?}", virt_mbox);
+        debug!("page size: {:?}", page_size);
+        debug!("page: {:?}", page);
+
+        let query = self
+            .get_virt_mbox_query(virt_mbox)?
+            .unwrap_or_else(|| String::from("all"));
+        debug!("query: {:?}", query);
+        let envelopes = self._search_envelopes(&query, page_size, page)?;
+
+        info!("<< get notmuch envelopes");
+        Ok(envelopes)
+    }
+
+    fn search_envelopes(
+        &mut self,
+        virt_mbox: &str,
+        query: &str,
+        _sort: &SortCriteria,
+        page_size: usize,
+        page: usize,
+    ) -> Result<Box<dyn Envelopes>> {
+        info!(">> search notmuch envelopes");
+        debug!("virtual mailbox: {:?}", virt_mbox);
+        debug!("query: {:?}", query);
+        debug!("page size: {:?}", page_size);
+        debug!("page: {:?}", page);
+
+        let query = if query.trim().is_empty() {
+            self.get_virt_mbox_query(virt_mbox)?
+                .unwrap_or_else(|| String::from("all"))
+        } else {
+            SearchQuery::try_from(query)?.to_notmuch_query()?
+        };
+        debug!("final query: {:?}", query);
+        let envelopes = self._search_envelopes(&query, page_size, page)?;
+
+        info!("<< search notmuch envelopes");
+        Ok(envelopes)
+    }
+
+    fn add_msg(&mut self, _: &str, msg: &[u8], tags: &Flags) -> Result<Hash> {
+        info!(">> add notmuch envelopes");
+        debug!("tags: {:?}", tags);
+
+        let dir = &self.notmuch_config.notmuch_database_dir;
+
+        // Adds the message to the maildir folder and gets its hash.
+        let hash = self
+            .mdir
+            .add_msg("", msg, &Flags::from(vec![Flag::Seen]))
+            .with_context(|| {
+                format!(
+                    "cannot add notmuch message to maildir {:?}",
+                    self.notmuch_config.notmuch_database_dir
+                )
+            })?
+            .to_string();
+        debug!("hash: {:?}", hash);
+
+        // Retrieves the file path of the added message by its maildir
+        // identifier.
+        let mut mapper = IdMapper::new(dir)
+            .with_context(|| format!("cannot create id mapper instance for {:?}", dir))?;
+        let id = mapper
+            .find(&hash)
+            .with_context(|| format!("cannot find notmuch message from short hash {:?}", hash))?;
+        debug!("id: {:?}", id);
+        let file_path = dir.join("cur").join(format!("{}:2,S", id));
+        debug!("file path: {:?}", file_path);
+
+        // Adds the message to the notmuch database by indexing it.
+        let id = self
+            .db
+            .index_file(&file_path, None)
+            .with_context(|| format!("cannot index notmuch message from file {:?}", file_path))?
+            .id()
+            .to_string();
+        let hash = format!("{:x}", md5::compute(&id));
+
+        // Appends hash entry to the id mapper cache file.
+        mapper
+            .append(vec![(hash.clone(), id.clone())])
+            .with_context(|| {
+                format!(
+                    "cannot append hash {:?} with id {:?} to id mapper",
+                    hash, id
+                )
+            })?;
+
+        // Sets the tags of the notmuch message, so that it is tagged
+        // as unread unless it is seen.
+        let hash = Hash(hash);
+        self.set_flags("", &hash.clone().into(), tags)
+            .with_context(|| format!("cannot set flags of notmuch message {:?}", id))?;
+
+        info!("<< add notmuch envelopes");
+        Ok(hash)
+    }
+
+    fn get_msg(&mut self, _: &str, short_hash: &Hash) -> Result<Msg> {
+        info!(">> add notmuch envelopes");
+        debug!("short hash: {:?}", short_hash);
+
+        let dir = &self.notmuch_config.notmuch_database_dir;
+        let id = IdMapper::new(dir)
+            .with_context(|| format!("cannot create id mapper instance for {:?}", dir))?
+            .find(&short_hash.to_string())
+            .with_context(|| {
+                format!(
+                    "cannot find notmuch message from short hash {:?}",
+                    short_hash.to_string()
+                )
+            })?;
+        debug!("id: {:?}", id);
+        let msg_file_path = self
+            .db
+            .find_message(&id)
+            .with_context(|| format!("cannot find notmuch message {:?}", id))?
+            .ok_or_else(|| anyhow!("cannot find notmuch message {:?}", id))?
+            .filename()
+            .to_owned();
+        debug!("message file path: {:?}", msg_file_path);
+        let raw_msg = fs::read(&msg_file_path).with_context(|| {
+            format!("cannot read notmuch message from file {:?}", msg_file_path)
+        })?;
+        let msg = mailparse::parse_mail(&raw_msg)
+            .with_context(|| format!("cannot parse raw notmuch message {:?}", id))?;
+        let msg = Msg::from_parsed_mail(msg, &self.account_config)
+            .with_context(|| format!("cannot parse notmuch message {:?}", id))?;
+        trace!("message: {:?}", msg);
+
+        info!("<< get notmuch message");
+        Ok(msg)
+    }
+
+    fn copy_msg(
+        &mut self,
+        _dir_src: &str,
+        _dir_dst: &str,
+        _short_hashes: &IdSet<Hash>,
+    ) -> Result<()> {
+        info!(">> copy notmuch message");
+        info!("<< copy notmuch message");
+        Err(anyhow!(
+            "cannot copy notmuch message: feature not implemented"
+        ))
+    }
+
+    fn move_msg(
+        &mut self,
+        _dir_src: &str,
+        _dir_dst: &str,
+        _short_hashes: &IdSet<Hash>,
+    ) -> Result<()> {
+        info!(">> move notmuch message");
+        info!("<< move notmuch message");
+        Err(anyhow!(
+            "cannot move notmuch message: feature not implemented"
+        ))
+    }
+
+    fn del_msg(&mut self, _virt_mbox: &str, short_hashes: &IdSet<Hash>) -> Result<()> {
+        info!(">> delete notmuch messages");
+        debug!("short hashes: {:?}", short_hashes.to_string());
+
+        let ids = self.find_ids(short_hashes)?;
+        debug!("ids: {:?}", ids);
+        for id in ids {
+            let msg_file_path = self
+                .db
+                .find_message(&id)
+                .with_context(|| format!("cannot find notmuch message {:?}", id))?
+                .ok_or_else(|| anyhow!("cannot find notmuch message {:?}", id))?
+                .filename()
+                .to_owned();
+            debug!("message file path: {:?}", msg_file_path);
+            self.db
+                .remove_message(msg_file_path)
+                .with_context(|| format!("cannot delete notmuch message {:?}", id))?;
+        }
+
+        info!("<< delete notmuch messages");
+        Ok(())
+    }
+
+    fn add_flags(
+        &mut self,
+        _virt_mbox: &str,
+        short_hashes: &IdSet<Hash>,
+        tags: &Flags,
+    ) -> Result<()> {
+        info!(">> add notmuch message flags");
+        debug!("tags: {:?}", tags);
+
+        let ids = self.find_ids(short_hashes)?;
+        debug!("ids: {:?}", ids);
+        for id in ids {
+            let query = format!("id:{}", id);
+            debug!("query: {:?}", query);
+            let query_builder = self
+                .db
+                .create_query(&query)
+                .with_context(|| format!("cannot create notmuch query from {:?}", query))?;
+            let msgs = query_builder
+                .search_messages()
+                .with_context(|| format!("cannot find notmuch envelopes from query {:?}", query))?;
+            for msg in msgs {
+                for tag in tags.iter() {
+                    let res = match notmuch_tag(tag) {
+                        (tag, false) => msg.add_tag(&tag),
+                        (tag, true) => msg.remove_tag(&tag),
+                    };
+                    res.with_context(|| {
+                        format!("cannot add tag {:?} to notmuch message {:?}", tag, msg.id())
+                    })?
+                }
+            }
+        }
+
+        info!("<< add notmuch message flags");
+        Ok(())
+    }
+
+    fn set_flags(
+        &mut self,
+        _virt_mbox: &str,
+        short_hashes: &IdSet<Hash>,
+        tags: &Flags,
+    ) -> Result<()> {
+        info!(">> set notmuch message flags");
+        debug!("tags: {:?}", tags);
+
+        let ids = self.find_ids(short_hashes)?;
+        debug!("ids: {:?}", ids);
+        for id in ids {
+            let query = format!("id:{}", id);
+            debug!("query: {:?}", query);
+            let query_builder = self
+                .db
+                .create_query(&query)
+                .with_context(|| format!("cannot create notmuch query from {:?}", query))?;
+            let msgs = query_builder
+                .search_messages()
+                .with_context(|| format!("cannot find notmuch envelopes from query {:?}", query))?;
+            for msg in msgs {
+                msg.remove_all_tags().with_context(|| {
+                    format!("cannot remove all tags from notmuch message {:?}", msg.id())
+                })?;
+                if !tags.contains(&Flag::Seen) {
+                    msg.add_tag("unread").with_context(|| {
+                        format!(
+                            "cannot add tag \"unread\" to notmuch message {:?}",
+                            msg.id()
+                        )
+                    })?
+                }
+                for tag in tags.iter().filter(|tag| **tag != Flag::Seen) {
+                    msg.add_tag(&notmuch_tag(tag).0).with_context(|| {
+                        format!("cannot add tag {:?} to notmuch message {:?}", tag, msg.id())
+                    })?
+                }
+            }
+        }
+
+        info!("<< set notmuch message flags");
+        Ok(())
+    }
+
+    fn del_flags(
+        &mut self,
+        _virt_mbox: &str,
+        short_hashes: &IdSet<Hash>,
+        tags: &Flags,
+    ) -> Result<()> {
+        info!(">> delete notmuch message flags");
+        debug!("tags: {:?}", tags);
+
+        let ids = self.find_ids(short_hashes)?;
+        debug!("ids: {:?}", ids);
+        for id in ids {
+            let query = format!("id:{}", id);
+            debug!("query: {:?}", query);
+            let query_builder = self
+                .db
+                .create_query(&query)
+                .with_context(|| format!("cannot create notmuch query from {:?}", query))?;
+            let msgs = query_builder
+                .search_messages()
+                .with_context(|| format!("cannot find notmuch envelopes from query {:?}", query))?;
+            for msg in msgs {
+                for tag in tags.iter() {
+                    let res = match notmuch_tag(tag) {
+                        (tag, false) => msg.remove_tag(&tag),
+                        (tag, true) => msg.add_tag(&tag),
+                    };
+                    res.with_context(|| {
+                        format!(
+                            "cannot delete tag {:?} from notmuch message {:?}",
+                            tag,
+                            msg.id()
+                        )
+                    })?
+                }
+            }
+        }
+
+        info!("<< delete notmuch message flags");
+        Ok(())
+    }
+}
+
+/// Converts the given flag to its notmuch tag. Notmuch tags unread
+/// messages rather than seen ones, so the seen flag is converted to
+/// the `unread` tag, which is inverted.
+fn notmuch_tag(flag: &Flag) -> (String, bool) {
+    match flag {
+        Flag::Seen => (String::from("unread"), true),
+        flag => (flag.to_string(), false),
+    }
+}
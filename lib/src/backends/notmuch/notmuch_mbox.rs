@@ -0,0 +1,56 @@
+//! Notmuch mailbox module.
+//!
+//! This module provides Notmuch types and conversion utilities
+//! related to the mailbox
+
+use std::{
+    fmt::{self, Display},
+    ops::Deref,
+};
+
+use crate::mbox::MboxCounts;
+
+/// Represents a list of Notmuch mailboxes.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct NotmuchMboxes {
+    #[serde(rename = "response")]
+    pub mboxes: Vec<NotmuchMbox>,
+}
+
+impl Deref for NotmuchMboxes {
+    type Target = Vec<NotmuchMbox>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mboxes
+    }
+}
+
+/// Represents the notmuch virtual mailbox.
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct NotmuchMbox {
+    /// Represents the virtual mailbox name.
+    pub name: String,
+
+    /// Represents the query associated to the virtual mailbox name.
+    pub query: String,
+
+    /// Represents the message counts of the virtual mailbox.
+    #[serde(flatten)]
+    pub counts: Option<MboxCounts>,
+}
+
+impl NotmuchMbox {
+    pub fn new(name: &str, query: &str) -> Self {
+        Self {
+            name: name.into(),
+            query: query.into(),
+            counts: None,
+        }
+    }
+}
+
+impl Display for NotmuchMbox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
@@ -1,10 +1,10 @@
 use anyhow::{anyhow, Context, Result};
-use lettre::transport::smtp::authentication::Credentials as SmtpCredentials;
+use lettre::transport::smtp::authentication::{Credentials as SmtpCredentials, Mechanism};
 use log::{debug, info, trace};
 use mailparse::MailAddr;
 use std::{collections::HashMap, env, ffi::OsStr, fs, path::PathBuf};
 
-use crate::{config::*, output::run_cmd};
+use crate::{backends::Backend, config::*, mbox::SpecialUse, process::run_cmd};
 
 /// Represents the user account.
 #[derive(Debug, Default, Clone)]
@@ -46,14 +46,14 @@ pub struct AccountConfig {
     pub smtp_host: String,
     /// Represents the SMTP port.
     pub smtp_port: u16,
-    /// Enables StartTLS.
-    pub smtp_starttls: bool,
-    /// Trusts any certificate.
-    pub smtp_insecure: bool,
+    /// Represents the SMTP TLS config.
+    pub smtp_tls: TlsConfig,
     /// Represents the SMTP login.
     pub smtp_login: String,
     /// Represents the SMTP password command.
     pub smtp_passwd_cmd: String,
+    /// Represents the SMTP OAuth 2.0 config.
+    pub smtp_oauth2: Option<OAuth2Config>,
 
     /// Represents the command used to encrypt a message.
     pub pgp_encrypt_cmd: Option<String>,
@@ -85,6 +85,9 @@ impl<'a> AccountConfig {
                     DeserializedAccountConfig::Notmuch(account) => {
                         account.default.unwrap_or_default()
                     }
+                    DeserializedAccountConfig::Custom(account) => {
+                        account.default.unwrap_or_default()
+                    }
                 })
                 .map(|(name, account)| (name.to_owned(), account))
                 .ok_or_else(|| anyhow!("cannot find default account")),
@@ -96,6 +99,16 @@ impl<'a> AccountConfig {
         }?;
 
         let base_account = account.to_base();
+
+        // Lettre does not expose the certificate of the connection it
+        // authenticates on, so a pinned SMTP certificate could not be
+        // checked. Refuse it rather than silently trusting any chain.
+        if base_account.smtp_tls_fingerprint.is_some() {
+            return Err(anyhow!(
+                "cannot pin SMTP certificate of account {:?}: feature not implemented, remove smtp-tls-fingerprint",
+                name
+            ));
+        }
         let downloads_dir = base_account
             .downloads_dir
             .as_ref()
@@ -172,10 +185,17 @@ impl<'a> AccountConfig {
 
             smtp_host: base_account.smtp_host.to_owned(),
             smtp_port: base_account.smtp_port,
-            smtp_starttls: base_account.smtp_starttls.unwrap_or_default(),
-            smtp_insecure: base_account.smtp_insecure.unwrap_or_default(),
+            smtp_tls: TlsConfig {
+                mode: TlsMode::from_opts(base_account.smtp_tls, base_account.smtp_starttls),
+                insecure: base_account.smtp_insecure.unwrap_or_default(),
+                ca_file: expand_path(base_account.smtp_tls_ca_file.as_ref())?,
+                cert_file: expand_path(base_account.smtp_tls_cert_file.as_ref())?,
+                key_file: expand_path(base_account.smtp_tls_key_file.as_ref())?,
+                fingerprint: None,
+            },
             smtp_login: base_account.smtp_login.to_owned(),
             smtp_passwd_cmd: base_account.smtp_passwd_cmd.to_owned(),
+            smtp_oauth2: base_account.smtp_oauth2.to_owned(),
 
             pgp_encrypt_cmd: base_account.pgp_encrypt_cmd.to_owned(),
             pgp_decrypt_cmd: base_account.pgp_decrypt_cmd.to_owned(),
@@ -187,10 +207,17 @@ impl<'a> AccountConfig {
             DeserializedAccountConfig::Imap(config) => BackendConfig::Imap(ImapBackendConfig {
                 imap_host: config.imap_host.clone(),
                 imap_port: config.imap_port.clone(),
-                imap_starttls: config.imap_starttls.unwrap_or_default(),
-                imap_insecure: config.imap_insecure.unwrap_or_default(),
+                imap_tls: TlsConfig {
+                    mode: TlsMode::from_opts(config.imap_tls, config.imap_starttls),
+                    insecure: config.imap_insecure.unwrap_or_default(),
+                    ca_file: expand_path(config.imap_tls_ca_file.as_ref())?,
+                    cert_file: expand_path(config.imap_tls_cert_file.as_ref())?,
+                    key_file: expand_path(config.imap_tls_key_file.as_ref())?,
+                    fingerprint: config.imap_tls_fingerprint.clone(),
+                },
                 imap_login: config.imap_login.clone(),
                 imap_passwd_cmd: config.imap_passwd_cmd.clone(),
+                imap_oauth2: config.imap_oauth2.clone(),
             }),
             #[cfg(feature = "maildir-backend")]
             DeserializedAccountConfig::Maildir(config) => {
@@ -206,6 +233,15 @@ impl<'a> AccountConfig {
                         .into(),
                 })
             }
+            DeserializedAccountConfig::Custom(config) => {
+                BackendConfig::Custom(CustomBackendConfig {
+                    backend: config.backend.clone(),
+                    backend_config: config
+                        .backend_config
+                        .clone()
+                        .unwrap_or_else(|| toml::Value::Table(Default::default())),
+                })
+            }
         };
         trace!("backend config: {:?}", backend_config);
 
@@ -235,8 +271,21 @@ impl<'a> AccountConfig {
             .clone())
     }
 
-    /// Builds the user account SMTP credentials.
+    /// Builds the user account SMTP credentials. With OAuth 2.0, the
+    /// password is the access token.
     pub fn smtp_creds(&self) -> Result<SmtpCredentials> {
+        if let Some(ref oauth2_config) = self.smtp_oauth2 {
+            let key =
+                OAuth2TokenCache::key("smtp", &self.smtp_login, &self.smtp_host, self.smtp_port);
+            let access_token = oauth2_config
+                .access_token(&key, &self.smtp_tls)
+                .context("cannot get SMTP OAuth2 access token")?;
+            return Ok(SmtpCredentials::new(
+                self.smtp_login.to_owned(),
+                access_token,
+            ));
+        }
+
         let passwd = run_cmd(&self.smtp_passwd_cmd).context("cannot run SMTP passwd cmd")?;
         let passwd = passwd
             .trim_end_matches(|c| c == '\r' || c == '\n')
@@ -245,6 +294,18 @@ impl<'a> AccountConfig {
         Ok(SmtpCredentials::new(self.smtp_login.to_owned(), passwd))
     }
 
+    /// Builds the user account SMTP authentication mechanisms.
+    pub fn smtp_mechanisms(&self) -> Result<Vec<Mechanism>> {
+        match self.smtp_oauth2.as_ref().map(|config| config.method) {
+            None => Ok(vec![Mechanism::Plain, Mechanism::Login]),
+            Some(OAuth2Method::XOAuth2) => Ok(vec![Mechanism::Xoauth2]),
+            Some(method) => Err(anyhow!(
+                "cannot authenticate to SMTP server using {}: only XOAUTH2 is supported",
+                method
+            )),
+        }
+    }
+
     /// Encrypts a file.
     pub fn pgp_encrypt_file(&self, addr: &str, path: PathBuf) -> Result<Option<String>> {
         if let Some(cmd) = self.pgp_encrypt_cmd.as_ref() {
@@ -338,6 +399,43 @@ impl<'a> AccountConfig {
             .map(String::from)
             .with_context(|| format!("cannot expand mailbox path {:?}", mbox))
     }
+
+    /// Gets the mailbox of the given special use: the one configured
+    /// in the mailbox aliases first, then the one flagged by the
+    /// backend, then the default one.
+    pub fn get_special_mbox<'b, B: Backend<'b> + ?Sized>(
+        &self,
+        backend: &mut B,
+        special_use: SpecialUse,
+    ) -> Result<String> {
+        if let Some(alias) = special_use
+            .aliases()
+            .iter()
+            .find(|alias| self.mailboxes.contains_key(**alias))
+        {
+            return self.get_mbox_alias(alias);
+        }
+
+        let mbox = backend
+            .find_special_mbox(special_use)
+            .with_context(|| format!("cannot find {} mailbox", special_use))?
+            .unwrap_or_else(|| special_use.default_mbox().to_owned());
+        debug!("{} mailbox: {:?}", special_use, mbox);
+        Ok(mbox)
+    }
+}
+
+/// Expands the shell variables of the given optional path.
+fn expand_path(path: Option<&PathBuf>) -> Result<Option<PathBuf>> {
+    match path.and_then(|path| path.to_str()) {
+        Some(path) => Ok(Some(
+            shellexpand::full(path)
+                .with_context(|| format!("cannot expand path {:?}", path))?
+                .to_string()
+                .into(),
+        )),
+        None => Ok(path.cloned()),
+    }
 }
 
 /// Represents all existing kind of account (backend).
@@ -349,6 +447,7 @@ pub enum BackendConfig {
     Maildir(MaildirBackendConfig),
     #[cfg(feature = "notmuch-backend")]
     Notmuch(NotmuchBackendConfig),
+    Custom(CustomBackendConfig),
 }
 
 /// Represents the IMAP backend.
@@ -359,14 +458,14 @@ pub struct ImapBackendConfig {
     pub imap_host: String,
     /// Represents the IMAP port.
     pub imap_port: u16,
-    /// Enables StartTLS.
-    pub imap_starttls: bool,
-    /// Trusts any certificate.
-    pub imap_insecure: bool,
+    /// Represents the IMAP TLS config.
+    pub imap_tls: TlsConfig,
     /// Represents the IMAP login.
     pub imap_login: String,
     /// Represents the IMAP password command.
     pub imap_passwd_cmd: String,
+    /// Represents the IMAP OAuth 2.0 config.
+    pub imap_oauth2: Option<OAuth2Config>,
 }
 
 #[cfg(feature = "imap-backend")]
@@ -397,6 +496,16 @@ pub struct NotmuchBackendConfig {
     pub notmuch_database_dir: PathBuf,
 }
 
+/// Represents a backend kind registered by a library user.
+#[derive(Debug, Clone)]
+pub struct CustomBackendConfig {
+    /// Represents the kind of backend, as registered in the backend
+    /// builder.
+    pub backend: String,
+    /// Represents the backend specific config.
+    pub backend_config: toml::Value,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -438,4 +547,22 @@ mod tests {
             Ok(path) if path == PathBuf::from("downloads/file.ext_5.ext2")
         ));
     }
+
+    #[test]
+    fn it_should_reject_smtp_fingerprint() {
+        let account = DeserializedCustomAccountConfig {
+            email: "test@localhost".into(),
+            smtp_tls_fingerprint: Some("AB:CD".into()),
+            backend: "memory".into(),
+            ..DeserializedCustomAccountConfig::default()
+        };
+        let config = DeserializedConfig {
+            accounts: HashMap::from([("test".into(), DeserializedAccountConfig::Custom(account))]),
+            ..DeserializedConfig::default()
+        };
+
+        let err =
+            AccountConfig::from_config_and_opt_account_name(&config, Some("test")).unwrap_err();
+        assert!(err.to_string().contains("smtp-tls-fingerprint"));
+    }
 }
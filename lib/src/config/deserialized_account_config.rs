@@ -1,7 +1,7 @@
 use serde::Deserialize;
 use std::{collections::HashMap, path::PathBuf};
 
-use crate::config::{Format, Hooks};
+use crate::config::{Format, Hooks, OAuth2Config, TlsMode};
 
 pub trait ToDeserializedBaseAccountConfig {
     fn to_base(&self) -> DeserializedBaseAccountConfig;
@@ -17,6 +17,7 @@ pub enum DeserializedAccountConfig {
     Maildir(DeserializedMaildirAccountConfig),
     #[cfg(feature = "notmuch-backend")]
     Notmuch(DeserializedNotmuchAccountConfig),
+    Custom(DeserializedCustomAccountConfig),
 }
 
 impl ToDeserializedBaseAccountConfig for DeserializedAccountConfig {
@@ -28,12 +29,13 @@ impl ToDeserializedBaseAccountConfig for DeserializedAccountConfig {
             Self::Maildir(config) => config.to_base(),
             #[cfg(feature = "notmuch-backend")]
             Self::Notmuch(config) => config.to_base(),
+            Self::Custom(config) => config.to_base(),
         }
     }
 }
 
 macro_rules! make_account_config {
-    ($AccountConfig:ident, $($element: ident: $ty: ty),*) => {
+    ($AccountConfig:ident, $($(#[$attr: meta])* $element: ident: $ty: ty),*) => {
 	#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
 	#[serde(rename_all = "kebab-case")]
 	pub struct $AccountConfig {
@@ -70,14 +72,29 @@ macro_rules! make_account_config {
             pub smtp_host: String,
             /// Represents the SMTP port.
             pub smtp_port: u16,
-            /// Enables StartTLS.
+            /// Represents the way the SMTP connection is secured.
+            pub smtp_tls: Option<TlsMode>,
+            /// Enables StartTLS, deprecated in favour of `smtp-tls`.
             pub smtp_starttls: Option<bool>,
             /// Trusts any certificate.
             pub smtp_insecure: Option<bool>,
+            /// Represents the SMTP CA certificates file.
+            pub smtp_tls_ca_file: Option<PathBuf>,
+            /// Represents the SMTP client certificate file.
+            pub smtp_tls_cert_file: Option<PathBuf>,
+            /// Represents the SMTP client private key file.
+            pub smtp_tls_key_file: Option<PathBuf>,
+            /// Represents the SMTP server certificate fingerprint. It
+            /// is rejected, since the SMTP client cannot check it.
+            pub smtp_tls_fingerprint: Option<String>,
             /// Represents the SMTP login.
             pub smtp_login: String,
             /// Represents the SMTP password command.
+            #[serde(default)]
             pub smtp_passwd_cmd: String,
+            /// Enables OAuth 2.0 authentication, the password
+            /// command is not used anymore.
+            pub smtp_oauth2: Option<OAuth2Config>,
 
             /// Represents the command used to encrypt a message.
             pub pgp_encrypt_cmd: Option<String>,
@@ -91,7 +108,7 @@ macro_rules! make_account_config {
     	    /// Represents hooks.
     	    pub hooks: Option<Hooks>,
 
-	    $(pub $element: $ty),*
+	    $($(#[$attr])* pub $element: $ty),*
 	}
 
 	impl ToDeserializedBaseAccountConfig for $AccountConfig {
@@ -113,10 +130,16 @@ macro_rules! make_account_config {
 
             	    smtp_host: self.smtp_host.clone(),
             	    smtp_port: self.smtp_port.clone(),
+            	    smtp_tls: self.smtp_tls.clone(),
             	    smtp_starttls: self.smtp_starttls.clone(),
             	    smtp_insecure: self.smtp_insecure.clone(),
+            	    smtp_tls_ca_file: self.smtp_tls_ca_file.clone(),
+            	    smtp_tls_cert_file: self.smtp_tls_cert_file.clone(),
+            	    smtp_tls_key_file: self.smtp_tls_key_file.clone(),
+            	    smtp_tls_fingerprint: self.smtp_tls_fingerprint.clone(),
             	    smtp_login: self.smtp_login.clone(),
             	    smtp_passwd_cmd: self.smtp_passwd_cmd.clone(),
+            	    smtp_oauth2: self.smtp_oauth2.clone(),
 
             	    pgp_encrypt_cmd: self.pgp_encrypt_cmd.clone(),
             	    pgp_decrypt_cmd: self.pgp_decrypt_cmd.clone(),
@@ -136,10 +159,17 @@ make_account_config!(
     DeserializedImapAccountConfig,
     imap_host: String,
     imap_port: u16,
+    imap_tls: Option<TlsMode>,
     imap_starttls: Option<bool>,
     imap_insecure: Option<bool>,
+    imap_tls_ca_file: Option<PathBuf>,
+    imap_tls_cert_file: Option<PathBuf>,
+    imap_tls_key_file: Option<PathBuf>,
+    imap_tls_fingerprint: Option<String>,
     imap_login: String,
-    imap_passwd_cmd: String
+    #[serde(default)]
+    imap_passwd_cmd: String,
+    imap_oauth2: Option<OAuth2Config>
 );
 
 #[cfg(feature = "maildir-backend")]
@@ -150,3 +180,9 @@ make_account_config!(
     DeserializedNotmuchAccountConfig,
     notmuch_database_dir: String
 );
+
+make_account_config!(
+    DeserializedCustomAccountConfig,
+    backend: String,
+    backend_config: Option<toml::Value>
+);
@@ -12,6 +12,9 @@ pub const DEFAULT_SIG_DELIM: &str = "-- \n";
 pub const DEFAULT_INBOX_FOLDER: &str = "INBOX";
 pub const DEFAULT_SENT_FOLDER: &str = "Sent";
 pub const DEFAULT_DRAFT_FOLDER: &str = "Drafts";
+pub const DEFAULT_TRASH_FOLDER: &str = "Trash";
+pub const DEFAULT_JUNK_FOLDER: &str = "Junk";
+pub const DEFAULT_ARCHIVE_FOLDER: &str = "Archive";
 
 /// Represents the user config file.
 #[derive(Debug, Default, Clone, Deserialize)]
@@ -94,4 +97,20 @@ impl DeserializedConfig {
             .or_else(|_| Self::path_from_home())
             .context("cannot find config path")
     }
+
+    /// Tries to get the cache directory, from XDG_CACHE_HOME then
+    /// from HOME environment variables. The directory may not exist.
+    pub fn cache_dir() -> Result<PathBuf> {
+        let home_var = if cfg!(target_family = "windows") {
+            "USERPROFILE"
+        } else {
+            "HOME"
+        };
+        let path = env::var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|_| env::var(home_var).map(|path| PathBuf::from(path).join(".cache")))
+            .context("cannot find cache directory")?
+            .join("himalaya");
+        Ok(path)
+    }
 }
@@ -0,0 +1,477 @@
+//! OAuth 2.0 config module.
+//!
+//! This module contains the OAuth 2.0 config shared by the IMAP
+//! backend and the SMTP service. Access tokens either come from a
+//! user command, or are obtained by exchanging a refresh token
+//! against the token endpoint of the provider. In the latter case,
+//! tokens are cached until they expire, and rotated refresh tokens
+//! are kept in the cache as well. The token endpoint is reached
+//! through the [`OAuth2Client`].
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    fs,
+    io::Write,
+    path::PathBuf,
+};
+
+use crate::{
+    config::{DeserializedConfig, TlsConfig},
+    oauth2::OAuth2Client,
+    process::run_cmd,
+};
+
+/// Represents the number of seconds before the expiry of an access
+/// token from which it is considered as expired.
+const EXPIRY_MARGIN: i64 = 60;
+
+/// Represents the lifetime of an access token when the token
+/// endpoint does not give one.
+const DEFAULT_EXPIRES_IN: i64 = 3600;
+
+/// Represents the SASL mechanism used to authenticate with an access
+/// token.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OAuth2Method {
+    /// Represents the Google and Microsoft `XOAUTH2` mechanism.
+    #[default]
+    XOAuth2,
+    /// Represents the `OAUTHBEARER` mechanism as defined in the
+    /// [RFC7628](https://www.rfc-editor.org/rfc/rfc7628).
+    OAuthBearer,
+}
+
+impl Display for OAuth2Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::XOAuth2 => write!(f, "XOAUTH2"),
+            Self::OAuthBearer => write!(f, "OAUTHBEARER"),
+        }
+    }
+}
+
+impl OAuth2Method {
+    /// Builds the SASL initial client response.
+    pub fn sasl_initial_response(
+        &self,
+        login: &str,
+        host: &str,
+        port: u16,
+        access_token: &str,
+    ) -> String {
+        match self {
+            Self::XOAuth2 => format!("user={}\x01auth=Bearer {}\x01\x01", login, access_token),
+            Self::OAuthBearer => format!(
+                "n,a={},\x01host={}\x01port={}\x01auth=Bearer {}\x01\x01",
+                // Commas and equal signs are not allowed in the
+                // GS2 authzid, see the RFC5801.
+                login.replace('=', "=3D").replace(',', "=2C"),
+                host,
+                port,
+                access_token
+            ),
+        }
+    }
+
+    /// Builds the SASL client response to an error challenge. Servers
+    /// send their error as a challenge, then wait for this response
+    /// before failing the authentication.
+    pub fn sasl_error_response(&self) -> &'static str {
+        match self {
+            Self::XOAuth2 => "",
+            Self::OAuthBearer => "\x01",
+        }
+    }
+}
+
+/// Represents the OAuth 2.0 config of a protocol (IMAP or SMTP).
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OAuth2Config {
+    /// Represents the SASL mechanism, `xoauth2` by default.
+    #[serde(default)]
+    pub method: OAuth2Method,
+    /// Represents the command printing an access token. It is only
+    /// used when no token URL is given.
+    pub access_token_cmd: Option<String>,
+    /// Represents the token endpoint URL of the provider.
+    pub token_url: Option<String>,
+    /// Represents the client id registered at the provider.
+    pub client_id: Option<String>,
+    /// Represents the command printing the client secret.
+    pub client_secret_cmd: Option<String>,
+    /// Represents the command printing the refresh token.
+    pub refresh_token_cmd: Option<String>,
+}
+
+impl OAuth2Config {
+    /// Gets an access token for the given cache key, using the
+    /// default token cache.
+    pub fn access_token(&self, key: &str, tls: &TlsConfig) -> Result<String> {
+        self.access_token_from_cache(key, tls, &mut OAuth2TokenCache::from_default_path()?)
+    }
+
+    /// Gets an access token for the given cache key. Cached tokens
+    /// are used until they expire, then they are refreshed against
+    /// the token endpoint, using the TLS config of the protocol.
+    /// Without token endpoint, the access token command is run.
+    pub fn access_token_from_cache(
+        &self,
+        key: &str,
+        tls: &TlsConfig,
+        cache: &mut OAuth2TokenCache,
+    ) -> Result<String> {
+        info!(">> get OAuth2 access token");
+        debug!("key: {}", key);
+
+        if let Some(access_token) = cache.access_token(key) {
+            debug!("use cached access token");
+            info!("<< get OAuth2 access token");
+            return Ok(access_token.to_owned());
+        }
+
+        let access_token = match (self.token_url.as_ref(), self.access_token_cmd.as_ref()) {
+            (Some(token_url), _) => {
+                let token = match cache.refresh_token(key) {
+                    Some(refresh_token) => {
+                        debug!("refresh access token using cached refresh token");
+                        self.refresh(token_url, tls, refresh_token).or_else(|err| {
+                            warn!(
+                                "cannot refresh access token using cached refresh token: {:#}",
+                                err
+                            );
+                            self.refresh(token_url, tls, &self.refresh_token_from_cmd()?)
+                        })?
+                    }
+                    None => {
+                        debug!("refresh access token using refresh token cmd");
+                        self.refresh(token_url, tls, &self.refresh_token_from_cmd()?)?
+                    }
+                };
+                let access_token = token.access_token.clone();
+                cache.insert(key, token)?;
+                access_token
+            }
+            (None, Some(cmd)) => {
+                debug!("access token cmd: {}", cmd);
+                run_secret_cmd(cmd).context("cannot run OAuth2 access token cmd")?
+            }
+            (None, None) => {
+                bail!("cannot get OAuth2 access token: missing access token cmd or token URL")
+            }
+        };
+
+        info!("<< get OAuth2 access token");
+        Ok(access_token)
+    }
+
+    fn refresh_token_from_cmd(&self) -> Result<String> {
+        let cmd = self.refresh_token_cmd.as_ref().ok_or_else(|| {
+            anyhow!("cannot refresh OAuth2 access token: missing refresh token cmd")
+        })?;
+        run_secret_cmd(cmd).context("cannot run OAuth2 refresh token cmd")
+    }
+
+    /// Exchanges the given refresh token for an access token.
+    fn refresh(
+        &self,
+        token_url: &str,
+        tls: &TlsConfig,
+        refresh_token: &str,
+    ) -> Result<OAuth2Token> {
+        let client_id = self
+            .client_id
+            .as_ref()
+            .ok_or_else(|| anyhow!("cannot refresh OAuth2 access token: missing client id"))?;
+        let client_secret = match self.client_secret_cmd {
+            Some(ref cmd) => {
+                Some(run_secret_cmd(cmd).context("cannot run OAuth2 client secret cmd")?)
+            }
+            None => None,
+        };
+
+        let res = OAuth2Client::new(tls)?
+            .refresh(
+                token_url,
+                client_id,
+                client_secret.as_deref(),
+                refresh_token,
+            )
+            .context("cannot refresh OAuth2 access token")?;
+        Ok(OAuth2Token {
+            access_token: res.access_token,
+            expires_at: Utc::now().timestamp() + res.expires_in.unwrap_or(DEFAULT_EXPIRES_IN),
+            // Providers may not rotate refresh tokens, in which case
+            // the current one stays valid.
+            refresh_token: Some(
+                res.refresh_token
+                    .unwrap_or_else(|| refresh_token.to_owned()),
+            ),
+        })
+    }
+}
+
+/// Runs the given command, then returns its output without the
+/// trailing new line.
+fn run_secret_cmd(cmd: &str) -> Result<String> {
+    let output = run_cmd(cmd)?;
+    Ok(output.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Represents a cached access token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuth2Token {
+    /// Represents the access token.
+    pub access_token: String,
+    /// Represents the expiry of the access token, as a UNIX
+    /// timestamp.
+    pub expires_at: i64,
+    /// Represents the last refresh token given by the provider.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+/// Represents the OAuth 2.0 token cache. Entries are indexed by a key
+/// identifying the protocol and the account.
+#[derive(Debug, Default)]
+pub struct OAuth2TokenCache {
+    path: PathBuf,
+    entries: BTreeMap<String, OAuth2Token>,
+}
+
+impl OAuth2TokenCache {
+    /// Reads the cache from the default cache file.
+    pub fn from_default_path() -> Result<Self> {
+        Self::from_path(DeserializedConfig::cache_dir()?.join("oauth2-tokens.toml"))
+    }
+
+    /// Reads the cache from the given file. A missing file is
+    /// considered as an empty cache.
+    pub fn from_path(path: PathBuf) -> Result<Self> {
+        let entries = if path.is_file() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("cannot read OAuth2 token cache file {:?}", path))?;
+            toml::from_str(&content)
+                .with_context(|| format!("cannot parse OAuth2 token cache file {:?}", path))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { path, entries })
+    }
+
+    /// Builds the key identifying the given protocol of the given
+    /// account.
+    pub fn key(protocol: &str, login: &str, host: &str, port: u16) -> String {
+        format!("{}:{}@{}:{}", protocol, login, host, port)
+    }
+
+    /// Gets the access token of the given key if it did not expire.
+    pub fn access_token(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(key)
+            .filter(|token| token.expires_at - EXPIRY_MARGIN > Utc::now().timestamp())
+            .map(|token| token.access_token.as_str())
+    }
+
+    /// Gets the refresh token of the given key.
+    pub fn refresh_token(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(key)
+            .and_then(|token| token.refresh_token.as_deref())
+    }
+
+    /// Inserts the token of the given key, then writes the cache
+    /// file.
+    pub fn insert(&mut self, key: &str, token: OAuth2Token) -> Result<()> {
+        debug!("cache access token of {:?} until {}", key, token.expires_at);
+        self.entries.insert(key.to_owned(), token);
+        self.write()
+    }
+
+    /// Expires the access token of the given key, so that the next
+    /// access token is refreshed. The refresh token is kept.
+    pub fn expire(&mut self, key: &str) -> Result<()> {
+        match self.entries.get_mut(key) {
+            Some(token) if token.expires_at != 0 => {
+                debug!("expire access token of {:?}", key);
+                token.expires_at = 0;
+                self.write()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create cache directory {:?}", dir))?;
+        }
+        let content =
+            toml::to_string(&self.entries).context("cannot serialize OAuth2 token cache")?;
+
+        let mut opts = fs::OpenOptions::new();
+        opts.write(true).create(true).truncate(true);
+        // Tokens are secrets, only the user should be able to read
+        // them.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+        opts.open(&self.path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .with_context(|| format!("cannot write OAuth2 token cache file {:?}", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::Read, net::TcpListener, thread};
+
+    use super::*;
+
+    /// Spawns a token endpoint serving a single request. The endpoint
+    /// checks the refresh token, then answers with the given access
+    /// and refresh tokens.
+    fn spawn_token_endpoint(
+        expected_refresh_token: &'static str,
+        access_token: &'static str,
+        refresh_token: &'static str,
+    ) -> (String, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut req = Vec::new();
+            let mut buf = [0; 1024];
+            // Reads until the whole form is received.
+            while !String::from_utf8_lossy(&req).contains("client_id=client") {
+                let len = stream.read(&mut buf).unwrap();
+                assert!(len > 0, "unexpected end of request");
+                req.extend_from_slice(&buf[..len]);
+            }
+            let req = String::from_utf8(req).unwrap();
+            assert!(req.starts_with("POST /token HTTP/1.1\r\n"));
+            assert!(req.contains("grant_type=refresh_token"));
+            assert!(req.contains(&format!("refresh_token={}", expected_refresh_token)));
+            assert!(req.contains("client_secret=secret"));
+
+            let body = format!(
+                r#"{{"access_token":"{}","token_type":"Bearer","expires_in":3600,"refresh_token":"{}"}}"#,
+                access_token, refresh_token
+            );
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn it_should_build_sasl_responses() {
+        assert_eq!(
+            "user=user@localhost\x01auth=Bearer token\x01\x01",
+            OAuth2Method::XOAuth2.sasl_initial_response(
+                "user@localhost",
+                "localhost",
+                993,
+                "token"
+            )
+        );
+        assert_eq!(
+            "n,a=us=2Cer@localhost,\x01host=localhost\x01port=993\x01auth=Bearer token\x01\x01",
+            OAuth2Method::OAuthBearer.sasl_initial_response(
+                "us,er@localhost",
+                "localhost",
+                993,
+                "token"
+            )
+        );
+        assert_eq!("", OAuth2Method::XOAuth2.sasl_error_response());
+        assert_eq!("\x01", OAuth2Method::OAuthBearer.sasl_error_response());
+    }
+
+    #[test]
+    fn it_should_get_access_token_from_cmd() {
+        let path = env::temp_dir().join(format!("himalaya-oauth2-{}.toml", uuid::Uuid::new_v4()));
+        let mut cache = OAuth2TokenCache::from_path(path.clone()).unwrap();
+        let config = OAuth2Config {
+            access_token_cmd: Some("echo token".into()),
+            ..OAuth2Config::default()
+        };
+
+        let key = OAuth2TokenCache::key("imap", "user", "localhost", 993);
+        assert_eq!(
+            "token",
+            config
+                .access_token_from_cache(&key, &TlsConfig::default(), &mut cache)
+                .unwrap()
+        );
+        assert!(!path.exists());
+
+        let config = OAuth2Config::default();
+        assert!(config
+            .access_token_from_cache(&key, &TlsConfig::default(), &mut cache)
+            .is_err());
+    }
+
+    #[test]
+    fn it_should_refresh_and_cache_access_token() {
+        let path = env::temp_dir().join(format!("himalaya-oauth2-{}.toml", uuid::Uuid::new_v4()));
+        let key = OAuth2TokenCache::key("smtp", "user", "localhost", 465);
+
+        let (url, endpoint) = spawn_token_endpoint("refresh-1", "access-1", "refresh-2");
+        let config = OAuth2Config {
+            method: OAuth2Method::XOAuth2,
+            access_token_cmd: None,
+            token_url: Some(url),
+            client_id: Some("client".into()),
+            client_secret_cmd: Some("echo secret".into()),
+            refresh_token_cmd: Some("echo refresh-1".into()),
+        };
+        let mut cache = OAuth2TokenCache::from_path(path.clone()).unwrap();
+        assert_eq!(
+            "access-1",
+            config
+                .access_token_from_cache(&key, &TlsConfig::default(), &mut cache)
+                .unwrap()
+        );
+        endpoint.join().unwrap();
+
+        // The endpoint is gone, so the token must come from the cache.
+        let mut cache = OAuth2TokenCache::from_path(path.clone()).unwrap();
+        assert_eq!(
+            "access-1",
+            config
+                .access_token_from_cache(&key, &TlsConfig::default(), &mut cache)
+                .unwrap()
+        );
+        assert_eq!(Some("refresh-2"), cache.refresh_token(&key));
+
+        // Once expired, the token is refreshed using the rotated
+        // refresh token.
+        cache.expire(&key).unwrap();
+        assert_eq!(None, cache.access_token(&key));
+        let (url, endpoint) = spawn_token_endpoint("refresh-2", "access-2", "refresh-3");
+        let config = OAuth2Config {
+            token_url: Some(url),
+            ..config
+        };
+        assert_eq!(
+            "access-2",
+            config
+                .access_token_from_cache(&key, &TlsConfig::default(), &mut cache)
+                .unwrap()
+        );
+        endpoint.join().unwrap();
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+//! TLS config module.
+//!
+//! This module contains the TLS config shared by the IMAP backend and
+//! the SMTP service: how the connection is secured, which certificate
+//! authorities are trusted, which client certificate is presented and
+//! which server certificate is expected.
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::debug;
+use native_tls::{Certificate, Identity, TlsConnector};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::PathBuf,
+};
+
+const PEM_CERT_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_CERT_END: &str = "-----END CERTIFICATE-----";
+
+/// Represents the way a connection is secured.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsMode {
+    /// Represents a plaintext connection, nothing is encrypted.
+    None,
+    /// Represents a plaintext connection upgraded to TLS using the
+    /// STARTTLS command.
+    Starttls,
+    /// Represents a connection encrypted from the start.
+    #[default]
+    Implicit,
+}
+
+impl TlsMode {
+    /// Gets the TLS mode from the given option, or from the legacy
+    /// `starttls` option if missing.
+    pub fn from_opts(mode: Option<TlsMode>, starttls: Option<bool>) -> Self {
+        match (mode, starttls) {
+            (Some(mode), _) => mode,
+            (None, Some(true)) => Self::Starttls,
+            (None, _) => Self::Implicit,
+        }
+    }
+}
+
+impl Display for TlsMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Starttls => write!(f, "starttls"),
+            Self::Implicit => write!(f, "implicit"),
+        }
+    }
+}
+
+/// Represents the TLS config of an IMAP or SMTP connection.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// Represents the way the connection is secured.
+    pub mode: TlsMode,
+    /// Trusts any certificate.
+    pub insecure: bool,
+    /// Represents the PEM file containing the certificate
+    /// authorities to trust in addition to the system ones.
+    pub ca_file: Option<PathBuf>,
+    /// Represents the PEM file containing the client certificate.
+    pub cert_file: Option<PathBuf>,
+    /// Represents the PEM file containing the PKCS#8 private key of
+    /// the client certificate.
+    pub key_file: Option<PathBuf>,
+    /// Represents the SHA-256 fingerprint the server certificate
+    /// must match. When set, the certificate is trusted even if it
+    /// is self-signed or issued for another host name. Only IMAP
+    /// connections support it, the SMTP one is always `None`.
+    pub fingerprint: Option<String>,
+}
+
+impl TlsConfig {
+    /// Returns `true` if the certificate chain and the host name
+    /// should not be verified, either because the connection is
+    /// insecure or because the certificate is pinned. Connectors
+    /// built this way must check the fingerprint of the connection
+    /// before authenticating.
+    pub fn accept_invalid_certs(&self) -> bool {
+        self.insecure || self.fingerprint.is_some()
+    }
+
+    /// Reads the certificate authorities to trust, as PEM blocks.
+    pub fn ca_certs_pem(&self) -> Result<Vec<String>> {
+        let path = match self.ca_file {
+            Some(ref path) => path,
+            None => return Ok(vec![]),
+        };
+        let pem =
+            fs::read_to_string(path).with_context(|| format!("cannot read CA file {:?}", path))?;
+        let certs = split_pem_certs(&pem);
+        if certs.is_empty() {
+            bail!("cannot find any certificate in CA file {:?}", path);
+        }
+        debug!("found {} certificates in CA file {:?}", certs.len(), path);
+        Ok(certs)
+    }
+
+    /// Reads the client certificate and its private key, as PEM.
+    pub fn identity_pem(&self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        match (self.cert_file.as_ref(), self.key_file.as_ref()) {
+            (None, None) => Ok(None),
+            (Some(cert_file), Some(key_file)) => {
+                let cert = fs::read(cert_file)
+                    .with_context(|| format!("cannot read certificate file {:?}", cert_file))?;
+                let key = fs::read(key_file)
+                    .with_context(|| format!("cannot read key file {:?}", key_file))?;
+                Ok(Some((cert, key)))
+            }
+            (Some(_), None) => Err(anyhow!("cannot use client certificate: key file missing")),
+            (None, Some(_)) => Err(anyhow!("cannot use client key: certificate file missing")),
+        }
+    }
+
+    /// Reads the certificate authorities to trust.
+    pub fn ca_certs(&self) -> Result<Vec<Certificate>> {
+        self.ca_certs_pem()?
+            .iter()
+            .map(|pem| Certificate::from_pem(pem.as_bytes()).context("cannot parse CA certificate"))
+            .collect()
+    }
+
+    /// Reads the client certificate.
+    pub fn identity(&self) -> Result<Option<Identity>> {
+        match self.identity_pem()? {
+            Some((cert, key)) => Identity::from_pkcs8(&cert, &key)
+                .map(Some)
+                .context("cannot parse client certificate"),
+            None => Ok(None),
+        }
+    }
+
+    /// Builds the TLS connector matching the config.
+    pub fn connector(&self) -> Result<TlsConnector> {
+        let mut builder = TlsConnector::builder();
+        builder
+            .danger_accept_invalid_certs(self.accept_invalid_certs())
+            .danger_accept_invalid_hostnames(self.accept_invalid_certs());
+        for cert in self.ca_certs()? {
+            builder.add_root_certificate(cert);
+        }
+        if let Some(identity) = self.identity()? {
+            builder.identity(identity);
+        }
+        builder.build().context("cannot create TLS connector")
+    }
+
+    /// Checks the given server certificate against the pinned
+    /// fingerprint, if any.
+    pub fn check_fingerprint(&self, cert: Option<&Certificate>) -> Result<()> {
+        let expected = match self.fingerprint {
+            Some(ref fingerprint) => normalize_fingerprint(fingerprint),
+            None => return Ok(()),
+        };
+        let cert = cert.ok_or_else(|| anyhow!("cannot get server certificate"))?;
+        let der = cert.to_der().context("cannot encode server certificate")?;
+        let fingerprint = fingerprint(&der);
+        debug!("server certificate fingerprint: {}", fingerprint);
+        if normalize_fingerprint(&fingerprint) != expected {
+            bail!(
+                "cannot trust server certificate: fingerprint {} does not match the pinned one",
+                fingerprint
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Computes the SHA-256 fingerprint of the given DER certificate,
+/// formatted as colon-separated uppercase hexadecimal bytes.
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Removes separators and case from the given fingerprint, so that
+/// fingerprints copied from different tools can be compared.
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    let fingerprint = fingerprint.trim();
+    let fingerprint = fingerprint
+        .strip_prefix("sha256:")
+        .or_else(|| fingerprint.strip_prefix("SHA256:"))
+        .unwrap_or(fingerprint);
+    fingerprint
+        .chars()
+        .filter(|c| !matches!(c, ':' | ' '))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Splits the given PEM bundle into its certificates.
+fn split_pem_certs(pem: &str) -> Vec<String> {
+    pem.split(PEM_CERT_BEGIN)
+        .skip(1)
+        .filter_map(|block| block.find(PEM_CERT_END).map(|end| &block[..end]))
+        .map(|block| format!("{}{}{}\n", PEM_CERT_BEGIN, block, PEM_CERT_END))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_get_tls_mode_from_opts() {
+        assert_eq!(TlsMode::Implicit, TlsMode::from_opts(None, None));
+        assert_eq!(TlsMode::Implicit, TlsMode::from_opts(None, Some(false)));
+        assert_eq!(TlsMode::Starttls, TlsMode::from_opts(None, Some(true)));
+        assert_eq!(
+            TlsMode::None,
+            TlsMode::from_opts(Some(TlsMode::None), Some(true))
+        );
+    }
+
+    #[test]
+    fn it_should_format_and_normalize_fingerprints() {
+        let fingerprint = fingerprint(b"");
+        assert_eq!(95, fingerprint.len());
+        assert!(fingerprint.starts_with("E3:B0:C4:42"));
+        assert_eq!(
+            normalize_fingerprint(&fingerprint),
+            normalize_fingerprint(
+                "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_split_pem_bundles() {
+        let pem = "\
+subject=CN = Root CA
+-----BEGIN CERTIFICATE-----
+AAAA
+-----END CERTIFICATE-----
+
+-----BEGIN CERTIFICATE-----
+BBBB
+-----END CERTIFICATE-----
+-----BEGIN CERTIFICATE-----
+truncated
+";
+        assert_eq!(
+            vec![
+                "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n",
+                "-----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----\n",
+            ],
+            split_pem_certs(pem)
+        );
+    }
+}
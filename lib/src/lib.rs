@@ -1,8 +1,220 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
+//! Himalaya library.
+//!
+//! This crate contains the email domain of Himalaya: backends,
+//! message building and parsing, SMTP sending and config loading. It
+//! does not depend on any terminal or command-line related crate, so
+//! it can be embedded in any Rust program.
+
+pub mod process;
+
+pub mod mbox {
+    pub mod mbox;
+    pub use mbox::*;
+
+    pub mod mbox_counts;
+    pub use mbox_counts::*;
+
+    pub mod special_use;
+    pub use special_use::*;
+}
+
+pub mod msg {
+    pub mod envelope;
+    pub use envelope::*;
+
+    pub mod id_entity;
+    pub use id_entity::*;
+
+    pub mod flag_entity;
+    pub use flag_entity::*;
+
+    pub mod sort_entity;
+    pub use sort_entity::*;
+
+    pub mod query_entity;
+    pub use query_entity::*;
+
+    pub mod tpl_entity;
+    pub use tpl_entity::*;
+
+    pub mod thread_entity;
+    pub use thread_entity::*;
+
+    pub mod msg_entity;
+    pub use msg_entity::*;
+
+    pub mod parts_entity;
+    pub use parts_entity::*;
+
+    pub mod attachment_entity;
+    pub use attachment_entity::*;
+
+    pub mod addr_entity;
+    pub use addr_entity::*;
+}
+
+pub mod backends {
+    pub mod backend;
+    pub use backend::*;
+
+    pub mod backend_builder;
+    pub use backend_builder::*;
+
+    #[cfg(feature = "async")]
+    pub mod async_backend;
+    #[cfg(feature = "async")]
+    pub use async_backend::*;
+
+    pub mod dry_run_backend;
+    pub use dry_run_backend::*;
+
+    pub mod id_mapper;
+    pub use id_mapper::*;
+
+    pub mod memory {
+        pub mod memory_backend;
+        pub use memory_backend::*;
+
+        pub mod memory_mbox;
+        pub use memory_mbox::*;
+
+        pub mod memory_envelope;
+        pub use memory_envelope::*;
+    }
+
+    pub use self::memory::*;
+
+    #[cfg(feature = "imap-backend")]
+    pub mod imap {
+        pub mod imap_backend;
+        pub use imap_backend::*;
+
+        #[cfg(feature = "async")]
+        pub mod async_imap_backend;
+        #[cfg(feature = "async")]
+        pub use async_imap_backend::*;
+
+        pub mod imap_mbox;
+        pub use imap_mbox::*;
+
+        pub mod imap_mbox_attr;
+        pub use imap_mbox_attr::*;
+
+        pub mod imap_utf7;
+        pub use imap_utf7::*;
+
+        pub mod imap_envelope;
+        pub use imap_envelope::*;
+
+        pub mod imap_thread;
+        pub use imap_thread::*;
+
+        pub mod imap_attachment;
+        pub use imap_attachment::*;
+
+        pub mod imap_search;
+        pub use imap_search::*;
+
+        pub mod imap_info;
+        pub use imap_info::*;
+
+        pub mod imap_envelope_cache;
+        pub use imap_envelope_cache::*;
+
+        pub mod imap_flag;
+        pub use imap_flag::*;
+
+        pub mod imap_uid_validity;
+        pub use imap_uid_validity::*;
+
+        pub mod imap_oauth2;
+        pub use imap_oauth2::*;
+
+        pub mod imap_watcher;
+        pub use imap_watcher::*;
+
+        #[cfg(feature = "maildir-backend")]
+        pub mod imap_sync;
+        #[cfg(feature = "maildir-backend")]
+        pub use imap_sync::*;
+
+        pub mod msg_sort_criterion;
+    }
+
+    #[cfg(feature = "imap-backend")]
+    pub use self::imap::*;
+
+    #[cfg(feature = "maildir-backend")]
+    pub mod maildir {
+        pub mod maildir_backend;
+        pub use maildir_backend::*;
+
+        pub mod maildir_mbox;
+        pub use maildir_mbox::*;
+
+        pub mod maildir_envelope;
+        pub use maildir_envelope::*;
+
+        pub mod maildir_flag;
+        pub use maildir_flag::*;
+
+        pub mod maildir_sort;
+        pub use maildir_sort::*;
     }
+
+    #[cfg(feature = "maildir-backend")]
+    pub use self::maildir::*;
+
+    #[cfg(feature = "notmuch-backend")]
+    pub mod notmuch {
+        pub mod notmuch_backend;
+        pub use notmuch_backend::*;
+
+        pub mod notmuch_mbox;
+        pub use notmuch_mbox::*;
+
+        pub mod notmuch_envelope;
+        pub use notmuch_envelope::*;
+    }
+
+    #[cfg(feature = "notmuch-backend")]
+    pub use self::notmuch::*;
+}
+
+pub mod smtp {
+    pub mod smtp_service;
+    pub use smtp_service::*;
+
+    #[cfg(feature = "async")]
+    pub mod async_smtp_service;
+    #[cfg(feature = "async")]
+    pub use async_smtp_service::*;
+}
+
+pub mod oauth2 {
+    pub mod oauth2_client;
+    pub use oauth2_client::*;
+}
+
+pub mod config {
+    pub mod deserialized_config;
+    pub use deserialized_config::*;
+
+    pub mod deserialized_account_config;
+    pub use deserialized_account_config::*;
+
+    pub mod account_config;
+    pub use account_config::*;
+
+    pub mod format;
+    pub use format::*;
+
+    pub mod hooks;
+    pub use hooks::*;
+
+    pub mod oauth2_config;
+    pub use oauth2_config::*;
+
+    pub mod tls_config;
+    pub use tls_config::*;
 }
@@ -0,0 +1,11 @@
+use std::{any, fmt};
+
+pub trait Mboxes: fmt::Debug + erased_serde::Serialize + any::Any + Send {
+    fn as_any(&self) -> &dyn any::Any;
+}
+
+impl<T: fmt::Debug + erased_serde::Serialize + any::Any + Send> Mboxes for T {
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
@@ -0,0 +1,75 @@
+//! Mailbox counts module.
+//!
+//! This module provides the message counts shown alongside the
+//! mailboxes of a listing.
+
+/// Represents the message counts of a mailbox. Backends that cannot
+/// tell the number of recent messages or the size of the mailbox
+/// leave them empty.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct MboxCounts {
+    /// Represents the number of messages.
+    pub messages: usize,
+
+    /// Represents the number of messages not seen yet.
+    pub unseen: usize,
+
+    /// Represents the number of messages that arrived since the last
+    /// session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recent: Option<usize>,
+
+    /// Represents the size of all the messages, in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl MboxCounts {
+    /// Gets the size of the mailbox in a human readable format, using
+    /// binary prefixes.
+    pub fn size_to_string(&self) -> String {
+        self.size.map(size_to_string).unwrap_or_default()
+    }
+}
+
+/// Formats the given size in bytes in a human readable format, using
+/// binary prefixes.
+pub fn size_to_string(size: u64) -> String {
+    let mut value = size as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if value < 1024.0 {
+            return if unit == "B" {
+                format!("{}{}", size, unit)
+            } else {
+                format!("{:.1}{}", value, unit)
+            };
+        }
+        value /= 1024.0;
+    }
+    format!("{:.1}TiB", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_format_size() {
+        macro_rules! size_to_string {
+            ($size:expr) => {
+                MboxCounts {
+                    size: $size,
+                    ..MboxCounts::default()
+                }
+                .size_to_string()
+            };
+        }
+
+        assert_eq!("", size_to_string!(None));
+        assert_eq!("0B", size_to_string!(Some(0)));
+        assert_eq!("1023B", size_to_string!(Some(1023)));
+        assert_eq!("1.0KiB", size_to_string!(Some(1024)));
+        assert_eq!("1.5MiB", size_to_string!(Some(1536 * 1024)));
+        assert_eq!("2.0TiB", size_to_string!(Some(2 << 40)));
+    }
+}
@@ -0,0 +1,91 @@
+//! Special-use mailbox module.
+//!
+//! Servers name the mailboxes holding sent messages, drafts or
+//! deleted messages differently ("Sent", "Sent Items", "[Gmail]/Sent
+//! Mail"…). IMAP servers flag them with the special-use attributes
+//! defined in [RFC6154], other backends rely on the mailbox aliases of
+//! the account config.
+//!
+//! [RFC6154]: https://datatracker.ietf.org/doc/html/rfc6154
+
+use std::fmt::{self, Display};
+
+use crate::config::{
+    DEFAULT_ARCHIVE_FOLDER, DEFAULT_DRAFT_FOLDER, DEFAULT_JUNK_FOLDER, DEFAULT_SENT_FOLDER,
+    DEFAULT_TRASH_FOLDER,
+};
+
+/// Represents the special use of a mailbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecialUse {
+    Sent,
+    Drafts,
+    Trash,
+    Junk,
+    Archive,
+}
+
+impl SpecialUse {
+    /// Parses the given IMAP mailbox attribute, like `\Sent`.
+    pub fn from_attr(attr: &str) -> Option<Self> {
+        match attr.trim_start_matches('\\').to_lowercase().as_str() {
+            "sent" => Some(Self::Sent),
+            "drafts" => Some(Self::Drafts),
+            "trash" => Some(Self::Trash),
+            "junk" => Some(Self::Junk),
+            "archive" => Some(Self::Archive),
+            _ => None,
+        }
+    }
+
+    /// Gets the keys of the account `mailboxes` table configuring the
+    /// mailbox of this special use, by order of preference.
+    pub fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Self::Sent => &["sent"],
+            Self::Drafts => &["draft", "drafts"],
+            Self::Trash => &["trash"],
+            Self::Junk => &["junk", "spam"],
+            Self::Archive => &["archive"],
+        }
+    }
+
+    /// Gets the mailbox used when neither the config nor the backend
+    /// knows the mailbox of this special use.
+    pub fn default_mbox(&self) -> &'static str {
+        match self {
+            Self::Sent => DEFAULT_SENT_FOLDER,
+            Self::Drafts => DEFAULT_DRAFT_FOLDER,
+            Self::Trash => DEFAULT_TRASH_FOLDER,
+            Self::Junk => DEFAULT_JUNK_FOLDER,
+            Self::Archive => DEFAULT_ARCHIVE_FOLDER,
+        }
+    }
+}
+
+impl Display for SpecialUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Sent => write!(f, "sent"),
+            Self::Drafts => write!(f, "drafts"),
+            Self::Trash => write!(f, "trash"),
+            Self::Junk => write!(f, "junk"),
+            Self::Archive => write!(f, "archive"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_special_use_attrs() {
+        assert_eq!(Some(SpecialUse::Sent), SpecialUse::from_attr("\\Sent"));
+        assert_eq!(Some(SpecialUse::Drafts), SpecialUse::from_attr("\\drafts"));
+        assert_eq!(Some(SpecialUse::Junk), SpecialUse::from_attr("\\Junk"));
+        assert_eq!(None, SpecialUse::from_attr("\\All"));
+        assert_eq!(None, SpecialUse::from_attr("\\HasNoChildren"));
+    }
+}
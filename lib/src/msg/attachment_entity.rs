@@ -0,0 +1,166 @@
+//! Message attachment module.
+//!
+//! This module provides the attachment entity, which describes an
+//! attachment without its content, so that attachments can be listed
+//! and selected before being downloaded.
+
+use anyhow::{anyhow, Error, Result};
+use std::{convert::TryFrom, ops::Deref};
+
+/// Represents a list of attachments.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Attachments {
+    #[serde(rename = "response")]
+    pub attachments: Vec<Attachment>,
+}
+
+impl Deref for Attachments {
+    type Target = Vec<Attachment>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.attachments
+    }
+}
+
+/// Represents the attachment of a message, without its content.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Attachment {
+    /// Represents the position of the attachment in the message,
+    /// starting at 1.
+    pub index: usize,
+
+    /// Represents the file name of the attachment.
+    pub filename: String,
+
+    /// Represents the MIME type of the attachment.
+    pub mime: String,
+
+    /// Represents the size of the attachment, in bytes. It is an
+    /// estimate when computed from the encoded part.
+    pub size: usize,
+
+    /// Represents the IMAP section of the part, used to fetch the
+    /// attachment alone.
+    #[serde(skip)]
+    pub section: Option<String>,
+
+    /// Represents the content transfer encoding of the part, needed
+    /// to decode the fetched section.
+    #[serde(skip)]
+    pub encoding: Option<String>,
+}
+
+/// Represents the way the user picks attachments: by index, by MIME
+/// type or by file name. MIME types and file names can contain `*`
+/// and `?` wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachmentSelector {
+    Index(usize),
+    Mime(String),
+    Filename(String),
+}
+
+impl AttachmentSelector {
+    /// Returns true if the given attachment is selected.
+    pub fn matches(&self, attachment: &Attachment) -> bool {
+        match self {
+            Self::Index(index) => attachment.index == *index,
+            Self::Mime(pattern) => glob_match(pattern, &attachment.mime),
+            Self::Filename(pattern) => glob_match(pattern, &attachment.filename),
+        }
+    }
+}
+
+impl TryFrom<&str> for AttachmentSelector {
+    type Error = Error;
+
+    fn try_from(selector: &str) -> Result<Self, Self::Error> {
+        if let Ok(index) = selector.parse::<usize>() {
+            return match index {
+                0 => Err(anyhow!(
+                    "cannot parse attachment selector {:?}: indexes start at 1",
+                    selector
+                )),
+                index => Ok(Self::Index(index)),
+            };
+        }
+        // File names cannot contain slashes, MIME types always do.
+        if selector.contains('/') {
+            Ok(Self::Mime(selector.to_owned()))
+        } else {
+            Ok(Self::Filename(selector.to_owned()))
+        }
+    }
+}
+
+/// Matches the given value against the given pattern, where `*`
+/// matches any sequence of characters and `?` any single character.
+/// The match is case-insensitive.
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let value: Vec<char> = value.to_lowercase().chars().collect();
+
+    let (mut p, mut v) = (0, 0);
+    // Position of the last `*` in the pattern, and of the value
+    // character it was matched against, in order to backtrack.
+    let mut star = None;
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, v));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match star {
+                Some((star_p, star_v)) => {
+                    star = Some((star_p, star_v + 1));
+                    p = star_p + 1;
+                    v = star_v + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_globs() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.pdf", "report.pdf"));
+        assert!(glob_match("*.PDF", "Report.pdf"));
+        assert!(glob_match("rep?rt*", "report.pdf"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(glob_match("image/*", "image/png"));
+        assert!(!glob_match("*.pdf", "report.pdf.exe"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("report", "report.pdf"));
+    }
+
+    #[test]
+    fn it_should_select_attachments() {
+        let attachment = Attachment {
+            index: 2,
+            filename: "report.pdf".into(),
+            mime: "application/pdf".into(),
+            ..Attachment::default()
+        };
+
+        let select = |selector| AttachmentSelector::try_from(selector).unwrap();
+        assert_eq!(AttachmentSelector::Index(2), select("2"));
+        assert!(select("2").matches(&attachment));
+        assert!(!select("1").matches(&attachment));
+        assert!(select("application/*").matches(&attachment));
+        assert!(!select("image/*").matches(&attachment));
+        assert!(select("*.pdf").matches(&attachment));
+        assert!(!select("*.png").matches(&attachment));
+        assert!(AttachmentSelector::try_from("0").is_err());
+    }
+}
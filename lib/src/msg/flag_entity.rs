@@ -0,0 +1,161 @@
+//! Module related to message flags.
+//!
+//! This module regroups the message flag entities shared by all
+//! backends, and their parsers. Each backend converts them into its
+//! own representation.
+
+use anyhow::{anyhow, Context, Error, Result};
+use std::{convert::TryFrom, fmt, ops::Deref};
+
+/// Represents the flag variants.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+pub enum Flag {
+    Seen,
+    Answered,
+    Flagged,
+    Deleted,
+    Draft,
+    Recent,
+    Custom(String),
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Flag::Seen => write!(f, "seen"),
+            Flag::Answered => write!(f, "answered"),
+            Flag::Flagged => write!(f, "flagged"),
+            Flag::Deleted => write!(f, "deleted"),
+            Flag::Draft => write!(f, "draft"),
+            Flag::Recent => write!(f, "recent"),
+            Flag::Custom(custom) => write!(f, "{}", custom),
+        }
+    }
+}
+
+/// Parses a flag. Flags are case-insensitive, and they do not need
+/// to be prefixed with `\`. Custom flags must be valid IMAP atoms.
+impl TryFrom<&str> for Flag {
+    type Error = Error;
+
+    fn try_from(flag_str: &str) -> Result<Self, Self::Error> {
+        let flag_str = flag_str.trim();
+        let flag_str = flag_str.strip_prefix('\\').unwrap_or(flag_str);
+
+        match flag_str.to_lowercase().as_str() {
+            "seen" => Ok(Flag::Seen),
+            "answered" | "replied" => Ok(Flag::Answered),
+            "flagged" => Ok(Flag::Flagged),
+            "deleted" | "trashed" => Ok(Flag::Deleted),
+            "draft" => Ok(Flag::Draft),
+            "recent" => Ok(Flag::Recent),
+            "" => Err(anyhow!("cannot parse empty flag")),
+            _ if flag_str
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || "(){%*\"\\]".contains(c)) =>
+            {
+                Err(anyhow!(
+                    "cannot parse flag {:?}: invalid character",
+                    flag_str
+                ))
+            }
+            _ => Ok(Flag::Custom(flag_str.to_owned())),
+        }
+    }
+}
+
+/// Represents a list of flags.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Flags(pub Vec<Flag>);
+
+impl Flags {
+    /// Builds a symbols string
+    pub fn to_symbols_string(&self) -> String {
+        let mut flags = String::new();
+        flags.push_str(if self.contains(&Flag::Seen) {
+            " "
+        } else {
+            "✷"
+        });
+        flags.push_str(if self.contains(&Flag::Answered) {
+            "↵"
+        } else {
+            " "
+        });
+        flags.push_str(if self.contains(&Flag::Flagged) {
+            "⚑"
+        } else {
+            " "
+        });
+        flags
+    }
+}
+
+impl Deref for Flags {
+    type Target = Vec<Flag>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<Flag>> for Flags {
+    fn from(flags: Vec<Flag>) -> Self {
+        Self(flags)
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut glue = "";
+        for flag in self.iter() {
+            write!(f, "{}{}", glue, flag)?;
+            glue = " ";
+        }
+        Ok(())
+    }
+}
+
+/// Parses a whitespace-separated list of flags.
+impl TryFrom<&str> for Flags {
+    type Error = Error;
+
+    fn try_from(flags_str: &str) -> Result<Self, Self::Error> {
+        let mut flags: Vec<Flag> = vec![];
+        for flag_str in flags_str.split_whitespace() {
+            let flag = Flag::try_from(flag_str)
+                .with_context(|| format!("cannot parse flags {:?}", flags_str))?;
+            if !flags.contains(&flag) {
+                flags.push(flag);
+            }
+        }
+        Ok(Self(flags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_flags() {
+        assert_eq!(
+            Flags(vec![
+                Flag::Seen,
+                Flag::Answered,
+                Flag::Deleted,
+                Flag::Custom("passed".into())
+            ]),
+            Flags::try_from("\\Seen replied TRASHED passed seen").unwrap()
+        );
+        assert_eq!(Flags::default(), Flags::try_from("  ").unwrap());
+        assert!(Flags::try_from("seen (flagged)").is_err());
+        assert!(Flags::try_from("seen \\").is_err());
+    }
+
+    #[test]
+    fn it_should_display_flags() {
+        let flags = Flags(vec![Flag::Seen, Flag::Custom("Work".into())]);
+        assert_eq!("seen Work", flags.to_string());
+    }
+}
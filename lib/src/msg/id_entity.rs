@@ -0,0 +1,271 @@
+//! Module related to message identifiers.
+//!
+//! This module regroups the message identifier entities and their
+//! parsers. Each backend declares the kind of identifier it uses, see
+//! [`crate::backends::Backend::Id`], so that identifiers of a backend
+//! cannot be given to another one.
+
+use anyhow::{anyhow, Context, Error, Result};
+use serde::Serialize;
+use std::{convert::TryFrom, fmt, ops::Deref};
+
+/// Represents a kind of message identifier.
+pub trait MsgId:
+    fmt::Debug
+    + fmt::Display
+    + Default
+    + Clone
+    + Eq
+    + Serialize
+    + Send
+    + Sync
+    + for<'a> TryFrom<&'a str, Error = Error>
+{
+    /// Tells if identifiers of this kind can be grouped in ranges,
+    /// for example `1:3`.
+    const RANGES: bool = false;
+}
+
+/// Represents a message number: the UID of an IMAP message, or the
+/// identifier of an in-memory message.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Uid(pub u32);
+
+impl MsgId for Uid {
+    const RANGES: bool = true;
+}
+
+impl fmt::Display for Uid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TryFrom<&str> for Uid {
+    type Error = Error;
+
+    fn try_from(uid_str: &str) -> Result<Self, Self::Error> {
+        let uid_str = uid_str.trim();
+        uid_str
+            .parse()
+            .map(Self)
+            .with_context(|| format!("cannot parse message number {:?}", uid_str))
+    }
+}
+
+/// Represents the (short) hash of a Maildir or Notmuch message.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Hash(pub String);
+
+impl MsgId for Hash {}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TryFrom<&str> for Hash {
+    type Error = Error;
+
+    fn try_from(hash_str: &str) -> Result<Self, Self::Error> {
+        let hash_str = hash_str.trim();
+
+        if hash_str.is_empty() {
+            return Err(anyhow!("cannot parse empty message hash"));
+        }
+
+        if !hash_str.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("cannot parse message hash {:?}", hash_str));
+        }
+
+        Ok(Self(hash_str.to_lowercase()))
+    }
+}
+
+/// Represents an item of a message identifier set: either a single
+/// identifier or a range of identifiers. A range without end matches
+/// up to the last message of the mailbox.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum IdRange<I> {
+    One(I),
+    Range(I, Option<I>),
+}
+
+impl<I: MsgId> fmt::Display for IdRange<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdRange::One(id) => write!(f, "{}", id),
+            IdRange::Range(begin, Some(end)) => write!(f, "{}:{}", begin, end),
+            IdRange::Range(begin, None) => write!(f, "{}:*", begin),
+        }
+    }
+}
+
+impl<I: MsgId> TryFrom<&str> for IdRange<I> {
+    type Error = Error;
+
+    fn try_from(range_str: &str) -> Result<Self, Self::Error> {
+        match range_str.trim().split_once(':') {
+            None => Ok(IdRange::One(I::try_from(range_str)?)),
+            Some(_) if !I::RANGES => Err(anyhow!(
+                "cannot parse range {:?}: messages cannot be selected by range",
+                range_str.trim()
+            )),
+            Some((begin, end)) => {
+                let begin = I::try_from(begin).context("cannot parse range begin")?;
+                let end = match end.trim() {
+                    "*" => None,
+                    end => Some(I::try_from(end).context("cannot parse range end")?),
+                };
+                Ok(IdRange::Range(begin, end))
+            }
+        }
+    }
+}
+
+/// Represents a set of message identifiers, for example `1:3,5` or
+/// `a1b2,c3d4`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct IdSet<I>(pub Vec<IdRange<I>>);
+
+impl<I: MsgId> IdSet<I> {
+    /// Returns the identifiers of the set. Fails if the set contains
+    /// a range, since ranges cannot be expanded without the mailbox.
+    pub fn to_ids(&self) -> Result<Vec<I>> {
+        self.iter()
+            .map(|range| match range {
+                IdRange::One(id) => Ok(id.to_owned()),
+                range => Err(anyhow!(
+                    "cannot expand range {:?} into message ids",
+                    range.to_string()
+                )),
+            })
+            .collect()
+    }
+}
+
+impl IdSet<Uid> {
+    /// Builds the IMAP sequence set matching the identifier set.
+    pub fn to_seq_set(&self) -> String {
+        self.to_string()
+    }
+
+    /// Checks if the given message number belongs to the set.
+    pub fn contains(&self, uid: Uid) -> bool {
+        self.iter().any(|range| match range {
+            IdRange::One(id) => *id == uid,
+            IdRange::Range(begin, Some(end)) => *begin <= uid && uid <= *end,
+            IdRange::Range(begin, None) => *begin <= uid,
+        })
+    }
+}
+
+impl<I> Deref for IdSet<I> {
+    type Target = Vec<IdRange<I>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<I> From<I> for IdSet<I> {
+    fn from(id: I) -> Self {
+        Self(vec![IdRange::One(id)])
+    }
+}
+
+impl<I: MsgId> fmt::Display for IdSet<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut glue = "";
+        for range in self.iter() {
+            write!(f, "{}{}", glue, range)?;
+            glue = ",";
+        }
+        Ok(())
+    }
+}
+
+impl<I: MsgId> TryFrom<&str> for IdSet<I> {
+    type Error = Error;
+
+    fn try_from(set_str: &str) -> Result<Self, Self::Error> {
+        let mut ranges = vec![];
+        for range_str in set_str.split(',') {
+            ranges.push(
+                IdRange::try_from(range_str)
+                    .with_context(|| format!("cannot parse message id set {:?}", set_str))?,
+            );
+        }
+        Ok(Self(ranges))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_id() {
+        assert_eq!(Uid(42), Uid::try_from("42").unwrap());
+        assert_eq!(Uid(123), Uid::try_from("0123").unwrap());
+        assert!(Uid::try_from("").is_err());
+        assert!(Uid::try_from("a1b2").is_err());
+        assert!(Uid::try_from("12345678901").is_err());
+
+        assert_eq!(Hash("a1b2".into()), Hash::try_from("A1B2").unwrap());
+        assert_eq!("0123", Hash::try_from("0123").unwrap().to_string());
+        assert!(Hash::try_from("").is_err());
+        assert!(Hash::try_from("1 OR 2").is_err());
+    }
+
+    #[test]
+    fn it_should_parse_id_set() {
+        assert_eq!(
+            IdSet(vec![
+                IdRange::Range(Uid(1), Some(Uid(3))),
+                IdRange::One(Uid(5)),
+                IdRange::Range(Uid(7), None),
+            ]),
+            IdSet::try_from("1:3,5,7:*").unwrap()
+        );
+        assert_eq!(
+            "1:3,5,7:*",
+            IdSet::<Uid>::try_from("1:3, 5, 7:*").unwrap().to_string()
+        );
+        assert!(IdSet::<Uid>::try_from("1:a").is_err());
+        assert!(IdSet::<Uid>::try_from("1,,2").is_err());
+
+        assert_eq!(
+            IdSet(vec![
+                IdRange::One(Hash("a1b2".into())),
+                IdRange::One(Hash("5".into())),
+            ]),
+            IdSet::try_from("a1b2,5").unwrap()
+        );
+        assert!(IdSet::<Hash>::try_from("a1:b2").is_err());
+    }
+
+    #[test]
+    fn it_should_convert_id_set() {
+        let ids = IdSet::<Uid>::try_from("1:3,5").unwrap();
+        assert_eq!("1:3,5", ids.to_seq_set());
+        assert!(ids.to_ids().is_err());
+
+        let ids = IdSet::<Hash>::try_from("a1b2,5").unwrap();
+        assert_eq!(
+            vec![Hash("a1b2".into()), Hash("5".into())],
+            ids.to_ids().unwrap()
+        );
+    }
+
+    #[test]
+    fn it_should_check_id_set_contains_uid() {
+        let ids = IdSet::try_from("1:3,5,8:*").unwrap();
+        assert!(ids.contains(Uid(2)));
+        assert!(ids.contains(Uid(5)));
+        assert!(ids.contains(Uid(42)));
+        assert!(!ids.contains(Uid(4)));
+        assert!(!ids.contains(Uid(7)));
+    }
+}
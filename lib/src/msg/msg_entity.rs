@@ -17,17 +17,10 @@ use std::{
 use uuid::Uuid;
 
 use crate::{
-    backends::Backend,
-    config::{AccountConfig, DEFAULT_DRAFT_FOLDER, DEFAULT_SENT_FOLDER, DEFAULT_SIG_DELIM},
+    config::{AccountConfig, DEFAULT_SIG_DELIM},
     msg::{
-        from_addrs_to_sendable_addrs, from_addrs_to_sendable_mbox, from_slice_to_addrs, msg_utils,
-        Addr, Addrs, BinaryPart, Part, Parts, TextPlainPart, TplOverride,
-    },
-    output::PrinterService,
-    smtp::SmtpService,
-    ui::{
-        choice::{self, PostEditChoice, PreEditChoice},
-        editor,
+        from_addrs_to_sendable_addrs, from_addrs_to_sendable_mbox, from_slice_to_addrs, Addr,
+        Addrs, BinaryPart, Part, Parts, TextPlainPart, TplOverride,
     },
 };
 
@@ -327,100 +320,6 @@ impl Msg {
         Ok(self)
     }
 
-    fn _edit_with_editor(&self, tpl: TplOverride, account: &AccountConfig) -> Result<Self> {
-        let tpl = self.to_tpl(tpl, account)?;
-        let tpl = editor::open_with_tpl(tpl)?;
-        Self::from_tpl(&tpl)
-    }
-
-    pub fn edit_with_editor<'a, P: PrinterService, B: Backend<'a> + ?Sized, S: SmtpService>(
-        mut self,
-        tpl: TplOverride,
-        account: &AccountConfig,
-        printer: &mut P,
-        backend: Box<&'a mut B>,
-        smtp: &mut S,
-    ) -> Result<Box<&'a mut B>> {
-        info!("start editing with editor");
-
-        let draft = msg_utils::local_draft_path();
-        if draft.exists() {
-            loop {
-                match choice::pre_edit() {
-                    Ok(choice) => match choice {
-                        PreEditChoice::Edit => {
-                            let tpl = editor::open_with_draft()?;
-                            self.merge_with(Msg::from_tpl(&tpl)?);
-                            break;
-                        }
-                        PreEditChoice::Discard => {
-                            self.merge_with(self._edit_with_editor(tpl.clone(), account)?);
-                            break;
-                        }
-                        PreEditChoice::Quit => return Ok(backend),
-                    },
-                    Err(err) => {
-                        println!("{}", err);
-                        continue;
-                    }
-                }
-            }
-        } else {
-            self.merge_with(self._edit_with_editor(tpl.clone(), account)?);
-        }
-
-        loop {
-            match choice::post_edit() {
-                Ok(PostEditChoice::Send) => {
-                    printer.print_str("Sending message…")?;
-                    let sent_msg = smtp.send(account, &self)?;
-                    let sent_folder = account
-                        .mailboxes
-                        .get("sent")
-                        .map(|s| s.as_str())
-                        .unwrap_or(DEFAULT_SENT_FOLDER);
-                    printer
-                        .print_str(format!("Adding message to the {:?} folder…", sent_folder))?;
-                    backend.add_msg(&sent_folder, &sent_msg, "seen")?;
-                    msg_utils::remove_local_draft()?;
-                    printer.print_struct("Done!")?;
-                    break;
-                }
-                Ok(PostEditChoice::Edit) => {
-                    self.merge_with(self._edit_with_editor(tpl.clone(), account)?);
-                    continue;
-                }
-                Ok(PostEditChoice::LocalDraft) => {
-                    printer.print_struct("Message successfully saved locally")?;
-                    break;
-                }
-                Ok(PostEditChoice::RemoteDraft) => {
-                    let tpl = self.to_tpl(TplOverride::default(), account)?;
-                    let draft_folder = account
-                        .mailboxes
-                        .get("draft")
-                        .map(|s| s.as_str())
-                        .unwrap_or(DEFAULT_DRAFT_FOLDER);
-                    backend.add_msg(&draft_folder, tpl.as_bytes(), "seen draft")?;
-                    msg_utils::remove_local_draft()?;
-                    printer
-                        .print_struct(format!("Message successfully saved to {}", draft_folder))?;
-                    break;
-                }
-                Ok(PostEditChoice::Discard) => {
-                    msg_utils::remove_local_draft()?;
-                    break;
-                }
-                Err(err) => {
-                    println!("{}", err);
-                    continue;
-                }
-            }
-        }
-
-        Ok(backend)
-    }
-
     pub fn encrypt(mut self, encrypt: bool) -> Self {
         self.encrypt = encrypt;
         self
@@ -0,0 +1,481 @@
+//! Module related to message search queries.
+//!
+//! This module contains the search query language shared by all
+//! backends, and its parser. A query is made of terms combined with
+//! `and`, `or` and `not` (or `-`), grouped with parentheses. Terms
+//! next to each other are implicitly combined with `and`, which has
+//! a higher precedence than `or`.
+//!
+//! A term is either `key:value` where key is one of `from`, `to`,
+//! `cc`, `subject`, `body`, `flag`, `before` or `after`, or a bare
+//! value matching the subject, the sender or the body. Values
+//! containing whitespaces can be double-quoted. Dates follow the
+//! `YYYY-MM-DD` format: `before` is exclusive, `after` is inclusive.
+//!
+//! Queries are compiled to IMAP SEARCH keys, to notmuch queries, or
+//! matched in-process against parsed messages.
+
+use anyhow::{anyhow, Context, Error, Result};
+use chrono::{DateTime, NaiveDate};
+use mailparse::{MailHeaderMap, ParsedMail};
+use std::{convert::TryFrom, iter::Peekable, str::Chars};
+
+use crate::msg::{Flag, Flags};
+
+/// Represents a parsed search query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchQuery {
+    /// Matches all messages. This is the query of an empty string.
+    All,
+    And(Box<SearchQuery>, Box<SearchQuery>),
+    Or(Box<SearchQuery>, Box<SearchQuery>),
+    Not(Box<SearchQuery>),
+    /// Matches the subject, the sender or the body.
+    Text(String),
+    From(String),
+    To(String),
+    Cc(String),
+    Subject(String),
+    Body(String),
+    Flag(Flag),
+    Before(NaiveDate),
+    After(NaiveDate),
+}
+
+impl SearchQuery {
+    /// Compiles the query to IMAP SEARCH keys.
+    ///
+    /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-6.4.4
+    pub fn to_imap_query(&self) -> String {
+        match self {
+            Self::All => String::from("ALL"),
+            Self::And(left, right) => format!("{} {}", left.to_imap_query(), right.to_imap_query()),
+            Self::Or(left, right) => {
+                format!("OR {} {}", left.to_imap_key(), right.to_imap_key())
+            }
+            Self::Not(query) => format!("NOT {}", query.to_imap_key()),
+            Self::Text(value) => format!(
+                "OR SUBJECT {} OR FROM {} BODY {}",
+                imap_quote(value),
+                imap_quote(value),
+                imap_quote(value)
+            ),
+            Self::From(value) => format!("FROM {}", imap_quote(value)),
+            Self::To(value) => format!("TO {}", imap_quote(value)),
+            Self::Cc(value) => format!("CC {}", imap_quote(value)),
+            Self::Subject(value) => format!("SUBJECT {}", imap_quote(value)),
+            Self::Body(value) => format!("BODY {}", imap_quote(value)),
+            Self::Flag(Flag::Seen) => String::from("SEEN"),
+            Self::Flag(Flag::Answered) => String::from("ANSWERED"),
+            Self::Flag(Flag::Flagged) => String::from("FLAGGED"),
+            Self::Flag(Flag::Deleted) => String::from("DELETED"),
+            Self::Flag(Flag::Draft) => String::from("DRAFT"),
+            Self::Flag(Flag::Recent) => String::from("RECENT"),
+            Self::Flag(Flag::Custom(keyword)) => format!("KEYWORD {}", keyword),
+            Self::Before(date) => format!("BEFORE {}", date.format("%d-%b-%Y")),
+            Self::After(date) => format!("SINCE {}", date.format("%d-%b-%Y")),
+        }
+    }
+
+    /// Compiles the query to a single IMAP search key, wrapping
+    /// lists of keys with parentheses.
+    fn to_imap_key(&self) -> String {
+        match self {
+            Self::And(..) => format!("({})", self.to_imap_query()),
+            _ => self.to_imap_query(),
+        }
+    }
+
+    /// Compiles the query to a notmuch query. Flags are compiled to
+    /// tags, except the seen flag since notmuch tags unread messages
+    /// instead. Notmuch cannot search by Cc header, so `cc` terms are
+    /// rejected.
+    ///
+    /// [notmuch]: https://notmuchmail.org/doc/latest/man7/notmuch-search-terms.html
+    pub fn to_notmuch_query(&self) -> Result<String> {
+        Ok(match self {
+            Self::All => String::from("*"),
+            Self::And(left, right) => format!(
+                "({} and {})",
+                left.to_notmuch_query()?,
+                right.to_notmuch_query()?
+            ),
+            Self::Or(left, right) => format!(
+                "({} or {})",
+                left.to_notmuch_query()?,
+                right.to_notmuch_query()?
+            ),
+            Self::Not(query) if **query == Self::Flag(Flag::Seen) => String::from("tag:unread"),
+            Self::Not(query) => format!("(not {})", query.to_notmuch_query()?),
+            Self::Text(value) => format!(
+                "(subject:{} or from:{} or body:{})",
+                notmuch_quote(value),
+                notmuch_quote(value),
+                notmuch_quote(value)
+            ),
+            Self::From(value) => format!("from:{}", notmuch_quote(value)),
+            Self::To(value) => format!("to:{}", notmuch_quote(value)),
+            Self::Cc(value) => {
+                return Err(anyhow!(
+                    "cannot compile query cc:{:?} to notmuch: unsupported term",
+                    value
+                ))
+            }
+            Self::Subject(value) => format!("subject:{}", notmuch_quote(value)),
+            Self::Body(value) => format!("body:{}", notmuch_quote(value)),
+            Self::Flag(Flag::Seen) => String::from("(not tag:unread)"),
+            Self::Flag(flag) => format!("tag:{}", notmuch_quote(&flag.to_string())),
+            // Notmuch date ranges include the whole end day.
+            Self::Before(date) => format!(
+                "date:..{}",
+                date.pred_opt().unwrap_or(*date).format("%Y-%m-%d")
+            ),
+            Self::After(date) => format!("date:{}..", date.format("%Y-%m-%d")),
+        })
+    }
+
+    /// Checks if the given parsed message with the given flags
+    /// matches the query. Text comparisons are case-insensitive, and
+    /// dates are compared in the timezone of the message.
+    pub fn matches(&self, parsed_mail: &ParsedMail, flags: &Flags) -> bool {
+        let contains = |key: &str, value: &str| {
+            parsed_mail
+                .headers
+                .get_all_values(key)
+                .iter()
+                .any(|header| header.to_lowercase().contains(&value.to_lowercase()))
+        };
+        match self {
+            Self::All => true,
+            Self::And(left, right) => {
+                left.matches(parsed_mail, flags) && right.matches(parsed_mail, flags)
+            }
+            Self::Or(left, right) => {
+                left.matches(parsed_mail, flags) || right.matches(parsed_mail, flags)
+            }
+            Self::Not(query) => !query.matches(parsed_mail, flags),
+            Self::Text(value) => {
+                contains("subject", value)
+                    || contains("from", value)
+                    || body(parsed_mail).contains(&value.to_lowercase())
+            }
+            Self::From(value) => contains("from", value),
+            Self::To(value) => contains("to", value),
+            Self::Cc(value) => contains("cc", value),
+            Self::Subject(value) => contains("subject", value),
+            Self::Body(value) => body(parsed_mail).contains(&value.to_lowercase()),
+            Self::Flag(flag) => flags.contains(flag),
+            Self::Before(date) => matches!(local_date(parsed_mail), Some(d) if d < *date),
+            Self::After(date) => matches!(local_date(parsed_mail), Some(d) if d >= *date),
+        }
+    }
+}
+
+/// Parses a query. An empty query matches all messages.
+impl TryFrom<&str> for SearchQuery {
+    type Error = Error;
+
+    fn try_from(query_str: &str) -> Result<Self, Self::Error> {
+        let tokens =
+            tokenize(query_str).with_context(|| format!("cannot parse query {:?}", query_str))?;
+        if tokens.is_empty() {
+            return Ok(Self::All);
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser
+            .parse_or()
+            .with_context(|| format!("cannot parse query {:?}", query_str))?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(query),
+            Some(token) => Err(anyhow!(
+                "cannot parse query {:?}: unexpected {:?}",
+                query_str,
+                token
+            )),
+        }
+    }
+}
+
+/// Represents a query token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term(Option<String>, String),
+}
+
+/// Splits the given query into tokens.
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '"' => tokens.push(Token::Term(None, read_quoted(&mut chars)?)),
+            '-' if matches!(chars.peek(), Some(c) if !c.is_whitespace()) => tokens.push(Token::Not),
+            c => {
+                let mut key = None;
+                let mut value = String::from(c);
+                while let Some(c) = chars.peek().cloned() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    if c == ':' && key.is_none() {
+                        key = Some(value.to_lowercase());
+                        value = if chars.peek() == Some(&'"') {
+                            chars.next();
+                            read_quoted(&mut chars)?
+                        } else {
+                            String::new()
+                        };
+                    } else {
+                        value.push(c);
+                    }
+                }
+                tokens.push(match (key, value.to_lowercase().as_str()) {
+                    (None, "and") => Token::And,
+                    (None, "or") => Token::Or,
+                    (None, "not") => Token::Not,
+                    (key, _) => Token::Term(key, value),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads a double-quoted string, the opening quote being already
+/// consumed. Quotes and backslashes can be escaped with a backslash.
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(value),
+            Some('\\') => match chars.next() {
+                Some(c) => value.push(c),
+                None => break,
+            },
+            Some(c) => value.push(c),
+            None => break,
+        }
+    }
+    Err(anyhow!("unterminated quoted string {:?}", value))
+}
+
+/// Represents the recursive descent parser of the query tokens.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn parse_or(&mut self) -> Result<SearchQuery> {
+        let mut query = self.parse_and()?;
+        while self.tokens.get(self.pos) == Some(&Token::Or) {
+            self.pos += 1;
+            query = SearchQuery::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<SearchQuery> {
+        let mut query = self.parse_not()?;
+        loop {
+            match self.tokens.get(self.pos) {
+                None | Some(Token::Or) | Some(Token::RParen) => return Ok(query),
+                Some(Token::And) => self.pos += 1,
+                Some(_) => (),
+            }
+            query = SearchQuery::And(Box::new(query), Box::new(self.parse_not()?));
+        }
+    }
+
+    fn parse_not(&mut self) -> Result<SearchQuery> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("unexpected end of query"))?;
+        self.pos += 1;
+        match token {
+            Token::Not => Ok(SearchQuery::Not(Box::new(self.parse_not()?))),
+            Token::LParen => {
+                let query = self.parse_or()?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::RParen) => {
+                        self.pos += 1;
+                        Ok(query)
+                    }
+                    _ => Err(anyhow!("missing closing parenthesis")),
+                }
+            }
+            Token::Term(key, value) => parse_term(key.as_deref(), value),
+            token => Err(anyhow!("unexpected {:?}", token)),
+        }
+    }
+}
+
+/// Parses a `key:value` term, or a bare value if there is no key.
+fn parse_term(key: Option<&str>, value: String) -> Result<SearchQuery> {
+    if value.is_empty() {
+        return Err(anyhow!(
+            "empty value for term {:?}",
+            key.unwrap_or_default()
+        ));
+    }
+    let parse_date = |value: &str| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .with_context(|| format!("cannot parse date {:?}, expected YYYY-MM-DD", value))
+    };
+    Ok(match key {
+        None => SearchQuery::Text(value),
+        Some("from") => SearchQuery::From(value),
+        Some("to") => SearchQuery::To(value),
+        Some("cc") => SearchQuery::Cc(value),
+        Some("subject") => SearchQuery::Subject(value),
+        Some("body") => SearchQuery::Body(value),
+        Some("flag") => SearchQuery::Flag(Flag::try_from(value.as_str())?),
+        Some("before") => SearchQuery::Before(parse_date(&value)?),
+        Some("after") => SearchQuery::After(parse_date(&value)?),
+        Some(key) => return Err(anyhow!("unknown term key {:?}", key)),
+    })
+}
+
+/// Quotes the given value as an IMAP quoted string.
+pub(crate) fn imap_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quotes the given value as a notmuch quoted string.
+fn notmuch_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Returns the date of the message in its own timezone, if any.
+fn local_date(parsed_mail: &ParsedMail) -> Option<NaiveDate> {
+    let date = parsed_mail.headers.get_first_value("date")?;
+    let date = date.split_at(date.find(" (").unwrap_or(date.len())).0;
+    DateTime::parse_from_rfc2822(date.trim())
+        .ok()
+        .map(|date| date.naive_local().date())
+}
+
+/// Returns the lowercased text parts of the message.
+fn body(parsed_mail: &ParsedMail) -> String {
+    if parsed_mail.subparts.is_empty() {
+        if parsed_mail.ctype.mimetype.starts_with("text/") {
+            parsed_mail.get_body().unwrap_or_default().to_lowercase()
+        } else {
+            String::new()
+        }
+    } else {
+        parsed_mail
+            .subparts
+            .iter()
+            .map(body)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(query: &str) -> SearchQuery {
+        SearchQuery::try_from(query).unwrap()
+    }
+
+    #[test]
+    fn it_should_parse_queries() {
+        assert_eq!(SearchQuery::All, query("  "));
+        assert_eq!(
+            SearchQuery::Or(
+                Box::new(SearchQuery::And(
+                    Box::new(SearchQuery::From(String::from("alice"))),
+                    Box::new(SearchQuery::Not(Box::new(SearchQuery::Flag(Flag::Seen)))),
+                )),
+                Box::new(SearchQuery::Subject(String::from("hello world"))),
+            ),
+            query("from:alice -flag:seen OR subject:\"hello world\"")
+        );
+        assert_eq!(query("(a or b) and not c"), query("(a OR b) -c"),);
+        assert_eq!(
+            SearchQuery::After(NaiveDate::from_ymd_opt(2022, 3, 1).unwrap()),
+            query("after:2022-03-01")
+        );
+        assert!(SearchQuery::try_from("subject:").is_err());
+        assert!(SearchQuery::try_from("flag:(seen)").is_err());
+        assert!(SearchQuery::try_from("(a or b").is_err());
+        assert!(SearchQuery::try_from("a)").is_err());
+        assert!(SearchQuery::try_from("size:42").is_err());
+        assert!(SearchQuery::try_from("before:yesterday").is_err());
+        assert!(SearchQuery::try_from("\"unterminated").is_err());
+    }
+
+    #[test]
+    fn it_should_compile_to_imap() {
+        assert_eq!("ALL", query("").to_imap_query());
+        assert_eq!(
+            "OR (FROM \"alice\" SEEN) NOT SUBJECT \"say \\\"hi\\\"\"",
+            query("from:alice flag:seen or not subject:\"say \\\"hi\\\"\"").to_imap_query()
+        );
+        assert_eq!(
+            "SINCE 01-Mar-2022 BEFORE 02-Mar-2022 KEYWORD todo",
+            query("after:2022-03-01 before:2022-03-02 flag:todo").to_imap_query()
+        );
+    }
+
+    #[test]
+    fn it_should_compile_to_notmuch() {
+        assert_eq!("*", query("").to_notmuch_query().unwrap());
+        assert_eq!(
+            "((from:\"alice\" and (not tag:unread)) or (not subject:\"say \"\"hi\"\"\"))",
+            query("from:alice flag:seen or not subject:\"say \\\"hi\\\"\"")
+                .to_notmuch_query()
+                .unwrap()
+        );
+        assert_eq!(
+            "(date:2022-03-01.. and date:..2022-03-01)",
+            query("after:2022-03-01 before:2022-03-02")
+                .to_notmuch_query()
+                .unwrap()
+        );
+        assert_eq!(
+            "(tag:unread and tag:\"flagged\")",
+            query("-flag:seen flag:flagged").to_notmuch_query().unwrap()
+        );
+        assert!(query("cc:bob").to_notmuch_query().is_err());
+    }
+
+    #[test]
+    fn it_should_match_messages() {
+        let raw = concat!(
+            "From: Alice <alice@localhost>\r\n",
+            "To: bob@localhost\r\n",
+            "Subject: Meeting\r\n",
+            "Date: Tue, 1 Mar 2022 23:30:00 -0500\r\n",
+            "\r\n",
+            "See you in the meeting room.\r\n",
+        );
+        let parsed_mail = mailparse::parse_mail(raw.as_bytes()).unwrap();
+        let flags = Flags::from(vec![Flag::Seen]);
+        let matches = |query_str: &str| query(query_str).matches(&parsed_mail, &flags);
+
+        assert!(matches(""));
+        assert!(matches("ROOM"));
+        assert!(matches("from:alice subject:meeting"));
+        assert!(matches("to:bob flag:seen"));
+        assert!(!matches("flag:flagged"));
+        assert!(matches("flag:flagged or body:\"meeting room\""));
+        assert!(!matches("not (alice or carol)"));
+        assert!(matches("after:2022-03-01 before:2022-03-02"));
+        assert!(!matches("after:2022-03-02"));
+    }
+}
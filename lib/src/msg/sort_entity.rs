@@ -0,0 +1,181 @@
+//! Module related to message sort criteria.
+//!
+//! This module regroups the message sort criteria shared by all
+//! backends, and their parser.
+
+use anyhow::{anyhow, Error, Result};
+use chrono::{DateTime, FixedOffset};
+use std::{convert::TryFrom, fmt, ops::Deref};
+
+/// Represents the message property a criterion sorts by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum SortCriterionKind {
+    Arrival,
+    Cc,
+    Date,
+    From,
+    Size,
+    Subject,
+    To,
+}
+
+/// Represents the sort order of a criterion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum SortCriterionOrder {
+    Asc,
+    Desc,
+}
+
+/// Represents a message sort criterion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct SortCriterion {
+    pub kind: SortCriterionKind,
+    pub order: SortCriterionOrder,
+}
+
+impl fmt::Display for SortCriterion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            SortCriterionKind::Arrival => "arrival",
+            SortCriterionKind::Cc => "cc",
+            SortCriterionKind::Date => "date",
+            SortCriterionKind::From => "from",
+            SortCriterionKind::Size => "size",
+            SortCriterionKind::Subject => "subject",
+            SortCriterionKind::To => "to",
+        };
+        let order = match self.order {
+            SortCriterionOrder::Asc => "asc",
+            SortCriterionOrder::Desc => "desc",
+        };
+        write!(f, "{}:{}", kind, order)
+    }
+}
+
+/// Parses a criterion of the form `kind[:order]`, the order being
+/// ascending by default.
+impl TryFrom<&str> for SortCriterion {
+    type Error = Error;
+
+    fn try_from(criterion_str: &str) -> Result<Self, Self::Error> {
+        let (kind_str, order_str) = criterion_str
+            .trim()
+            .split_once(':')
+            .unwrap_or((criterion_str.trim(), "asc"));
+        let kind = match kind_str {
+            "arrival" => SortCriterionKind::Arrival,
+            "cc" => SortCriterionKind::Cc,
+            "date" => SortCriterionKind::Date,
+            "from" => SortCriterionKind::From,
+            "size" => SortCriterionKind::Size,
+            "subject" => SortCriterionKind::Subject,
+            "to" => SortCriterionKind::To,
+            _ => return Err(anyhow!("cannot parse sort criterion {:?}", criterion_str)),
+        };
+        let order = match order_str {
+            "asc" => SortCriterionOrder::Asc,
+            "desc" => SortCriterionOrder::Desc,
+            _ => return Err(anyhow!("cannot parse sort criterion {:?}", criterion_str)),
+        };
+        Ok(Self { kind, order })
+    }
+}
+
+/// Represents the message sort criteria. Empty criteria mean that
+/// the backend default order applies.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SortCriteria(pub Vec<SortCriterion>);
+
+impl Deref for SortCriteria {
+    type Target = Vec<SortCriterion>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Display for SortCriteria {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut glue = "";
+        for criterion in self.iter() {
+            write!(f, "{}{}", glue, criterion)?;
+            glue = " ";
+        }
+        Ok(())
+    }
+}
+
+/// Parses a whitespace-separated list of criteria.
+impl TryFrom<&str> for SortCriteria {
+    type Error = Error;
+
+    fn try_from(criteria_str: &str) -> Result<Self, Self::Error> {
+        let mut criteria = vec![];
+        for criterion_str in criteria_str.split_whitespace() {
+            criteria.push(SortCriterion::try_from(criterion_str)?);
+        }
+        Ok(Self(criteria))
+    }
+}
+
+/// Parses the given RFC2822 date, ignoring trailing comments like
+/// `(UTC)`.
+pub fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    let date = date.split_at(date.find(" (").unwrap_or(date.len())).0;
+    DateTime::parse_from_rfc2822(date.trim()).ok()
+}
+
+/// Extracts the base subject of the given subject, as defined by
+/// [RFC5256] for backends sorting messages in-process: leading reply
+/// and forward prefixes and trailing `(fwd)` are removed.
+///
+/// [RFC5256]: https://datatracker.ietf.org/doc/html/rfc5256#section-2.1
+pub fn base_subject(subject: &str) -> String {
+    let mut subject = subject.trim().to_lowercase();
+    loop {
+        let prev_len = subject.len();
+        for prefix in ["re:", "fwd:", "fw:"] {
+            if let Some(rest) = subject.strip_prefix(prefix) {
+                subject = rest.trim_start().to_owned();
+            }
+        }
+        if let Some(rest) = subject.strip_suffix("(fwd)") {
+            subject = rest.trim_end().to_owned();
+        }
+        if subject.len() == prev_len {
+            return subject;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_sort_criteria() {
+        assert_eq!(
+            SortCriteria(vec![
+                SortCriterion {
+                    kind: SortCriterionKind::Date,
+                    order: SortCriterionOrder::Desc,
+                },
+                SortCriterion {
+                    kind: SortCriterionKind::Subject,
+                    order: SortCriterionOrder::Asc,
+                },
+            ]),
+            SortCriteria::try_from("date:desc subject").unwrap()
+        );
+        assert_eq!(SortCriteria::default(), SortCriteria::try_from("").unwrap());
+        assert!(SortCriteria::try_from("date:up").is_err());
+        assert!(SortCriteria::try_from("weight").is_err());
+    }
+
+    #[test]
+    fn it_should_extract_base_subject() {
+        assert_eq!("meeting", base_subject("Re: FWD: Meeting (fwd)"));
+        assert_eq!("re meeting", base_subject("  Re Meeting "));
+        assert_eq!("", base_subject("Re: "));
+    }
+}
@@ -0,0 +1,429 @@
+//! Message thread module.
+//!
+//! This module provides the message thread tree shared by the
+//! backends, and the client-side threading algorithm described by
+//! Jamie Zawinski ([JWZ]), used when the backend cannot thread
+//! messages on its own.
+//!
+//! [JWZ]: https://www.jwz.org/doc/threading.html
+
+use std::{any, collections::HashMap, fmt};
+
+pub trait Threads: fmt::Debug + erased_serde::Serialize + any::Any + Send {
+    fn as_any(&self) -> &dyn any::Any;
+}
+
+impl<T: fmt::Debug + erased_serde::Serialize + any::Any + Send> Threads for T {
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
+
+/// Represents a node of a message thread. Nodes without message
+/// stand for messages referenced by others but missing from the
+/// mailbox, they only group their children.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ThreadNode<T> {
+    /// Represents the message of the node.
+    #[serde(flatten)]
+    pub msg: Option<T>,
+
+    /// Represents the replies to the message.
+    pub children: Vec<ThreadNode<T>>,
+}
+
+impl<T> ThreadNode<T> {
+    pub fn new(msg: Option<T>, children: Vec<ThreadNode<T>>) -> Self {
+        Self { msg, children }
+    }
+
+    /// Gets the messages of the thread, depth first.
+    pub fn msgs(&self) -> Vec<&T> {
+        self.flatten().into_iter().map(|(_, msg)| msg).collect()
+    }
+
+    /// Gets the messages of the thread along with their depth, depth
+    /// first. Children of nodes without message keep the depth of
+    /// their parent.
+    pub fn flatten(&self) -> Vec<(usize, &T)> {
+        let mut msgs = vec![];
+        self.flatten_at(0, &mut msgs);
+        msgs
+    }
+
+    fn flatten_at<'a>(&'a self, depth: usize, msgs: &mut Vec<(usize, &'a T)>) {
+        let depth = match self.msg {
+            Some(ref msg) => {
+                msgs.push((depth, msg));
+                depth + 1
+            }
+            None => depth,
+        };
+        for child in self.children.iter() {
+            child.flatten_at(depth, msgs);
+        }
+    }
+
+    /// Maps the messages of the thread. Messages that cannot be
+    /// mapped leave their node empty.
+    pub fn map<U>(self, f: &mut impl FnMut(T) -> Option<U>) -> ThreadNode<U> {
+        ThreadNode {
+            msg: self.msg.and_then(&mut *f),
+            children: self
+                .children
+                .into_iter()
+                .map(|child| child.map(f))
+                .collect(),
+        }
+    }
+}
+
+/// Represents the headers of a message used to thread it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ThreadRefs {
+    /// Represents the id of the message.
+    pub message_id: Option<String>,
+
+    /// Represents the ids of the ancestors of the message, from the
+    /// root of the thread to the parent of the message.
+    pub references: Vec<String>,
+}
+
+impl ThreadRefs {
+    /// Builds the thread references out of the raw values of the
+    /// `Message-ID`, `In-Reply-To` and `References` headers. The
+    /// parent from `In-Reply-To` is added to the references when
+    /// missing.
+    pub fn new(
+        message_id: Option<&str>,
+        in_reply_to: Option<&str>,
+        references: Option<&str>,
+    ) -> Self {
+        let message_id = message_id.and_then(|id| parse_msg_ids(id).into_iter().next());
+        let mut references = references.map(parse_msg_ids).unwrap_or_default();
+        if let Some(parent) = in_reply_to.and_then(|id| parse_msg_ids(id).into_iter().next()) {
+            if !references.contains(&parent) {
+                references.push(parent);
+            }
+        }
+        references.retain(|id| Some(id) != message_id.as_ref());
+        Self {
+            message_id,
+            references,
+        }
+    }
+}
+
+/// Extracts the message ids (`<id@host>`) out of a header value.
+pub fn parse_msg_ids(value: &str) -> Vec<String> {
+    let mut ids = vec![];
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        match rest.find('>') {
+            Some(end) => {
+                let id: String = rest[..=end].split_whitespace().collect();
+                if id.len() > 2 {
+                    ids.push(id);
+                }
+                rest = &rest[end + 1..];
+            }
+            None => break,
+        }
+    }
+    ids
+}
+
+/// Represents a message container of the JWZ algorithm.
+struct Container<T> {
+    msg: Option<T>,
+    order: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// Represents the state of the JWZ algorithm: containers are stored
+/// in an arena and refer to each other by index.
+struct Threader<T> {
+    containers: Vec<Container<T>>,
+    ids: HashMap<String, usize>,
+}
+
+impl<T> Threader<T> {
+    fn new_container(&mut self) -> usize {
+        self.containers.push(Container {
+            msg: None,
+            order: None,
+            parent: None,
+            children: vec![],
+        });
+        self.containers.len() - 1
+    }
+
+    fn get_or_new_container(&mut self, id: &str) -> usize {
+        match self.ids.get(id) {
+            Some(idx) => *idx,
+            None => {
+                let idx = self.new_container();
+                self.ids.insert(id.to_owned(), idx);
+                idx
+            }
+        }
+    }
+
+    /// Checks if linking the given child to the given parent would
+    /// create a loop, in other words if the child is an ancestor of
+    /// the parent.
+    fn is_ancestor(&self, idx: usize, of: usize) -> bool {
+        let mut cursor = Some(of);
+        while let Some(current) = cursor {
+            if current == idx {
+                return true;
+            }
+            cursor = self.containers[current].parent;
+        }
+        false
+    }
+
+    fn unlink(&mut self, idx: usize) {
+        if let Some(parent) = self.containers[idx].parent.take() {
+            self.containers[parent]
+                .children
+                .retain(|child| *child != idx);
+        }
+    }
+
+    fn link(&mut self, parent: usize, child: usize) {
+        self.unlink(child);
+        self.containers[child].parent = Some(parent);
+        self.containers[parent].children.push(child);
+    }
+
+    fn add_msg(&mut self, order: usize, msg: T, refs: ThreadRefs) {
+        // Messages sharing the same id are all kept, the first one
+        // gets the id.
+        let idx = match refs.message_id {
+            Some(ref id) => match self.ids.get(id) {
+                Some(idx) if self.containers[*idx].msg.is_none() => *idx,
+                Some(_) => self.new_container(),
+                None => self.get_or_new_container(id),
+            },
+            None => self.new_container(),
+        };
+        self.containers[idx].msg = Some(msg);
+        self.containers[idx].order = Some(order);
+
+        // Links the references together, without breaking the links
+        // already known.
+        let mut prev = None;
+        for id in refs.references.iter() {
+            let ref_idx = self.get_or_new_container(id);
+            if let Some(prev) = prev {
+                if self.containers[ref_idx].parent.is_none() && !self.is_ancestor(ref_idx, prev) {
+                    self.link(prev, ref_idx);
+                }
+            }
+            prev = Some(ref_idx);
+        }
+
+        // The references of the message itself are authoritative:
+        // its parent is the last reference.
+        match prev {
+            Some(parent) if !self.is_ancestor(idx, parent) => self.link(parent, idx),
+            Some(_) => (),
+            None => self.unlink(idx),
+        }
+    }
+
+    /// Builds the nodes of the given container, along with the order
+    /// of their first message. Empty containers are replaced by
+    /// their children.
+    fn build(&mut self, idx: usize) -> Vec<(usize, ThreadNode<T>)> {
+        let mut children: Vec<_> = self.containers[idx]
+            .children
+            .clone()
+            .into_iter()
+            .flat_map(|child| self.build(child))
+            .collect();
+        children.sort_by_key(|(order, _)| *order);
+
+        let container = &mut self.containers[idx];
+        match container.msg.take() {
+            Some(msg) => {
+                let order = container.order.unwrap_or(usize::MAX);
+                let children = children.into_iter().map(|(_, node)| node).collect();
+                vec![(order, ThreadNode::new(Some(msg), children))]
+            }
+            None => children,
+        }
+    }
+
+    fn into_threads(mut self) -> Vec<ThreadNode<T>> {
+        let roots: Vec<_> = (0..self.containers.len())
+            .filter(|idx| self.containers[*idx].parent.is_none())
+            .collect();
+
+        let mut threads = vec![];
+        for idx in roots {
+            let nodes = self.build(idx);
+            // Replies to the same missing message stay grouped under
+            // an empty root, instead of becoming separate threads.
+            if self.containers[idx].order.is_none() && nodes.len() > 1 {
+                let order = nodes[0].0;
+                let children = nodes.into_iter().map(|(_, node)| node).collect();
+                threads.push((order, ThreadNode::new(None, children)));
+            } else {
+                threads.extend(nodes);
+            }
+        }
+        threads.sort_by_key(|(order, _)| *order);
+        threads.into_iter().map(|(_, node)| node).collect()
+    }
+}
+
+/// Threads the given messages using the JWZ algorithm, without its
+/// subject grouping step. Messages are expected in chronological
+/// order, which is kept among threads and among siblings.
+pub fn thread_msgs<T>(msgs: Vec<(T, ThreadRefs)>) -> Vec<ThreadNode<T>> {
+    let mut threader = Threader {
+        containers: vec![],
+        ids: HashMap::new(),
+    };
+    for (order, (msg, refs)) in msgs.into_iter().enumerate() {
+        threader.add_msg(order, msg, refs);
+    }
+    threader.into_threads()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(msg: u32, children: Vec<ThreadNode<u32>>) -> ThreadNode<u32> {
+        ThreadNode::new(Some(msg), children)
+    }
+
+    fn refs(id: &str, references: &str) -> ThreadRefs {
+        ThreadRefs::new(Some(id), None, Some(references))
+    }
+
+    #[test]
+    fn it_should_parse_msg_ids() {
+        assert_eq!(
+            vec!["<a@host>", "<b@host>"],
+            parse_msg_ids("<a@host>\r\n <b@host>")
+        );
+        assert_eq!(
+            vec!["<a@host>"],
+            parse_msg_ids("<a@host> (Alice's message)")
+        );
+        assert_eq!(vec!["<a@host>"], parse_msg_ids("< a@host >"));
+        assert!(parse_msg_ids("a@host").is_empty());
+        assert!(parse_msg_ids("<>").is_empty());
+    }
+
+    #[test]
+    fn it_should_build_thread_refs() {
+        assert_eq!(
+            ThreadRefs {
+                message_id: Some("<c@host>".into()),
+                references: vec!["<a@host>".into(), "<b@host>".into()],
+            },
+            ThreadRefs::new(Some("<c@host>"), Some("<b@host>"), Some("<a@host>"))
+        );
+        assert_eq!(
+            ThreadRefs {
+                message_id: Some("<c@host>".into()),
+                references: vec!["<a@host>".into(), "<b@host>".into()],
+            },
+            ThreadRefs::new(
+                Some("<c@host>"),
+                Some("<b@host>"),
+                Some("<a@host> <b@host>")
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_thread_msgs() {
+        let threads = thread_msgs(vec![
+            (1, refs("<1@host>", "")),
+            (2, refs("<2@host>", "<1@host>")),
+            (3, refs("<3@host>", "")),
+            (4, refs("<4@host>", "<1@host> <2@host>")),
+            (5, refs("<5@host>", "<1@host>")),
+        ]);
+        assert_eq!(
+            vec![
+                node(1, vec![node(2, vec![node(4, vec![])]), node(5, vec![])]),
+                node(3, vec![]),
+            ],
+            threads
+        );
+    }
+
+    #[test]
+    fn it_should_thread_msgs_received_out_of_order() {
+        let threads = thread_msgs(vec![
+            (3, refs("<3@host>", "<1@host> <2@host>")),
+            (2, refs("<2@host>", "<1@host>")),
+            (1, refs("<1@host>", "")),
+        ]);
+        assert_eq!(vec![node(1, vec![node(2, vec![node(3, vec![])])])], threads);
+    }
+
+    #[test]
+    fn it_should_group_replies_to_missing_msgs() {
+        let threads = thread_msgs(vec![
+            (2, refs("<2@host>", "<1@host>")),
+            (3, refs("<3@host>", "<1@host>")),
+            (4, refs("<4@host>", "<0@host> <1@host> <x@host>")),
+            (5, refs("<5@host>", "<y@host>")),
+        ]);
+        assert_eq!(
+            vec![
+                ThreadNode::new(
+                    None,
+                    vec![node(2, vec![]), node(3, vec![]), node(4, vec![])]
+                ),
+                node(5, vec![]),
+            ],
+            threads
+        );
+        assert_eq!(vec![(0, &2), (0, &3), (0, &4)], threads[0].flatten());
+    }
+
+    #[test]
+    fn it_should_not_create_loops() {
+        let threads = thread_msgs(vec![
+            (1, refs("<1@host>", "<2@host>")),
+            (2, refs("<2@host>", "<1@host>")),
+        ]);
+        assert_eq!(vec![node(2, vec![node(1, vec![])])], threads);
+
+        let threads = thread_msgs(vec![
+            (1, refs("<1@host>", "")),
+            (2, refs("<1@host>", "<1@host>")),
+        ]);
+        assert_eq!(vec![node(1, vec![]), node(2, vec![])], threads);
+    }
+
+    #[test]
+    fn it_should_flatten_and_map_threads() {
+        let thread = node(1, vec![node(2, vec![node(4, vec![])]), node(5, vec![])]);
+        assert_eq!(vec![&1, &2, &4, &5], thread.msgs());
+        assert_eq!(vec![(0, &1), (1, &2), (2, &4), (1, &5)], thread.flatten());
+
+        let thread = thread.map(&mut |n| if n == 2 { None } else { Some(n * 10) });
+        assert_eq!(
+            ThreadNode::new(
+                Some(10),
+                vec![
+                    ThreadNode::new(None, vec![node(40, vec![])]),
+                    node(50, vec![])
+                ]
+            ),
+            thread
+        );
+    }
+}
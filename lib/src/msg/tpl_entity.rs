@@ -0,0 +1,18 @@
+//! Module related to message templates.
+//!
+//! This module regroups the entities used to customize message
+//! templates.
+
+/// Represents the values that override the ones of the message when
+/// generating a template.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct TplOverride<'a> {
+    pub subject: Option<&'a str>,
+    pub from: Option<Vec<&'a str>>,
+    pub to: Option<Vec<&'a str>>,
+    pub cc: Option<Vec<&'a str>>,
+    pub bcc: Option<Vec<&'a str>>,
+    pub headers: Option<Vec<&'a str>>,
+    pub body: Option<&'a str>,
+    pub sig: Option<&'a str>,
+}
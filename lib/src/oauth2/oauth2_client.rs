@@ -0,0 +1,253 @@
+//! OAuth 2.0 client module.
+//!
+//! This module contains the client of the OAuth 2.0 token endpoint,
+//! used to exchange refresh tokens for access tokens. Requests go
+//! through a [`ureq`] agent with timeouts, secured like the protocol
+//! connection.
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::debug;
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use url::Url;
+
+use crate::config::TlsConfig;
+
+/// Represents the maximum time spent connecting to the token
+/// endpoint.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Represents the maximum time spent on a token endpoint request,
+/// from the connection to the end of the response.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Represents the maximum number of redirections followed when
+/// posting a form.
+const MAX_REDIRECTS: usize = 5;
+
+/// Represents the successful response of a token endpoint.
+#[derive(Debug, Deserialize)]
+pub struct OAuth2TokenResponse {
+    pub access_token: String,
+    pub expires_in: Option<i64>,
+    pub refresh_token: Option<String>,
+}
+
+/// Represents the error response of a token endpoint. Only the error
+/// code is kept, the rest of the response may contain secrets.
+#[derive(Debug, Deserialize)]
+struct OAuth2ErrorResponse {
+    error: String,
+}
+
+/// Represents the client of a token endpoint.
+pub struct OAuth2Client {
+    agent: ureq::Agent,
+}
+
+impl OAuth2Client {
+    /// Builds a client trusting the same certificate authorities as
+    /// the given protocol TLS config. The client certificate and the
+    /// pinned fingerprint are left aside since they belong to the
+    /// mail server.
+    pub fn new(tls: &TlsConfig) -> Result<Self> {
+        Self::with_timeout(tls, TIMEOUT)
+    }
+
+    /// Builds a client giving up on requests lasting more than the
+    /// given timeout.
+    pub fn with_timeout(tls: &TlsConfig, timeout: Duration) -> Result<Self> {
+        let connector = TlsConfig {
+            insecure: tls.insecure,
+            ca_file: tls.ca_file.clone(),
+            ..TlsConfig::default()
+        }
+        .connector()?;
+        // Redirections are followed by hand, see `post_form`.
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT.min(timeout))
+            .timeout(timeout)
+            .redirects(0)
+            .tls_connector(Arc::new(connector))
+            .build();
+        Ok(Self { agent })
+    }
+
+    /// Exchanges the given refresh token for an access token at the
+    /// given token endpoint.
+    pub fn refresh(
+        &self,
+        token_url: &str,
+        client_id: &str,
+        client_secret: Option<&str>,
+        refresh_token: &str,
+    ) -> Result<OAuth2TokenResponse> {
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", client_id),
+        ];
+        if let Some(secret) = client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let body = self.post_form(token_url, &form)?;
+        serde_json::from_str(&body).context("cannot parse token endpoint response")
+    }
+
+    /// Sends a form to the given URL and returns the body of the
+    /// response. Only the redirections keeping the method (307 and
+    /// 308) are followed, and never from HTTPS to HTTP since the form
+    /// contains secrets. Error responses are reduced to their status
+    /// and OAuth 2.0 error code, and bodies are never logged.
+    fn post_form(&self, url: &str, form: &[(&str, &str)]) -> Result<String> {
+        let mut url = Url::parse(url).with_context(|| format!("cannot parse URL {:?}", url))?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            debug!("post form to {}", url);
+            let res = match self
+                .agent
+                .post(url.as_str())
+                .set("Accept", "application/json")
+                .send_form(form)
+            {
+                Ok(res) => res,
+                Err(ureq::Error::Status(status, res)) => {
+                    let code = res
+                        .into_string()
+                        .ok()
+                        .and_then(|body| serde_json::from_str::<OAuth2ErrorResponse>(&body).ok())
+                        .map(|res| res.error);
+                    match code {
+                        Some(code) => bail!("token endpoint answered {} ({})", status, code),
+                        None => bail!("token endpoint answered {}", status),
+                    }
+                }
+                Err(err) => {
+                    return Err(anyhow!(err))
+                        .with_context(|| format!("cannot reach token endpoint {}", url))
+                }
+            };
+            debug!("token endpoint status: {}", res.status());
+
+            if !matches!(res.status(), 307 | 308) {
+                if res.status() != 200 {
+                    bail!("token endpoint answered {}", res.status());
+                }
+                return res.into_string().context("cannot read response body");
+            }
+
+            let location = res
+                .header("location")
+                .ok_or_else(|| anyhow!("cannot follow redirection: missing location"))?;
+            let next_url = url
+                .join(location)
+                .with_context(|| format!("cannot parse redirection URL {:?}", location))?;
+            if url.scheme() == "https" && next_url.scheme() != "https" {
+                bail!(
+                    "cannot follow redirection to insecure URL {:?}",
+                    next_url.as_str()
+                );
+            }
+            debug!("follow redirection to {}", next_url);
+            url = next_url;
+        }
+
+        bail!("cannot post form: too many redirections")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+        time::Instant,
+    };
+
+    use super::*;
+
+    /// Spawns a token endpoint answering each connection with the
+    /// next given response, once the form is received. A `None`
+    /// response keeps the connection open until the client gives up.
+    fn spawn_token_endpoint(ress: Vec<Option<String>>) -> (String, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            for res in ress {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut req = Vec::new();
+                let mut buf = [0; 1024];
+                while !String::from_utf8_lossy(&req).contains("client_id=client") {
+                    let len = stream.read(&mut buf).unwrap();
+                    assert!(len > 0, "unexpected end of request");
+                    req.extend_from_slice(&buf[..len]);
+                }
+                match res {
+                    Some(res) => stream.write_all(res.as_bytes()).unwrap(),
+                    None => while matches!(stream.read(&mut buf), Ok(len) if len > 0) {},
+                }
+            }
+        });
+        (url, handle)
+    }
+
+    fn res(status: &str, headers: &str, body: &str) -> Option<String> {
+        Some(format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        ))
+    }
+
+    #[test]
+    fn it_should_follow_redirections() {
+        let (url, endpoint) = spawn_token_endpoint(vec![
+            res("308 Permanent Redirect", "Location: /v2/token\r\n", ""),
+            res(
+                "200 OK",
+                "Content-Type: application/json\r\n",
+                r#"{"access_token":"access","expires_in":3600}"#,
+            ),
+        ]);
+        let client = OAuth2Client::new(&TlsConfig::default()).unwrap();
+        let token = client.refresh(&url, "client", None, "refresh").unwrap();
+        endpoint.join().unwrap();
+
+        assert_eq!("access", token.access_token);
+        assert_eq!(Some(3600), token.expires_in);
+        assert_eq!(None, token.refresh_token);
+    }
+
+    #[test]
+    fn it_should_not_leak_error_response() {
+        let (url, endpoint) = spawn_token_endpoint(vec![res(
+            "400 Bad Request",
+            "Content-Type: application/json\r\n",
+            r#"{"error":"invalid_grant","error_description":"refresh token revoked"}"#,
+        )]);
+        let client = OAuth2Client::new(&TlsConfig::default()).unwrap();
+        let err = client
+            .refresh(&url, "client", Some("secret"), "refresh")
+            .unwrap_err();
+        endpoint.join().unwrap();
+
+        let err = format!("{:#}", err);
+        assert_eq!("token endpoint answered 400 (invalid_grant)", err);
+    }
+
+    #[test]
+    fn it_should_time_out() {
+        let (url, endpoint) = spawn_token_endpoint(vec![None]);
+        let client =
+            OAuth2Client::with_timeout(&TlsConfig::default(), Duration::from_millis(200)).unwrap();
+        let now = Instant::now();
+        assert!(client.refresh(&url, "client", None, "refresh").is_err());
+        assert!(now.elapsed() < Duration::from_secs(5));
+        drop(client);
+        endpoint.join().unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+//! Process module.
+//!
+//! This module contains utilities to run shell commands.
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::{
+    io::{self, prelude::*},
+    process::{Command, Stdio},
+};
+
+/// Runs the given command in a shell and returns its standard
+/// output.
+pub fn run_cmd(cmd: &str) -> Result<String> {
+    debug!("running command: {}", cmd);
+
+    let output = if cfg!(target_os = "windows") {
+        Command::new("cmd").args(&["/C", cmd]).output()
+    } else {
+        Command::new("sh").arg("-c").arg(cmd).output()
+    }?;
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Runs the given command in a shell with the given environment
+/// variables, writes the given data to its standard input and returns
+/// its standard output. The command is free not to read its standard
+/// input.
+pub fn run_cmd_with_env(cmd: &str, envs: &[(&str, String)], data: &[u8]) -> Result<String> {
+    debug!("running command: {}", cmd);
+
+    let mut process = if cfg!(target_os = "windows") {
+        let mut process = Command::new("cmd");
+        process.args(["/C", cmd]);
+        process
+    } else {
+        let mut process = Command::new("sh");
+        process.arg("-c").arg(cmd);
+        process
+    };
+    let mut process = process
+        .envs(envs.iter().map(|(key, val)| (key, val)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("cannot spawn process from command {:?}", cmd))?;
+
+    // The standard input is dropped once written, so that the
+    // command reading it gets an end of file.
+    let mut stdin = process
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("cannot get stdin"))?;
+    if let Err(err) = stdin.write_all(data) {
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            return Err(err).context("cannot write data to stdin");
+        }
+    }
+    drop(stdin);
+
+    let output = process
+        .wait_with_output()
+        .with_context(|| format!("cannot wait for command {:?}", cmd))?;
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Pipes the given data to the standard input of the given command
+/// and returns its standard output.
+pub fn pipe_cmd(cmd: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let mut res = Vec::new();
+
+    let process = Command::new(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("cannot spawn process from command {:?}", cmd))?;
+    process
+        .stdin
+        .ok_or_else(|| anyhow!("cannot get stdin"))?
+        .write_all(data)
+        .with_context(|| "cannot write data to stdin")?;
+    process
+        .stdout
+        .ok_or_else(|| anyhow!("cannot get stdout"))?
+        .read_to_end(&mut res)
+        .with_context(|| "cannot read data from stdout")?;
+
+    Ok(res)
+}
@@ -0,0 +1,92 @@
+//! Async SMTP service module.
+//!
+//! This module exposes the async variant of the SMTP service trait,
+//! its `lettre` implementation and a wrapper turning any async SMTP
+//! service into a blocking one.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use tokio::runtime::{self, Runtime};
+
+use crate::{
+    config::AccountConfig,
+    msg::Msg,
+    smtp::{prepare_msg, smtp_tls, SmtpService},
+};
+
+#[async_trait]
+pub trait AsyncSmtpService: Send {
+    async fn send(&mut self, account: &AccountConfig, msg: &Msg) -> Result<Vec<u8>>;
+}
+
+pub struct AsyncLettreService<'a> {
+    account: &'a AccountConfig,
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+}
+
+impl AsyncLettreService<'_> {
+    fn transport(&mut self) -> Result<&AsyncSmtpTransport<Tokio1Executor>> {
+        if let Some(ref transport) = self.transport {
+            Ok(transport)
+        } else {
+            self.transport = Some(
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.account.smtp_host)
+                    .tls(smtp_tls(self.account)?)
+                    .port(self.account.smtp_port)
+                    .credentials(self.account.smtp_creds()?)
+                    .authentication(self.account.smtp_mechanisms()?)
+                    .build(),
+            );
+
+            Ok(self.transport.as_ref().unwrap())
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncSmtpService for AsyncLettreService<'_> {
+    async fn send(&mut self, account: &AccountConfig, msg: &Msg) -> Result<Vec<u8>> {
+        let (envelope, raw_msg) = prepare_msg(account, msg)?;
+        self.transport()?
+            .send_raw(&envelope, &raw_msg)
+            .await
+            .context("cannot send message")?;
+        Ok(raw_msg)
+    }
+}
+
+impl<'a> From<&'a AccountConfig> for AsyncLettreService<'a> {
+    fn from(account: &'a AccountConfig) -> Self {
+        Self {
+            account,
+            transport: None,
+        }
+    }
+}
+
+/// Wraps an async SMTP service into a blocking one.
+pub struct BlockingSmtpService<S: AsyncSmtpService> {
+    service: S,
+    runtime: Runtime,
+}
+
+impl<S: AsyncSmtpService> BlockingSmtpService<S> {
+    pub fn new(service: S) -> Result<Self> {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("cannot build async runtime")?;
+        Ok(Self { service, runtime })
+    }
+
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+impl<S: AsyncSmtpService> SmtpService for BlockingSmtpService<S> {
+    fn send(&mut self, account: &AccountConfig, msg: &Msg) -> Result<Vec<u8>> {
+        self.runtime.block_on(self.service.send(account, msg))
+    }
+}
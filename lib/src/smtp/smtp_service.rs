@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use lettre::{
+    self,
+    transport::smtp::{
+        client::{Certificate, Identity, Tls, TlsParameters},
+        SmtpTransport,
+    },
+    Transport,
+};
+use log::debug;
+use std::convert::TryInto;
+
+use crate::{
+    config::{AccountConfig, TlsMode},
+    msg::Msg,
+    process::pipe_cmd,
+};
+
+pub trait SmtpService {
+    fn send(&mut self, account: &AccountConfig, msg: &Msg) -> Result<Vec<u8>>;
+}
+
+pub struct LettreService<'a> {
+    account: &'a AccountConfig,
+    transport: Option<SmtpTransport>,
+}
+
+impl LettreService<'_> {
+    fn transport(&mut self) -> Result<&SmtpTransport> {
+        if let Some(ref transport) = self.transport {
+            Ok(transport)
+        } else {
+            self.transport = Some(
+                SmtpTransport::builder_dangerous(&self.account.smtp_host)
+                    .tls(smtp_tls(self.account)?)
+                    .port(self.account.smtp_port)
+                    .credentials(self.account.smtp_creds()?)
+                    .authentication(self.account.smtp_mechanisms()?)
+                    .build(),
+            );
+
+            Ok(self.transport.as_ref().unwrap())
+        }
+    }
+}
+
+impl SmtpService for LettreService<'_> {
+    fn send(&mut self, account: &AccountConfig, msg: &Msg) -> Result<Vec<u8>> {
+        let (envelope, raw_msg) = prepare_msg(account, msg)?;
+        self.transport()?.send_raw(&envelope, &raw_msg)?;
+        Ok(raw_msg)
+    }
+}
+
+impl<'a> From<&'a AccountConfig> for LettreService<'a> {
+    fn from(account: &'a AccountConfig) -> Self {
+        Self {
+            account,
+            transport: None,
+        }
+    }
+}
+
+impl<S: SmtpService + ?Sized> SmtpService for Box<S> {
+    fn send(&mut self, account: &AccountConfig, msg: &Msg) -> Result<Vec<u8>> {
+        (**self).send(account, msg)
+    }
+}
+
+/// Represents a SMTP service that never sends messages: they are
+/// formatted then kept in memory. Hooks are not executed.
+#[derive(Debug, Default)]
+pub struct MemorySmtpService {
+    pub sent: Vec<Vec<u8>>,
+}
+
+impl SmtpService for MemorySmtpService {
+    fn send(&mut self, account: &AccountConfig, msg: &Msg) -> Result<Vec<u8>> {
+        let raw_msg = msg.into_sendable_msg(account)?.formatted();
+        self.sent.push(raw_msg.clone());
+        Ok(raw_msg)
+    }
+}
+
+/// Builds the TLS parameters of the SMTP transport.
+pub(crate) fn smtp_tls(account: &AccountConfig) -> Result<Tls> {
+    let tls = &account.smtp_tls;
+    debug!("tls: {}", tls.mode);
+    debug!("insecure: {}", tls.insecure);
+    if tls.mode == TlsMode::None {
+        return Ok(Tls::None);
+    }
+
+    let mut builder = TlsParameters::builder(account.smtp_host.to_owned())
+        .dangerous_accept_invalid_hostnames(tls.insecure)
+        .dangerous_accept_invalid_certs(tls.insecure);
+    for cert in tls.ca_certs_pem()? {
+        builder = builder.add_root_certificate(
+            Certificate::from_pem(cert.as_bytes()).context("cannot parse CA certificate")?,
+        );
+    }
+    if let Some((cert, key)) = tls.identity_pem()? {
+        builder = builder.identify_with(
+            Identity::from_pem(&cert, &key).context("cannot parse client certificate")?,
+        );
+    }
+    let params = builder
+        .build()
+        .context("cannot build SMTP TLS parameters")?;
+
+    Ok(match tls.mode {
+        TlsMode::Starttls => Tls::Required(params),
+        _ => Tls::Wrapper(params),
+    })
+}
+
+/// Formats the given message and builds its SMTP envelope, after
+/// running the pre-send hook if any.
+pub(crate) fn prepare_msg(
+    account: &AccountConfig,
+    msg: &Msg,
+) -> Result<(lettre::address::Envelope, Vec<u8>)> {
+    let mut raw_msg = msg.into_sendable_msg(account)?.formatted();
+
+    let envelope: lettre::address::Envelope = if let Some(cmd) = account.hooks.pre_send.as_deref() {
+        for cmd in cmd.split('|') {
+            raw_msg = pipe_cmd(cmd.trim(), &raw_msg)
+                .with_context(|| format!("cannot execute pre-send hook {:?}", cmd))?
+        }
+        let parsed_mail = mailparse::parse_mail(&raw_msg)?;
+        Msg::from_parsed_mail(parsed_mail, account)?.try_into()
+    } else {
+        msg.try_into()
+    }?;
+
+    Ok((envelope, raw_msg))
+}
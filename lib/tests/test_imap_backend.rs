@@ -0,0 +1,159 @@
+#[cfg(feature = "imap-backend")]
+use std::convert::TryFrom;
+
+#[cfg(feature = "imap-backend")]
+use himalaya_lib::{
+    backends::{Backend, ImapBackend, ImapEnvelopes, ImapFlag},
+    config::{AccountConfig, ImapBackendConfig, TlsConfig, TlsMode},
+    msg::{Flags, IdSet, Uid},
+};
+
+#[cfg(feature = "imap-backend")]
+#[test]
+fn test_imap_backend() {
+    // configure accounts
+    let account_config = AccountConfig {
+        smtp_host: "localhost".into(),
+        smtp_port: 3465,
+        smtp_tls: TlsConfig {
+            mode: TlsMode::Implicit,
+            insecure: true,
+            ..TlsConfig::default()
+        },
+        smtp_login: "inbox@localhost".into(),
+        smtp_passwd_cmd: "echo 'password'".into(),
+        ..AccountConfig::default()
+    };
+    let imap_config = ImapBackendConfig {
+        imap_host: "localhost".into(),
+        imap_port: 3993,
+        imap_tls: TlsConfig {
+            mode: TlsMode::Implicit,
+            insecure: true,
+            ..TlsConfig::default()
+        },
+        imap_login: "inbox@localhost".into(),
+        imap_passwd_cmd: "echo 'password'".into(),
+        ..ImapBackendConfig::default()
+    };
+    let mut imap = ImapBackend::new(&account_config, &imap_config);
+    imap.connect().unwrap();
+
+    // set up mailboxes
+    if let Err(_) = imap.add_mbox("Mailbox1") {};
+    if let Err(_) = imap.add_mbox("Mailbox2") {};
+    let all = IdSet::try_from("1:*").unwrap();
+    imap.del_msg("Mailbox1", &all).unwrap();
+    imap.del_msg("Mailbox2", &all).unwrap();
+
+    // check that a message can be added
+    let msg = include_bytes!("./emails/alice-to-patrick.eml");
+    let flags = Flags::try_from("seen").unwrap();
+    let id = imap.add_msg("Mailbox1", msg, &flags).unwrap();
+
+    // check that the added message exists
+    let msg = imap.get_msg("Mailbox1", &id).unwrap();
+    assert_eq!("alice@localhost", msg.from.clone().unwrap().to_string());
+    assert_eq!("patrick@localhost", msg.to.clone().unwrap().to_string());
+    assert_eq!("Ceci est un message.", msg.fold_text_plain_parts());
+
+    // check that the envelope of the added message exists
+    let envelopes = imap.get_envelopes("Mailbox1", 10, 0).unwrap();
+    let envelopes: &ImapEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert_eq!(1, envelopes.len());
+    let envelope = envelopes.first().unwrap();
+    assert_eq!("alice@localhost", envelope.sender);
+    assert_eq!("Plain message", envelope.subject);
+
+    // check that the message can be copied
+    let ids = IdSet::from(Uid(envelope.id));
+    imap.copy_msg("Mailbox1", "Mailbox2", &ids).unwrap();
+    let envelopes = imap.get_envelopes("Mailbox1", 10, 0).unwrap();
+    let envelopes: &ImapEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert_eq!(1, envelopes.len());
+    let envelopes = imap.get_envelopes("Mailbox2", 10, 0).unwrap();
+    let envelopes: &ImapEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert_eq!(1, envelopes.len());
+
+    // check that the message can be moved, flags are kept
+    imap.move_msg("Mailbox1", "Mailbox2", &ids).unwrap();
+    let envelopes = imap.get_envelopes("Mailbox1", 10, 0).unwrap();
+    let envelopes: &ImapEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert_eq!(0, envelopes.len());
+    let envelopes = imap.get_envelopes("Mailbox2", 10, 0).unwrap();
+    let envelopes: &ImapEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert_eq!(2, envelopes.len());
+    assert!(envelopes
+        .iter()
+        .all(|envelope| envelope.flags.contains(&ImapFlag::Seen)));
+    let id = Uid(envelopes.last().unwrap().id);
+    let other_id = Uid(envelopes.first().unwrap().id);
+
+    // check that the message can be deleted, and that ids of other
+    // messages are not affected
+    imap.del_msg("Mailbox2", &id.clone().into()).unwrap();
+    assert!(imap.get_msg("Mailbox2", &id).is_err());
+    assert!(imap.get_msg("Mailbox2", &other_id).is_ok());
+
+    // check that disconnection works
+    imap.disconnect().unwrap();
+}
+
+#[cfg(all(feature = "imap-backend", feature = "maildir-backend"))]
+#[test]
+fn test_imap_sync() {
+    let account_config = AccountConfig::default();
+    let imap_config = ImapBackendConfig {
+        imap_host: "localhost".into(),
+        imap_port: 3993,
+        imap_tls: TlsConfig {
+            mode: TlsMode::Implicit,
+            insecure: true,
+            ..TlsConfig::default()
+        },
+        imap_login: "inbox@localhost".into(),
+        imap_passwd_cmd: "echo 'password'".into(),
+        ..ImapBackendConfig::default()
+    };
+    let mut imap = ImapBackend::new(&account_config, &imap_config);
+
+    // set up mailbox and maildir
+    if let Err(_) = imap.add_mbox("Mailbox3") {};
+    let all = IdSet::try_from("1:*").unwrap();
+    imap.del_msg("Mailbox3", &all).unwrap();
+    let msg = include_bytes!("./emails/alice-to-patrick.eml");
+    imap.add_msg("Mailbox3", msg, &Flags::default()).unwrap();
+    let mdir = maildir::Maildir::from(
+        std::env::temp_dir().join(format!("himalaya-sync-{}", uuid::Uuid::new_v4())),
+    );
+
+    // check that new IMAP messages are downloaded
+    let report = imap.sync_mbox("Mailbox3", &mdir).unwrap();
+    assert_eq!(1, report.pulled);
+    assert_eq!(1, mdir.count_cur());
+
+    // check that new maildir messages and flags are uploaded
+    let id = mdir.list_cur().next().unwrap().unwrap().id().to_owned();
+    mdir.add_flags(&id, "S").unwrap();
+    mdir.store_new(msg).unwrap();
+    let report = imap.sync_mbox("Mailbox3", &mdir).unwrap();
+    assert_eq!(1, report.pushed);
+    assert_eq!(1, report.flagged_remote);
+    let envelopes = imap.get_envelopes("Mailbox3", 10, 0).unwrap();
+    let envelopes: &ImapEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert_eq!(2, envelopes.len());
+    assert!(envelopes
+        .iter()
+        .any(|envelope| envelope.flags.contains(&ImapFlag::Seen)));
+
+    // check that deletions are propagated
+    mdir.delete(&id).unwrap();
+    let report = imap.sync_mbox("Mailbox3", &mdir).unwrap();
+    assert_eq!(1, report.deleted_remote);
+    let envelopes = imap.get_envelopes("Mailbox3", 10, 0).unwrap();
+    let envelopes: &ImapEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert_eq!(1, envelopes.len());
+
+    std::fs::remove_dir_all(mdir.path()).unwrap();
+    imap.disconnect().unwrap();
+}
@@ -0,0 +1,234 @@
+#[cfg(feature = "imap-backend")]
+use anyhow::{anyhow, Result};
+#[cfg(feature = "imap-backend")]
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+#[cfg(feature = "imap-backend")]
+use himalaya_lib::{
+    backends::{ImapBackend, ImapBackoff, ImapWatchEventKind},
+    config::{AccountConfig, ImapBackendConfig, TlsConfig, TlsMode},
+};
+
+/// Represents a minimal IMAP server holding a single mailbox. The
+/// second connection is dropped right after being accepted, as if the
+/// server was restarting. The first session gets a new message during
+/// its first IDLE, then gets another one and is dropped during its
+/// second IDLE.
+#[cfg(feature = "imap-backend")]
+struct ImapStandIn {
+    uids: Mutex<Vec<u32>>,
+    conns: AtomicUsize,
+    idles: AtomicUsize,
+}
+
+#[cfg(feature = "imap-backend")]
+impl ImapStandIn {
+    fn start() -> (Arc<Self>, u16) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = Arc::new(Self {
+            uids: Mutex::new(vec![1]),
+            conns: AtomicUsize::new(0),
+            idles: AtomicUsize::new(0),
+        });
+        let server_ref = server.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                if server_ref.conns.fetch_add(1, Ordering::SeqCst) == 1 {
+                    continue;
+                }
+                let server = server_ref.clone();
+                thread::spawn(move || server.serve(stream));
+            }
+        });
+        (server, port)
+    }
+
+    fn serve(&self, mut stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        stream.write_all(b"* OK IMAP stand-in ready\r\n").unwrap();
+
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let (tag, cmd) = line.trim_end().split_once(' ').unwrap();
+            let cmd = cmd.to_uppercase();
+            let uids = self.uids.lock().unwrap().clone();
+
+            let res = if cmd.starts_with("LOGIN") || cmd.starts_with("LOGOUT") {
+                format!("{} OK done\r\n", tag)
+            } else if cmd.starts_with("CAPABILITY") {
+                format!("* CAPABILITY IMAP4rev1 IDLE\r\n{} OK done\r\n", tag)
+            } else if cmd.starts_with("EXAMINE") {
+                format!(
+                    "* {} EXISTS\r\n* 0 RECENT\r\n* OK [UIDVALIDITY 1] ok\r\n{} OK [READ-ONLY] done\r\n",
+                    uids.len(),
+                    tag
+                )
+            } else if cmd.starts_with("UID SEARCH") {
+                let uids: Vec<String> = uids.iter().map(|uid| uid.to_string()).collect();
+                format!("* SEARCH {}\r\n{} OK done\r\n", uids.join(" "), tag)
+            } else if cmd.starts_with("UID FETCH") || cmd.starts_with("FETCH") {
+                let by_uid = cmd.starts_with("UID");
+                let set = cmd.split(' ').nth(if by_uid { 2 } else { 1 }).unwrap();
+                let mut res = String::new();
+                for (seq, uid) in (1..).zip(uids.iter()) {
+                    let id = if by_uid { *uid } else { seq };
+                    if !in_set(set, id, uids.len() as u32) {
+                        continue;
+                    }
+                    if cmd.contains("ENVELOPE") {
+                        res.push_str(&format!(
+                            "* {} FETCH (UID {} ENVELOPE (\"Tue, 1 Mar 2022 10:00:00 +0000\" \"msg {}\" ((\"Alice\" NIL \"alice\" \"localhost\")) NIL NIL ((NIL NIL \"bob\" \"localhost\")) NIL NIL NIL \"<{}@localhost>\"))\r\n",
+                            seq, uid, uid, uid
+                        ));
+                    } else {
+                        res.push_str(&format!("* {} FETCH (UID {})\r\n", seq, uid));
+                    }
+                }
+                res + &format!("{} OK done\r\n", tag)
+            } else if cmd.starts_with("IDLE") {
+                stream.write_all(b"+ idling\r\n").unwrap();
+                match self.idles.fetch_add(1, Ordering::SeqCst) {
+                    0 => {
+                        self.uids.lock().unwrap().push(2);
+                        stream.write_all(b"* 2 EXISTS\r\n").unwrap();
+                    }
+                    1 => {
+                        self.uids.lock().unwrap().push(3);
+                        return;
+                    }
+                    _ => (),
+                }
+                let mut done = String::new();
+                if reader.read_line(&mut done).unwrap_or(0) == 0 {
+                    return;
+                }
+                format!("{} OK idle terminated\r\n", tag)
+            } else {
+                format!("{} BAD unknown command\r\n", tag)
+            };
+            stream.write_all(res.as_bytes()).unwrap();
+        }
+    }
+}
+
+/// Checks if the given id belongs to the given sequence set, where
+/// `*` stands for the given last id.
+#[cfg(feature = "imap-backend")]
+fn in_set(set: &str, id: u32, last: u32) -> bool {
+    let parse = |id: &str| if id == "*" { last } else { id.parse().unwrap() };
+    set.split(',').any(|range| match range.split_once(':') {
+        Some((start, end)) => (parse(start)..=parse(end)).contains(&id),
+        None => parse(range) == id,
+    })
+}
+
+#[cfg(feature = "imap-backend")]
+fn configs(port: u16) -> (AccountConfig, ImapBackendConfig) {
+    let account_config = AccountConfig {
+        name: "stand-in".into(),
+        notify_query: "NEW".into(),
+        ..AccountConfig::default()
+    };
+    let imap_config = ImapBackendConfig {
+        imap_host: "127.0.0.1".into(),
+        imap_port: port,
+        imap_tls: TlsConfig {
+            mode: TlsMode::None,
+            ..TlsConfig::default()
+        },
+        imap_login: "inbox@localhost".into(),
+        imap_passwd_cmd: "echo 'password'".into(),
+        ..ImapBackendConfig::default()
+    };
+    (account_config, imap_config)
+}
+
+#[cfg(feature = "imap-backend")]
+fn backoff() -> ImapBackoff {
+    ImapBackoff {
+        min: Duration::from_millis(10),
+        max: Duration::from_millis(100),
+    }
+}
+
+#[cfg(feature = "imap-backend")]
+#[test]
+fn test_imap_notify_reconnects() {
+    let (server, port) = ImapStandIn::start();
+    let (account_config, imap_config) = configs(port);
+    let mut imap = ImapBackend::new(&account_config, &imap_config);
+    imap.set_backoff(backoff());
+
+    let mut subjects = vec![];
+    let res: Result<()> = imap.notify(60, "INBOX", |event| {
+        subjects.extend(event.subject);
+        if subjects.len() == 2 {
+            return Err(anyhow!("stop"));
+        }
+        Ok(())
+    });
+
+    // The message received while disconnected is notified once the
+    // connection is back, and the one notified before is not
+    // notified again.
+    assert_eq!("stop", res.unwrap_err().to_string());
+    assert_eq!(vec!["msg 2", "msg 3"], subjects);
+    assert_eq!(3, server.conns.load(Ordering::SeqCst));
+}
+
+#[cfg(feature = "imap-backend")]
+#[test]
+fn test_imap_watch_reconnects() {
+    let (server, port) = ImapStandIn::start();
+    let (account_config, imap_config) = configs(port);
+    let mut imap = ImapBackend::new(&account_config, &imap_config);
+    imap.set_backoff(backoff());
+
+    let mut events = vec![];
+    let res: Result<()> = imap.watch(60, "INBOX", |event| {
+        assert_eq!(ImapWatchEventKind::New, event.kind);
+        assert_eq!("INBOX", event.mbox);
+        events.push((event.uids, event.subject));
+        if events.len() == 2 {
+            return Err(anyhow!("stop"));
+        }
+        Ok(())
+    });
+
+    // The first message is announced by an EXISTS response during the
+    // idle mode, the second one is found when resynchronizing the
+    // mailbox after the reconnection.
+    assert_eq!("stop", res.unwrap_err().to_string());
+    assert_eq!(
+        vec![
+            (vec![2], Some("msg 2".to_owned())),
+            (vec![3], Some("msg 3".to_owned())),
+        ],
+        events
+    );
+    assert_eq!(3, server.conns.load(Ordering::SeqCst));
+}
+
+#[cfg(feature = "imap-backend")]
+#[test]
+fn test_imap_backoff() {
+    let backoff = ImapBackoff::default();
+    assert_eq!(Duration::from_secs(1), backoff.delay(0));
+    assert_eq!(Duration::from_secs(8), backoff.delay(3));
+    assert_eq!(Duration::from_secs(300), backoff.delay(9));
+    assert_eq!(Duration::from_secs(300), backoff.delay(u32::MAX));
+}
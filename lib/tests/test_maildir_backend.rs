@@ -1,9 +1,10 @@
 use maildir::Maildir;
-use std::{collections::HashMap, env, fs, iter::FromIterator};
+use std::{collections::HashMap, convert::TryFrom, env, fs, iter::FromIterator};
 
-use himalaya::{
-    backends::{Backend, MaildirBackend, MaildirEnvelopes, MaildirFlag},
+use himalaya_lib::{
+    backends::{Backend, MaildirBackend, MaildirEnvelopes, MaildirFlag, MaildirMboxes},
     config::{AccountConfig, MaildirBackendConfig},
+    msg::{Flags, IdSet, SortCriteria},
 };
 
 #[test]
@@ -33,7 +34,8 @@ fn test_maildir_backend() {
 
     // check that a message can be added
     let msg = include_bytes!("./emails/alice-to-patrick.eml");
-    let hash = mdir.add_msg("inbox", msg, "seen").unwrap().to_string();
+    let flags = Flags::try_from("seen").unwrap();
+    let hash = mdir.add_msg("inbox", msg, &flags).unwrap();
 
     // check that the added message exists
     let msg = mdir.get_msg("inbox", &hash).unwrap();
@@ -49,9 +51,23 @@ fn test_maildir_backend() {
     assert_eq!("alice@localhost", envelope.sender);
     assert_eq!("Plain message", envelope.subject);
 
-    // check that a flag can be added to the message
-    mdir.add_flags("inbox", &envelope.hash, "flagged passed")
+    // check that the message can be searched and sorted
+    let sort = SortCriteria::try_from("subject from:desc").unwrap();
+    let envelopes = mdir
+        .search_envelopes("inbox", "from:alice and flag:seen", &sort, 10, 0)
+        .unwrap();
+    let envelopes: &MaildirEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert_eq!(1, envelopes.len());
+    let envelopes = mdir
+        .search_envelopes("inbox", "not flag:seen", &SortCriteria::default(), 10, 0)
         .unwrap();
+    let envelopes: &MaildirEnvelopes = envelopes.as_any().downcast_ref().unwrap();
+    assert!(envelopes.is_empty());
+
+    // check that a flag can be added to the message
+    let ids = IdSet::try_from(envelope.hash.as_str()).unwrap();
+    let flags = Flags::try_from("flagged passed").unwrap();
+    mdir.add_flags("inbox", &ids, &flags).unwrap();
     let envelopes = mdir.get_envelopes("inbox", 1, 0).unwrap();
     let envelopes: &MaildirEnvelopes = envelopes.as_any().downcast_ref().unwrap();
     let envelope = envelopes.first().unwrap();
@@ -60,7 +76,8 @@ fn test_maildir_backend() {
     assert!(envelope.flags.contains(&MaildirFlag::Passed));
 
     // check that the message flags can be changed
-    mdir.set_flags("inbox", &envelope.hash, "passed").unwrap();
+    let flags = Flags::try_from("passed").unwrap();
+    mdir.set_flags("inbox", &ids, &flags).unwrap();
     let envelopes = mdir.get_envelopes("inbox", 1, 0).unwrap();
     let envelopes: &MaildirEnvelopes = envelopes.as_any().downcast_ref().unwrap();
     let envelope = envelopes.first().unwrap();
@@ -69,7 +86,7 @@ fn test_maildir_backend() {
     assert!(envelope.flags.contains(&MaildirFlag::Passed));
 
     // check that a flag can be removed from the message
-    mdir.del_flags("inbox", &envelope.hash, "passed").unwrap();
+    mdir.del_flags("inbox", &ids, &flags).unwrap();
     let envelopes = mdir.get_envelopes("inbox", 1, 0).unwrap();
     let envelopes: &MaildirEnvelopes = envelopes.as_any().downcast_ref().unwrap();
     let envelope = envelopes.first().unwrap();
@@ -78,19 +95,81 @@ fn test_maildir_backend() {
     assert!(!envelope.flags.contains(&MaildirFlag::Passed));
 
     // check that the message can be copied
-    mdir.copy_msg("inbox", "subdir", &envelope.hash).unwrap();
+    mdir.copy_msg("inbox", "subdir", &ids).unwrap();
     assert!(mdir.get_msg("inbox", &hash).is_ok());
     assert!(mdir.get_msg("subdir", &hash).is_ok());
     assert!(mdir_subdir.get_msg("inbox", &hash).is_ok());
 
     // check that the message can be moved
-    mdir.move_msg("inbox", "subdir", &envelope.hash).unwrap();
+    mdir.move_msg("inbox", "subdir", &ids).unwrap();
     assert!(mdir.get_msg("inbox", &hash).is_err());
     assert!(mdir.get_msg("subdir", &hash).is_ok());
     assert!(mdir_subdir.get_msg("inbox", &hash).is_ok());
 
     // check that the message can be deleted
-    mdir.del_msg("subdir", &hash).unwrap();
+    mdir.del_msg("subdir", &hash.clone().into()).unwrap();
     assert!(mdir.get_msg("subdir", &hash).is_err());
     assert!(mdir_subdir.get_msg("inbox", &hash).is_err());
 }
+
+#[test]
+fn test_maildir_backend_mboxes() {
+    // set up maildir folder
+    let mdir: Maildir = env::temp_dir().join("himalaya-test-mdir-mboxes").into();
+    if let Err(_) = fs::remove_dir_all(mdir.path()) {}
+    mdir.create_dirs().unwrap();
+
+    let account_config = AccountConfig::default();
+    let mdir_config = MaildirBackendConfig {
+        maildir_dir: mdir.path().to_owned(),
+    };
+    let mut mdir = MaildirBackend::new(&account_config, &mdir_config);
+
+    macro_rules! mbox_names {
+        () => {{
+            let mboxes = mdir.get_mboxes().unwrap();
+            let mboxes: &MaildirMboxes = mboxes.as_any().downcast_ref().unwrap();
+            let mut names: Vec<_> = mboxes.iter().map(|mbox| mbox.name.clone()).collect();
+            names.sort();
+            names
+        }};
+    }
+
+    // check that mailboxes can be created
+    mdir.add_mbox("Foo").unwrap();
+    mdir.add_mbox("Foo.Child").unwrap();
+    mdir.add_mbox("Bar").unwrap();
+    assert!(mdir.add_mbox("Bar").is_err());
+    assert_eq!(vec!["Bar", "Foo", "Foo.Child"], mbox_names!());
+
+    // check that a message can be added to a created mailbox
+    let msg = include_bytes!("./emails/alice-to-patrick.eml");
+    let hash = mdir.add_msg("Foo.Child", msg, &Flags::default()).unwrap();
+
+    // check that a mailbox is renamed along with its children
+    mdir.rename_mbox("Foo", "Baz").unwrap();
+    assert_eq!(vec!["Bar", "Baz", "Baz.Child"], mbox_names!());
+    assert!(mdir.get_msg("Baz.Child", &hash).is_ok());
+
+    // check that mailboxes come with their message counts
+    let mboxes = mdir.get_mboxes().unwrap();
+    let mboxes: &MaildirMboxes = mboxes.as_any().downcast_ref().unwrap();
+    let counts = mboxes
+        .iter()
+        .find(|mbox| mbox.name == "Baz.Child")
+        .and_then(|mbox| mbox.counts)
+        .unwrap();
+    assert_eq!(1, counts.messages);
+    assert_eq!(1, counts.unseen);
+    assert_eq!(Some(0), counts.recent);
+    assert_eq!(Some(msg.len() as u64), counts.size);
+    assert!(mdir.rename_mbox("Foo", "Qux").is_err());
+    assert!(mdir.rename_mbox("Baz", "Bar").is_err());
+
+    // check that mailboxes can be deleted
+    mdir.del_mbox("Bar").unwrap();
+    assert_eq!(vec!["Baz", "Baz.Child"], mbox_names!());
+
+    // check that maildir folders cannot be subscribed to
+    assert!(mdir.subscribe_mbox("Baz").is_err());
+}
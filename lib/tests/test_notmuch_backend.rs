@@ -1,10 +1,11 @@
 #[cfg(feature = "notmuch-backend")]
-use std::{collections::HashMap, env, fs, iter::FromIterator};
+use std::{collections::HashMap, convert::TryFrom, env, fs, iter::FromIterator};
 
 #[cfg(feature = "notmuch-backend")]
-use himalaya::{
-    backends::{Backend, MaildirBackend, NotmuchBackend, NotmuchEnvelopes},
-    config::{AccountConfig, MaildirBackendConfig, NotmuchBackendConfig},
+use himalaya_lib::{
+    backends::{Backend, NotmuchBackend, NotmuchEnvelopes},
+    config::{AccountConfig, NotmuchBackendConfig},
+    msg::{Flags, IdSet},
 };
 
 #[cfg(feature = "notmuch-backend")]
@@ -21,18 +22,15 @@ fn test_notmuch_backend() {
         mailboxes: HashMap::from_iter([("inbox".into(), "*".into())]),
         ..AccountConfig::default()
     };
-    let mdir_config = MaildirBackendConfig {
-        maildir_dir: mdir.path().to_owned(),
-    };
     let notmuch_config = NotmuchBackendConfig {
         notmuch_database_dir: mdir.path().to_owned(),
     };
-    let mut mdir = MaildirBackend::new(&account_config, &mdir_config);
-    let mut notmuch = NotmuchBackend::new(&account_config, &notmuch_config, &mut mdir).unwrap();
+    let mut notmuch = NotmuchBackend::new(&account_config, &notmuch_config).unwrap();
 
     // check that a message can be added
     let msg = include_bytes!("./emails/alice-to-patrick.eml");
-    let hash = notmuch.add_msg("", msg, "inbox seen").unwrap().to_string();
+    let flags = Flags::try_from("inbox seen").unwrap();
+    let hash = notmuch.add_msg("", msg, &flags).unwrap();
 
     // check that the added message exists
     let msg = notmuch.get_msg("", &hash).unwrap();
@@ -49,9 +47,9 @@ fn test_notmuch_backend() {
     assert_eq!("Plain message", envelope.subject);
 
     // check that a flag can be added to the message
-    notmuch
-        .add_flags("", &envelope.hash, "flagged passed")
-        .unwrap();
+    let ids = IdSet::try_from(envelope.hash.as_str()).unwrap();
+    let flags = Flags::try_from("flagged passed").unwrap();
+    notmuch.add_flags("", &ids, &flags).unwrap();
     let envelopes = notmuch.get_envelopes("inbox", 10, 0).unwrap();
     let envelopes: &NotmuchEnvelopes = envelopes.as_any().downcast_ref().unwrap();
     let envelope = envelopes.first().unwrap();
@@ -61,9 +59,8 @@ fn test_notmuch_backend() {
     assert!(envelope.flags.contains(&"passed".into()));
 
     // check that the message flags can be changed
-    notmuch
-        .set_flags("", &envelope.hash, "inbox passed")
-        .unwrap();
+    let flags = Flags::try_from("inbox passed").unwrap();
+    notmuch.set_flags("", &ids, &flags).unwrap();
     let envelopes = notmuch.get_envelopes("inbox", 10, 0).unwrap();
     let envelopes: &NotmuchEnvelopes = envelopes.as_any().downcast_ref().unwrap();
     let envelope = envelopes.first().unwrap();
@@ -73,7 +70,8 @@ fn test_notmuch_backend() {
     assert!(envelope.flags.contains(&"passed".into()));
 
     // check that a flag can be removed from the message
-    notmuch.del_flags("", &envelope.hash, "passed").unwrap();
+    let flags = Flags::try_from("passed").unwrap();
+    notmuch.del_flags("", &ids, &flags).unwrap();
     let envelopes = notmuch.get_envelopes("inbox", 10, 0).unwrap();
     let envelopes: &NotmuchEnvelopes = envelopes.as_any().downcast_ref().unwrap();
     let envelope = envelopes.first().unwrap();
@@ -83,6 +81,6 @@ fn test_notmuch_backend() {
     assert!(!envelope.flags.contains(&"passed".into()));
 
     // check that the message can be deleted
-    notmuch.del_msg("", &hash).unwrap();
+    notmuch.del_msg("", &hash.clone().into()).unwrap();
     assert!(notmuch.get_msg("inbox", &hash).is_err());
 }